@@ -140,7 +140,7 @@ fn process_data(data: &str) -> String {
 }
 
 pub fn bench_database_operations(c: &mut Criterion) {
-    use code_search::{init_db, Chunk};
+    use code_search::init_db;
     use tempfile::TempDir;
 
     let mut group = c.benchmark_group("database_operations");
@@ -148,7 +148,7 @@ pub fn bench_database_operations(c: &mut Criterion) {
     group.bench_function("init_db", |b| {
         b.iter(|| {
             let temp_dir = TempDir::new().unwrap();
-            let db_path = temp_dir.path().join("test.db");
+            let _db_path = temp_dir.path().join("test.db");
             std::env::set_var("CODE_SEARCH_DATA_DIR", temp_dir.path().to_str().unwrap());
             let result = init_db();
             std::env::remove_var("CODE_SEARCH_DATA_DIR");
@@ -159,6 +159,123 @@ pub fn bench_database_operations(c: &mut Criterion) {
     group.finish();
 }
 
+pub fn bench_chunk_insertion(c: &mut Criterion) {
+    use code_search::{init_db_at, insert_chunks, Chunk};
+    use tempfile::TempDir;
+
+    let mut group = c.benchmark_group("chunk_insertion");
+    group.sample_size(10);
+    // Ten 100k-chunk inserts take longer than the default 5s
+    group.measurement_time(std::time::Duration::from_secs(30));
+
+    for count in [1_000usize, 10_000, 100_000] {
+        let chunks: Vec<Chunk> = (0..count)
+            .map(|i| Chunk {
+                id: None,
+                codebase_id: "bench".to_string(),
+                file_path: format!("src/file_{}.rs", i / 20),
+                start_line: (i % 20) as i64 * 50 + 1,
+                end_line: (i % 20) as i64 * 50 + 50,
+                content: format!("fn function_{}() {{ let value = {}; }}", i, i),
+                language: Some("rust".to_string()),
+                embedding: vec![0.1; 384],
+                hash: format!("{:016x}", i),
+                chunk_kind: Some("code".to_string()),
+                author: None,
+            })
+            .collect();
+
+        group.throughput(Throughput::Elements(count as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(count), &chunks, |b, chunks| {
+            b.iter_with_setup(
+                || {
+                    let temp_dir = TempDir::new().unwrap();
+                    let conn = init_db_at(&temp_dir.path().join("bench.db")).unwrap();
+                    (temp_dir, conn)
+                },
+                |(_temp_dir, conn)| insert_chunks(&conn, black_box(chunks)).unwrap(),
+            );
+        });
+    }
+
+    group.finish();
+}
+
+/// One model call per chunk against one batched call for a medium file's
+/// chunks, as indexing embeds them. The gap is largest with `--features onnx`.
+pub fn bench_file_embedding(c: &mut Criterion) {
+    use code_search::{get_embedding_with_model, get_embeddings_batch_with_model, DEFAULT_MODEL};
+
+    let content = (0..600)
+        .map(|i| format!("fn function_{}(x: i32) -> i32 {{ x * {} + 1 }}", i, i))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let chunks: Vec<String> = split_file("medium.rs", &content, None, None)
+        .into_iter()
+        .map(|chunk| chunk.content)
+        .collect();
+
+    let mut group = c.benchmark_group("file_embedding");
+    group.sample_size(10);
+    group.throughput(Throughput::Elements(chunks.len() as u64));
+
+    group.bench_function("per_chunk", |b| {
+        b.iter(|| {
+            chunks
+                .iter()
+                .map(|chunk| get_embedding_with_model(black_box(chunk), DEFAULT_MODEL))
+                .collect::<Vec<_>>()
+        });
+    });
+    group.bench_function("batched", |b| {
+        b.iter(|| get_embeddings_batch_with_model(black_box(&chunks), 32, false, DEFAULT_MODEL));
+    });
+
+    group.finish();
+}
+
+/// Per-chunk embedding from a rayon pool of 1, 2, 4... threads, as
+/// `Indexer` embeds files. With `--features onnx` each thread checks out its
+/// own session (see `model.inference_sessions`), so throughput should grow
+/// with the thread count up to the number of sessions.
+pub fn bench_parallel_embedding(c: &mut Criterion) {
+    use code_search::{get_embedding_with_model, DEFAULT_MODEL};
+    use rayon::prelude::*;
+
+    let texts: Vec<String> = (0..256)
+        .map(|i| format!("fn function_{}(x: i32) -> i32 {{ x * {} + 1 }}", i, i))
+        .collect();
+    // Load the model outside the measurement
+    get_embedding_with_model("warm up", DEFAULT_MODEL);
+
+    let mut group = c.benchmark_group("parallel_embedding");
+    group.sample_size(10);
+    group.throughput(Throughput::Elements(texts.len() as u64));
+
+    let cores = num_cpus::get();
+    let thread_counts = std::iter::successors(Some(1), |n| Some(n * 2))
+        .take_while(|&n| n < cores)
+        .chain(std::iter::once(cores));
+    for threads in thread_counts {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .unwrap();
+        group.bench_with_input(BenchmarkId::from_parameter(threads), &texts, |b, texts| {
+            b.iter(|| {
+                pool.install(|| {
+                    texts
+                        .par_iter()
+                        .map(|text| get_embedding_with_model(black_box(text), DEFAULT_MODEL))
+                        .collect::<Vec<_>>()
+                })
+            });
+        });
+    }
+
+    group.finish();
+}
+
 criterion_group!(
     benches,
     bench_language_detection,
@@ -167,5 +284,8 @@ criterion_group!(
     bench_chunk_id_generation,
     bench_context_enrichment,
     bench_database_operations,
+    bench_chunk_insertion,
+    bench_file_embedding,
+    bench_parallel_embedding,
 );
 criterion_main!(benches);
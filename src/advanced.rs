@@ -999,6 +999,7 @@ pub fn predict_changes(codebase_path: &str, modified_files: &[String], model: &s
     let codebase_id = get_codebase_hash(&path.canonicalize()?);
 
     let conn = database::init_db()?;
+    let format = database::embedding_format(&conn)?;
 
     // Get embeddings for modified files
     let mut file_embeddings: HashMap<String, Vec<f32>> = HashMap::new();
@@ -1011,7 +1012,7 @@ pub fn predict_changes(codebase_path: &str, modified_files: &[String], model: &s
         if let Ok((content, embedding_blob)) = stmt.query_row(params![&codebase_id, file], |row| {
             Ok((row.get::<_, String>(0)?, row.get::<_, Vec<u8>>(1)?))
         }) {
-            let embedding = deserialize_embedding(&embedding_blob);
+            let embedding = database::deserialize_embedding(&embedding_blob, format);
             file_embeddings.insert(file.clone(), embedding);
         }
     }
@@ -1041,7 +1042,7 @@ pub fn predict_changes(codebase_path: &str, modified_files: &[String], model: &s
         if let Ok(embedding_blob) = stmt.query_row(params![&codebase_id, &file], |row| {
             row.get::<_, Vec<u8>>(0)
         }) {
-            let embedding = deserialize_embedding(&embedding_blob);
+            let embedding = database::deserialize_embedding(&embedding_blob, format);
 
             // Calculate similarity to any modified file
             let mut max_similarity = 0.0_f64;
@@ -1094,12 +1095,6 @@ pub fn predict_changes(codebase_path: &str, modified_files: &[String], model: &s
     })
 }
 
-fn deserialize_embedding(blob: &[u8]) -> Vec<f32> {
-    blob.chunks_exact(4)
-        .map(|chunk| f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
-        .collect()
-}
-
 fn cosine_similarity(a: &[f32], b: &[f32]) -> f64 {
     if a.len() != b.len() || a.is_empty() {
         return 0.0;
@@ -0,0 +1,925 @@
+//! Approximate nearest-neighbour (HNSW) index over chunk embeddings.
+//!
+//! The graph is persisted in the SQLite database next to the chunks it
+//! indexes and is maintained incrementally: triggers on `chunks` queue new
+//! rows and tombstone deleted ones, and `maintain` folds the queue into the
+//! graph at the end of each write. A full rebuild only happens when the share
+//! of tombstoned nodes exceeds `performance.ann_rebuild_threshold`, or on
+//! request through [`rebuild_ann_index`].
+
+use crate::config::get_config;
+use crate::database::{
+    deserialize_embedding, embedding_format, search_result_for_chunk, serialize_embedding,
+    EmbeddingFormat, SearchResult,
+};
+use crate::error::{CodeSearchError, Result};
+use rusqlite::{params, Connection, OptionalExtension};
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+/// Candidate list width used while linking new nodes into the graph
+const EF_CONSTRUCTION: usize = 100;
+/// Upper bound on node levels, far above what realistic index sizes reach
+const MAX_LEVEL: usize = 16;
+
+pub(crate) const SCHEMA: &str = r#"
+        -- HNSW graph per codebase; see src/ann.rs
+        -- vector_format is the encoding of ann_nodes.vector, NULL for f32
+        CREATE TABLE IF NOT EXISTS ann_meta (
+            codebase_id TEXT PRIMARY KEY,
+            entry_point INTEGER,
+            max_level INTEGER NOT NULL DEFAULT 0,
+            dimension INTEGER,
+            vector_format TEXT
+        );
+
+        CREATE TABLE IF NOT EXISTS ann_nodes (
+            chunk_id INTEGER PRIMARY KEY,
+            codebase_id TEXT NOT NULL,
+            level INTEGER NOT NULL,
+            vector BLOB NOT NULL,
+            deleted INTEGER NOT NULL DEFAULT 0
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_ann_nodes_codebase ON ann_nodes(codebase_id, deleted);
+
+        CREATE TABLE IF NOT EXISTS ann_edges (
+            chunk_id INTEGER NOT NULL,
+            layer INTEGER NOT NULL,
+            neighbors BLOB NOT NULL,
+            PRIMARY KEY (chunk_id, layer)
+        ) WITHOUT ROWID;
+
+        -- Chunks inserted since the graph was last brought up to date
+        CREATE TABLE IF NOT EXISTS ann_pending (
+            chunk_id INTEGER PRIMARY KEY,
+            codebase_id TEXT NOT NULL
+        );
+
+        CREATE TRIGGER IF NOT EXISTS chunks_ann_ai AFTER INSERT ON chunks
+        WHEN length(NEW.embedding) > 0
+            AND EXISTS (SELECT 1 FROM ann_meta WHERE codebase_id = NEW.codebase_id)
+        BEGIN
+            INSERT OR IGNORE INTO ann_pending (chunk_id, codebase_id)
+            VALUES (NEW.id, NEW.codebase_id);
+        END;
+
+        CREATE TRIGGER IF NOT EXISTS chunks_ann_ad AFTER DELETE ON chunks BEGIN
+            UPDATE ann_nodes SET deleted = 1 WHERE chunk_id = OLD.id;
+            DELETE FROM ann_pending WHERE chunk_id = OLD.id;
+        END;
+"#;
+
+/// Add the columns later versions gave the ANN tables to an older database
+pub(crate) fn migrate(conn: &Connection) -> Result<()> {
+    let has_vector_format: bool = conn
+        .query_row(
+            "SELECT COUNT(*) > 0 FROM pragma_table_info('ann_meta') WHERE name = 'vector_format'",
+            [],
+            |row| row.get(0),
+        )
+        .map_err(CodeSearchError::Database)?;
+    if !has_vector_format {
+        conn.execute("ALTER TABLE ann_meta ADD COLUMN vector_format TEXT", [])
+            .map_err(CodeSearchError::Database)?;
+    }
+    Ok(())
+}
+
+/// Size of a codebase's ANN index
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AnnIndexStats {
+    /// Nodes that still point at a live chunk
+    pub live: usize,
+    /// Nodes kept only as tombstones for graph navigation
+    pub deleted: usize,
+}
+
+impl AnnIndexStats {
+    /// Share of graph nodes that are tombstones
+    pub fn fragmentation(&self) -> f64 {
+        let total = self.live + self.deleted;
+        if total == 0 {
+            0.0
+        } else {
+            self.deleted as f64 / total as f64
+        }
+    }
+}
+
+/// Whether an ANN index exists for the codebase
+pub fn has_ann_index(conn: &Connection, codebase_id: &str) -> Result<bool> {
+    conn.query_row(
+        "SELECT 1 FROM ann_meta WHERE codebase_id = ?1",
+        params![codebase_id],
+        |_| Ok(()),
+    )
+    .optional()
+    .map(|row| row.is_some())
+    .map_err(CodeSearchError::Database)
+}
+
+/// Node counts for a codebase's ANN index, or `None` if it has none
+pub fn ann_index_stats(conn: &Connection, codebase_id: &str) -> Result<Option<AnnIndexStats>> {
+    if !has_ann_index(conn, codebase_id)? {
+        return Ok(None);
+    }
+
+    let (live, deleted): (i64, i64) = conn
+        .query_row(
+            "SELECT COALESCE(SUM(deleted = 0), 0), COALESCE(SUM(deleted), 0)
+             FROM ann_nodes WHERE codebase_id = ?1",
+            params![codebase_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .map_err(CodeSearchError::Database)?;
+
+    Ok(Some(AnnIndexStats {
+        live: live as usize,
+        deleted: deleted as usize,
+    }))
+}
+
+/// Build (or rebuild) the ANN index for a codebase from its stored chunk
+/// embeddings. Once built, the index is kept up to date by `insert_chunks`
+/// and the chunk delete functions. Returns the number of indexed vectors.
+pub fn rebuild_ann_index(conn: &Connection, codebase_id: &str) -> Result<usize> {
+    let tx = conn
+        .unchecked_transaction()
+        .map_err(CodeSearchError::Database)?;
+    let count = rebuild(&tx, codebase_id)?;
+    tx.commit().map_err(CodeSearchError::Database)?;
+    Ok(count)
+}
+
+/// Approximate top-`limit` chunks by cosine similarity to `query_embedding`.
+/// Returns an empty list when the codebase has no ANN index.
+pub fn ann_search(
+    conn: &Connection,
+    codebase_id: &str,
+    query_embedding: &[f32],
+    limit: usize,
+) -> Result<Vec<SearchResult>> {
+    let limit = crate::database::checked_limit(limit.try_into().unwrap_or(i64::MAX)) as usize;
+    let mut graph = match Graph::load(conn, codebase_id)? {
+        Some(graph) => graph,
+        None => return Ok(Vec::new()),
+    };
+
+    // Tombstones take up places among the nearest candidates, so the search
+    // widens until it yields `limit` live chunks or runs out of nodes
+    let mut ef = graph.ef_search.max(limit);
+    loop {
+        let nearest = graph.search(query_embedding, ef)?;
+        let exhausted = nearest.len() < ef;
+
+        let mut results = Vec::with_capacity(limit);
+        for candidate in nearest {
+            if results.len() == limit {
+                break;
+            }
+            if graph.is_deleted(candidate.id)? {
+                continue;
+            }
+            let result = search_result_for_chunk(
+                conn,
+                candidate.id,
+                1.0 - candidate.distance as f64,
+                results.len() as i64 + 1,
+            )?;
+            results.extend(result);
+        }
+
+        if results.len() == limit || exhausted {
+            return Ok(results);
+        }
+        ef *= 2;
+    }
+}
+
+/// Create the index for a codebase when ANN is enabled in the config and the
+/// codebase doesn't have one yet.
+pub(crate) fn ensure_index(conn: &Connection, codebase_id: &str) -> Result<()> {
+    if get_config().performance.hnsw_enabled && !has_ann_index(conn, codebase_id)? {
+        rebuild(conn, codebase_id)?;
+    }
+    Ok(())
+}
+
+/// Fold queued inserts into the graph and rebuild it if tombstones have
+/// piled up past the configured threshold. A no-op for codebases without an
+/// ANN index.
+pub(crate) fn maintain(conn: &Connection, codebase_id: &str) -> Result<()> {
+    let stats = match ann_index_stats(conn, codebase_id)? {
+        Some(stats) => stats,
+        None => return Ok(()),
+    };
+
+    if stats.fragmentation() > get_config().performance.ann_rebuild_threshold {
+        rebuild(conn, codebase_id)?;
+        return Ok(());
+    }
+
+    let pending = load_pending(conn, codebase_id)?;
+    if pending.is_empty() {
+        return Ok(());
+    }
+
+    let mut graph = Graph::load(conn, codebase_id)?
+        .ok_or_else(|| CodeSearchError::Other("ANN index metadata vanished".to_string()))?;
+    for (chunk_id, vector) in pending {
+        graph.insert(chunk_id, vector)?;
+    }
+    graph.flush()?;
+
+    conn.execute(
+        "DELETE FROM ann_pending WHERE codebase_id = ?1",
+        params![codebase_id],
+    )
+    .map_err(CodeSearchError::Database)?;
+
+    Ok(())
+}
+
+/// Empty a codebase's graph while keeping the index enabled, used when all
+/// of its chunks are deleted.
+pub(crate) fn clear(conn: &Connection, codebase_id: &str) -> Result<()> {
+    conn.execute(
+        "DELETE FROM ann_edges WHERE chunk_id IN
+            (SELECT chunk_id FROM ann_nodes WHERE codebase_id = ?1)",
+        params![codebase_id],
+    )
+    .map_err(CodeSearchError::Database)?;
+    for sql in [
+        "DELETE FROM ann_nodes WHERE codebase_id = ?1",
+        "DELETE FROM ann_pending WHERE codebase_id = ?1",
+    ] {
+        conn.execute(sql, params![codebase_id])
+            .map_err(CodeSearchError::Database)?;
+    }
+    // The emptied graph stores vectors as the chunks do from now on
+    conn.execute(
+        "UPDATE ann_meta
+         SET entry_point = NULL, max_level = 0, dimension = NULL, vector_format = ?2
+         WHERE codebase_id = ?1",
+        params![codebase_id, embedding_format(conn)?.as_str()],
+    )
+    .map_err(CodeSearchError::Database)?;
+    Ok(())
+}
+
+/// Move a codebase's graph to a new codebase id; edges are keyed by chunk
+/// id and need no change
+pub(crate) fn rekey(conn: &Connection, old_codebase_id: &str, new_codebase_id: &str) -> Result<()> {
+    for sql in [
+        "UPDATE ann_meta SET codebase_id = ?2 WHERE codebase_id = ?1",
+        "UPDATE ann_nodes SET codebase_id = ?2 WHERE codebase_id = ?1",
+        "UPDATE ann_pending SET codebase_id = ?2 WHERE codebase_id = ?1",
+    ] {
+        conn.execute(sql, params![old_codebase_id, new_codebase_id])
+            .map_err(CodeSearchError::Database)?;
+    }
+    Ok(())
+}
+
+fn rebuild(conn: &Connection, codebase_id: &str) -> Result<usize> {
+    conn.execute(
+        "INSERT OR IGNORE INTO ann_meta (codebase_id) VALUES (?1)",
+        params![codebase_id],
+    )
+    .map_err(CodeSearchError::Database)?;
+    clear(conn, codebase_id)?;
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, embedding FROM chunks
+             WHERE codebase_id = ?1 AND length(embedding) > 0
+             ORDER BY id",
+        )
+        .map_err(CodeSearchError::Database)?;
+    let vectors = stmt
+        .query_map(params![codebase_id], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, Vec<u8>>(1)?))
+        })
+        .map_err(CodeSearchError::Database)?
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map_err(CodeSearchError::Database)?;
+
+    let format = embedding_format(conn)?;
+    let mut graph = Graph::load(conn, codebase_id)?
+        .ok_or_else(|| CodeSearchError::Other("ANN index metadata vanished".to_string()))?;
+    let mut count = 0;
+    for (chunk_id, blob) in vectors {
+        if graph.insert(chunk_id, deserialize_embedding(&blob, format))? {
+            count += 1;
+        }
+    }
+    graph.flush()?;
+
+    Ok(count)
+}
+
+fn load_pending(conn: &Connection, codebase_id: &str) -> Result<Vec<(i64, Vec<f32>)>> {
+    let format = embedding_format(conn)?;
+    let mut stmt = conn
+        .prepare(
+            "SELECT p.chunk_id, c.embedding FROM ann_pending p
+             JOIN chunks c ON c.id = p.chunk_id
+             WHERE p.codebase_id = ?1
+             ORDER BY p.chunk_id",
+        )
+        .map_err(CodeSearchError::Database)?;
+    let pending = stmt
+        .query_map(params![codebase_id], |row| {
+            let blob: Vec<u8> = row.get(1)?;
+            Ok((row.get(0)?, deserialize_embedding(&blob, format)))
+        })
+        .map_err(CodeSearchError::Database)?
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map_err(CodeSearchError::Database)?;
+    Ok(pending)
+}
+
+/// Deterministic node level drawn from the usual exponential distribution,
+/// seeded by the chunk id so rebuilds produce the same graph.
+fn node_level(chunk_id: i64, max_connections: usize) -> usize {
+    // splitmix64
+    let mut z = (chunk_id as u64).wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^= z >> 31;
+
+    let uniform = ((z >> 11) as f64 + 1.0) / (1u64 << 53) as f64;
+    let level_mult = 1.0 / (max_connections.max(2) as f64).ln();
+    ((-uniform.ln() * level_mult) as usize).min(MAX_LEVEL)
+}
+
+fn cosine_distance(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() {
+        return f32::MAX;
+    }
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm_a: f32 = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norm_b: f32 = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm_a == 0.0 || norm_b == 0.0 {
+        return 1.0;
+    }
+    1.0 - dot / (norm_a * norm_b)
+}
+
+fn encode_ids(ids: &[i64]) -> Vec<u8> {
+    ids.iter().flat_map(|id| id.to_le_bytes()).collect()
+}
+
+fn decode_ids(blob: &[u8]) -> Vec<i64> {
+    blob.chunks_exact(8)
+        .map(|bytes| i64::from_le_bytes(bytes.try_into().unwrap()))
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Candidate {
+    distance: f32,
+    id: i64,
+}
+
+impl PartialEq for Candidate {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Candidate {}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.distance
+            .total_cmp(&other.distance)
+            .then(self.id.cmp(&other.id))
+    }
+}
+
+struct Node {
+    vector: Vec<f32>,
+    deleted: bool,
+}
+
+/// A codebase's graph, loaded lazily from the database. Edge changes are
+/// buffered until [`Graph::flush`].
+struct Graph<'c> {
+    conn: &'c Connection,
+    codebase_id: String,
+    max_connections: usize,
+    ef_search: usize,
+    entry_point: Option<i64>,
+    max_level: usize,
+    dimension: Option<usize>,
+    /// Encoding of the vectors in `ann_nodes`
+    format: EmbeddingFormat,
+    nodes: HashMap<i64, Node>,
+    edges: HashMap<(i64, usize), Vec<i64>>,
+    dirty: HashSet<(i64, usize)>,
+}
+
+impl<'c> Graph<'c> {
+    fn load(conn: &'c Connection, codebase_id: &str) -> Result<Option<Self>> {
+        let meta = conn
+            .query_row(
+                "SELECT entry_point, max_level, dimension, vector_format
+                 FROM ann_meta WHERE codebase_id = ?1",
+                params![codebase_id],
+                |row| {
+                    Ok((
+                        row.get::<_, Option<i64>>(0)?,
+                        row.get::<_, i64>(1)?,
+                        row.get::<_, Option<i64>>(2)?,
+                        row.get::<_, Option<String>>(3)?,
+                    ))
+                },
+            )
+            .optional()
+            .map_err(CodeSearchError::Database)?;
+        let Some((entry_point, max_level, dimension, format)) = meta else {
+            return Ok(None);
+        };
+        // Graphs built before the column existed hold f32 vectors
+        let format = format
+            .as_deref()
+            .map(EmbeddingFormat::parse)
+            .transpose()?
+            .unwrap_or(EmbeddingFormat::F32);
+
+        let config = &get_config().performance;
+        Ok(Some(Self {
+            conn,
+            codebase_id: codebase_id.to_string(),
+            max_connections: config.hnsw_max_connections.max(2),
+            ef_search: config.hnsw_ef_search.max(1),
+            entry_point,
+            max_level: max_level as usize,
+            dimension: dimension.map(|d| d as usize),
+            format,
+            nodes: HashMap::new(),
+            edges: HashMap::new(),
+            dirty: HashSet::new(),
+        }))
+    }
+
+    fn max_neighbors(&self, layer: usize) -> usize {
+        if layer == 0 {
+            self.max_connections * 2
+        } else {
+            self.max_connections
+        }
+    }
+
+    fn node(&mut self, id: i64) -> Result<&Node> {
+        if !self.nodes.contains_key(&id) {
+            let (blob, deleted): (Vec<u8>, bool) = self
+                .conn
+                .prepare_cached("SELECT vector, deleted FROM ann_nodes WHERE chunk_id = ?1")
+                .and_then(|mut stmt| {
+                    stmt.query_row(params![id], |row| Ok((row.get(0)?, row.get(1)?)))
+                })
+                .map_err(CodeSearchError::Database)?;
+            self.nodes.insert(
+                id,
+                Node {
+                    vector: deserialize_embedding(&blob, self.format),
+                    deleted,
+                },
+            );
+        }
+        Ok(&self.nodes[&id])
+    }
+
+    fn is_deleted(&mut self, id: i64) -> Result<bool> {
+        Ok(self.node(id)?.deleted)
+    }
+
+    fn distance(&mut self, query: &[f32], id: i64) -> Result<f32> {
+        Ok(cosine_distance(query, &self.node(id)?.vector))
+    }
+
+    fn neighbors(&mut self, id: i64, layer: usize) -> Result<Vec<i64>> {
+        if let Some(neighbors) = self.edges.get(&(id, layer)) {
+            return Ok(neighbors.clone());
+        }
+        let blob: Option<Vec<u8>> = self
+            .conn
+            .prepare_cached("SELECT neighbors FROM ann_edges WHERE chunk_id = ?1 AND layer = ?2")
+            .and_then(|mut stmt| {
+                stmt.query_row(params![id, layer as i64], |row| row.get(0))
+                    .optional()
+            })
+            .map_err(CodeSearchError::Database)?;
+        let neighbors = blob.map(|b| decode_ids(&b)).unwrap_or_default();
+        self.edges.insert((id, layer), neighbors.clone());
+        Ok(neighbors)
+    }
+
+    fn set_neighbors(&mut self, id: i64, layer: usize, neighbors: Vec<i64>) {
+        self.edges.insert((id, layer), neighbors);
+        self.dirty.insert((id, layer));
+    }
+
+    /// Greedy descent through one layer towards `query`
+    fn closest(&mut self, query: &[f32], mut best: Candidate, layer: usize) -> Result<Candidate> {
+        loop {
+            let mut improved = false;
+            for id in self.neighbors(best.id, layer)? {
+                let distance = self.distance(query, id)?;
+                if distance < best.distance {
+                    best = Candidate { distance, id };
+                    improved = true;
+                }
+            }
+            if !improved {
+                return Ok(best);
+            }
+        }
+    }
+
+    /// Beam search of one layer; returns up to `ef` candidates, closest first
+    fn search_layer(
+        &mut self,
+        query: &[f32],
+        entry: &[Candidate],
+        ef: usize,
+        layer: usize,
+    ) -> Result<Vec<Candidate>> {
+        let mut visited: HashSet<i64> = entry.iter().map(|c| c.id).collect();
+        let mut frontier: BinaryHeap<std::cmp::Reverse<Candidate>> =
+            entry.iter().copied().map(std::cmp::Reverse).collect();
+        let mut found: BinaryHeap<Candidate> = entry.iter().copied().collect();
+
+        while let Some(std::cmp::Reverse(current)) = frontier.pop() {
+            let worst = found.peek().map(|c| c.distance).unwrap_or(f32::MAX);
+            if current.distance > worst && found.len() >= ef {
+                break;
+            }
+            for id in self.neighbors(current.id, layer)? {
+                if !visited.insert(id) {
+                    continue;
+                }
+                let distance = self.distance(query, id)?;
+                let worst = found.peek().map(|c| c.distance).unwrap_or(f32::MAX);
+                if found.len() < ef || distance < worst {
+                    let candidate = Candidate { distance, id };
+                    frontier.push(std::cmp::Reverse(candidate));
+                    found.push(candidate);
+                    if found.len() > ef {
+                        found.pop();
+                    }
+                }
+            }
+        }
+
+        Ok(found.into_sorted_vec())
+    }
+
+    fn search(&mut self, query: &[f32], ef: usize) -> Result<Vec<Candidate>> {
+        let entry_point = match self.entry_point {
+            Some(id) => id,
+            None => return Ok(Vec::new()),
+        };
+        if self.dimension != Some(query.len()) {
+            return Err(CodeSearchError::InvalidInput(format!(
+                "Query embedding has dimension {} but the ANN index expects {:?}",
+                query.len(),
+                self.dimension
+            )));
+        }
+
+        let mut best = Candidate {
+            distance: self.distance(query, entry_point)?,
+            id: entry_point,
+        };
+        for layer in (1..=self.max_level).rev() {
+            best = self.closest(query, best, layer)?;
+        }
+        self.search_layer(query, &[best], ef, 0)
+    }
+
+    /// Link a new point into the graph. Vectors whose dimension doesn't match
+    /// the index are skipped; returns whether the point was added.
+    fn insert(&mut self, id: i64, vector: Vec<f32>) -> Result<bool> {
+        match self.dimension {
+            Some(dim) if dim != vector.len() => return Ok(false),
+            None if vector.is_empty() => return Ok(false),
+            _ => self.dimension = Some(vector.len()),
+        }
+
+        let level = node_level(id, self.max_connections);
+        self.conn
+            .prepare_cached(
+                "INSERT OR REPLACE INTO ann_nodes (chunk_id, codebase_id, level, vector, deleted)
+                 VALUES (?1, ?2, ?3, ?4, 0)",
+            )
+            .and_then(|mut stmt| {
+                let blob = serialize_embedding(&vector, self.format);
+                stmt.execute(params![id, self.codebase_id, level as i64, blob])
+            })
+            .map_err(CodeSearchError::Database)?;
+        self.nodes.insert(
+            id,
+            Node {
+                vector: vector.clone(),
+                deleted: false,
+            },
+        );
+
+        let entry_point = match self.entry_point {
+            Some(entry_point) => entry_point,
+            None => {
+                self.entry_point = Some(id);
+                self.max_level = level;
+                return Ok(true);
+            }
+        };
+
+        let mut best = Candidate {
+            distance: self.distance(&vector, entry_point)?,
+            id: entry_point,
+        };
+        for layer in (level + 1..=self.max_level).rev() {
+            best = self.closest(&vector, best, layer)?;
+        }
+
+        let mut entry = vec![best];
+        for layer in (0..=level.min(self.max_level)).rev() {
+            let found = self.search_layer(&vector, &entry, EF_CONSTRUCTION, layer)?;
+            let selected: Vec<i64> = found
+                .iter()
+                .take(self.max_connections)
+                .map(|c| c.id)
+                .collect();
+            self.set_neighbors(id, layer, selected.clone());
+
+            let max_neighbors = self.max_neighbors(layer);
+            for neighbor in selected {
+                let mut links = self.neighbors(neighbor, layer)?;
+                links.push(id);
+                if links.len() > max_neighbors {
+                    let origin = self.node(neighbor)?.vector.clone();
+                    let mut scored = Vec::with_capacity(links.len());
+                    for link in links {
+                        scored.push(Candidate {
+                            distance: self.distance(&origin, link)?,
+                            id: link,
+                        });
+                    }
+                    scored.sort();
+                    links = scored
+                        .into_iter()
+                        .take(max_neighbors)
+                        .map(|c| c.id)
+                        .collect();
+                }
+                self.set_neighbors(neighbor, layer, links);
+            }
+            entry = found;
+        }
+
+        if level > self.max_level {
+            self.entry_point = Some(id);
+            self.max_level = level;
+        }
+        Ok(true)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        {
+            let mut stmt = self
+                .conn
+                .prepare_cached(
+                    "INSERT OR REPLACE INTO ann_edges (chunk_id, layer, neighbors)
+                     VALUES (?1, ?2, ?3)",
+                )
+                .map_err(CodeSearchError::Database)?;
+            for key in self.dirty.drain() {
+                let (id, layer) = key;
+                stmt.execute(params![id, layer as i64, encode_ids(&self.edges[&key])])
+                    .map_err(CodeSearchError::Database)?;
+            }
+        }
+
+        self.conn
+            .execute(
+                "UPDATE ann_meta SET entry_point = ?2, max_level = ?3, dimension = ?4
+                 WHERE codebase_id = ?1",
+                params![
+                    self.codebase_id,
+                    self.entry_point,
+                    self.max_level as i64,
+                    self.dimension.map(|d| d as i64)
+                ],
+            )
+            .map_err(CodeSearchError::Database)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::{delete_chunks_for_file, init_db_at, insert_chunks, Chunk};
+
+    fn embedding(seed: usize) -> Vec<f32> {
+        (0..16)
+            .map(|j| ((seed + 1) as f32 * (j + 1) as f32 * 0.37).sin())
+            .collect()
+    }
+
+    fn chunk(file: usize, seed: usize) -> Chunk {
+        Chunk {
+            id: None,
+            codebase_id: "ann".to_string(),
+            file_path: format!("src/file_{}.rs", file),
+            start_line: seed as i64 * 10 + 1,
+            end_line: seed as i64 * 10 + 10,
+            content: format!("fn item_{}() {{}}", seed),
+            language: Some("rust".to_string()),
+            embedding: embedding(seed),
+            hash: format!("{:016x}", seed),
+            chunk_kind: Some("code".to_string()),
+            author: None,
+        }
+    }
+
+    fn top_hit(conn: &Connection, seed: usize) -> Option<(String, i64)> {
+        ann_search(conn, "ann", &embedding(seed), 1)
+            .unwrap()
+            .into_iter()
+            .next()
+            .map(|r| (r.file_path, r.start_line))
+    }
+
+    #[test]
+    fn test_ann_tracks_inserts_and_deletes_without_rebuild() {
+        let dir = tempfile::tempdir().unwrap();
+        let conn = init_db_at(&dir.path().join("index.db")).unwrap();
+
+        // Enable the index on an empty codebase; everything after this is incremental
+        assert_eq!(rebuild_ann_index(&conn, "ann").unwrap(), 0);
+
+        let chunks: Vec<Chunk> = (0..60).map(|seed| chunk(seed % 6, seed)).collect();
+        insert_chunks(&conn, &chunks).unwrap();
+        let stats = ann_index_stats(&conn, "ann").unwrap().unwrap();
+        assert_eq!(
+            stats,
+            AnnIndexStats {
+                live: 60,
+                deleted: 0
+            }
+        );
+        for seed in [0, 17, 59] {
+            assert_eq!(
+                top_hit(&conn, seed),
+                Some((format!("src/file_{}.rs", seed % 6), seed as i64 * 10 + 1))
+            );
+        }
+
+        // Deleted chunks drop out of results straight away
+        delete_chunks_for_file(&conn, "ann", "src/file_5.rs").unwrap();
+        assert_eq!(
+            ann_index_stats(&conn, "ann").unwrap().unwrap(),
+            AnnIndexStats {
+                live: 50,
+                deleted: 10
+            }
+        );
+        let hits = ann_search(&conn, "ann", &embedding(5), 60).unwrap();
+        assert_eq!(hits.len(), 50);
+        assert!(hits.iter().all(|r| r.file_path != "src/file_5.rs"));
+
+        // Re-inserted chunks get new ids and are found again
+        let reinserted: Vec<Chunk> = (0..60)
+            .filter(|seed| seed % 6 == 5)
+            .map(|seed| chunk(5, seed))
+            .collect();
+        insert_chunks(&conn, &reinserted).unwrap();
+        assert_eq!(top_hit(&conn, 5), Some(("src/file_5.rs".to_string(), 51)));
+        assert_eq!(
+            ann_search(&conn, "ann", &embedding(5), 100).unwrap().len(),
+            60
+        );
+    }
+
+    #[test]
+    fn test_ann_fills_the_limit_past_tombstones() {
+        let dir = tempfile::tempdir().unwrap();
+        let conn = init_db_at(&dir.path().join("index.db")).unwrap();
+        rebuild_ann_index(&conn, "ann").unwrap();
+
+        let chunks: Vec<Chunk> = (0..120).map(|seed| chunk(seed % 12, seed)).collect();
+        insert_chunks(&conn, &chunks).unwrap();
+        delete_chunks_for_file(&conn, "ann", "src/file_0.rs").unwrap();
+        assert_eq!(ann_index_stats(&conn, "ann").unwrap().unwrap().deleted, 10);
+
+        // More than the default ef_search, so tombstones crowd the first pass
+        let hits = ann_search(&conn, "ann", &embedding(0), 110).unwrap();
+        assert_eq!(hits.len(), 110);
+        assert!(hits.iter().all(|r| r.file_path != "src/file_0.rs"));
+    }
+
+    #[test]
+    fn test_ann_stores_vectors_in_the_embedding_format() {
+        use crate::database::set_embedding_format;
+
+        let dir = tempfile::tempdir().unwrap();
+        let conn = init_db_at(&dir.path().join("index.db")).unwrap();
+        set_embedding_format(&conn, EmbeddingFormat::F16).unwrap();
+        rebuild_ann_index(&conn, "ann").unwrap();
+
+        let chunks: Vec<Chunk> = (0..30).map(|seed| chunk(seed % 3, seed)).collect();
+        insert_chunks(&conn, &chunks).unwrap();
+        let lengths: Vec<i64> = conn
+            .prepare("SELECT DISTINCT length(vector) FROM ann_nodes")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<std::result::Result<_, _>>()
+            .unwrap();
+        assert_eq!(lengths, vec![16 * 2]);
+        assert_eq!(top_hit(&conn, 7), Some(("src/file_1.rs".to_string(), 71)));
+    }
+
+    #[test]
+    fn test_ann_rebuilds_past_fragmentation_threshold() {
+        let dir = tempfile::tempdir().unwrap();
+        let conn = init_db_at(&dir.path().join("index.db")).unwrap();
+        rebuild_ann_index(&conn, "ann").unwrap();
+
+        let chunks: Vec<Chunk> = (0..40).map(|seed| chunk(seed % 4, seed)).collect();
+        insert_chunks(&conn, &chunks).unwrap();
+
+        // A quarter of the nodes is within the default threshold...
+        delete_chunks_for_file(&conn, "ann", "src/file_0.rs").unwrap();
+        assert_eq!(ann_index_stats(&conn, "ann").unwrap().unwrap().deleted, 10);
+
+        // ...but a further file pushes it over and compacts the graph
+        delete_chunks_for_file(&conn, "ann", "src/file_1.rs").unwrap();
+        assert_eq!(
+            ann_index_stats(&conn, "ann").unwrap().unwrap(),
+            AnnIndexStats {
+                live: 20,
+                deleted: 0
+            }
+        );
+        assert_eq!(top_hit(&conn, 2), Some(("src/file_2.rs".to_string(), 21)));
+    }
+
+    #[test]
+    fn test_codebases_without_ann_index_are_untouched() {
+        let dir = tempfile::tempdir().unwrap();
+        let conn = init_db_at(&dir.path().join("index.db")).unwrap();
+
+        insert_chunks(&conn, &[chunk(0, 0)]).unwrap();
+        assert!(!has_ann_index(&conn, "ann").unwrap());
+        assert!(ann_search(&conn, "ann", &embedding(0), 5)
+            .unwrap()
+            .is_empty());
+
+        assert_eq!(rebuild_ann_index(&conn, "ann").unwrap(), 1);
+        assert_eq!(top_hit(&conn, 0), Some(("src/file_0.rs".to_string(), 1)));
+    }
+    #[test]
+    fn test_vector_search_consults_ann_index() {
+        use crate::database::{vector_search, vector_search_filtered, SearchFilters};
+
+        let dir = tempfile::tempdir().unwrap();
+        let conn = init_db_at(&dir.path().join("index.db")).unwrap();
+        let mut chunks: Vec<Chunk> = (0..60).map(|seed| chunk(seed % 6, seed)).collect();
+        chunks[17].language = Some("python".to_string());
+        insert_chunks(&conn, &chunks).unwrap();
+
+        let hits = |conn: &Connection, seed: usize| -> Vec<(String, i64)> {
+            vector_search(conn, Some("ann"), &embedding(seed), 3)
+                .unwrap()
+                .into_iter()
+                .map(|r| (r.file_path, r.start_line))
+                .collect()
+        };
+        let scanned: Vec<_> = [0, 17, 59].map(|seed| hits(&conn, seed)).into();
+
+        rebuild_ann_index(&conn, "ann").unwrap();
+        let indexed: Vec<_> = [0, 17, 59].map(|seed| hits(&conn, seed)).into();
+        assert_eq!(indexed, scanned);
+
+        // Filters still go through the scan, which applies them exactly
+        let filters = SearchFilters::parse("-lang:python").unwrap();
+        let filtered =
+            vector_search_filtered(&conn, Some("ann"), &embedding(17), 3, &filters).unwrap();
+        assert_eq!(filtered.len(), 3);
+        assert!(filtered
+            .iter()
+            .all(|r| r.language.as_deref() == Some("rust")));
+    }
+}
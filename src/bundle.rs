@@ -0,0 +1,593 @@
+//! Portable single-file index bundles.
+//!
+//! A bundle is a SQLite database with the regular index schema holding one
+//! codebase: its chunks and embeddings, its metadata and chunking, and the
+//! model the embeddings came from. It can be copied to another machine and
+//! searched read-only, without the source tree, or imported into that
+//! machine's index with its manifest so later runs only re-embed what
+//! changed.
+
+use crate::database::{
+    delete_chunks_for_codebase, embedding_format, get_chunking_params, get_codebase_metadata,
+    init_db, init_db_at, insert_chunks, iter_chunks_by_file, register_codebase,
+    set_chunking_params, set_embedding_format, Chunk,
+};
+use crate::embedding::{ModelType, DEFAULT_MODEL};
+use crate::error::{CodeSearchError, Result};
+use crate::manifest::{
+    get_manifest_path, load_index_params, load_manifest, save_index_params, save_manifest,
+    IndexParams, Manifest,
+};
+use rusqlite::{params, Connection, OpenFlags, OptionalExtension};
+use std::fs;
+use std::path::Path;
+
+/// Layout version written into every bundle
+pub const BUNDLE_VERSION: i64 = 1;
+
+// Chunks copied per insert, so a large codebase isn't held in memory at once
+const COPY_PAGE_ROWS: usize = 1000;
+
+/// What a bundle holds, read from its `index_settings`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BundleInfo {
+    pub codebase_id: String,
+    pub name: String,
+    /// Model the embeddings were computed with; queries must use it too
+    pub model: String,
+    pub dimension: usize,
+    pub chunk_count: usize,
+}
+
+/// Write the indexed codebase `codebase_id` to a new bundle at `out`,
+/// replacing any file there, together with its manifest when it has one.
+/// Fails if the codebase isn't indexed or its embeddings don't match its
+/// recorded model.
+pub fn create_bundle(conn: &Connection, codebase_id: &str, out: &Path) -> Result<BundleInfo> {
+    let meta = get_codebase_metadata(conn, codebase_id)?
+        .ok_or_else(|| CodeSearchError::CodebaseNotIndexed(codebase_id.to_string()))?;
+    let model = meta
+        .model
+        .clone()
+        .unwrap_or_else(|| DEFAULT_MODEL.to_string());
+    let format = embedding_format(conn)?;
+
+    let stored_len: Option<i64> = conn
+        .query_row(
+            "SELECT length(embedding) FROM chunks
+             WHERE codebase_id = ?1 AND length(embedding) > 0 LIMIT 1",
+            params![codebase_id],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(CodeSearchError::Database)?;
+    let dimension = stored_len
+        .map(|len| format.dimension(len as usize))
+        .ok_or_else(|| CodeSearchError::CodebaseNotIndexed(meta.path.clone()))?;
+    let model_dimension = ModelType::parse(&model).dimension();
+    if dimension != model_dimension {
+        return Err(CodeSearchError::InvalidConfiguration(format!(
+            "{} has {}-dimensional embeddings but its model '{}' produces {}; \
+             re-index it before bundling",
+            meta.path, dimension, model, model_dimension
+        )));
+    }
+
+    if out.exists() {
+        fs::remove_file(out)?;
+    }
+    let bundle = init_db_at(out)?;
+    set_embedding_format(&bundle, format)?;
+
+    let chunk_count = copy_chunks(conn, &bundle, codebase_id)?;
+
+    register_codebase(
+        &bundle,
+        codebase_id,
+        &meta.name,
+        &meta.path,
+        Some(&model),
+        meta.tags.as_deref(),
+    )?;
+    if let Some((chunk_size, chunk_overlap)) = get_chunking_params(conn, codebase_id)? {
+        set_chunking_params(&bundle, codebase_id, chunk_size, chunk_overlap)?;
+    }
+
+    let info = BundleInfo {
+        codebase_id: codebase_id.to_string(),
+        name: meta.name,
+        model,
+        dimension,
+        chunk_count,
+    };
+    write_info(&bundle, &info)?;
+
+    let manifest_path = get_manifest_path()?.join(format!("{}.json", codebase_id));
+    if manifest_path.exists() {
+        let manifest = serde_json::to_string(&load_manifest(&manifest_path)?)?;
+        write_setting(&bundle, "bundle_manifest", &manifest)?;
+        if let Some(params) = load_index_params(&manifest_path)? {
+            write_setting(
+                &bundle,
+                "bundle_index_params",
+                &serde_json::to_string(&params)?,
+            )?;
+        }
+    }
+
+    Ok(info)
+}
+
+/// Bundle the codebase `codebase_id` from this machine's index into
+/// `out_path`, for [`import_index`] elsewhere
+pub fn export_index(codebase_id: &str, out_path: &Path) -> Result<()> {
+    let conn = init_db()?;
+    create_bundle(&conn, codebase_id, out_path)?;
+    Ok(())
+}
+
+/// Load the bundle at `in_path` into this machine's index, replacing any
+/// chunks already held for its codebase, and restore its manifest. Returns
+/// the codebase id, which still names the path the bundle was built from.
+/// Fails if the configured model would embed queries differently from the
+/// bundle's embeddings.
+pub fn import_index(in_path: &Path) -> Result<String> {
+    let (bundle, info) = open_bundle(in_path, None)?;
+    let config = crate::config::get_config();
+    let local_model = config.model_name();
+    let local_dimension = ModelType::parse(local_model).dimension();
+    if ModelType::parse(local_model) != ModelType::parse(&info.model)
+        || local_dimension != info.dimension
+    {
+        return Err(CodeSearchError::InvalidConfiguration(format!(
+            "{} holds {}-dimensional embeddings from model '{}', but the configured model \
+             '{}' produces {}; set model.model_type = \"{}\" to import it",
+            in_path.display(),
+            info.dimension,
+            info.model,
+            local_model,
+            local_dimension,
+            info.model
+        )));
+    }
+    let meta = get_codebase_metadata(&bundle, &info.codebase_id)?
+        .ok_or_else(|| CodeSearchError::CodebaseNotIndexed(info.codebase_id.clone()))?;
+
+    // A failed copy leaves the codebase's previous chunks in place
+    let conn = init_db()?;
+    let tx = conn
+        .unchecked_transaction()
+        .map_err(CodeSearchError::Database)?;
+    delete_chunks_for_codebase(&tx, &info.codebase_id)?;
+    copy_chunks(&bundle, &tx, &info.codebase_id)?;
+
+    register_codebase(
+        &tx,
+        &info.codebase_id,
+        &meta.name,
+        &meta.path,
+        Some(&info.model),
+        meta.tags.as_deref(),
+    )?;
+    if let Some((chunk_size, chunk_overlap)) = get_chunking_params(&bundle, &info.codebase_id)? {
+        set_chunking_params(&tx, &info.codebase_id, chunk_size, chunk_overlap)?;
+    }
+    tx.commit().map_err(CodeSearchError::Database)?;
+
+    let manifest_path = get_manifest_path()?.join(format!("{}.json", info.codebase_id));
+    if let Some(manifest) = read_setting(&bundle, "bundle_manifest")? {
+        let manifest: Manifest = serde_json::from_str(&manifest)?;
+        save_manifest(&manifest_path, &manifest)?;
+    }
+    if let Some(params) = read_setting(&bundle, "bundle_index_params")? {
+        let params: IndexParams = serde_json::from_str(&params)?;
+        save_index_params(&manifest_path, &params)?;
+    }
+
+    Ok(info.codebase_id)
+}
+
+/// Copy the chunks of `codebase_id` from `from` to `to` a page at a time.
+/// Returns the chunks copied.
+fn copy_chunks(from: &Connection, to: &Connection, codebase_id: &str) -> Result<usize> {
+    let mut copied = 0;
+    let mut page: Vec<Chunk> = Vec::with_capacity(COPY_PAGE_ROWS);
+    for chunk in iter_chunks_by_file(from, codebase_id) {
+        page.push(chunk?);
+        if page.len() >= COPY_PAGE_ROWS {
+            copied += insert_chunks(to, &page)? as usize;
+            page.clear();
+        }
+    }
+    copied += insert_chunks(to, &page)? as usize;
+    Ok(copied)
+}
+
+/// Open the bundle at `path` read-only for searching with `model`, or with
+/// the model it was built with when `None`. Fails if the file isn't a bundle,
+/// or was built with a different model or embedding dimension than `model`.
+pub fn open_bundle(path: &Path, model: Option<&str>) -> Result<(Connection, BundleInfo)> {
+    if !path.is_file() {
+        return Err(CodeSearchError::FileRead {
+            path: path.to_string_lossy().to_string(),
+        });
+    }
+    let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .map_err(CodeSearchError::Database)?;
+    let info = read_info(&conn).map_err(|_| {
+        CodeSearchError::InvalidInput(format!("{} is not a code-search bundle", path.display()))
+    })?;
+
+    let model = model.unwrap_or(&info.model);
+    if ModelType::parse(&info.model) != ModelType::parse(model) {
+        return Err(CodeSearchError::InvalidConfiguration(format!(
+            "{} was built with model '{}'; search it with --model {}",
+            path.display(),
+            info.model,
+            info.model
+        )));
+    }
+    let model_dimension = ModelType::parse(model).dimension();
+    let format = embedding_format(&conn)?;
+    let stored_len: Option<i64> = conn
+        .query_row(
+            "SELECT length(embedding) FROM chunks WHERE length(embedding) > 0 LIMIT 1",
+            [],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(CodeSearchError::Database)?;
+    let stored_dimension = stored_len.map(|len| format.dimension(len as usize));
+    if info.dimension != model_dimension || stored_dimension.is_some_and(|d| d != info.dimension) {
+        return Err(CodeSearchError::InvalidConfiguration(format!(
+            "{} holds {}-dimensional embeddings but model '{}' produces {}",
+            path.display(),
+            stored_dimension.unwrap_or(info.dimension),
+            model,
+            model_dimension
+        )));
+    }
+
+    Ok((conn, info))
+}
+
+fn write_info(conn: &Connection, info: &BundleInfo) -> Result<()> {
+    let settings = [
+        ("bundle_version", BUNDLE_VERSION.to_string()),
+        ("bundle_codebase_id", info.codebase_id.clone()),
+        ("bundle_model", info.model.clone()),
+        ("bundle_dimension", info.dimension.to_string()),
+    ];
+    for (key, value) in settings {
+        write_setting(conn, key, &value)?;
+    }
+    Ok(())
+}
+
+fn write_setting(conn: &Connection, key: &str, value: &str) -> Result<()> {
+    conn.execute(
+        "INSERT OR REPLACE INTO index_settings (key, value) VALUES (?1, ?2)",
+        params![key, value],
+    )
+    .map_err(CodeSearchError::Database)?;
+    Ok(())
+}
+
+/// An optional `index_settings` value of a bundle
+fn read_setting(conn: &Connection, key: &str) -> Result<Option<String>> {
+    conn.query_row(
+        "SELECT value FROM index_settings WHERE key = ?1",
+        params![key],
+        |row| row.get(0),
+    )
+    .optional()
+    .map_err(CodeSearchError::Database)
+}
+
+fn read_info(conn: &Connection) -> Result<BundleInfo> {
+    let setting = |key: &str| -> Result<String> {
+        conn.query_row(
+            "SELECT value FROM index_settings WHERE key = ?1",
+            params![key],
+            |row| row.get(0),
+        )
+        .map_err(CodeSearchError::Database)
+    };
+
+    let version: i64 = setting("bundle_version")?
+        .parse()
+        .map_err(|_| CodeSearchError::InvalidInput("invalid bundle_version".to_string()))?;
+    if version != BUNDLE_VERSION {
+        return Err(CodeSearchError::InvalidInput(format!(
+            "unsupported bundle version {}",
+            version
+        )));
+    }
+    let codebase_id = setting("bundle_codebase_id")?;
+    let dimension = setting("bundle_dimension")?
+        .parse()
+        .map_err(|_| CodeSearchError::InvalidInput("invalid bundle_dimension".to_string()))?;
+    let name = conn
+        .query_row(
+            "SELECT name FROM codebases WHERE codebase_id = ?1",
+            params![codebase_id],
+            |row| row.get(0),
+        )
+        .map_err(CodeSearchError::Database)?;
+    let chunk_count: i64 = conn
+        .query_row(
+            "SELECT COUNT(*) FROM chunks WHERE codebase_id = ?1",
+            params![codebase_id],
+            |row| row.get(0),
+        )
+        .map_err(CodeSearchError::Database)?;
+
+    Ok(BundleInfo {
+        model: setting("bundle_model")?,
+        codebase_id,
+        name,
+        dimension,
+        chunk_count: chunk_count as usize,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::{hybrid_search, init_db, iter_chunks, SearchFilters};
+    use crate::embedding::get_query_embedding_with_model;
+    use crate::indexing::{Indexer, IndexingOptions};
+    use crate::manifest::get_codebase_hash;
+
+    #[test]
+    fn test_bundle_round_trip_search() {
+        let _data_dir = crate::test_support::use_temp_data_dir();
+
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("src");
+        fs::create_dir_all(&src).unwrap();
+        fs::write(
+            src.join("ledger.rs"),
+            "fn reconcile_ledger_entries() {\n    // balance debits and credits\n}\n",
+        )
+        .unwrap();
+        fs::write(src.join("mailer.rs"), "fn send_ledger_summary_mail() {}\n").unwrap();
+        fs::write(src.join("other.rs"), "fn unrelated_helper() {}\n").unwrap();
+        Indexer::new(IndexingOptions::default())
+            .index_codebase(&src)
+            .unwrap();
+
+        let codebase_id = get_codebase_hash(&src.canonicalize().unwrap());
+        let conn = init_db().unwrap();
+        let out = dir.path().join("ledger.bundle");
+        let info = create_bundle(&conn, &codebase_id, &out).unwrap();
+        assert_eq!(info.chunk_count, 3);
+        assert_eq!(info.model, DEFAULT_MODEL);
+
+        // The bundle stands alone once the sources are gone
+        fs::remove_dir_all(&src).unwrap();
+        let (bundle, opened) = open_bundle(&out, None).unwrap();
+        assert_eq!(opened, info);
+
+        let query = "reconcile ledger";
+        let embedding = get_query_embedding_with_model(query, DEFAULT_MODEL);
+        let top = |conn: &Connection| {
+            hybrid_search(
+                conn,
+                query,
+                Some(&codebase_id),
+                &embedding,
+                3,
+                &SearchFilters::default(),
+                false,
+            )
+            .unwrap()
+            .into_iter()
+            .map(|r| (r.file_path, r.start_line, r.end_line))
+            .collect::<Vec<_>>()
+        };
+        let expected = top(&conn);
+        assert_eq!(expected[0].0, "ledger.rs");
+        assert_eq!(top(&bundle), expected);
+
+        // Read-only: nothing can be written through the bundle connection
+        assert!(bundle.execute("DELETE FROM chunks", []).is_err());
+
+        assert!(matches!(
+            open_bundle(&out, Some("nomic")),
+            Err(CodeSearchError::InvalidConfiguration(_))
+        ));
+        assert!(matches!(
+            open_bundle(&dir.path().join("missing.bundle"), None),
+            Err(CodeSearchError::FileRead { .. })
+        ));
+    }
+
+    #[test]
+    fn test_export_import_round_trip() {
+        let _data_dir = crate::test_support::use_temp_data_dir();
+
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("shared");
+        fs::create_dir_all(&src).unwrap();
+        fs::write(
+            src.join("invoice.rs"),
+            "fn render_invoice_pdf() {\n    // exported on CI\n}\n",
+        )
+        .unwrap();
+        fs::write(src.join("tax.py"), "def compute_export_tax():\n    pass\n").unwrap();
+        Indexer::new(IndexingOptions::default())
+            .index_codebase(&src)
+            .unwrap();
+
+        let codebase_id = get_codebase_hash(&src.canonicalize().unwrap());
+        let conn = init_db().unwrap();
+        let snapshot = || {
+            let mut chunks: Vec<_> = iter_chunks(&conn, &codebase_id)
+                .map(|chunk| {
+                    let c = chunk.unwrap();
+                    (
+                        c.file_path,
+                        c.start_line,
+                        c.end_line,
+                        c.content,
+                        c.language,
+                        c.embedding,
+                        c.hash,
+                    )
+                })
+                .collect();
+            chunks.sort_by(|a, b| (&a.0, a.1).cmp(&(&b.0, b.1)));
+            chunks
+        };
+        let before = snapshot();
+        assert_eq!(before.len(), 2);
+        let manifest_path = get_manifest_path()
+            .unwrap()
+            .join(format!("{}.json", codebase_id));
+        let manifest = load_manifest(&manifest_path).unwrap();
+
+        let out = dir.path().join("shared.bundle");
+        export_index(&codebase_id, &out).unwrap();
+
+        // As on a machine that never indexed it
+        delete_chunks_for_codebase(&conn, &codebase_id).unwrap();
+        crate::manifest::delete_manifest(&codebase_id).unwrap();
+        assert!(snapshot().is_empty());
+
+        assert_eq!(import_index(&out).unwrap(), codebase_id);
+        assert_eq!(snapshot(), before);
+        assert_eq!(load_manifest(&manifest_path).unwrap(), manifest);
+        assert!(load_index_params(&manifest_path).unwrap().is_some());
+        let meta = get_codebase_metadata(&conn, &codebase_id).unwrap().unwrap();
+        assert_eq!(meta.name, "shared");
+        assert_eq!(meta.model.as_deref(), Some(DEFAULT_MODEL));
+
+        // Nothing to re-embed against the unchanged tree
+        let stats = Indexer::new(IndexingOptions::default())
+            .index_codebase(&src)
+            .unwrap();
+        assert_eq!(stats.files_indexed, 0);
+
+        // Importing again replaces rather than duplicates
+        import_index(&out).unwrap();
+        assert_eq!(snapshot(), before);
+    }
+
+    #[test]
+    fn test_copy_counts_identifiers_of_files_longer_than_a_page() {
+        let dir = tempfile::tempdir().unwrap();
+        let from = init_db_at(&dir.path().join("from.db")).unwrap();
+        let to = init_db_at(&dir.path().join("to.db")).unwrap();
+
+        // A file of more chunks than a page, interleaved by id with another
+        let chunk = |file_path: &str, line: usize| Chunk {
+            id: None,
+            codebase_id: "paged".to_string(),
+            file_path: file_path.to_string(),
+            start_line: line as i64,
+            end_line: line as i64,
+            content: format!("let stream_{} = parse_tokens();", line),
+            language: Some("rust".to_string()),
+            embedding: vec![1.0, 0.0, 0.5],
+            hash: format!("{:016x}", line),
+            chunk_kind: Some("code".to_string()),
+            author: None,
+        };
+        let chunks: Vec<Chunk> = (1..=COPY_PAGE_ROWS + 10)
+            .flat_map(|line| {
+                let mut chunks = vec![chunk("big.rs", line)];
+                if line % 100 == 0 {
+                    chunks.push(chunk("small.rs", line));
+                }
+                chunks
+            })
+            .collect();
+        insert_chunks(&from, &chunks).unwrap();
+
+        assert_eq!(copy_chunks(&from, &to, "paged").unwrap(), chunks.len());
+        let counts = |conn: &Connection| -> Vec<(String, String, i64)> {
+            conn.prepare(
+                "SELECT file_path, term, frequency FROM identifier_counts
+                 WHERE term = 'parse_tokens' ORDER BY file_path",
+            )
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap()
+        };
+        assert_eq!(counts(&to), counts(&from));
+        assert_eq!(counts(&to)[0].2, (COPY_PAGE_ROWS + 10) as i64);
+    }
+
+    #[test]
+    fn test_failed_import_keeps_the_previous_chunks() {
+        let _data_dir = crate::test_support::use_temp_data_dir();
+
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("kept");
+        fs::create_dir_all(&src).unwrap();
+        fs::write(src.join("a.rs"), "fn kept_alpha() {}\n").unwrap();
+        fs::write(src.join("b.rs"), "fn kept_beta() {}\n").unwrap();
+        Indexer::new(IndexingOptions::default())
+            .index_codebase(&src)
+            .unwrap();
+
+        let codebase_id = get_codebase_hash(&src.canonicalize().unwrap());
+        let out = dir.path().join("kept.bundle");
+        export_index(&codebase_id, &out).unwrap();
+
+        // Corrupt the second file's embeddings so its insert fails
+        Connection::open(&out)
+            .unwrap()
+            .execute(
+                "UPDATE chunks SET embedding = zeroblob(8) WHERE file_path = 'b.rs'",
+                [],
+            )
+            .unwrap();
+        assert!(import_index(&out).is_err());
+
+        let conn = init_db().unwrap();
+        let files: Vec<String> = crate::database::chunk_counts_by_file(&conn, &codebase_id)
+            .unwrap()
+            .into_iter()
+            .map(|(path, _)| path)
+            .collect();
+        assert_eq!(files, vec!["a.rs", "b.rs"]);
+    }
+
+    #[test]
+    fn test_import_refuses_bundle_from_another_model() {
+        use crate::config::Config;
+
+        let config = crate::test_support::override_config(Config::default());
+
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join("lib.rs"),
+            "fn shared_between_machines() {}\n",
+        )
+        .unwrap();
+        Indexer::new(IndexingOptions::default())
+            .index_codebase(dir.path())
+            .unwrap();
+        let codebase_id = get_codebase_hash(&dir.path().canonicalize().unwrap());
+        let out_dir = tempfile::tempdir().unwrap();
+        let out = out_dir.path().join("minilm.bundle");
+        export_index(&codebase_id, &out).unwrap();
+
+        let mut nomic = Config::default();
+        nomic.model.model_type = "nomic".to_string();
+        config.set(nomic);
+        let err = import_index(&out).unwrap_err();
+        assert!(
+            matches!(err, CodeSearchError::InvalidConfiguration(ref m) if m.contains("model.model_type")),
+            "{}",
+            err
+        );
+
+        config.set(Config::default());
+        assert_eq!(import_index(&out).unwrap(), codebase_id);
+    }
+}
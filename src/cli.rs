@@ -1,21 +1,35 @@
 use crate::config::Config;
-use crate::database::{delete_chunks_for_codebase, get_codebase_stats, get_global_stats, init_db};
+use crate::database::{
+    delete_chunks_for_codebase, get_codebase_breakdown, get_codebase_stats, get_global_stats,
+    init_db, Breakdown, CodebaseMetadata, Stats,
+};
 use crate::embedding::{ensure_model_available_with_model, get_query_embedding_with_model};
 use crate::error::{CodeSearchError, Result};
-use crate::indexing::{list_indexed_codebases, Indexer, IndexingOptions};
+use crate::indexing::{ErrorPolicy, Indexer, IndexingOptions};
 use clap::{Parser, Subcommand};
+use rusqlite::Connection;
+use serde::Serialize;
 use std::io::Write;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 #[derive(Parser)]
 #[command(name = "code-search")]
 #[command(about = "Semantic code search using embeddings", long_about = None)]
 #[command(version)]
 pub struct Cli {
+    #[arg(
+        long,
+        global = true,
+        value_name = "DIR",
+        help = "Directory holding the index database and manifests (overrides config)"
+    )]
+    pub data_dir: Option<PathBuf>,
     #[command(subcommand)]
     pub command: Commands,
 }
 
+// Parsed once per process, so the Search variant's size is of no concern
+#[allow(clippy::large_enum_variant)]
 #[derive(Subcommand)]
 pub enum Commands {
     #[command(about = "Index a codebase for searching")]
@@ -26,15 +40,62 @@ pub enum Commands {
         force: bool,
         #[arg(long, short, help = "Enable verbose output")]
         verbose: bool,
+        #[arg(
+            long,
+            help = "Report which files would be added, modified or removed without embedding or writing anything"
+        )]
+        dry_run: bool,
         #[arg(long, help = "Disable gitignore filtering")]
         no_gitignore: bool,
+        #[arg(
+            long,
+            help = "Also apply the nearest .gitignore above the repository and the global git excludes file"
+        )]
+        global_gitignore: bool,
         #[arg(
             long,
             value_name = "MODEL",
-            help = "Embedding model to use (minilm, nomic, nemotron)",
-            default_value = "minilm"
+            help = "Embedding model to use (minilm, nomic, nemotron) [default: model.model_type from config]"
+        )]
+        model: Option<String>,
+        #[arg(
+            long,
+            value_name = "LABEL",
+            help = "Name for the codebase, usable with search --codebase"
+        )]
+        label: Option<String>,
+        #[arg(
+            long,
+            help = "Insert files and chunks in sorted order for reproducible chunk ids"
+        )]
+        deterministic: bool,
+        #[arg(long, help = "Fail on the first file that cannot be indexed")]
+        strict: bool,
+        #[arg(
+            long,
+            value_name = "BYTES",
+            help = "Skip files larger than BYTES, 0 for no limit [default: indexing.max_file_bytes from config]"
+        )]
+        max_file_size: Option<u64>,
+        #[arg(
+            long,
+            value_name = "N",
+            help = "Also index the newest N commit messages (requires the git feature)"
+        )]
+        commits: Option<usize>,
+        #[arg(
+            long,
+            value_name = "DATE",
+            help = "Only index commit messages since DATE, e.g. 2024-01-01 or \"6 months ago\""
+        )]
+        commits_since: Option<String>,
+        #[arg(
+            long,
+            value_name = "FILE",
+            conflicts_with_all = ["force", "dry_run", "commits", "commits_since"],
+            help = "Re-index only the newline-separated paths listed in FILE, or on stdin for -"
         )]
-        model: String,
+        files_from: Option<String>,
     },
     #[command(about = "Search indexed code")]
     Search {
@@ -43,10 +104,10 @@ pub enum Commands {
         #[arg(
             long,
             value_name = "CODEBASE",
-            help = "Path to the indexed codebase",
-            required = true
+            help = "Indexed codebase: a path, a label, or a unique part of either \
+                    [default: search.default_codebase from the config]"
         )]
-        codebase: String,
+        codebase: Option<String>,
         #[arg(
             long,
             short = 'n',
@@ -57,17 +118,35 @@ pub enum Commands {
         limit: i64,
         #[arg(long, help = "Use vector search only (no FTS)")]
         vector_only: bool,
+        #[arg(
+            long,
+            value_name = "SCORE",
+            help = "Drop results scoring below SCORE (cosine similarity with --vector-only, else the fused hybrid score, roughly 0 to 1)"
+        )]
+        min_score: Option<f64>,
         #[arg(long, short, help = "Pretty print results with colors")]
         pretty: bool,
+        #[arg(
+            long,
+            conflicts_with = "pretty",
+            help = "Output results as a JSON array"
+        )]
+        json: bool,
         #[arg(
             long,
             value_name = "MODEL",
-            help = "Embedding model to use (minilm, nomic, nemotron)",
-            default_value = "minilm"
+            help = "Embedding model to use (minilm, nomic, nemotron) [default: model.model_type from config]"
         )]
-        model: String,
+        model: Option<String>,
         #[arg(long, short = 'l', help = "Filter by programming language")]
         language: Option<String>,
+        #[arg(
+            long = "lang",
+            value_name = "LANGS",
+            value_delimiter = ',',
+            help = "Only return results in these languages, e.g. rust,go"
+        )]
+        langs: Vec<String>,
         #[arg(long, help = "Filter by file type/extension (e.g., rs, py, js)")]
         file_type: Option<String>,
         #[arg(
@@ -79,22 +158,221 @@ pub enum Commands {
         author: Option<String>,
         #[arg(long, help = "Filter by imports/dependencies in code")]
         imports: Option<String>,
+        #[arg(
+            long,
+            value_name = "KIND",
+            value_parser = crate::splitter::CHUNK_KINDS,
+            help = "Only return chunks of this kind"
+        )]
+        kind: Option<String>,
+        #[arg(
+            long = "in",
+            value_name = "COLUMNS",
+            value_parser = ["content", "path", "both"],
+            default_value = "both",
+            help = "Match full-text terms against chunk content, file paths, or both"
+        )]
+        search_in: String,
+        #[arg(
+            long,
+            value_name = "EXPR",
+            help = "Filter expression, e.g. 'lang:rust path:src/** -path:src/gen/** -lang:json'"
+        )]
+        filter: Option<String>,
         #[arg(long, help = "Enable fuzzy matching for typos")]
         fuzzy: Option<bool>,
         #[arg(long, help = "Disable learning-to-rank personalization")]
         no_ltr: bool,
+        #[arg(
+            long,
+            value_name = "N",
+            num_args = 0..=1,
+            require_equals = true,
+            default_missing_value = "1",
+            help = "Open the Nth result (default 1) in $EDITOR at its start line"
+        )]
+        open: Option<usize>,
+        #[arg(
+            long,
+            help = "Warn when files changed on disk since the codebase was indexed"
+        )]
+        check_fresh: bool,
+        #[arg(
+            long,
+            help = "Re-index changed files before searching (implies --check-fresh)"
+        )]
+        auto_reindex: bool,
+        #[arg(
+            long,
+            value_name = "N",
+            help = "Show only the best-matching line of each result and N lines around it"
+        )]
+        context: Option<usize>,
     },
     #[command(about = "Show status of indexed codebases")]
     Status {
         #[arg(long, short, help = "List all indexed codebases")]
         list: bool,
+        #[arg(
+            long,
+            value_name = "CODEBASE",
+            conflicts_with = "list",
+            help = "Break one indexed codebase (a path or label) down by language and file"
+        )]
+        codebase: Option<String>,
+        #[arg(long, help = "Output in JSON format")]
+        json: bool,
+    },
+    #[command(about = "List recent searches against an indexed codebase")]
+    History {
+        #[arg(value_name = "CODEBASE_PATH", help = "Path to the indexed codebase")]
+        codebase_path: String,
+        #[arg(
+            long,
+            value_name = "N",
+            help = "Maximum number of queries to show",
+            default_value = "20"
+        )]
+        limit: i64,
+        #[arg(long, help = "Output in JSON format")]
+        json: bool,
+    },
+    #[command(
+        name = "bench-search",
+        about = "Measure search latency on an indexed codebase with a file of queries"
+    )]
+    BenchSearch {
+        #[arg(value_name = "CODEBASE_PATH", help = "Path to the indexed codebase")]
+        codebase_path: String,
+        #[arg(long, value_name = "FILE", help = "File with one query per line")]
+        queries: PathBuf,
+        #[arg(
+            long,
+            value_name = "N",
+            help = "Times to run each query",
+            default_value = "5"
+        )]
+        runs: usize,
+        #[arg(
+            long,
+            short = 'n',
+            value_name = "N",
+            help = "Maximum number of results per search",
+            default_value = "10"
+        )]
+        limit: i64,
+        #[arg(long, value_name = "MODEL", help = "Embedding model to use")]
+        model: Option<String>,
         #[arg(long, help = "Output in JSON format")]
         json: bool,
     },
+    #[command(about = "Recompute embeddings for an indexed codebase without re-chunking")]
+    Reembed {
+        #[arg(value_name = "CODEBASE_PATH", help = "Path to the indexed codebase")]
+        codebase_path: String,
+        #[arg(
+            long,
+            value_name = "MODEL",
+            help = "Embedding model to use [default: the codebase's recorded model, else config]"
+        )]
+        model: Option<String>,
+    },
+    #[command(
+        name = "rebuild-ann",
+        alias = "rebuild_ann",
+        about = "Rebuild the approximate nearest-neighbour index for a codebase"
+    )]
+    RebuildAnn {
+        #[arg(value_name = "CODEBASE_PATH", help = "Path to the indexed codebase")]
+        codebase_path: String,
+    },
+    #[command(about = "Show how a file would be chunked, without indexing it")]
+    Chunks {
+        #[arg(value_name = "FILE", help = "File to split into chunks")]
+        file: String,
+    },
+    #[command(about = "Explain why a file of a codebase is or isn't indexed")]
+    ExplainFile {
+        #[arg(
+            value_name = "CODEBASE",
+            help = "Codebase directory, or the label of an indexed one"
+        )]
+        codebase: String,
+        #[arg(value_name = "FILE", help = "File path relative to the codebase root")]
+        file: String,
+    },
     #[command(about = "Delete an indexed codebase")]
     Delete {
         #[arg(value_name = "CODEBASE_PATH", help = "Path to the codebase to delete")]
         codebase_path: String,
+        #[arg(
+            long,
+            short = 'y',
+            help = "Delete without asking for confirmation (required when stdin is not a terminal)"
+        )]
+        yes: bool,
+        #[arg(long, help = "Report what would be deleted without deleting anything")]
+        dry_run: bool,
+    },
+    #[command(
+        about = "Remove the files under a path prefix (e.g. vendor/) from an index; exclude them too or the next index run brings them back"
+    )]
+    DeletePath {
+        #[arg(value_name = "CODEBASE_PATH", help = "Path to the indexed codebase")]
+        codebase_path: String,
+        #[arg(
+            value_name = "PREFIX",
+            help = "Path prefix relative to the codebase root, matched literally"
+        )]
+        prefix: String,
+    },
+    #[command(about = "Point an index at a codebase's new location after moving it on disk")]
+    Move {
+        #[arg(
+            value_name = "OLD_PATH",
+            help = "Where the codebase was indexed (may no longer exist)"
+        )]
+        old_path: String,
+        #[arg(value_name = "NEW_PATH", help = "Where the codebase is now")]
+        new_path: String,
+    },
+    #[command(about = "Package an indexed codebase into a single portable bundle file")]
+    Bundle {
+        #[arg(
+            value_name = "CODEBASE",
+            help = "Indexed codebase: a path, a label, or a unique part of either"
+        )]
+        codebase: String,
+        #[arg(long, value_name = "FILE", help = "Bundle file to write")]
+        out: PathBuf,
+    },
+    #[command(about = "Open a bundle read-only and describe it, or search it given a query")]
+    OpenBundle {
+        #[arg(value_name = "BUNDLE", help = "Bundle file written by `bundle`")]
+        bundle: PathBuf,
+        #[arg(value_name = "QUERY", help = "Search the bundle for this query")]
+        query: Option<String>,
+        #[arg(
+            long,
+            short = 'n',
+            value_name = "N",
+            help = "Maximum number of results",
+            default_value = "10"
+        )]
+        limit: i64,
+        #[arg(long, short, help = "Pretty print results with colors")]
+        pretty: bool,
+        #[arg(
+            long,
+            value_name = "MODEL",
+            help = "Embedding model to use; must match the bundle's [default: the bundle's model]"
+        )]
+        model: Option<String>,
+    },
+    #[command(about = "Load a bundle into the local index, with its manifest")]
+    ImportBundle {
+        #[arg(value_name = "BUNDLE", help = "Bundle file written by `bundle`")]
+        bundle: PathBuf,
     },
     #[command(about = "Show current configuration")]
     Config {
@@ -102,9 +380,29 @@ pub enum Commands {
         path: bool,
         #[arg(long, help = "Create default config file")]
         create: bool,
+        #[command(subcommand)]
+        action: Option<ConfigAction>,
     },
     #[command(about = "Start MCP server for IDE integration")]
     Mcp {},
+    #[cfg(unix)]
+    #[command(
+        about = "Keep the embedding model loaded and answer index, search and embed requests on a Unix socket"
+    )]
+    Serve {
+        #[arg(
+            long,
+            value_name = "PATH",
+            help = "Socket to listen on [default: serve.sock in the data directory]"
+        )]
+        socket: Option<PathBuf>,
+        #[arg(
+            long,
+            value_name = "MODEL",
+            help = "Embedding model to load (minilm, nomic, nemotron) [default: model.model_type from config]"
+        )]
+        model: Option<String>,
+    },
     #[command(about = "Record click feedback for Learning-to-Rank")]
     Click {
         #[arg(value_name = "QUERY", help = "The search query")]
@@ -123,21 +421,60 @@ pub enum Commands {
     },
 }
 
+#[derive(Subcommand)]
+pub enum ConfigAction {
+    #[command(about = "Set a config value in the config file")]
+    Set {
+        #[arg(
+            value_name = "KEY",
+            help = "Dotted key, such as chunking.chunk_size or search.default_codebase"
+        )]
+        key: String,
+        #[arg(
+            value_name = "VALUE",
+            help = "New value, checked against the field's type; lists are comma-separated, and empty clears an optional value or restores the default"
+        )]
+        value: String,
+    },
+}
+
 pub fn run(cli: Cli) -> Result<()> {
+    let _data_dir = cli
+        .data_dir
+        .clone()
+        .map(crate::config::scoped_data_dir_override);
     let config = Config::load();
     match cli.command {
         Commands::Index {
             codebase_path,
             force,
             verbose,
+            dry_run,
             no_gitignore,
+            global_gitignore,
             model,
+            label,
+            deterministic,
+            strict,
+            max_file_size,
+            commits,
+            commits_since,
+            files_from,
         } => run_index(
             &codebase_path,
             force,
             verbose,
+            dry_run,
             !no_gitignore,
-            &model,
+            global_gitignore,
+            model.as_deref(),
+            label,
+            deterministic,
+            strict,
+            max_file_size,
+            commits,
+            commits_since,
+            files_from.as_deref(),
             &config,
         ),
         Commands::Search {
@@ -145,35 +482,118 @@ pub fn run(cli: Cli) -> Result<()> {
             codebase,
             limit,
             vector_only,
+            min_score,
             pretty,
+            json,
             model,
             language,
+            langs,
             file_type,
             after,
             author,
             imports,
+            kind,
+            search_in,
+            filter,
             fuzzy,
             no_ltr,
+            open,
+            check_fresh,
+            auto_reindex,
+            context,
         } => run_search(
             &query,
-            &codebase,
+            codebase.as_deref(),
             limit,
             vector_only,
+            min_score,
             pretty,
-            &model,
+            json,
+            model.as_deref(),
             &config,
             language,
+            &langs,
             file_type,
             after,
             author,
             imports,
+            kind,
+            &search_in,
+            filter.as_deref(),
             fuzzy,
             no_ltr,
+            open,
+            check_fresh,
+            auto_reindex,
+            context,
+        ),
+        Commands::Status {
+            list,
+            codebase,
+            json,
+        } => run_status(list, codebase.as_deref(), json),
+        Commands::History {
+            codebase_path,
+            limit,
+            json,
+        } => run_history(&codebase_path, limit, json, &config),
+        Commands::BenchSearch {
+            codebase_path,
+            queries,
+            runs,
+            limit,
+            model,
+            json,
+        } => run_bench_search(
+            &codebase_path,
+            &queries,
+            runs,
+            limit,
+            model.as_deref(),
+            json,
+            &config,
+        ),
+        Commands::Reembed {
+            codebase_path,
+            model,
+        } => run_reembed(&codebase_path, model.as_deref(), &config),
+        Commands::RebuildAnn { codebase_path } => run_rebuild_ann(&codebase_path),
+        Commands::Chunks { file } => run_chunks(&file),
+        Commands::ExplainFile { codebase, file } => run_explain_file(&codebase, &file),
+        Commands::Delete {
+            codebase_path,
+            yes,
+            dry_run,
+        } => run_delete(&codebase_path, yes, dry_run),
+        Commands::DeletePath {
+            codebase_path,
+            prefix,
+        } => run_delete_path(&codebase_path, &prefix),
+        Commands::Move { old_path, new_path } => run_move(&old_path, &new_path),
+        Commands::Bundle { codebase, out } => run_bundle(&codebase, &out),
+        Commands::OpenBundle {
+            bundle,
+            query,
+            limit,
+            pretty,
+            model,
+        } => run_open_bundle(
+            &bundle,
+            query.as_deref(),
+            limit,
+            pretty,
+            model.as_deref(),
+            &config,
         ),
-        Commands::Status { list, json } => run_status(list, json),
-        Commands::Delete { codebase_path } => run_delete(&codebase_path),
-        Commands::Config { path, create } => run_config(path, create, &config),
+        Commands::ImportBundle { bundle } => run_import_bundle(&bundle),
+        Commands::Config {
+            action: Some(ConfigAction::Set { key, value }),
+            ..
+        } => run_config_set(&key, &value),
+        Commands::Config { path, create, .. } => run_config(path, create, &config),
         Commands::Mcp {} => run_mcp(),
+        #[cfg(unix)]
+        Commands::Serve { socket, model } => run_serve(socket, model.as_deref(), &config),
         Commands::Click {
             query,
             chunk_id,
@@ -183,19 +603,44 @@ pub fn run(cli: Cli) -> Result<()> {
     }
 }
 
+/// An explicit `--model` always wins; the config only fills in when the flag is absent.
+fn resolve_model<'a>(model: Option<&'a str>, config: &'a Config) -> &'a str {
+    model.unwrap_or(config.model.model_type.as_str())
+}
+
+/// An explicit `--codebase` wins; `search.default_codebase` fills in when the
+/// flag is absent.
+fn resolve_codebase_flag<'a>(codebase: Option<&'a str>, config: &'a Config) -> Result<&'a str> {
+    codebase
+        .or(config.search.default_codebase.as_deref())
+        .ok_or_else(|| {
+            CodeSearchError::InvalidInput(
+                "no codebase to search: pass --codebase, or set a default with \
+                 `code-search config set search.default_codebase <path>`"
+                    .to_string(),
+            )
+        })
+}
+
+#[allow(clippy::too_many_arguments)]
 fn run_index(
     codebase_path: &str,
     force: bool,
     verbose: bool,
+    dry_run: bool,
     use_gitignore: bool,
-    model: &str,
+    use_global_gitignore: bool,
+    model: Option<&str>,
+    label: Option<String>,
+    deterministic: bool,
+    strict: bool,
+    max_file_bytes: Option<u64>,
+    commit_limit: Option<usize>,
+    commits_since: Option<String>,
+    files_from: Option<&str>,
     config: &Config,
 ) -> Result<()> {
-    let model = if model == "minilm" {
-        config.model.model_type.as_str()
-    } else {
-        model
-    };
+    let model = resolve_model(model, config);
     let path = Path::new(codebase_path);
     if !path.exists() {
         return Err(CodeSearchError::Io(std::io::Error::new(
@@ -204,26 +649,47 @@ fn run_index(
         )));
     }
 
-    if verbose {
-        println!("Loading embedding model '{}'...", model);
-    }
+    // A dry run embeds nothing, so it need not wait on the model
+    if !dry_run {
+        if verbose {
+            println!("Loading embedding model '{}'...", model);
+        }
 
-    if let Err(e) = ensure_model_available_with_model(model) {
-        eprintln!("Warning: Could not load embedding model: {}", e);
-        eprintln!("Indexing will continue without embeddings (search will not work until model is available)");
+        if let Err(e) = ensure_model_available_with_model(model) {
+            eprintln!("Warning: Could not load embedding model: {}", e);
+            eprintln!("Indexing will continue without embeddings (search will not work until model is available)");
+        }
     }
 
     let config = IndexingOptions {
         force,
         verbose,
+        dry_run,
         use_gitignore,
+        use_global_gitignore,
         model_name: Some(model.to_string()),
+        label,
+        deterministic_order: deterministic.then_some(true),
+        on_error: if strict {
+            ErrorPolicy::Abort
+        } else {
+            ErrorPolicy::Skip
+        },
+        max_file_bytes,
+        commit_limit,
+        commits_since,
         ..Default::default()
     };
 
     let mut indexer = Indexer::new(config);
 
-    match indexer.index_codebase(codebase_path) {
+    let result = match files_from {
+        Some(source) => {
+            read_file_list(source).and_then(|files| indexer.index_files(codebase_path, &files))
+        }
+        None => indexer.index_codebase(codebase_path),
+    };
+    match result {
         Ok(stats) => {
             println!("{}", stats);
             Ok(())
@@ -235,50 +701,61 @@ fn run_index(
     }
 }
 
+/// Non-blank lines of `source`, a file or `-` for stdin, as paths
+fn read_file_list(source: &str) -> Result<Vec<String>> {
+    let text = if source == "-" {
+        std::io::read_to_string(std::io::stdin())?
+    } else {
+        std::fs::read_to_string(source)?
+    };
+    Ok(text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(str::to_string)
+        .collect())
+}
+
+#[allow(clippy::too_many_arguments)]
 fn run_search(
     query: &str,
-    codebase_path: &str,
+    codebase: Option<&str>,
     limit: i64,
-    _vector_only: bool,
+    vector_only: bool,
+    min_score: Option<f64>,
     pretty: bool,
-    model: &str,
+    json: bool,
+    model: Option<&str>,
     config: &Config,
     language: Option<String>,
+    langs: &[String],
     file_type: Option<String>,
     after: Option<String>,
     author: Option<String>,
     imports: Option<String>,
+    kind: Option<String>,
+    search_in: &str,
+    filter: Option<&str>,
     fuzzy: Option<bool>,
     no_ltr: bool,
+    open: Option<usize>,
+    check_fresh: bool,
+    auto_reindex: bool,
+    context: Option<usize>,
 ) -> Result<()> {
-    let model = if model == "minilm" {
-        config.model.model_type.as_str()
-    } else {
-        model
-    };
+    let model = resolve_model(model, config);
     let limit = if limit == 10 {
         config.search.default_limit as i64
     } else {
         limit
     };
-    let path = Path::new(codebase_path);
-    if !path.exists() {
-        return Err(CodeSearchError::Io(std::io::Error::new(
-            std::io::ErrorKind::NotFound,
-            format!("Codebase path does not exist: {}", codebase_path),
-        )));
-    }
-
-    let canonical_path = path.canonicalize().map_err(CodeSearchError::Io)?;
-    let codebase_id = crate::manifest::get_codebase_hash(&canonical_path);
-
+    let codebase_path = resolve_codebase_flag(codebase, config)?;
     let conn = init_db()?;
+    let (codebase_id, canonical_path) = resolve_codebase_arg(&conn, codebase_path)?;
+    crate::database::check_codebase_model(&conn, &codebase_id, model)?;
 
-    let stats = get_codebase_stats(&conn, &codebase_id)?;
-    if stats.is_none() {
-        return Err(CodeSearchError::CodebaseNotIndexed(
-            codebase_path.to_string(),
-        ));
+    if check_fresh || auto_reindex {
+        check_freshness(&canonical_path, model, auto_reindex)?;
     }
 
     // Parse after filter (supports ISO 8601 or Unix timestamp)
@@ -288,14 +765,27 @@ fn run_search(
         None
     };
 
-    // Build search filters
-    let filters = crate::database::SearchFilters {
+    // Build search filters; the individual flags combine with --filter
+    let mut filters = crate::database::SearchFilters {
         language,
         after_timestamp,
         author,
         file_type,
         imports,
+        kind,
+        fts_columns: search_in.parse()?,
+        min_score,
+        ..Default::default()
     };
+    filters.include_languages.extend(
+        langs
+            .iter()
+            .map(|lang| lang.trim().to_lowercase())
+            .filter(|lang| !lang.is_empty()),
+    );
+    if let Some(expr) = filter {
+        filters.apply_expr(expr)?;
+    }
 
     // Determine fuzzy and LTR settings
     let enable_fuzzy = fuzzy.unwrap_or(config.search.enable_fuzzy);
@@ -310,38 +800,130 @@ fn run_search(
 
     let query_embedding = get_query_embedding_with_model(query, model);
 
-    let db_results = crate::database::hybrid_search(
-        &conn,
-        query,
-        Some(&codebase_id),
-        &query_embedding,
-        limit,
-        &filters,
-        enable_fuzzy,
-    )?;
+    // --vector-only skips the full-text pass, so scores are plain cosine
+    // similarity and FTS tokenization never touches the query
+    let db_results = if vector_only {
+        crate::database::vector_only_search(
+            &conn,
+            query,
+            Some(&codebase_id),
+            &query_embedding,
+            limit,
+            &filters,
+        )?
+    } else {
+        crate::database::hybrid_search_with_ltr(
+            &conn,
+            query,
+            Some(&codebase_id),
+            &query_embedding,
+            limit,
+            &filters,
+            enable_fuzzy,
+            enable_ltr,
+        )?
+    };
+
+    // Resolve the chosen result before db_results is consumed
+    let open_target = match open {
+        Some(n) if n == 0 || n > db_results.len() => {
+            return Err(CodeSearchError::InvalidInput(format!(
+                "--open={} is out of range ({} results)",
+                n,
+                db_results.len()
+            )));
+        }
+        Some(n) => {
+            let r = &db_results[n - 1];
+            Some((canonical_path.join(&r.file_path), r.start_line))
+        }
+        None => None,
+    };
 
     let results: Vec<crate::search::SearchResult> = db_results
         .into_iter()
-        .map(|r| crate::search::SearchResult {
-            file: r.file_path,
-            lines: format!("{}-{}", r.start_line, r.end_line),
-            content: r.content,
-            score: r.score,
-            language: r.language,
+        .map(|r| {
+            let result = crate::search::SearchResult::from(r);
+            match context {
+                Some(lines) => result.with_snippet(query, lines),
+                None => result,
+            }
         })
         .collect();
 
-    if results.is_empty() {
-        println!("No results found for query: {}", query);
+    if json {
+        // An empty array rather than the human-readable notice, so scripts
+        // can always parse the output
+        println!("{}", search_results_json(&results)?);
+    } else if results.is_empty() {
+        match min_score {
+            Some(min_score) => println!(
+                "No results scoring at least {} for query: {}",
+                min_score, query
+            ),
+            None => println!("No results found for query: {}", query),
+        }
         return Ok(());
-    }
-
-    if pretty {
+    } else if pretty {
         print_results_pretty(&results);
     } else {
         print_results_simple(&results);
     }
 
+    if let Some((file, line)) = open_target {
+        let editor = std::env::var("VISUAL")
+            .or_else(|_| std::env::var("EDITOR"))
+            .unwrap_or_else(|_| "vi".to_string());
+        open_in_editor(&editor, &file, line)?;
+    }
+
+    Ok(())
+}
+
+/// Output of `search --json`: the results as an array of objects with
+/// `file`, `lines`, `content`, `score` and `language`, plus `snippet` with
+/// `--context`
+fn search_results_json(results: &[crate::search::SearchResult]) -> Result<String> {
+    Ok(serde_json::to_string_pretty(results)?)
+}
+
+/// Build the command that opens `file` at `line` in `editor`.
+///
+/// `editor` may carry its own arguments (e.g. `code --wait`). VS Code style
+/// editors take `--goto file:line`; everything else gets the `+line file`
+/// form understood by vi, vim, nvim, nano, emacs and most terminal editors.
+fn editor_command(editor: &str, file: &Path, line: i64) -> std::process::Command {
+    let mut parts = editor.split_whitespace();
+    let program = parts.next().unwrap_or("vi");
+    let mut cmd = std::process::Command::new(program);
+    cmd.args(parts);
+
+    let name = Path::new(program)
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or(program);
+    match name {
+        "code" | "code-insiders" | "codium" | "cursor" => {
+            cmd.arg("--goto")
+                .arg(format!("{}:{}", file.display(), line));
+        }
+        _ => {
+            cmd.arg(format!("+{}", line)).arg(file);
+        }
+    }
+    cmd
+}
+
+fn open_in_editor(editor: &str, file: &Path, line: i64) -> Result<()> {
+    let status = editor_command(editor, file, line).status().map_err(|e| {
+        CodeSearchError::Other(format!("Failed to launch editor '{}': {}", editor, e))
+    })?;
+    if !status.success() {
+        return Err(CodeSearchError::Other(format!(
+            "Editor '{}' exited with {}",
+            editor, status
+        )));
+    }
     Ok(())
 }
 
@@ -368,10 +950,56 @@ fn parse_timestamp(s: &str) -> Result<i64> {
     )))
 }
 
-fn run_status(list: bool, json: bool) -> Result<()> {
+/// Version of the `status --json` output; bumped on incompatible changes
+pub const STATUS_SCHEMA_VERSION: u32 = 1;
+
+/// Output of `status --json`. Every mode serializes this same shape, with
+/// `null` for the parts it doesn't report.
+#[derive(Debug, Clone, Serialize)]
+pub struct StatusReport {
+    pub schema_version: u32,
+    /// Totals across all codebases; `null` when nothing is indexed
+    pub global: Option<Stats>,
+    /// Per-codebase details, only filled in with `--list`
+    pub codebases: Option<Vec<CodebaseMetadata>>,
+    /// One codebase by language and file, only filled in with `--codebase`
+    pub breakdown: Option<Breakdown>,
+}
+
+impl StatusReport {
+    /// The report for `--list`, or for the codebase `breakdown_of` names
+    pub fn build(conn: &Connection, list: bool, breakdown_of: Option<&str>) -> Result<Self> {
+        Ok(Self {
+            schema_version: STATUS_SCHEMA_VERSION,
+            global: get_global_stats(conn)?,
+            codebases: if list {
+                Some(crate::database::list_codebases_with_metadata(conn)?)
+            } else {
+                None
+            },
+            breakdown: breakdown_of
+                .map(|codebase_id| get_codebase_breakdown(conn, codebase_id))
+                .transpose()?,
+        })
+    }
+}
+
+fn run_status(list: bool, codebase: Option<&str>, json: bool) -> Result<()> {
     let conn = init_db()?;
+    let codebase_id = match codebase {
+        Some(arg) => Some(resolve_codebase_arg(&conn, arg)?.0),
+        None => None,
+    };
+
+    if json {
+        let report = StatusReport::build(&conn, list, codebase_id.as_deref())?;
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
 
-    if list {
+    if let Some(codebase_id) = codebase_id {
+        print_breakdown(&get_codebase_breakdown(&conn, &codebase_id)?);
+    } else if list {
         let codebases = crate::database::list_codebases_with_metadata(&conn)?;
 
         if codebases.is_empty() {
@@ -379,33 +1007,24 @@ fn run_status(list: bool, json: bool) -> Result<()> {
             return Ok(());
         }
 
-        if json {
-            println!("{}", serde_json::to_string_pretty(&codebases).unwrap());
-        } else {
-            println!("Indexed codebases:");
+        println!("Indexed codebases:");
+        println!();
+        for cb in codebases {
+            println!("  {} ({})", cb.name, cb.path);
+            println!("    ID: {}", cb.codebase_id);
+            println!("    Files: {}, Chunks: {}", cb.file_count, cb.chunk_count);
+            if let Some(model) = cb.model {
+                println!("    Model: {}", model);
+            }
+            if let Some(tags) = cb.tags {
+                println!("    Tags: {}", tags);
+            }
             println!();
-            for cb in codebases {
-                println!("  {} ({})", cb.name, cb.path);
-                println!("    ID: {}", cb.codebase_id);
-                println!("    Files: {}, Chunks: {}", cb.file_count, cb.chunk_count);
-                if let Some(model) = cb.model {
-                    println!("    Model: {}", model);
-                }
-                if let Some(tags) = cb.tags {
-                    println!("    Tags: {}", tags);
-                }
-                println!();
-            }
         }
     } else {
         let stats = get_global_stats(&conn)?;
 
-        if json {
-            println!(
-                "{}",
-                serde_json::to_string_pretty(&stats).unwrap_or_else(|_| "null".to_string())
-            );
-        } else if let Some(stats) = stats {
+        if let Some(stats) = stats {
             println!("Global status:");
             println!("  Total codebases: {}", stats.total_codebases);
             println!("  Total files: {}", stats.total_files);
@@ -418,7 +1037,227 @@ fn run_status(list: bool, json: bool) -> Result<()> {
     Ok(())
 }
 
-fn run_delete(codebase_path: &str) -> Result<()> {
+fn print_breakdown(breakdown: &Breakdown) {
+    println!("Codebase {}:", breakdown.codebase_id);
+    println!(
+        "  Files: {}, Chunks: {}",
+        breakdown.total_files, breakdown.total_chunks
+    );
+    println!(
+        "  Average chunk: {:.1} lines, {:.0} characters",
+        breakdown.avg_chunk_lines, breakdown.avg_chunk_chars
+    );
+    if breakdown.path_chunks > 0 || breakdown.commit_chunks > 0 {
+        println!(
+            "  Not counted above: {} file-name chunks, {} commit chunks",
+            breakdown.path_chunks, breakdown.commit_chunks
+        );
+    }
+    println!();
+    println!("By language:");
+    for language in &breakdown.languages {
+        println!(
+            "  {:<16} {:>7} chunks {:>6} files {:>7.1} lines/chunk",
+            language.language.as_deref().unwrap_or("(unknown)"),
+            language.chunk_count,
+            language.file_count,
+            language.avg_chunk_lines
+        );
+    }
+    println!();
+    println!("Largest files:");
+    for file in &breakdown.top_files {
+        println!("  {:>7} chunks  {}", file.chunk_count, file.file_path);
+    }
+}
+
+fn run_history(codebase_path: &str, limit: i64, json: bool, config: &Config) -> Result<()> {
+    let path = Path::new(codebase_path);
+    if !path.exists() {
+        return Err(CodeSearchError::Io(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!("Codebase path does not exist: {}", codebase_path),
+        )));
+    }
+
+    let canonical_path = path.canonicalize().map_err(CodeSearchError::Io)?;
+    let codebase_id = crate::manifest::get_codebase_hash(&canonical_path);
+
+    let conn = init_db()?;
+    let history = crate::database::get_search_history(&conn, Some(&codebase_id), limit)?;
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&history).unwrap());
+    } else if history.is_empty() {
+        println!("No search history for {}.", codebase_path);
+        if !config.search.record_history {
+            println!("Enable search.record_history in the config to record searches.");
+        }
+    } else {
+        println!("Recent searches:");
+        for entry in history {
+            let when = chrono::DateTime::from_timestamp(entry.timestamp, 0)
+                .map(|t| t.format("%Y-%m-%d %H:%M:%S").to_string())
+                .unwrap_or_else(|| entry.timestamp.to_string());
+            println!(
+                "  {}  {} ({} results)",
+                when, entry.query, entry.result_count
+            );
+        }
+    }
+
+    Ok(())
+}
+
+fn run_bench_search(
+    codebase_path: &str,
+    queries_file: &Path,
+    runs: usize,
+    limit: i64,
+    model: Option<&str>,
+    json: bool,
+    config: &Config,
+) -> Result<()> {
+    let content = std::fs::read_to_string(queries_file).map_err(|_| CodeSearchError::FileRead {
+        path: queries_file.display().to_string(),
+    })?;
+    let queries: Vec<String> = content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(String::from)
+        .collect();
+
+    let model = resolve_model(model, config);
+    let report = crate::search::bench_search(codebase_path, &queries, runs, limit, model)?;
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report).unwrap());
+    } else {
+        println!(
+            "{} queries x {} runs ({} searches)",
+            report.queries,
+            report.runs,
+            report.queries * report.runs
+        );
+        println!("  p50: {:.2} ms", report.p50_ms);
+        println!("  p90: {:.2} ms", report.p90_ms);
+        println!("  p99: {:.2} ms", report.p99_ms);
+        println!("  mean results: {:.1}", report.mean_results);
+    }
+
+    Ok(())
+}
+
+fn run_reembed(codebase_path: &str, model: Option<&str>, config: &Config) -> Result<()> {
+    let path = Path::new(codebase_path);
+    if !path.exists() {
+        return Err(CodeSearchError::Io(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!("Codebase path does not exist: {}", codebase_path),
+        )));
+    }
+
+    let canonical_path = path.canonicalize().map_err(CodeSearchError::Io)?;
+    let codebase_id = crate::manifest::get_codebase_hash(&canonical_path);
+
+    let conn = init_db()?;
+    if get_codebase_stats(&conn, &codebase_id)?.is_none() {
+        return Err(CodeSearchError::CodebaseNotIndexed(
+            codebase_path.to_string(),
+        ));
+    }
+
+    let recorded = crate::database::get_codebase_metadata(&conn, &codebase_id)?
+        .and_then(|metadata| metadata.model);
+    let model = match model {
+        Some(model) => model,
+        None => recorded
+            .as_deref()
+            .unwrap_or(config.model.model_type.as_str()),
+    };
+
+    println!("Re-embedding {} with model '{}'...", codebase_path, model);
+    let count = crate::indexing::reembed_codebase(&conn, &codebase_id, model)?;
+    println!("Re-embedded {} chunks", count);
+
+    Ok(())
+}
+
+fn run_rebuild_ann(codebase_path: &str) -> Result<()> {
+    let path = Path::new(codebase_path);
+    if !path.exists() {
+        return Err(CodeSearchError::Io(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!("Codebase path does not exist: {}", codebase_path),
+        )));
+    }
+
+    let canonical_path = path.canonicalize().map_err(CodeSearchError::Io)?;
+    let codebase_id = crate::manifest::get_codebase_hash(&canonical_path);
+
+    let conn = init_db()?;
+    if get_codebase_stats(&conn, &codebase_id)?.is_none() {
+        return Err(CodeSearchError::CodebaseNotIndexed(
+            codebase_path.to_string(),
+        ));
+    }
+
+    let count = crate::ann::rebuild_ann_index(&conn, &codebase_id)?;
+    println!("Rebuilt ANN index for {}: {} vectors", codebase_path, count);
+
+    Ok(())
+}
+
+fn run_chunks(file: &str) -> Result<()> {
+    let chunks = crate::splitter::preview_chunks(file)?;
+    if chunks.is_empty() {
+        println!("{} produces no chunks.", file);
+        return Ok(());
+    }
+
+    println!("{} chunk(s) for {}:", chunks.len(), file);
+    for (i, chunk) in chunks.iter().enumerate() {
+        println!(
+            "  {:>3}. lines {}-{} [{}, {}] {}",
+            i + 1,
+            chunk.start_line,
+            chunk.end_line,
+            chunk.language,
+            chunk.kind,
+            chunk.content.lines().next().unwrap_or("").trim()
+        );
+    }
+
+    Ok(())
+}
+
+fn run_explain_file(codebase: &str, file: &str) -> Result<()> {
+    let conn = init_db()?;
+    let codebase_path = match Path::new(codebase).canonicalize() {
+        Ok(path) => path,
+        Err(_) => PathBuf::from(crate::database::resolve_codebase(&conn, codebase)?.path),
+    };
+
+    let explanation = crate::indexing::explain_file(&conn, &codebase_path, file)?;
+    let path = &explanation.file_path;
+    match (&explanation.skipped, explanation.chunks) {
+        (None, 0) => println!(
+            "{}: not indexed yet; the next index run will pick it up",
+            path
+        ),
+        (None, chunks) => println!("{}: indexed, {} chunk(s)", path, chunks),
+        (Some(reason), 0) => println!("{}: skipped, {}", path, reason),
+        (Some(reason), chunks) => println!(
+            "{}: skipped, {}; {} stale chunk(s) remain until the next index run",
+            path, reason, chunks
+        ),
+    }
+
+    Ok(())
+}
+
+fn run_delete(codebase_path: &str, yes: bool, dry_run: bool) -> Result<()> {
     let path = Path::new(codebase_path);
     if !path.exists() {
         return Err(CodeSearchError::Io(std::io::Error::new(
@@ -432,10 +1271,28 @@ fn run_delete(codebase_path: &str) -> Result<()> {
 
     let conn = init_db()?;
 
-    let stats = get_codebase_stats(&conn, &codebase_id)?;
-    if stats.is_none() {
+    let Some(stats) = get_codebase_stats(&conn, &codebase_id)? else {
         println!("Codebase '{}' is not indexed.", codebase_path);
         return Ok(());
+    };
+    let summary = format!(
+        "codebase '{}' ({} chunks in {} files)",
+        canonical_path.display(),
+        stats.total_chunks,
+        stats.total_files
+    );
+
+    if dry_run {
+        println!("Would delete {}", summary);
+        return Ok(());
+    }
+    if !yes {
+        let stdin = std::io::stdin();
+        let interactive = std::io::IsTerminal::is_terminal(&stdin);
+        if !confirm_delete(&summary, &mut stdin.lock(), interactive)? {
+            println!("Aborted; nothing deleted.");
+            return Ok(());
+        }
     }
 
     let deleted_count = delete_chunks_for_codebase(&conn, &codebase_id)?;
@@ -449,6 +1306,171 @@ fn run_delete(codebase_path: &str) -> Result<()> {
     Ok(())
 }
 
+fn run_delete_path(codebase_path: &str, prefix: &str) -> Result<()> {
+    let conn = init_db()?;
+    let deleted_count = crate::indexing::delete_path(&conn, codebase_path, prefix)?;
+    println!(
+        "Deleted {} chunks under '{}' from '{}'",
+        deleted_count, prefix, codebase_path
+    );
+    Ok(())
+}
+
+/// Compare the tree at `codebase_path` with its manifest before a search.
+/// Changed files are reported, or re-indexed first with `auto_reindex`.
+/// Returns how many files had changed.
+fn check_freshness(codebase_path: &Path, model: &str, auto_reindex: bool) -> Result<usize> {
+    let stale = crate::indexing::stale_files(codebase_path)?.len();
+    if stale == 0 {
+        return Ok(0);
+    }
+
+    if auto_reindex {
+        eprintln!("Re-indexing {} changed file(s) before searching...", stale);
+        Indexer::new(IndexingOptions {
+            model_name: Some(model.to_string()),
+            ..Default::default()
+        })
+        .index_codebase(codebase_path)?;
+    } else {
+        eprintln!(
+            "Warning: {} file(s) changed since {} was indexed; results may be stale \
+             (use --auto-reindex to update them first)",
+            stale,
+            codebase_path.display()
+        );
+    }
+    Ok(stale)
+}
+
+/// Ask on stderr whether to delete `summary`, reading the answer from
+/// `input`. Without a terminal to ask on, deletion needs `--yes`.
+fn confirm_delete(
+    summary: &str,
+    input: &mut impl std::io::BufRead,
+    interactive: bool,
+) -> Result<bool> {
+    if !interactive {
+        return Err(CodeSearchError::InvalidInput(format!(
+            "refusing to delete {} without confirmation; pass --yes in non-interactive use",
+            summary
+        )));
+    }
+    eprint!("Delete {}? [y/N] ", summary);
+    std::io::stderr().flush()?;
+    let mut answer = String::new();
+    input.read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+fn run_move(old_path: &str, new_path: &str) -> Result<()> {
+    let conn = init_db()?;
+    let moved = crate::indexing::relocate_codebase(&conn, old_path, new_path)?;
+    println!(
+        "Moved index of '{}' to '{}' ({} chunks, no re-embedding needed)",
+        old_path, new_path, moved
+    );
+    Ok(())
+}
+
+fn run_bundle(codebase: &str, out: &Path) -> Result<()> {
+    let conn = init_db()?;
+    let (codebase_id, _) = resolve_codebase_arg(&conn, codebase)?;
+    let info = crate::bundle::create_bundle(&conn, &codebase_id, out)?;
+    println!(
+        "Bundled '{}' into {} ({} chunks, model {}, {} dimensions)",
+        info.name,
+        out.display(),
+        info.chunk_count,
+        info.model,
+        info.dimension
+    );
+    Ok(())
+}
+
+fn run_import_bundle(bundle: &Path) -> Result<()> {
+    let codebase_id = crate::bundle::import_index(bundle)?;
+    let conn = init_db()?;
+    let meta = crate::database::get_codebase_metadata(&conn, &codebase_id)?
+        .ok_or_else(|| CodeSearchError::CodebaseNotIndexed(codebase_id.clone()))?;
+    let chunks = get_codebase_stats(&conn, &codebase_id)?.map_or(0, |stats| stats.total_chunks);
+    println!(
+        "Imported '{}' ({} chunks) as {}",
+        meta.name, chunks, meta.path
+    );
+    println!(
+        "If your checkout lives elsewhere, run `code-search move {} <your checkout>`",
+        meta.path
+    );
+    Ok(())
+}
+
+fn run_open_bundle(
+    bundle: &Path,
+    query: Option<&str>,
+    limit: i64,
+    pretty: bool,
+    model: Option<&str>,
+    config: &Config,
+) -> Result<()> {
+    let (conn, info) = crate::bundle::open_bundle(bundle, model)?;
+    let model = info.model.as_str();
+
+    let Some(query) = query else {
+        println!("Bundle: {}", bundle.display());
+        println!("  Codebase: {} ({})", info.name, info.codebase_id);
+        println!("  Chunks: {}", info.chunk_count);
+        println!("  Model: {} ({} dimensions)", info.model, info.dimension);
+        return Ok(());
+    };
+
+    ensure_model_available_with_model(model).map_err(|e| {
+        CodeSearchError::EmbeddingModelLoad(format!(
+            "Failed to load embedding model '{}': {}",
+            model, e
+        ))
+    })?;
+    let query_embedding = get_query_embedding_with_model(query, model);
+    let results: Vec<crate::search::SearchResult> = crate::database::hybrid_search(
+        &conn,
+        query,
+        Some(&info.codebase_id),
+        &query_embedding,
+        limit,
+        &crate::database::SearchFilters::default(),
+        config.search.enable_fuzzy,
+    )?
+    .into_iter()
+    .map(crate::search::SearchResult::from)
+    .collect();
+
+    if results.is_empty() {
+        println!("No results found for query: {}", query);
+    } else if pretty {
+        print_results_pretty(&results);
+    } else {
+        print_results_simple(&results);
+    }
+    Ok(())
+}
+
+fn run_config_set(key: &str, value: &str) -> Result<()> {
+    let path = Config::config_path()
+        .ok_or_else(|| CodeSearchError::Other("Config path not available".to_string()))?;
+    let content = if path.exists() {
+        std::fs::read_to_string(&path)?
+    } else {
+        String::new()
+    };
+    let toml_str = Config::edit_file(&content, &path, key, value)?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(&path, toml_str)?;
+    println!("Set {} = {} in {}", key, value, path.display());
+    Ok(())
+}
+
 fn run_config(show_path: bool, create: bool, config: &Config) -> Result<()> {
     // Handle --create flag
     if create {
@@ -491,7 +1513,26 @@ fn run_config(show_path: bool, create: bool, config: &Config) -> Result<()> {
         if let Some(dim) = config.model.embedding_dim {
             println!("    embedding_dim: {}", dim);
         }
+        if let Some(endpoint) = &config.model.endpoint {
+            println!("    endpoint: {}", endpoint);
+        }
+        if let Some(remote_model) = &config.model.remote_model {
+            println!("    remote_model: {}", remote_model);
+        }
         println!("    auto_download: {}", config.model.auto_download);
+        println!("    download_retries: {}", config.model.download_retries);
+        if let Some(timeout) = config.model.load_timeout_secs {
+            println!("    load_timeout_secs: {}", timeout);
+        }
+        println!(
+            "    execution_provider: {}",
+            config.model.execution_provider.as_str()
+        );
+        println!(
+            "    inference_sessions: {}",
+            config.model.inference_sessions
+        );
+        println!("    query_cache_size: {}", config.model.query_cache_size);
 
         // Indexing config
         println!("  [indexing]");
@@ -505,23 +1546,95 @@ fn run_config(show_path: bool, create: bool, config: &Config) -> Result<()> {
             config.indexing.skip_files.len()
         );
         println!("    use_gitignore: {}", config.indexing.use_gitignore);
+        println!("    detect_git_root: {}", config.indexing.detect_git_root);
+        println!("    use_ignore_files: {}", config.indexing.use_ignore_files);
+        println!("    use_git_exclude: {}", config.indexing.use_git_exclude);
+        println!(
+            "    use_global_gitignore: {}",
+            config.indexing.use_global_gitignore
+        );
         println!("    batch_size: {}", config.indexing.batch_size);
+        println!(
+            "    deterministic_order: {}",
+            config.indexing.deterministic_order
+        );
+        println!(
+            "    embedding_workers: {}",
+            config.indexing.embedding_workers
+        );
+        println!("    pipeline_buffer: {}", config.indexing.pipeline_buffer);
+        println!("    index_file_names: {}", config.indexing.index_file_names);
+        println!("    follow_symlinks: {}", config.indexing.follow_symlinks);
+        match config.indexing.max_file_bytes {
+            0 => println!("    max_file_bytes: unlimited"),
+            bytes => println!("    max_file_bytes: {}", bytes),
+        }
+        println!("    blame_authors: {}", config.indexing.blame_authors);
+        println!(
+            "    normalize_whitespace: {}",
+            config.indexing.normalize_whitespace
+        );
+        println!(
+            "    truncation_warning_threshold: {}",
+            config.indexing.truncation_warning_threshold
+        );
+        println!("    index_non_utf8: {}", config.indexing.index_non_utf8);
 
         // Chunking config
         println!("  [chunking]");
         println!("    chunk_size: {}", config.chunking.chunk_size);
         println!("    chunk_overlap: {}", config.chunking.chunk_overlap);
+        let mut per_language: Vec<_> = config.chunking.per_language.iter().collect();
+        per_language.sort_by(|a, b| a.0.cmp(b.0));
+        for (language, params) in per_language {
+            println!(
+                "    per_language.{}: chunk_size {}, chunk_overlap {}",
+                language, params.chunk_size, params.chunk_overlap
+            );
+        }
+        println!(
+            "    whole_file_max_lines: {}",
+            config.chunking.whole_file_max_lines
+        );
+        println!(
+            "    split_strategy: {}",
+            config.chunking.split_strategy.as_str()
+        );
 
         // Search config
         println!("  [search]");
         println!("    default_limit: {}", config.search.default_limit);
         println!("    fts_weight: {}", config.search.fts_weight);
         println!("    vector_weight: {}", config.search.vector_weight);
+        println!("    record_history: {}", config.search.record_history);
+        println!("    max_result_chars: {}", config.search.max_result_chars);
+        println!(
+            "    dedup_across_codebases: {}",
+            config.search.dedup_across_codebases
+        );
+        println!(
+            "    max_scan_embedding_bytes: {}",
+            config.search.max_scan_embedding_bytes
+        );
+        println!("    max_limit: {}", config.search.max_limit);
+        println!("    split_identifiers: {}", config.search.split_identifiers);
+        println!(
+            "    fts_default_operator: {}",
+            config.search.fts_default_operator.keyword()
+        );
+        println!(
+            "    fusion_strategy: {}",
+            config.search.fusion_strategy.as_str()
+        );
+        if let Some(codebase) = &config.search.default_codebase {
+            println!("    default_codebase: {}", codebase);
+        }
 
         // Database config
         println!("  [database]");
         println!("    data_dir: {}", config.database.data_dir);
         println!("    db_name: {}", config.database.db_name);
+        println!("    embedding_format: {}", config.database.embedding_format);
 
         println!();
         match Config::config_path() {
@@ -532,6 +1645,28 @@ fn run_config(show_path: bool, create: bool, config: &Config) -> Result<()> {
     Ok(())
 }
 
+/// Map a `--codebase` argument to the codebase id and root it refers to.
+///
+/// An existing path must itself be indexed; anything else is looked up as a
+/// label or partial path among the indexed codebases.
+fn resolve_codebase_arg(conn: &Connection, arg: &str) -> Result<(String, PathBuf)> {
+    if let Ok(canonical) = Path::new(arg).canonicalize() {
+        let codebase_id = crate::manifest::get_codebase_hash(&canonical);
+        if get_codebase_stats(conn, &codebase_id)?.is_none() {
+            return Err(CodeSearchError::CodebaseNotIndexed(
+                canonical.display().to_string(),
+            ));
+        }
+        return Ok((codebase_id, canonical));
+    }
+
+    let meta = crate::database::resolve_codebase(conn, arg)?;
+    if get_codebase_stats(conn, &meta.codebase_id)?.is_none() {
+        return Err(CodeSearchError::CodebaseNotIndexed(arg.to_string()));
+    }
+    Ok((meta.codebase_id, PathBuf::from(meta.path)))
+}
+
 fn run_click(query: &str, chunk_id: i64, rank: i64, codebase_path: Option<&str>) -> Result<()> {
     let codebase_id = if let Some(path) = codebase_path {
         let canonical = Path::new(path)
@@ -558,15 +1693,42 @@ fn run_mcp() -> Result<()> {
     Ok(())
 }
 
+#[cfg(unix)]
+fn run_serve(socket: Option<PathBuf>, model: Option<&str>, config: &Config) -> Result<()> {
+    let model = resolve_model(model, config);
+    let socket = match socket {
+        Some(socket) => socket,
+        None => crate::serve::default_socket_path()?,
+    };
+    let server = crate::serve::Server::bind(&socket, model)?;
+    eprintln!(
+        "Serving model '{}' on {} (send {{\"method\":\"shutdown\"}} to stop)",
+        model,
+        server.socket_path().display()
+    );
+    server.run()
+}
+
+/// The snippet of a result when `--context` asked for one, else its content
+fn displayed_content(result: &crate::search::SearchResult) -> &str {
+    result
+        .snippet
+        .as_ref()
+        .map_or(&result.content, |snippet| &snippet.text)
+}
+
 fn print_results_simple(results: &[crate::search::SearchResult]) {
     for (i, result) in results.iter().enumerate() {
         println!("{}. {} ({})", i + 1, result.file, result.lines);
         if let Some(lang) = &result.language {
             println!("   Language: {}", lang);
         }
+        if let Some(author) = &result.author {
+            println!("   Author: {}", author);
+        }
         println!("   Score: {:.4}", result.score);
         println!();
-        for line in result.content.lines() {
+        for line in displayed_content(result).lines() {
             println!("   {}", line);
         }
         println!();
@@ -591,6 +1753,13 @@ fn print_results_pretty(results: &[crate::search::SearchResult]) {
             let _ = writeln!(stdout, "   Language: {}", lang);
         }
 
+        if let Some(author) = &result.author {
+            stdout
+                .set_color(ColorSpec::new().set_fg(Some(Color::Cyan)))
+                .ok();
+            let _ = writeln!(stdout, "   Author: {}", author);
+        }
+
         stdout
             .set_color(ColorSpec::new().set_fg(Some(Color::Yellow)))
             .ok();
@@ -599,7 +1768,7 @@ fn print_results_pretty(results: &[crate::search::SearchResult]) {
         stdout.reset().ok();
         let _ = writeln!(stdout);
 
-        for line in result.content.lines() {
+        for line in displayed_content(result).lines() {
             let _ = writeln!(stdout, "   {}", line);
         }
 
@@ -621,6 +1790,12 @@ mod tests {
             "/path/to/code",
             "--verbose",
             "--force",
+            "--deterministic",
+            "--strict",
+            "--global-gitignore",
+            "--max-file-size",
+            "2048",
+            "--dry-run",
         ]);
         assert!(cli.is_ok());
         if let Ok(cli) = cli {
@@ -629,11 +1804,21 @@ mod tests {
                     codebase_path,
                     force,
                     verbose,
+                    deterministic,
+                    strict,
+                    global_gitignore,
+                    max_file_size,
+                    dry_run,
                     ..
                 } => {
                     assert_eq!(codebase_path, "/path/to/code");
                     assert!(force);
                     assert!(verbose);
+                    assert!(deterministic);
+                    assert!(strict);
+                    assert!(global_gitignore);
+                    assert_eq!(max_file_size, Some(2048));
+                    assert!(dry_run);
                 }
                 _ => panic!("Expected Index command"),
             }
@@ -651,6 +1836,8 @@ mod tests {
             "--limit",
             "5",
             "--pretty",
+            "--min-score",
+            "0.35",
         ]);
         assert!(cli.is_ok());
         if let Ok(cli) = cli {
@@ -660,31 +1847,617 @@ mod tests {
                     codebase,
                     limit,
                     pretty,
+                    min_score,
                     ..
                 } => {
                     assert_eq!(query, "test query");
-                    assert_eq!(codebase, "/path");
+                    assert_eq!(codebase.as_deref(), Some("/path"));
                     assert_eq!(limit, 5);
                     assert!(pretty);
+                    assert_eq!(min_score, Some(0.35));
                 }
                 _ => panic!("Expected Search command"),
             }
         }
     }
 
+    #[cfg(unix)]
+    #[test]
+    fn test_parse_cli_serve() {
+        let cli = Cli::try_parse_from([
+            "code-search",
+            "serve",
+            "--socket",
+            "/tmp/cs.sock",
+            "--model",
+            "nomic",
+        ])
+        .unwrap();
+        match cli.command {
+            Commands::Serve { socket, model } => {
+                assert_eq!(socket, Some(PathBuf::from("/tmp/cs.sock")));
+                assert_eq!(model.as_deref(), Some("nomic"));
+            }
+            _ => panic!("Expected Serve command"),
+        }
+    }
+
+    #[test]
+    fn test_parse_cli_global_data_dir() {
+        let cli = Cli::try_parse_from([
+            "code-search",
+            "status",
+            "--data-dir",
+            "/tmp/code-search-data",
+        ])
+        .unwrap();
+        assert_eq!(cli.data_dir, Some(PathBuf::from("/tmp/code-search-data")));
+
+        let cli = Cli::try_parse_from(["code-search", "status"]).unwrap();
+        assert!(cli.data_dir.is_none());
+    }
+
+    #[test]
+    fn test_parse_cli_search_filter() {
+        let cli = Cli::try_parse_from([
+            "code-search",
+            "search",
+            "q",
+            "--codebase",
+            "/path",
+            "--language",
+            "rust",
+            "--lang",
+            "rust,go",
+            "--filter",
+            "path:src/** -path:src/gen/**",
+            "--kind",
+            "doc",
+            "--in",
+            "content",
+        ])
+        .unwrap();
+        match cli.command {
+            Commands::Search {
+                language,
+                langs,
+                filter,
+                kind,
+                search_in,
+                ..
+            } => {
+                assert_eq!(language.as_deref(), Some("rust"));
+                assert_eq!(langs, vec!["rust", "go"]);
+                assert_eq!(filter.as_deref(), Some("path:src/** -path:src/gen/**"));
+                assert_eq!(kind.as_deref(), Some("doc"));
+                assert_eq!(search_in, "content");
+            }
+            _ => panic!("Expected Search command"),
+        }
+
+        assert!(Cli::try_parse_from([
+            "code-search",
+            "search",
+            "q",
+            "--codebase",
+            "/path",
+            "--kind",
+            "prose",
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn test_parse_cli_search_open() {
+        let parse = |args: &[&str]| match Cli::try_parse_from(args).unwrap().command {
+            Commands::Search { open, query, .. } => (open, query),
+            _ => panic!("Expected Search command"),
+        };
+
+        let base = ["code-search", "search", "q", "--codebase", "/path"];
+        assert_eq!(parse(&base).0, None);
+        assert_eq!(parse(&[&base[..], &["--open"]].concat()).0, Some(1));
+        assert_eq!(parse(&[&base[..], &["--open=3"]].concat()).0, Some(3));
+        // A bare --open must not swallow the following positional argument
+        let (open, query) = parse(&["code-search", "search", "--open", "q", "--codebase", "/p"]);
+        assert_eq!(open, Some(1));
+        assert_eq!(query, "q");
+    }
+
+    #[test]
+    fn test_editor_command_line_syntax() {
+        let file = Path::new("/repo/src/main.rs");
+        let args = |cmd: &std::process::Command| {
+            cmd.get_args()
+                .map(|a| a.to_string_lossy().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        let vim = editor_command("nvim", file, 42);
+        assert_eq!(vim.get_program(), "nvim");
+        assert_eq!(args(&vim), vec!["+42", "/repo/src/main.rs"]);
+
+        let code = editor_command("/usr/bin/code --wait", file, 7);
+        assert_eq!(code.get_program(), "/usr/bin/code");
+        assert_eq!(args(&code), vec!["--wait", "--goto", "/repo/src/main.rs:7"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_open_in_editor_invokes_stub() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let log = dir.path().join("args.log");
+        let stub = dir.path().join("vim");
+        std::fs::write(
+            &stub,
+            format!("#!/bin/sh\nprintf '%s\\n' \"$@\" > '{}'\n", log.display()),
+        )
+        .unwrap();
+        std::fs::set_permissions(&stub, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let target = dir.path().join("lib.rs");
+        open_in_editor(stub.to_str().unwrap(), &target, 120).unwrap();
+
+        let recorded = std::fs::read_to_string(&log).unwrap();
+        assert_eq!(recorded, format!("+120\n{}\n", target.display()));
+    }
+
+    #[test]
+    fn test_explicit_model_overrides_config() {
+        let mut config = Config::default();
+        config.model.model_type = "nomic".to_string();
+
+        let model_flag = |args: &[&str]| match Cli::try_parse_from(args).unwrap().command {
+            Commands::Search { model, .. } | Commands::Index { model, .. } => model,
+            _ => panic!("Expected Search or Index command"),
+        };
+
+        let explicit = model_flag(&[
+            "code-search",
+            "search",
+            "q",
+            "--codebase",
+            "/p",
+            "--model",
+            "minilm",
+        ]);
+        assert_eq!(resolve_model(explicit.as_deref(), &config), "minilm");
+
+        let explicit = model_flag(&["code-search", "index", "/p", "--model", "minilm"]);
+        assert_eq!(resolve_model(explicit.as_deref(), &config), "minilm");
+
+        let absent = model_flag(&["code-search", "search", "q", "--codebase", "/p"]);
+        assert_eq!(resolve_model(absent.as_deref(), &config), "nomic");
+    }
+
+    #[test]
+    fn test_search_uses_default_codebase() {
+        let _data_dir = crate::test_support::use_temp_data_dir();
+
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("lib.rs"), "fn default_target() {}\n").unwrap();
+        Indexer::new(IndexingOptions::default())
+            .index_codebase(dir.path())
+            .unwrap();
+        let expected = crate::manifest::get_codebase_hash(&dir.path().canonicalize().unwrap());
+
+        let flag = match Cli::try_parse_from(["code-search", "search", "q"])
+            .unwrap()
+            .command
+        {
+            Commands::Search { codebase, .. } => codebase,
+            _ => panic!("Expected Search command"),
+        };
+        assert_eq!(flag, None);
+
+        let mut config = Config::default();
+        assert!(matches!(
+            resolve_codebase_flag(flag.as_deref(), &config),
+            Err(CodeSearchError::InvalidInput(_))
+        ));
+
+        config
+            .set("search.default_codebase", dir.path().to_str().unwrap())
+            .unwrap();
+        let codebase = resolve_codebase_flag(flag.as_deref(), &config).unwrap();
+        let conn = init_db().unwrap();
+        let (codebase_id, _) = resolve_codebase_arg(&conn, codebase).unwrap();
+        assert_eq!(codebase_id, expected);
+
+        assert_eq!(
+            resolve_codebase_flag(Some("/explicit"), &config).unwrap(),
+            "/explicit"
+        );
+    }
+
+    #[test]
+    fn test_existing_unindexed_path_does_not_match_by_substring() {
+        let _data_dir = crate::test_support::use_temp_data_dir();
+
+        // The indexed codebase's path contains the unindexed one's
+        let parent = tempfile::tempdir().unwrap();
+        let indexed = parent.path().join("service");
+        std::fs::create_dir(&indexed).unwrap();
+        std::fs::write(indexed.join("lib.rs"), "fn nested_target() {}\n").unwrap();
+        Indexer::new(IndexingOptions::default())
+            .index_codebase(&indexed)
+            .unwrap();
+
+        let conn = init_db().unwrap();
+        let (codebase_id, root) = resolve_codebase_arg(&conn, indexed.to_str().unwrap()).unwrap();
+        assert_eq!(root, indexed.canonicalize().unwrap());
+        assert_eq!(codebase_id, crate::manifest::get_codebase_hash(&root));
+
+        assert!(matches!(
+            resolve_codebase_arg(&conn, parent.path().to_str().unwrap()),
+            Err(CodeSearchError::CodebaseNotIndexed(_))
+        ));
+    }
+
+    #[test]
+    fn test_parse_cli_config_set() {
+        let cli = Cli::try_parse_from([
+            "code-search",
+            "config",
+            "set",
+            "search.default_codebase",
+            "/repo",
+        ])
+        .unwrap();
+        match cli.command {
+            Commands::Config {
+                action: Some(ConfigAction::Set { key, value }),
+                ..
+            } => {
+                assert_eq!(key, "search.default_codebase");
+                assert_eq!(value, "/repo");
+            }
+            _ => panic!("Expected Config set command"),
+        }
+        assert!(Config::default().set("search.no_such_key", "1").is_err());
+    }
+
     #[test]
     fn test_parse_cli_status() {
         let cli = Cli::try_parse_from(["code-search", "status", "--list", "--json"]);
         assert!(cli.is_ok());
         if let Ok(cli) = cli {
             match cli.command {
-                Commands::Status { list, json } => {
+                Commands::Status {
+                    list,
+                    codebase,
+                    json,
+                } => {
                     assert!(list);
+                    assert!(codebase.is_none());
                     assert!(json);
                 }
                 _ => panic!("Expected Status command"),
             }
         }
+
+        let cli = Cli::try_parse_from(["code-search", "status", "--codebase", "backend"]).unwrap();
+        match cli.command {
+            Commands::Status { codebase, .. } => assert_eq!(codebase.as_deref(), Some("backend")),
+            _ => panic!("Expected Status command"),
+        }
+        assert!(
+            Cli::try_parse_from(["code-search", "status", "--list", "--codebase", "x"]).is_err()
+        );
+    }
+
+    #[test]
+    fn test_parse_cli_chunks() {
+        let cli = Cli::try_parse_from(["code-search", "chunks", "src/lib.rs"]).unwrap();
+        match cli.command {
+            Commands::Chunks { file } => assert_eq!(file, "src/lib.rs"),
+            _ => panic!("Expected Chunks command"),
+        }
+    }
+
+    #[test]
+    fn test_parse_cli_import_bundle() {
+        let cli = Cli::try_parse_from(["code-search", "import-bundle", "ci.bundle"]).unwrap();
+        match cli.command {
+            Commands::ImportBundle { bundle } => assert_eq!(bundle, PathBuf::from("ci.bundle")),
+            _ => panic!("Expected ImportBundle command"),
+        }
+    }
+
+    #[test]
+    fn test_parse_cli_explain_file() {
+        let cli =
+            Cli::try_parse_from(["code-search", "explain-file", "/repo", "src/lib.rs"]).unwrap();
+        match cli.command {
+            Commands::ExplainFile { codebase, file } => {
+                assert_eq!(codebase, "/repo");
+                assert_eq!(file, "src/lib.rs");
+            }
+            _ => panic!("Expected ExplainFile command"),
+        }
+    }
+
+    #[test]
+    fn test_auto_reindex_picks_up_modified_file() {
+        let _data_dir = crate::test_support::use_temp_data_dir();
+
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("lib.rs");
+        std::fs::write(&file, "fn before_edit_marker() {}\n").unwrap();
+        let config = crate::config::get_config();
+        let model = config.model_name();
+        Indexer::new(IndexingOptions::default())
+            .index_codebase(dir.path())
+            .unwrap();
+        let canonical = dir.path().canonicalize().unwrap();
+        let codebase_id = crate::manifest::get_codebase_hash(&canonical);
+        let conn = init_db().unwrap();
+        let find = |term: &str| {
+            crate::database::fts_search(
+                &conn,
+                term,
+                Some(&codebase_id),
+                5,
+                &crate::database::SearchFilters::default(),
+            )
+            .unwrap()
+            .len()
+        };
+
+        assert_eq!(check_freshness(&canonical, model, false).unwrap(), 0);
+        std::fs::write(&file, "fn after_edit_marker() {}\n").unwrap();
+
+        // --check-fresh only reports
+        assert_eq!(check_freshness(&canonical, model, false).unwrap(), 1);
+        assert_eq!(find("after_edit_marker"), 0);
+
+        // --auto-reindex updates the file before the search runs
+        assert_eq!(check_freshness(&canonical, model, true).unwrap(), 1);
+        assert_eq!(find("after_edit_marker"), 1);
+        assert_eq!(find("before_edit_marker"), 0);
+        assert_eq!(check_freshness(&canonical, model, false).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_delete_dry_run_and_yes() {
+        let _data_dir = crate::test_support::use_temp_data_dir();
+
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("gone.rs"), "fn soon_deleted() {}\n").unwrap();
+        Indexer::new(IndexingOptions::default())
+            .index_codebase(dir.path())
+            .unwrap();
+        let codebase_id = crate::manifest::get_codebase_hash(&dir.path().canonicalize().unwrap());
+        let path = dir.path().to_str().unwrap();
+        let conn = init_db().unwrap();
+
+        run_delete(path, false, true).unwrap();
+        assert!(get_codebase_stats(&conn, &codebase_id).unwrap().is_some());
+
+        run_delete(path, true, false).unwrap();
+        assert!(get_codebase_stats(&conn, &codebase_id).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_confirm_delete_prompt() {
+        let ask = |answer: &str, interactive| {
+            confirm_delete("codebase 'x'", &mut answer.as_bytes(), interactive)
+        };
+        assert!(ask("y\n", true).unwrap());
+        assert!(ask("Yes\n", true).unwrap());
+        assert!(!ask("\n", true).unwrap());
+        assert!(!ask("no\n", true).unwrap());
+        assert!(matches!(
+            ask("y\n", false),
+            Err(CodeSearchError::InvalidInput(_))
+        ));
+    }
+
+    #[test]
+    fn test_parse_cli_move() {
+        let cli = Cli::try_parse_from(["code-search", "move", "/old/repo", "/new/repo"]).unwrap();
+        match cli.command {
+            Commands::Move { old_path, new_path } => {
+                assert_eq!(old_path, "/old/repo");
+                assert_eq!(new_path, "/new/repo");
+            }
+            _ => panic!("Expected Move command"),
+        }
+    }
+
+    #[test]
+    fn test_search_results_json_round_trips() {
+        let cli = Cli::try_parse_from([
+            "code-search",
+            "search",
+            "q",
+            "--codebase",
+            "/path",
+            "--json",
+        ])
+        .unwrap();
+        assert!(matches!(cli.command, Commands::Search { json: true, .. }));
+        let cli = Cli::try_parse_from(["code-search", "search", "q", "--context", "2"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Commands::Search {
+                context: Some(2),
+                ..
+            }
+        ));
+        assert!(Cli::try_parse_from([
+            "code-search",
+            "search",
+            "q",
+            "--codebase",
+            "/path",
+            "--json",
+            "--pretty",
+        ])
+        .is_err());
+
+        let results = vec![
+            crate::search::SearchResult {
+                file: "src/lib.rs".to_string(),
+                lines: "3-9".to_string(),
+                content: "fn parse() {}".to_string(),
+                score: 0.75,
+                language: Some("rust".to_string()),
+                author: Some("Ann".to_string()),
+                snippet: None,
+            },
+            crate::search::SearchResult {
+                file: "README".to_string(),
+                lines: "1-2".to_string(),
+                content: "\"quoted\"\n".to_string(),
+                score: 0.5,
+                language: None,
+                author: None,
+                snippet: None,
+            },
+        ];
+        let parsed: Vec<serde_json::Value> =
+            serde_json::from_str(&search_results_json(&results).unwrap()).unwrap();
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(parsed[0]["file"], "src/lib.rs");
+        assert_eq!(parsed[0]["lines"], "3-9");
+        assert_eq!(parsed[0]["score"], 0.75);
+        assert_eq!(parsed[0]["language"], "rust");
+        assert_eq!(parsed[1]["content"], "\"quoted\"\n");
+        assert!(parsed[1]["language"].is_null());
+
+        // No results still parse, as an empty array
+        assert_eq!(search_results_json(&[]).unwrap(), "[]");
+    }
+
+    #[test]
+    fn test_parse_cli_history() {
+        let cli = Cli::try_parse_from(["code-search", "history", "/path", "--limit", "5"]).unwrap();
+        match cli.command {
+            Commands::History {
+                codebase_path,
+                limit,
+                json,
+            } => {
+                assert_eq!(codebase_path, "/path");
+                assert_eq!(limit, 5);
+                assert!(!json);
+            }
+            _ => panic!("Expected History command"),
+        }
+    }
+
+    #[test]
+    fn test_parse_cli_bench_search() {
+        let cli = Cli::try_parse_from([
+            "code-search",
+            "bench-search",
+            "/path",
+            "--queries",
+            "queries.txt",
+            "--runs",
+            "20",
+        ])
+        .unwrap();
+        match cli.command {
+            Commands::BenchSearch {
+                codebase_path,
+                queries,
+                runs,
+                limit,
+                ..
+            } => {
+                assert_eq!(codebase_path, "/path");
+                assert_eq!(queries, PathBuf::from("queries.txt"));
+                assert_eq!(runs, 20);
+                assert_eq!(limit, 10);
+            }
+            _ => panic!("Expected BenchSearch command"),
+        }
+    }
+
+    #[test]
+    fn test_parse_cli_reembed() {
+        let cli =
+            Cli::try_parse_from(["code-search", "reembed", "/path", "--model", "nomic"]).unwrap();
+        match cli.command {
+            Commands::Reembed {
+                codebase_path,
+                model,
+            } => {
+                assert_eq!(codebase_path, "/path");
+                assert_eq!(model.as_deref(), Some("nomic"));
+            }
+            _ => panic!("Expected Reembed command"),
+        }
+    }
+
+    #[test]
+    fn test_parse_cli_rebuild_ann() {
+        for name in ["rebuild-ann", "rebuild_ann"] {
+            let cli = Cli::try_parse_from(["code-search", name, "/path"]).unwrap();
+            match cli.command {
+                Commands::RebuildAnn { codebase_path } => assert_eq!(codebase_path, "/path"),
+                _ => panic!("Expected RebuildAnn command"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_status_report_shape_is_stable() {
+        let dir = tempfile::tempdir().unwrap();
+        let conn = crate::database::init_db_at(&dir.path().join("index.db")).unwrap();
+
+        let keys = |report: &StatusReport| {
+            let value = serde_json::to_value(report).unwrap();
+            let mut keys: Vec<String> = value.as_object().unwrap().keys().cloned().collect();
+            keys.sort();
+            keys
+        };
+        let expected = vec!["breakdown", "codebases", "global", "schema_version"];
+
+        // Empty index
+        for list in [false, true] {
+            let report = StatusReport::build(&conn, list, None).unwrap();
+            assert_eq!(keys(&report), expected);
+            assert_eq!(report.schema_version, STATUS_SCHEMA_VERSION);
+        }
+
+        crate::database::register_codebase(&conn, "cb1", "demo", "/tmp/demo", None, None).unwrap();
+        crate::database::insert_chunks(
+            &conn,
+            &[crate::database::Chunk {
+                id: None,
+                codebase_id: "cb1".to_string(),
+                file_path: "src/lib.rs".to_string(),
+                start_line: 1,
+                end_line: 3,
+                content: "fn demo() {}".to_string(),
+                language: Some("rust".to_string()),
+                embedding: vec![1.0, 0.0],
+                hash: "h".to_string(),
+                chunk_kind: Some("code".to_string()),
+                author: None,
+            }],
+        )
+        .unwrap();
+
+        let plain = StatusReport::build(&conn, false, None).unwrap();
+        let listed = StatusReport::build(&conn, true, None).unwrap();
+        let broken_down = StatusReport::build(&conn, false, Some("cb1")).unwrap();
+        assert_eq!(keys(&plain), expected);
+        assert_eq!(keys(&listed), expected);
+        assert_eq!(keys(&broken_down), expected);
+        assert!(plain.breakdown.is_none());
+        let breakdown = broken_down.breakdown.as_ref().unwrap();
+        assert_eq!(breakdown.languages[0].language.as_deref(), Some("rust"));
+        assert_eq!(breakdown.top_files[0].file_path, "src/lib.rs");
+        assert!(plain.codebases.is_none());
+        assert_eq!(listed.codebases.as_ref().unwrap().len(), 1);
+        assert_eq!(plain.global.as_ref().unwrap().total_chunks, 1);
+        assert_eq!(listed.global.as_ref().unwrap().total_chunks, 1);
     }
 
     #[test]
@@ -693,21 +2466,42 @@ mod tests {
         assert!(cli.is_ok());
         if let Ok(cli) = cli {
             match cli.command {
-                Commands::Delete { codebase_path } => {
+                Commands::Delete {
+                    codebase_path,
+                    yes,
+                    dry_run,
+                } => {
                     assert_eq!(codebase_path, "/path/to/code");
+                    assert!(!yes);
+                    assert!(!dry_run);
                 }
                 _ => panic!("Expected Delete command"),
             }
         }
     }
 
+    #[test]
+    fn test_parse_cli_delete_path() {
+        let cli = Cli::try_parse_from(["code-search", "delete-path", "/path/to/code", "vendor/"]);
+        match cli.map(|cli| cli.command) {
+            Ok(Commands::DeletePath {
+                codebase_path,
+                prefix,
+            }) => {
+                assert_eq!(codebase_path, "/path/to/code");
+                assert_eq!(prefix, "vendor/");
+            }
+            _ => panic!("Expected DeletePath command"),
+        }
+    }
+
     #[test]
     fn test_parse_cli_config() {
         let cli = Cli::try_parse_from(["code-search", "config"]);
         assert!(cli.is_ok());
         if let Ok(cli) = cli {
             match cli.command {
-                Commands::Config { path, create } => {
+                Commands::Config { path, create, .. } => {
                     assert!(!path);
                     assert!(!create);
                 }
@@ -722,7 +2516,7 @@ mod tests {
         assert!(cli.is_ok());
         if let Ok(cli) = cli {
             match cli.command {
-                Commands::Config { path, create } => {
+                Commands::Config { path, create, .. } => {
                     assert!(path);
                     assert!(!create);
                 }
@@ -1,8 +1,10 @@
+use crate::error::{CodeSearchError, Result};
 use directories::ProjectDirs;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::env;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 const QUALIFIER: &str = "com";
 const ORGANIZATION: &str = "code-search";
@@ -21,6 +23,62 @@ fn default_auto_download() -> bool {
     true
 }
 
+fn default_download_retries() -> u32 {
+    3
+}
+
+fn default_download_backoff_ms() -> u64 {
+    500
+}
+
+fn default_query_cache_size() -> usize {
+    256
+}
+
+/// Hardware the ONNX backend runs the model on. Providers other than the
+/// CPU need an ONNX Runtime build with them (see the `cuda`, `coreml` and
+/// `directml` features); when one can't be registered the CPU is used.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExecutionProvider {
+    #[default]
+    Cpu,
+    Cuda,
+    #[serde(alias = "CoreML")]
+    CoreMl,
+    #[serde(alias = "DirectML")]
+    DirectMl,
+}
+
+impl ExecutionProvider {
+    /// The name used in config files
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ExecutionProvider::Cpu => "cpu",
+            ExecutionProvider::Cuda => "cuda",
+            ExecutionProvider::CoreMl => "coreml",
+            ExecutionProvider::DirectMl => "directml",
+        }
+    }
+}
+
+impl std::str::FromStr for ExecutionProvider {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "cpu" => Ok(ExecutionProvider::Cpu),
+            "cuda" => Ok(ExecutionProvider::Cuda),
+            "coreml" => Ok(ExecutionProvider::CoreMl),
+            "directml" => Ok(ExecutionProvider::DirectMl),
+            _ => Err(format!(
+                "invalid execution provider (expected cpu/cuda/coreml/directml): {}",
+                s
+            )),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ModelConfig {
     #[serde(default = "default_model_type")]
@@ -33,8 +91,40 @@ pub struct ModelConfig {
     pub model_path: Option<String>,
     /// Embedding dimension (required for custom models)
     /// Example: 768 for all-mpnet-base-v2
+    /// For nomic, a smaller value (e.g. 256 or 512) keeps that prefix of
+    /// each embedding, re-normalized, to save storage
     #[serde(default)]
     pub embedding_dim: Option<usize>,
+    /// URL of the OpenAI-compatible embeddings API used by the `remote`
+    /// model, which also needs `embedding_dim`
+    /// Example: "https://api.openai.com/v1/embeddings"
+    #[serde(default)]
+    pub endpoint: Option<String>,
+    /// Model name the `remote` model asks the API for
+    /// Example: "text-embedding-3-small"
+    #[serde(default)]
+    pub remote_model: Option<String>,
+    /// Extra attempts for each model file download after the first fails
+    #[serde(default = "default_download_retries")]
+    pub download_retries: u32,
+    /// Delay before the first retry; doubled after each further failure
+    #[serde(default = "default_download_backoff_ms")]
+    pub download_backoff_ms: u64,
+    /// Overall time budget in seconds for loading a model, downloads and
+    /// retries included (unset for no limit)
+    #[serde(default)]
+    pub load_timeout_secs: Option<u64>,
+    /// Where the ONNX backend runs the model; ignored without the `onnx`
+    /// feature
+    #[serde(default)]
+    pub execution_provider: ExecutionProvider,
+    /// ONNX sessions a model may open so threads can embed concurrently;
+    /// the cores are split between them (0 = one per four cores)
+    #[serde(default)]
+    pub inference_sessions: usize,
+    /// Query embeddings kept for repeated searches (0 = no caching)
+    #[serde(default = "default_query_cache_size")]
+    pub query_cache_size: usize,
 }
 
 impl Default for ModelConfig {
@@ -44,6 +134,14 @@ impl Default for ModelConfig {
             auto_download: default_auto_download(),
             model_path: None,
             embedding_dim: None,
+            endpoint: None,
+            remote_model: None,
+            download_retries: default_download_retries(),
+            download_backoff_ms: default_download_backoff_ms(),
+            load_timeout_secs: None,
+            execution_provider: ExecutionProvider::default(),
+            inference_sessions: 0,
+            query_cache_size: default_query_cache_size(),
         }
     }
 }
@@ -244,10 +342,62 @@ fn default_use_gitignore() -> bool {
     true
 }
 
+fn default_detect_git_root() -> bool {
+    true
+}
+
+fn default_use_ignore_files() -> bool {
+    true
+}
+
+fn default_use_git_exclude() -> bool {
+    true
+}
+
 fn default_batch_size() -> usize {
     32
 }
 
+fn default_deterministic_order() -> bool {
+    false
+}
+
+fn default_embedding_workers() -> usize {
+    2
+}
+
+fn default_index_file_names() -> bool {
+    false
+}
+
+fn default_follow_symlinks() -> bool {
+    false
+}
+
+fn default_max_file_bytes() -> u64 {
+    1024 * 1024
+}
+
+fn default_pipeline_buffer() -> usize {
+    4
+}
+
+fn default_blame_authors() -> bool {
+    false
+}
+
+fn default_normalize_whitespace() -> bool {
+    false
+}
+
+fn default_truncation_warning_threshold() -> usize {
+    3
+}
+
+fn default_index_non_utf8() -> bool {
+    true
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct IndexingConfig {
     #[serde(default = "default_extensions")]
@@ -258,8 +408,65 @@ pub struct IndexingConfig {
     pub skip_files: Vec<String>,
     #[serde(default = "default_use_gitignore")]
     pub use_gitignore: bool,
+    /// Walk up to the enclosing git repository root so that `.gitignore`
+    /// files above the indexed directory are honoured
+    #[serde(default = "default_detect_git_root")]
+    pub detect_git_root: bool,
+    /// Honour `.ignore` files alongside `.gitignore` (with `use_gitignore`)
+    #[serde(default = "default_use_ignore_files")]
+    pub use_ignore_files: bool,
+    /// Honour the repository's `.git/info/exclude` (with `use_gitignore`)
+    #[serde(default = "default_use_git_exclude")]
+    pub use_git_exclude: bool,
+    /// Also honour the nearest `.gitignore` above the repository root and
+    /// the user's global excludes file (with `use_gitignore`)
+    #[serde(default)]
+    pub use_global_gitignore: bool,
     #[serde(default = "default_batch_size")]
     pub batch_size: usize,
+    /// Insert files in path order (and each file's chunks in line order) so
+    /// identical trees get identical chunk rowids on every run and platform
+    #[serde(default = "default_deterministic_order")]
+    pub deterministic_order: bool,
+    /// Threads embedding chunk batches while earlier batches are inserted
+    /// (forced to 1 when `deterministic_order` is set)
+    #[serde(default = "default_embedding_workers")]
+    pub embedding_workers: usize,
+    /// Batches each stage of the indexing pipeline may queue; bounds the
+    /// embeddings held in memory to roughly `(2 * buffer + workers) * batch_size`
+    #[serde(default = "default_pipeline_buffer")]
+    pub pipeline_buffer: usize,
+    /// Also index each file's path, split into words, as a `path` chunk so
+    /// files can be found by name
+    #[serde(default = "default_index_file_names")]
+    pub index_file_names: bool,
+    /// Descend into symlinked directories and index symlinked files. A file
+    /// reachable under several paths is indexed once, preferably under its
+    /// real path.
+    #[serde(default = "default_follow_symlinks")]
+    pub follow_symlinks: bool,
+    /// Skip files larger than this many bytes, such as minified bundles and
+    /// generated code (0 disables the limit)
+    #[serde(default = "default_max_file_bytes")]
+    pub max_file_bytes: u64,
+    /// Record the author of most of each chunk's lines from `git blame`,
+    /// run once per indexed file, for `search --author`. Needs the `git`
+    /// feature.
+    #[serde(default = "default_blame_authors")]
+    pub blame_authors: bool,
+    /// Embed chunk text with tabs turned to spaces, runs of spaces collapsed
+    /// and trailing whitespace trimmed. The stored content keeps its
+    /// original whitespace for display and line offsets.
+    #[serde(default = "default_normalize_whitespace")]
+    pub normalize_whitespace: bool,
+    /// Suggest smaller or token-sized chunks for a file once this many of
+    /// its chunks run past the model's input limit (0 disables)
+    #[serde(default = "default_truncation_warning_threshold")]
+    pub truncation_warning_threshold: usize,
+    /// Index files that aren't valid UTF-8, such as Latin-1 comments, with
+    /// U+FFFD in place of the bad bytes instead of skipping them
+    #[serde(default = "default_index_non_utf8")]
+    pub index_non_utf8: bool,
 }
 
 impl Default for IndexingConfig {
@@ -269,7 +476,21 @@ impl Default for IndexingConfig {
             skip_dirs: default_skip_dirs(),
             skip_files: default_skip_files(),
             use_gitignore: default_use_gitignore(),
+            detect_git_root: default_detect_git_root(),
+            use_ignore_files: default_use_ignore_files(),
+            use_git_exclude: default_use_git_exclude(),
+            use_global_gitignore: false,
             batch_size: default_batch_size(),
+            deterministic_order: default_deterministic_order(),
+            embedding_workers: default_embedding_workers(),
+            pipeline_buffer: default_pipeline_buffer(),
+            index_file_names: default_index_file_names(),
+            follow_symlinks: default_follow_symlinks(),
+            max_file_bytes: default_max_file_bytes(),
+            blame_authors: default_blame_authors(),
+            normalize_whitespace: default_normalize_whitespace(),
+            truncation_warning_threshold: default_truncation_warning_threshold(),
+            index_non_utf8: default_index_non_utf8(),
         }
     }
 }
@@ -280,8 +501,12 @@ fn default_chunk_size() -> usize {
     50
 }
 
-fn default_chunk_overlap() -> usize {
-    10
+fn default_chunk_overlap() -> ChunkOverlap {
+    ChunkOverlap::Lines(10)
+}
+
+fn default_whole_file_max_lines() -> usize {
+    0
 }
 
 fn default_token_budget() -> String {
@@ -292,18 +517,121 @@ fn default_use_syntax_aware() -> bool {
     true
 }
 
+/// Overlap between consecutive chunks, either an absolute number of lines
+/// (`chunk_overlap = 10`) or a percentage of the chunk size
+/// (`chunk_overlap = "20%"`).
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "ChunkOverlapRepr", into = "ChunkOverlapRepr")]
+pub enum ChunkOverlap {
+    Lines(usize),
+    /// Fraction of the chunk size in `[0, 1)`
+    Fraction(f64),
+}
+
+impl ChunkOverlap {
+    /// Resolve to a line count for the given effective chunk size. Fractions
+    /// round down, so they always stay below the chunk size.
+    pub fn resolve(&self, chunk_size: usize) -> usize {
+        match *self {
+            ChunkOverlap::Lines(lines) => lines,
+            ChunkOverlap::Fraction(fraction) => (chunk_size as f64 * fraction).floor() as usize,
+        }
+    }
+}
+
+impl std::str::FromStr for ChunkOverlap {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let s = s.trim();
+        if let Some(percent) = s.strip_suffix('%') {
+            let percent: f64 = percent
+                .trim()
+                .parse()
+                .map_err(|_| format!("invalid overlap percentage: {}", s))?;
+            if !(0.0..100.0).contains(&percent) {
+                return Err(format!("overlap percentage must be in [0%, 100%): {}", s));
+            }
+            Ok(ChunkOverlap::Fraction(percent / 100.0))
+        } else {
+            s.parse()
+                .map(ChunkOverlap::Lines)
+                .map_err(|_| format!("invalid overlap (expected lines or a percentage): {}", s))
+        }
+    }
+}
+
+impl std::fmt::Display for ChunkOverlap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ChunkOverlap::Lines(lines) => write!(f, "{}", lines),
+            ChunkOverlap::Fraction(fraction) => write!(f, "{}%", fraction * 100.0),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum ChunkOverlapRepr {
+    Lines(usize),
+    Text(String),
+}
+
+impl TryFrom<ChunkOverlapRepr> for ChunkOverlap {
+    type Error = String;
+
+    fn try_from(repr: ChunkOverlapRepr) -> std::result::Result<Self, Self::Error> {
+        match repr {
+            ChunkOverlapRepr::Lines(lines) => Ok(ChunkOverlap::Lines(lines)),
+            ChunkOverlapRepr::Text(text) => text.parse(),
+        }
+    }
+}
+
+impl From<ChunkOverlap> for ChunkOverlapRepr {
+    fn from(overlap: ChunkOverlap) -> Self {
+        match overlap {
+            ChunkOverlap::Lines(lines) => ChunkOverlapRepr::Lines(lines),
+            fraction => ChunkOverlapRepr::Text(fraction.to_string()),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChunkingConfig {
     #[serde(default = "default_chunk_size")]
     pub chunk_size: usize,
+    /// Lines shared by consecutive chunks: an absolute count or a percentage
+    /// of the chunk size such as "20%"
     #[serde(default = "default_chunk_overlap")]
-    pub chunk_overlap: usize,
+    pub chunk_overlap: ChunkOverlap,
+    /// Files with at most this many lines are embedded as a single
+    /// whole-file chunk regardless of chunk_size (0 disables)
+    #[serde(default = "default_whole_file_max_lines")]
+    pub whole_file_max_lines: usize,
     /// Token budget for chunks: "small" (256), "medium" (512), "large" (1024), or custom number
     #[serde(default = "default_token_budget")]
     pub token_budget: String,
-    /// Enable syntax-aware chunking using tree-sitter
+    /// Enable syntax-aware chunking using tree-sitter. Not consulted by
+    /// `split_file`; `split_strategy` selects the splitter.
     #[serde(default = "default_use_syntax_aware")]
     pub use_syntax_aware: bool,
+    /// How files are cut into chunks: "line_window" or "syntactic"
+    #[serde(default)]
+    pub split_strategy: SplitStrategy,
+    /// Chunk size and overlap for particular languages, keyed by the
+    /// language names results show ("python", "rust", ...). Languages not
+    /// listed use `chunk_size` and `chunk_overlap`.
+    #[serde(default)]
+    pub per_language: HashMap<String, ChunkParams>,
+}
+
+/// A language's entry in `chunking.per_language`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ChunkParams {
+    pub chunk_size: usize,
+    #[serde(default = "default_chunk_overlap")]
+    pub chunk_overlap: ChunkOverlap,
 }
 
 impl Default for ChunkingConfig {
@@ -311,8 +639,50 @@ impl Default for ChunkingConfig {
         Self {
             chunk_size: default_chunk_size(),
             chunk_overlap: default_chunk_overlap(),
+            whole_file_max_lines: default_whole_file_max_lines(),
             token_budget: default_token_budget(),
             use_syntax_aware: default_use_syntax_aware(),
+            split_strategy: SplitStrategy::default(),
+            per_language: HashMap::new(),
+        }
+    }
+}
+
+/// How `split_file` cuts a file into chunks
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SplitStrategy {
+    /// Fixed windows of `chunk_size` lines sharing `chunk_overlap` lines
+    #[default]
+    LineWindow,
+    /// Top-level items (functions, types, impls...) grouped into chunks of
+    /// up to `chunk_size` lines, for languages with a tree-sitter grammar.
+    /// Oversized items, unknown languages and builds without the
+    /// `tree-sitter` feature use line windows.
+    Syntactic,
+}
+
+impl SplitStrategy {
+    /// The name used in config files
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SplitStrategy::LineWindow => "line_window",
+            SplitStrategy::Syntactic => "syntactic",
+        }
+    }
+}
+
+impl std::str::FromStr for SplitStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.trim().to_lowercase().replace('-', "_").as_str() {
+            "line_window" => Ok(SplitStrategy::LineWindow),
+            "syntactic" => Ok(SplitStrategy::Syntactic),
+            _ => Err(format!(
+                "invalid split strategy (expected line_window/syntactic): {}",
+                s
+            )),
         }
     }
 }
@@ -351,6 +721,102 @@ fn default_bm25_b() -> f64 {
     0.75
 }
 
+fn default_record_history() -> bool {
+    false
+}
+
+fn default_max_result_chars() -> usize {
+    16_384
+}
+
+fn default_dedup_across_codebases() -> bool {
+    true
+}
+
+fn default_max_scan_embedding_bytes() -> usize {
+    0
+}
+
+fn default_max_limit() -> usize {
+    1000
+}
+
+fn default_split_identifiers() -> bool {
+    false
+}
+
+/// How the terms of a plain multi-word query are joined for full-text search
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FtsOperator {
+    /// A chunk matching any term is a hit
+    #[default]
+    #[serde(alias = "Or", alias = "OR")]
+    Or,
+    /// A chunk must contain every term
+    #[serde(alias = "And", alias = "AND")]
+    And,
+}
+
+impl FtsOperator {
+    /// The FTS5 keyword joining terms
+    pub fn keyword(&self) -> &'static str {
+        match self {
+            FtsOperator::Or => "OR",
+            FtsOperator::And => "AND",
+        }
+    }
+}
+
+impl std::str::FromStr for FtsOperator {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "or" => Ok(FtsOperator::Or),
+            "and" => Ok(FtsOperator::And),
+            _ => Err(format!("invalid FTS operator (expected or/and): {}", s)),
+        }
+    }
+}
+
+/// How hybrid search combines its full-text and vector results
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FusionStrategy {
+    /// Weighted scores blended with reciprocal rank fusion
+    #[default]
+    Rrf,
+    /// Each result list min-max normalized to 0..=1, then combined with
+    /// `fts_weight` and `vector_weight`
+    LinearBlend,
+}
+
+impl FusionStrategy {
+    /// The name used in config files
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FusionStrategy::Rrf => "rrf",
+            FusionStrategy::LinearBlend => "linear_blend",
+        }
+    }
+}
+
+impl std::str::FromStr for FusionStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.trim().to_lowercase().replace('-', "_").as_str() {
+            "rrf" => Ok(FusionStrategy::Rrf),
+            "linear_blend" => Ok(FusionStrategy::LinearBlend),
+            _ => Err(format!(
+                "invalid fusion strategy (expected rrf/linear_blend): {}",
+                s
+            )),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SearchConfig {
     #[serde(default = "default_limit")]
@@ -369,6 +835,42 @@ pub struct SearchConfig {
     pub bm25_k1: f64,
     #[serde(default = "default_bm25_b")]
     pub bm25_b: f64,
+    /// Append each search to the search_history table
+    #[serde(default = "default_record_history")]
+    pub record_history: bool,
+    /// Join bare multi-term queries with OR (any term, the default) or AND
+    /// (every term)
+    #[serde(default)]
+    pub fts_default_operator: FtsOperator,
+    /// Longest result content returned by a search, in characters; longer
+    /// chunks are cut and flagged `is_truncated` (0 disables)
+    #[serde(default = "default_max_result_chars")]
+    pub max_result_chars: usize,
+    /// In searches over all codebases, collapse chunks of the same file
+    /// content and line range indexed under several codebases (such as a
+    /// vendored file) into one result listing the others in `also_in`
+    #[serde(default = "default_dedup_across_codebases")]
+    pub dedup_across_codebases: bool,
+    /// Most embedding bytes a brute-force vector search holds at once; above
+    /// it candidates are scored in batches into a top-k heap (0 = no limit)
+    #[serde(default = "default_max_scan_embedding_bytes")]
+    pub max_scan_embedding_bytes: usize,
+    /// Ceiling on the number of results any search returns; larger (and
+    /// negative) limits are clamped with a warning
+    #[serde(default = "default_max_limit")]
+    pub max_limit: usize,
+    /// Index the parts of camelCase identifiers (`parseHTTPResponse` as
+    /// `parse HTTP Response`) and match query identifiers by their parts too.
+    /// Chunks indexed while this was off need re-indexing to benefit.
+    #[serde(default = "default_split_identifiers")]
+    pub split_identifiers: bool,
+    /// How hybrid search combines full-text and vector scores
+    #[serde(default)]
+    pub fusion_strategy: FusionStrategy,
+    /// Codebase (a path or label) searched when `search` gets no
+    /// `--codebase`
+    #[serde(default)]
+    pub default_codebase: Option<String>,
 }
 
 impl Default for SearchConfig {
@@ -382,6 +884,15 @@ impl Default for SearchConfig {
             enable_ltr: default_enable_ltr(),
             bm25_k1: default_bm25_k1(),
             bm25_b: default_bm25_b(),
+            record_history: default_record_history(),
+            fts_default_operator: FtsOperator::default(),
+            max_result_chars: default_max_result_chars(),
+            dedup_across_codebases: default_dedup_across_codebases(),
+            max_scan_embedding_bytes: default_max_scan_embedding_bytes(),
+            max_limit: default_max_limit(),
+            split_identifiers: default_split_identifiers(),
+            fusion_strategy: FusionStrategy::default(),
+            default_codebase: None,
         }
     }
 }
@@ -396,12 +907,21 @@ fn default_db_name() -> String {
     "index.db".to_string()
 }
 
+fn default_embedding_format() -> String {
+    "f32".to_string()
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DatabaseConfig {
     #[serde(default = "default_data_dir")]
     pub data_dir: String,
     #[serde(default = "default_db_name")]
     pub db_name: String,
+    /// How embeddings are stored: "f32", "f16" (half the size) or "int8"
+    /// (a quarter, scalar-quantized). Only applies when a database is
+    /// created or still empty.
+    #[serde(default = "default_embedding_format")]
+    pub embedding_format: String,
 }
 
 impl Default for DatabaseConfig {
@@ -409,6 +929,7 @@ impl Default for DatabaseConfig {
         Self {
             data_dir: default_data_dir(),
             db_name: default_db_name(),
+            embedding_format: default_embedding_format(),
         }
     }
 }
@@ -427,6 +948,10 @@ fn default_hnsw_ef_search() -> usize {
     64
 }
 
+fn default_ann_rebuild_threshold() -> f64 {
+    0.25
+}
+
 fn default_cache_size() -> usize {
     1000
 }
@@ -447,6 +972,9 @@ pub struct PerformanceConfig {
     pub hnsw_max_connections: usize,
     #[serde(default = "default_hnsw_ef_search")]
     pub hnsw_ef_search: usize,
+    /// Rebuild an ANN index once this share of its nodes are deleted tombstones
+    #[serde(default = "default_ann_rebuild_threshold")]
+    pub ann_rebuild_threshold: f64,
     #[serde(default = "default_cache_size")]
     pub cache_size: usize,
     #[serde(default = "default_batch_size_perf")]
@@ -461,6 +989,7 @@ impl Default for PerformanceConfig {
             hnsw_enabled: default_hnsw_enabled(),
             hnsw_max_connections: default_hnsw_max_connections(),
             hnsw_ef_search: default_hnsw_ef_search(),
+            ann_rebuild_threshold: default_ann_rebuild_threshold(),
             cache_size: default_cache_size(),
             batch_size: default_batch_size_perf(),
             use_gpu: default_use_gpu(),
@@ -504,7 +1033,7 @@ fn uuid_simple() -> String {
 
 // ============== Main Config ==============
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct Config {
     #[serde(default)]
     pub model: ModelConfig,
@@ -522,16 +1051,68 @@ pub struct Config {
     pub distributed: DistributedConfig,
 }
 
-impl Default for Config {
-    fn default() -> Self {
-        Self {
-            model: ModelConfig::default(),
-            indexing: IndexingConfig::default(),
-            chunking: ChunkingConfig::default(),
-            search: SearchConfig::default(),
-            database: DatabaseConfig::default(),
-            performance: PerformanceConfig::default(),
-            distributed: DistributedConfig::default(),
+/// `val`, the value of the environment override `CODE_SEARCH_<name>`,
+/// parsed; a failure is recorded in `errors`
+fn parse_env<T>(name: &str, val: &str, errors: &mut Vec<String>) -> Option<T>
+where
+    T: std::str::FromStr,
+    T::Err: std::fmt::Display,
+{
+    match val.parse() {
+        Ok(parsed) => Some(parsed),
+        Err(e) => {
+            errors.push(format!("{}{}={:?}: {}", ENV_PREFIX, name, val, e));
+            None
+        }
+    }
+}
+
+/// Whether `section.field` is a config field. Unknown fields are dropped on
+/// deserializing, so a field exists if a probe value set on it either fails
+/// to deserialize or survives the round trip.
+fn has_field(root: &toml::Value, section: &str, field: &str) -> bool {
+    let mut probe = root.clone();
+    let Some(table) = probe.get_mut(section).and_then(toml::Value::as_table_mut) else {
+        return false;
+    };
+    table.insert(field.to_string(), toml::Value::String(String::new()));
+    match probe.try_into::<Config>() {
+        Ok(config) => toml::Value::try_from(&config)
+            .ok()
+            .is_some_and(|value| value.get(section).and_then(|s| s.get(field)).is_some()),
+        Err(_) => true,
+    }
+}
+
+/// `value` as TOML, taking the type of the field's `current` value where
+/// it has one. Text that doesn't fit is left a string for deserializing
+/// to reject.
+fn parse_value(value: &str, current: Option<&toml::Value>) -> toml::Value {
+    use toml::Value;
+    match current {
+        Some(Value::String(_)) => Value::String(value.to_string()),
+        Some(Value::Array(_)) => Value::Array(
+            value
+                .split(',')
+                .map(str::trim)
+                .filter(|item| !item.is_empty())
+                .map(|item| Value::String(item.to_string()))
+                .collect(),
+        ),
+        Some(Value::Float(_)) => value
+            .parse()
+            .map(Value::Float)
+            .unwrap_or_else(|_| Value::String(value.to_string())),
+        _ => {
+            if let Ok(integer) = value.parse() {
+                Value::Integer(integer)
+            } else if let Ok(float) = value.parse() {
+                Value::Float(float)
+            } else if let Ok(boolean) = value.parse() {
+                Value::Boolean(boolean)
+            } else {
+                Value::String(value.to_string())
+            }
         }
     }
 }
@@ -573,6 +1154,14 @@ impl Config {
                 }
             }
         }
+        if self.model.model_type == "remote" {
+            if self.model.endpoint.is_none() {
+                return Some("Remote model requires endpoint in config".to_string());
+            }
+            if self.model.embedding_dim.is_none() {
+                return Some("Remote model requires embedding_dim in config".to_string());
+            }
+        }
         None
     }
 
@@ -580,8 +1169,11 @@ impl Config {
         self.chunking.chunk_size
     }
 
+    /// Overlap in lines, resolved against the configured chunk size
     pub fn chunk_overlap(&self) -> usize {
-        self.chunking.chunk_overlap
+        self.chunking
+            .chunk_overlap
+            .resolve(self.chunking.chunk_size)
     }
 
     pub fn token_budget(&self) -> &str {
@@ -693,18 +1285,7 @@ impl Config {
 
 impl Config {
     pub fn load() -> Self {
-        let mut config = Self::default();
-
-        // Try to load from config file
-        if let Some(path) = Self::config_path() {
-            if path.exists() {
-                if let Ok(content) = fs::read_to_string(&path) {
-                    if let Ok(file_config) = toml::from_str::<Config>(&content) {
-                        config = file_config;
-                    }
-                }
-            }
-        }
+        let mut config = Self::load_file();
 
         // Apply environment variable overrides
         config.apply_env_overrides();
@@ -712,46 +1293,343 @@ impl Config {
         config
     }
 
-    fn apply_env_overrides(&mut self) {
+    /// Like [`Config::load`], but a malformed config file, an environment
+    /// override that doesn't parse, or settings that can't work together
+    /// are an error instead of falling back to defaults
+    pub fn load_checked() -> Result<Self> {
+        let mut config = match Self::config_path().filter(|path| path.exists()) {
+            Some(path) => Self::from_toml(&fs::read_to_string(&path)?, &path)?,
+            None => Self::default(),
+        };
+
+        let errors = config.apply_env_overrides();
+        if !errors.is_empty() {
+            return Err(CodeSearchError::InvalidConfiguration(errors.join("; ")));
+        }
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Parse `content`, read from `path`, reporting where it is malformed
+    fn from_toml(content: &str, path: &Path) -> Result<Self> {
+        toml::from_str(content).map_err(|e| {
+            CodeSearchError::InvalidConfiguration(format!("{}: {}", path.display(), e.message()))
+        })
+    }
+
+    /// Reject settings that parse but can't work, such as an overlap that
+    /// leaves no room for chunks to advance
+    pub fn validate(&self) -> Result<()> {
+        let invalid = |message: String| Err(CodeSearchError::InvalidConfiguration(message));
+        let chunk_size = self.chunking.chunk_size;
+        if chunk_size == 0 {
+            return invalid("chunking.chunk_size must be at least 1".to_string());
+        }
+        let overlap = self.chunking.chunk_overlap.resolve(chunk_size);
+        if overlap >= chunk_size {
+            return invalid(format!(
+                "chunking.chunk_overlap ({} lines) must be smaller than chunking.chunk_size ({} lines)",
+                overlap, chunk_size
+            ));
+        }
+        for (language, params) in &self.chunking.per_language {
+            let key = format!("chunking.per_language.{}", language);
+            if params.chunk_size == 0 {
+                return invalid(format!("{}.chunk_size must be at least 1", key));
+            }
+            let overlap = params.chunk_overlap.resolve(params.chunk_size);
+            if overlap >= params.chunk_size {
+                return invalid(format!(
+                    "{0}.chunk_overlap ({1} lines) must be smaller than {0}.chunk_size ({2} lines)",
+                    key, overlap, params.chunk_size
+                ));
+            }
+        }
+        for (key, weight) in [
+            ("search.fts_weight", self.search.fts_weight),
+            ("search.vector_weight", self.search.vector_weight),
+        ] {
+            if !(0.0..=1.0).contains(&weight) {
+                return invalid(format!("{} must be between 0 and 1, not {}", key, weight));
+            }
+        }
+        // Anything else would quietly fall back to the default model
+        if self.model.model_type == "remote" {
+            if let Some(message) = self.validate_model_config() {
+                return invalid(message);
+            }
+        }
+        crate::embedding::check_embedding_dim(
+            &crate::embedding::ModelType::parse(&self.model.model_type),
+            self.model.embedding_dim,
+        )
+    }
+
+    /// The config file alone, without environment overrides, so it can be
+    /// changed and written back
+    pub fn load_file() -> Self {
+        Self::config_path()
+            .filter(|path| path.exists())
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|content| toml::from_str::<Config>(&content).ok())
+            .unwrap_or_default()
+    }
+
+    /// Set the field a dotted key such as `chunking.chunk_size` names,
+    /// parsing `value` as that field's type. Lists take comma-separated
+    /// items; an empty value clears an optional field and restores the
+    /// default of any other.
+    pub fn set(&mut self, key: &str, value: &str) -> std::result::Result<(), String> {
+        let unknown = || format!("unknown config key: {}", key);
+        let (section, field) = key.split_once('.').ok_or_else(unknown)?;
+        let mut root = toml::Value::try_from(&*self).map_err(|e| e.to_string())?;
+        if !has_field(&root, section, field) {
+            return Err(unknown());
+        }
+
+        let table = root
+            .get_mut(section)
+            .and_then(toml::Value::as_table_mut)
+            .ok_or_else(unknown)?;
+        let current = table.remove(field);
+        if !value.is_empty() {
+            table.insert(field.to_string(), parse_value(value, current.as_ref()));
+        }
+        *self = root
+            .try_into()
+            .map_err(|e: toml::de::Error| format!("invalid value for {}: {}", key, e.message()))?;
+        Ok(())
+    }
+
+    /// `content`, the config file at `path`, with `key` set as
+    /// [`Config::set`] does. A file that doesn't parse, or a value that
+    /// leaves settings [`Config::validate`] rejects, is an error rather
+    /// than something to write back. Only that key's line changes, so
+    /// comments and the order of the other keys survive.
+    pub fn edit_file(content: &str, path: &Path, key: &str, value: &str) -> Result<String> {
+        let invalid = |message: String| {
+            CodeSearchError::InvalidConfiguration(format!("{}: {}", path.display(), message))
+        };
+        let mut config = Self::from_toml(content, path)?;
+        config
+            .set(key, value)
+            .map_err(CodeSearchError::InvalidInput)?;
+        config.validate()?;
+
+        let mut document: toml_edit::DocumentMut = content
+            .parse()
+            .map_err(|e: toml_edit::TomlError| invalid(e.to_string()))?;
+        let Some((section, field)) = key.split_once('.') else {
+            unreachable!("Config::set accepted {}", key);
+        };
+        let root = toml::Value::try_from(&config).map_err(|e| invalid(e.to_string()))?;
+        let updated = root
+            .get(section)
+            .and_then(|table| table.get(field))
+            .filter(|_| !value.is_empty());
+
+        let Some(updated) = updated else {
+            // Cleared: drop the line and let the default apply
+            if let Some(table) = document
+                .get_mut(section)
+                .and_then(toml_edit::Item::as_table_like_mut)
+            {
+                table.remove(field);
+            }
+            return Ok(document.to_string());
+        };
+        let mut updated: toml_edit::Value = updated
+            .to_string()
+            .parse()
+            .map_err(|e: toml_edit::TomlError| invalid(e.to_string()))?;
+        let table = document
+            .entry(section)
+            .or_insert_with(toml_edit::table)
+            .as_table_like_mut()
+            .ok_or_else(|| invalid(format!("{} is not a table", section)))?;
+        // Keep the spacing and any comment trailing the old value
+        if let Some(current) = table.get(field).and_then(toml_edit::Item::as_value) {
+            *updated.decor_mut() = current.decor().clone();
+        }
+        table.insert(field, toml_edit::Item::Value(updated));
+        Ok(document.to_string())
+    }
+
+    /// Apply the `CODE_SEARCH_*` environment overrides. A value that fails
+    /// to parse falls back to the field's default and is reported in the
+    /// returned list.
+    fn apply_env_overrides(&mut self) -> Vec<String> {
+        let mut errors = Vec::new();
+
         // Model overrides
         if let Ok(val) = env::var(format!("{}MODEL", ENV_PREFIX)) {
             self.model.model_type = val;
         }
         if let Ok(val) = env::var(format!("{}MODEL_AUTO_DOWNLOAD", ENV_PREFIX)) {
-            self.model.auto_download = val.parse().unwrap_or(true);
+            self.model.auto_download =
+                parse_env("MODEL_AUTO_DOWNLOAD", &val, &mut errors).unwrap_or(true);
+        }
+        if let Ok(val) = env::var(format!("{}MODEL_DOWNLOAD_RETRIES", ENV_PREFIX)) {
+            self.model.download_retries = parse_env("MODEL_DOWNLOAD_RETRIES", &val, &mut errors)
+                .unwrap_or(default_download_retries());
+        }
+        if let Ok(val) = env::var(format!("{}MODEL_LOAD_TIMEOUT", ENV_PREFIX)) {
+            self.model.load_timeout_secs = parse_env("MODEL_LOAD_TIMEOUT", &val, &mut errors);
         }
         if let Ok(val) = env::var(format!("{}MODEL_PATH", ENV_PREFIX)) {
             self.model.model_path = Some(val);
         }
         if let Ok(val) = env::var(format!("{}EMBEDDING_DIM", ENV_PREFIX)) {
-            self.model.embedding_dim = val.parse().ok();
+            self.model.embedding_dim = parse_env("EMBEDDING_DIM", &val, &mut errors);
+        }
+        if let Ok(val) = env::var(format!("{}MODEL_ENDPOINT", ENV_PREFIX)) {
+            self.model.endpoint = Some(val);
+        }
+        if let Ok(val) = env::var(format!("{}REMOTE_MODEL", ENV_PREFIX)) {
+            self.model.remote_model = Some(val);
+        }
+        if let Ok(val) = env::var(format!("{}EXECUTION_PROVIDER", ENV_PREFIX)) {
+            self.model.execution_provider =
+                parse_env("EXECUTION_PROVIDER", &val, &mut errors).unwrap_or_default();
+        }
+        if let Ok(val) = env::var(format!("{}INFERENCE_SESSIONS", ENV_PREFIX)) {
+            self.model.inference_sessions =
+                parse_env("INFERENCE_SESSIONS", &val, &mut errors).unwrap_or(0);
+        }
+        if let Ok(val) = env::var(format!("{}QUERY_CACHE_SIZE", ENV_PREFIX)) {
+            if let Some(size) = parse_env("QUERY_CACHE_SIZE", &val, &mut errors) {
+                self.model.query_cache_size = size;
+            }
         }
 
         // Indexing overrides
         if let Ok(val) = env::var(format!("{}BATCH_SIZE", ENV_PREFIX)) {
-            self.indexing.batch_size = val.parse().unwrap_or(32);
+            self.indexing.batch_size = parse_env("BATCH_SIZE", &val, &mut errors).unwrap_or(32);
         }
         if let Ok(val) = env::var(format!("{}USE_GITIGNORE", ENV_PREFIX)) {
-            self.indexing.use_gitignore = val.parse().unwrap_or(true);
+            self.indexing.use_gitignore =
+                parse_env("USE_GITIGNORE", &val, &mut errors).unwrap_or(true);
+        }
+        if let Ok(val) = env::var(format!("{}DETECT_GIT_ROOT", ENV_PREFIX)) {
+            self.indexing.detect_git_root =
+                parse_env("DETECT_GIT_ROOT", &val, &mut errors).unwrap_or(true);
+        }
+        if let Ok(val) = env::var(format!("{}USE_IGNORE_FILES", ENV_PREFIX)) {
+            self.indexing.use_ignore_files =
+                parse_env("USE_IGNORE_FILES", &val, &mut errors).unwrap_or(true);
+        }
+        if let Ok(val) = env::var(format!("{}USE_GIT_EXCLUDE", ENV_PREFIX)) {
+            self.indexing.use_git_exclude =
+                parse_env("USE_GIT_EXCLUDE", &val, &mut errors).unwrap_or(true);
+        }
+        if let Ok(val) = env::var(format!("{}USE_GLOBAL_GITIGNORE", ENV_PREFIX)) {
+            self.indexing.use_global_gitignore =
+                parse_env("USE_GLOBAL_GITIGNORE", &val, &mut errors).unwrap_or(false);
+        }
+        if let Ok(val) = env::var(format!("{}DETERMINISTIC_ORDER", ENV_PREFIX)) {
+            self.indexing.deterministic_order =
+                parse_env("DETERMINISTIC_ORDER", &val, &mut errors).unwrap_or(false);
+        }
+        if let Ok(val) = env::var(format!("{}INDEX_FILE_NAMES", ENV_PREFIX)) {
+            self.indexing.index_file_names =
+                parse_env("INDEX_FILE_NAMES", &val, &mut errors).unwrap_or(false);
+        }
+        if let Ok(val) = env::var(format!("{}FOLLOW_SYMLINKS", ENV_PREFIX)) {
+            self.indexing.follow_symlinks =
+                parse_env("FOLLOW_SYMLINKS", &val, &mut errors).unwrap_or(false);
+        }
+        if let Ok(val) = env::var(format!("{}MAX_FILE_BYTES", ENV_PREFIX)) {
+            if let Some(bytes) = parse_env("MAX_FILE_BYTES", &val, &mut errors) {
+                self.indexing.max_file_bytes = bytes;
+            }
+        }
+        if let Ok(val) = env::var(format!("{}EMBEDDING_WORKERS", ENV_PREFIX)) {
+            self.indexing.embedding_workers =
+                parse_env("EMBEDDING_WORKERS", &val, &mut errors).unwrap_or(2);
+        }
+        if let Ok(val) = env::var(format!("{}PIPELINE_BUFFER", ENV_PREFIX)) {
+            self.indexing.pipeline_buffer =
+                parse_env("PIPELINE_BUFFER", &val, &mut errors).unwrap_or(4);
+        }
+        if let Ok(val) = env::var(format!("{}BLAME_AUTHORS", ENV_PREFIX)) {
+            self.indexing.blame_authors =
+                parse_env("BLAME_AUTHORS", &val, &mut errors).unwrap_or(false);
+        }
+        if let Ok(val) = env::var(format!("{}NORMALIZE_WHITESPACE", ENV_PREFIX)) {
+            self.indexing.normalize_whitespace =
+                parse_env("NORMALIZE_WHITESPACE", &val, &mut errors).unwrap_or(false);
+        }
+        if let Ok(val) = env::var(format!("{}TRUNCATION_WARNING_THRESHOLD", ENV_PREFIX)) {
+            self.indexing.truncation_warning_threshold =
+                parse_env("TRUNCATION_WARNING_THRESHOLD", &val, &mut errors).unwrap_or(3);
+        }
+        if let Ok(val) = env::var(format!("{}INDEX_NON_UTF8", ENV_PREFIX)) {
+            self.indexing.index_non_utf8 =
+                parse_env("INDEX_NON_UTF8", &val, &mut errors).unwrap_or(true);
         }
 
         // Chunking overrides
         if let Ok(val) = env::var(format!("{}CHUNK_SIZE", ENV_PREFIX)) {
-            self.chunking.chunk_size = val.parse().unwrap_or(50);
+            self.chunking.chunk_size = parse_env("CHUNK_SIZE", &val, &mut errors).unwrap_or(50);
         }
         if let Ok(val) = env::var(format!("{}CHUNK_OVERLAP", ENV_PREFIX)) {
-            self.chunking.chunk_overlap = val.parse().unwrap_or(10);
+            self.chunking.chunk_overlap =
+                parse_env("CHUNK_OVERLAP", &val, &mut errors).unwrap_or_else(default_chunk_overlap);
+        }
+        if let Ok(val) = env::var(format!("{}WHOLE_FILE_MAX_LINES", ENV_PREFIX)) {
+            self.chunking.whole_file_max_lines =
+                parse_env("WHOLE_FILE_MAX_LINES", &val, &mut errors).unwrap_or(0);
+        }
+        if let Ok(val) = env::var(format!("{}SPLIT_STRATEGY", ENV_PREFIX)) {
+            self.chunking.split_strategy =
+                parse_env("SPLIT_STRATEGY", &val, &mut errors).unwrap_or_default();
         }
 
         // Search overrides
         if let Ok(val) = env::var(format!("{}DEFAULT_LIMIT", ENV_PREFIX)) {
-            self.search.default_limit = val.parse().unwrap_or(10);
+            self.search.default_limit = parse_env("DEFAULT_LIMIT", &val, &mut errors).unwrap_or(10);
         }
         if let Ok(val) = env::var(format!("{}FTS_WEIGHT", ENV_PREFIX)) {
-            self.search.fts_weight = val.parse().unwrap_or(0.6);
+            self.search.fts_weight = parse_env("FTS_WEIGHT", &val, &mut errors).unwrap_or(0.6);
         }
         if let Ok(val) = env::var(format!("{}VECTOR_WEIGHT", ENV_PREFIX)) {
-            self.search.vector_weight = val.parse().unwrap_or(0.4);
+            self.search.vector_weight =
+                parse_env("VECTOR_WEIGHT", &val, &mut errors).unwrap_or(0.4);
+        }
+        if let Ok(val) = env::var(format!("{}RECORD_HISTORY", ENV_PREFIX)) {
+            self.search.record_history =
+                parse_env("RECORD_HISTORY", &val, &mut errors).unwrap_or(false);
+        }
+        if let Ok(val) = env::var(format!("{}MAX_RESULT_CHARS", ENV_PREFIX)) {
+            self.search.max_result_chars = parse_env("MAX_RESULT_CHARS", &val, &mut errors)
+                .unwrap_or(default_max_result_chars());
+        }
+        if let Ok(val) = env::var(format!("{}DEDUP_ACROSS_CODEBASES", ENV_PREFIX)) {
+            self.search.dedup_across_codebases =
+                parse_env("DEDUP_ACROSS_CODEBASES", &val, &mut errors).unwrap_or(true);
+        }
+        if let Ok(val) = env::var(format!("{}MAX_SCAN_EMBEDDING_BYTES", ENV_PREFIX)) {
+            self.search.max_scan_embedding_bytes =
+                parse_env("MAX_SCAN_EMBEDDING_BYTES", &val, &mut errors).unwrap_or(0);
+        }
+        if let Ok(val) = env::var(format!("{}MAX_LIMIT", ENV_PREFIX)) {
+            self.search.max_limit =
+                parse_env("MAX_LIMIT", &val, &mut errors).unwrap_or(default_max_limit());
+        }
+        if let Ok(val) = env::var(format!("{}SPLIT_IDENTIFIERS", ENV_PREFIX)) {
+            self.search.split_identifiers =
+                parse_env("SPLIT_IDENTIFIERS", &val, &mut errors).unwrap_or(false);
+        }
+        if let Ok(val) = env::var(format!("{}FTS_DEFAULT_OPERATOR", ENV_PREFIX)) {
+            self.search.fts_default_operator =
+                parse_env("FTS_DEFAULT_OPERATOR", &val, &mut errors).unwrap_or_default();
+        }
+        if let Ok(val) = env::var(format!("{}FUSION_STRATEGY", ENV_PREFIX)) {
+            self.search.fusion_strategy =
+                parse_env("FUSION_STRATEGY", &val, &mut errors).unwrap_or_default();
+        }
+        if let Ok(val) = env::var(format!("{}DEFAULT_CODEBASE", ENV_PREFIX)) {
+            self.search.default_codebase = Some(val);
         }
 
         // Database overrides
@@ -761,31 +1639,42 @@ impl Config {
         if let Ok(val) = env::var(format!("{}DB_NAME", ENV_PREFIX)) {
             self.database.db_name = val;
         }
+        if let Ok(val) = env::var(format!("{}EMBEDDING_FORMAT", ENV_PREFIX)) {
+            self.database.embedding_format = val;
+        }
 
         // Performance overrides
         if let Ok(val) = env::var(format!("{}HNSW_ENABLED", ENV_PREFIX)) {
-            self.performance.hnsw_enabled = val.parse().unwrap_or(false);
+            self.performance.hnsw_enabled =
+                parse_env("HNSW_ENABLED", &val, &mut errors).unwrap_or(false);
         }
         if let Ok(val) = env::var(format!("{}HNSW_MAX_CONNECTIONS", ENV_PREFIX)) {
-            self.performance.hnsw_max_connections = val.parse().unwrap_or(16);
+            self.performance.hnsw_max_connections =
+                parse_env("HNSW_MAX_CONNECTIONS", &val, &mut errors).unwrap_or(16);
         }
         if let Ok(val) = env::var(format!("{}HNSW_EF_SEARCH", ENV_PREFIX)) {
-            self.performance.hnsw_ef_search = val.parse().unwrap_or(64);
+            self.performance.hnsw_ef_search =
+                parse_env("HNSW_EF_SEARCH", &val, &mut errors).unwrap_or(64);
         }
         if let Ok(val) = env::var(format!("{}CACHE_SIZE", ENV_PREFIX)) {
-            self.performance.cache_size = val.parse().unwrap_or(1000);
+            self.performance.cache_size =
+                parse_env("CACHE_SIZE", &val, &mut errors).unwrap_or(1000);
         }
         if let Ok(val) = env::var(format!("{}PERF_BATCH_SIZE", ENV_PREFIX)) {
-            self.performance.batch_size = val.parse().unwrap_or(32);
+            self.performance.batch_size =
+                parse_env("PERF_BATCH_SIZE", &val, &mut errors).unwrap_or(32);
         }
         if let Ok(val) = env::var(format!("{}USE_GPU", ENV_PREFIX)) {
-            self.performance.use_gpu = val.parse().unwrap_or(true);
+            self.performance.use_gpu = parse_env("USE_GPU", &val, &mut errors).unwrap_or(true);
         }
 
         // Distributed overrides
         if let Ok(val) = env::var(format!("{}DISTRIBUTED_ENABLED", ENV_PREFIX)) {
-            self.distributed.enabled = val.parse().unwrap_or(false);
+            self.distributed.enabled =
+                parse_env("DISTRIBUTED_ENABLED", &val, &mut errors).unwrap_or(false);
         }
+
+        errors
     }
 
     pub fn config_path() -> Option<PathBuf> {
@@ -800,6 +1689,9 @@ impl Config {
 
     /// Get the data directory for storing index data
     pub fn get_data_dir(&self) -> Option<PathBuf> {
+        if let Some(dir) = data_dir_override() {
+            return Some(dir);
+        }
         ProjectDirs::from(QUALIFIER, ORGANIZATION, APPLICATION)
             .map(|dirs| dirs.data_dir().join(&self.database.data_dir))
     }
@@ -813,24 +1705,75 @@ impl Config {
 
 // ============== Global Config Access ==============
 
-use std::sync::OnceLock;
+use std::sync::{Arc, RwLock};
 
-static GLOBAL_CONFIG: OnceLock<Config> = OnceLock::new();
+static GLOBAL_CONFIG: RwLock<Option<Arc<Config>>> = RwLock::new(None);
 
-/// Get the global configuration, loading it if necessary
-pub fn get_config() -> &'static Config {
-    GLOBAL_CONFIG.get_or_init(Config::load)
+/// Get the global configuration, loading it if necessary.
+///
+/// The returned snapshot stays valid after a later [`set_config`]; call
+/// `get_config()` again to see the replacement.
+pub fn get_config() -> Arc<Config> {
+    if let Some(config) = GLOBAL_CONFIG.read().ok().and_then(|guard| guard.clone()) {
+        return config;
+    }
+    let mut guard = GLOBAL_CONFIG.write().unwrap_or_else(|e| e.into_inner());
+    guard
+        .get_or_insert_with(|| Arc::new(Config::load()))
+        .clone()
 }
 
-/// Set the global configuration (useful for testing)
-pub fn set_config(config: Config) -> &'static Config {
-    GLOBAL_CONFIG.get_or_init(|| config)
+/// Replace the global configuration for the rest of the process
+pub fn set_config(config: Config) {
+    let mut guard = GLOBAL_CONFIG.write().unwrap_or_else(|e| e.into_inner());
+    *guard = Some(Arc::new(config));
 }
 
-/// Reset the global configuration (useful for testing)
+/// Drop any configuration set with [`set_config`], so the next
+/// [`get_config`] loads it from the config file and environment again
 pub fn reset_config() {
-    // OnceLock doesn't support reset, so this is a no-op
-    // For testing, use set_config() to replace the config
+    let mut guard = GLOBAL_CONFIG.write().unwrap_or_else(|e| e.into_inner());
+    *guard = None;
+}
+
+// ============== Data Directory Override ==============
+
+static DATA_DIR_OVERRIDE: RwLock<Option<PathBuf>> = RwLock::new(None);
+
+/// Override the directory holding the database and manifests for the rest of
+/// the process. `None` restores the configured location.
+pub fn set_data_dir_override(dir: Option<PathBuf>) {
+    if let Ok(mut guard) = DATA_DIR_OVERRIDE.write() {
+        *guard = dir;
+    }
+}
+
+/// The active data directory override, if any
+pub fn data_dir_override() -> Option<PathBuf> {
+    DATA_DIR_OVERRIDE
+        .read()
+        .ok()
+        .and_then(|guard| guard.clone())
+}
+
+/// Puts back the data directory override it replaced when dropped
+#[must_use = "the previous override is restored when the guard drops"]
+pub(crate) struct DataDirOverride {
+    previous: Option<PathBuf>,
+}
+
+impl Drop for DataDirOverride {
+    fn drop(&mut self) {
+        set_data_dir_override(self.previous.take());
+    }
+}
+
+/// Override the data directory only until the guard drops, as for one
+/// CLI run inside a longer-lived process
+pub(crate) fn scoped_data_dir_override(dir: PathBuf) -> DataDirOverride {
+    let previous = data_dir_override();
+    set_data_dir_override(Some(dir));
+    DataDirOverride { previous }
 }
 
 #[cfg(test)]
@@ -842,7 +1785,7 @@ mod tests {
         let config = Config::default();
         assert_eq!(config.model.model_type, "minilm");
         assert_eq!(config.chunking.chunk_size, 50);
-        assert_eq!(config.chunking.chunk_overlap, 10);
+        assert_eq!(config.chunking.chunk_overlap, ChunkOverlap::Lines(10));
         assert_eq!(config.search.default_limit, 10);
         assert_eq!(config.search.fts_weight, 0.6);
         assert_eq!(config.search.vector_weight, 0.4);
@@ -867,12 +1810,220 @@ model_type = "nomic"
 
 [chunking]
 chunk_size = 100
+split_strategy = "syntactic"
+
+[search]
+fusion_strategy = "linear_blend"
 "#;
         let config: Config = toml::from_str(partial_toml).unwrap();
         assert_eq!(config.model.model_type, "nomic");
         assert_eq!(config.chunking.chunk_size, 100);
-        assert_eq!(config.chunking.chunk_overlap, 10); // default
+        assert_eq!(config.chunking.chunk_overlap, ChunkOverlap::Lines(10)); // default
         assert_eq!(config.search.default_limit, 10); // default
+        assert_eq!(config.search.fusion_strategy, FusionStrategy::LinearBlend);
+        assert_eq!("linear-blend".parse(), Ok(FusionStrategy::LinearBlend));
+        assert_eq!(config.chunking.split_strategy, SplitStrategy::Syntactic);
+        assert_eq!("line-window".parse(), Ok(SplitStrategy::LineWindow));
+    }
+
+    #[test]
+    fn test_set_parses_the_field_type() {
+        let mut config = Config::default();
+        config.set("chunking.chunk_size", "80").unwrap();
+        assert_eq!(config.chunking.chunk_size, 80);
+
+        config.set("search.fts_weight", "1").unwrap();
+        assert_eq!(config.search.fts_weight, 1.0);
+        config.set("indexing.use_gitignore", "false").unwrap();
+        assert!(!config.indexing.use_gitignore);
+        config.set("indexing.extensions", ".rs, .py").unwrap();
+        assert_eq!(config.indexing.extensions, vec![".rs", ".py"]);
+        config.set("chunking.chunk_overlap", "25%").unwrap();
+        assert_eq!(config.chunking.chunk_overlap.to_string(), "25%");
+        config.set("model.execution_provider", "cuda").unwrap();
+        assert_eq!(config.model.execution_provider, ExecutionProvider::Cuda);
+
+        config.set("search.default_codebase", "/repo").unwrap();
+        assert_eq!(config.search.default_codebase.as_deref(), Some("/repo"));
+        config.set("search.default_codebase", "").unwrap();
+        assert_eq!(config.search.default_codebase, None);
+        config.set("model.load_timeout_secs", "30").unwrap();
+        assert_eq!(config.model.load_timeout_secs, Some(30));
+    }
+
+    #[test]
+    fn test_set_rejects_unknown_keys_and_bad_values() {
+        let mut config = Config::default();
+        for key in ["chunking.no_such_key", "nosection.chunk_size", "chunk_size"] {
+            let err = config.set(key, "1").unwrap_err();
+            assert!(err.contains("unknown config key"), "{}", err);
+        }
+
+        let err = config.set("chunking.chunk_size", "eighty").unwrap_err();
+        assert!(
+            err.starts_with("invalid value for chunking.chunk_size"),
+            "{}",
+            err
+        );
+        assert!(config.set("indexing.use_gitignore", "maybe").is_err());
+        assert!(config.set("model.execution_provider", "tpu").is_err());
+        assert_eq!(
+            config.chunking.chunk_size,
+            ChunkingConfig::default().chunk_size
+        );
+    }
+
+    #[test]
+    fn test_edit_file_refuses_broken_files_and_settings() {
+        let path = Path::new("config.toml");
+        let edited = Config::edit_file("", path, "chunking.chunk_size", "80").unwrap();
+        assert_eq!(
+            Config::from_toml(&edited, path)
+                .unwrap()
+                .chunking
+                .chunk_size,
+            80
+        );
+
+        // A file that doesn't parse is reported, not replaced by defaults
+        let err = Config::edit_file("[chunking\n", path, "search.fts_weight", "1").unwrap_err();
+        assert!(matches!(err, CodeSearchError::InvalidConfiguration(_)));
+
+        // Settings that parse but can't work together aren't written
+        let content = "[chunking]\nchunk_size = 40\n";
+        let err = Config::edit_file(content, path, "chunking.chunk_overlap", "40").unwrap_err();
+        assert!(
+            matches!(err, CodeSearchError::InvalidConfiguration(ref m) if m.contains("chunk_overlap")),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_edit_file_keeps_comments_and_key_order() {
+        let path = Path::new("config.toml");
+        let content = "\
+# Tuned for the monorepo
+[search]
+default_limit = 20  # more context per query
+
+[chunking]
+chunk_overlap = 5
+chunk_size = 60 # lines
+";
+        let edited = Config::edit_file(content, path, "chunking.chunk_size", "80").unwrap();
+        assert_eq!(edited, content.replace("= 60 #", "= 80 #"));
+
+        let edited = Config::edit_file(&edited, path, "search.default_limit", "").unwrap();
+        assert!(!edited.contains("default_limit"));
+        assert!(edited.starts_with("# Tuned for the monorepo\n[search]\n"));
+
+        let edited = Config::edit_file(&edited, path, "indexing.use_gitignore", "false").unwrap();
+        assert!(edited.ends_with("chunk_size = 80 # lines\n\n[indexing]\nuse_gitignore = false\n"));
+        assert!(
+            !Config::from_toml(&edited, path)
+                .unwrap()
+                .indexing
+                .use_gitignore
+        );
+    }
+
+    #[test]
+    fn test_malformed_config_file_is_an_error() {
+        let path = Path::new("config.toml");
+        let err = Config::from_toml("[chunking]\nchunk_size = \"eighty\"\n", path).unwrap_err();
+        assert!(
+            matches!(err, CodeSearchError::InvalidConfiguration(ref m) if m.starts_with("config.toml"))
+        );
+        assert!(Config::from_toml("[chunking\n", path).is_err());
+        assert!(Config::from_toml("[chunking]\nchunk_size = 80\n", path).is_ok());
+    }
+
+    #[test]
+    fn test_validate_rejects_unworkable_settings() {
+        assert!(Config::default().validate().is_ok());
+
+        let mut config = Config::default();
+        config.chunking.chunk_size = 10;
+        config.chunking.chunk_overlap = ChunkOverlap::Lines(10);
+        let err = config.validate().unwrap_err().to_string();
+        assert!(err.contains("chunking.chunk_overlap"), "{}", err);
+
+        let mut config = Config::default();
+        config.chunking.chunk_size = 0;
+        assert!(config.validate().is_err());
+
+        for weight in [-0.1, 1.5, f64::NAN] {
+            let mut config = Config::default();
+            config.search.fts_weight = weight;
+            assert!(config.validate().is_err(), "fts_weight {}", weight);
+            let mut config = Config::default();
+            config.search.vector_weight = weight;
+            assert!(config.validate().is_err(), "vector_weight {}", weight);
+        }
+
+        let mut config = Config::default();
+        config.model.model_type = "nomic".to_string();
+        config.model.embedding_dim = Some(256);
+        assert!(config.validate().is_ok());
+        config.model.embedding_dim = Some(1024);
+        let err = config.validate().unwrap_err().to_string();
+        assert!(err.contains("model.embedding_dim"), "{}", err);
+
+        let mut config = Config::default();
+        config.model.model_type = "remote".to_string();
+        config.model.embedding_dim = Some(1536);
+        let err = config.validate().unwrap_err().to_string();
+        assert!(err.contains("endpoint"), "{}", err);
+        config.model.endpoint = Some("http://localhost:8080/v1/embeddings".to_string());
+        assert!(config.validate().is_ok());
+
+        let mut config: Config = toml::from_str(
+            "[chunking.per_language.python]\nchunk_size = 30\nchunk_overlap = \"10%\"\n",
+        )
+        .unwrap();
+        assert_eq!(
+            config.chunking.per_language["python"],
+            ChunkParams {
+                chunk_size: 30,
+                chunk_overlap: ChunkOverlap::Fraction(0.1),
+            }
+        );
+        assert!(config.validate().is_ok());
+        config.chunking.per_language.insert(
+            "go".to_string(),
+            ChunkParams {
+                chunk_size: 8,
+                chunk_overlap: ChunkOverlap::Lines(8),
+            },
+        );
+        let err = config.validate().unwrap_err().to_string();
+        assert!(
+            err.contains("chunking.per_language.go.chunk_overlap"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_chunk_overlap_forms() {
+        let config: Config =
+            toml::from_str("[chunking]\nchunk_size = 40\nchunk_overlap = 8\n").unwrap();
+        assert_eq!(config.chunking.chunk_overlap, ChunkOverlap::Lines(8));
+        assert_eq!(config.chunk_overlap(), 8);
+
+        let config: Config =
+            toml::from_str("[chunking]\nchunk_size = 40\nchunk_overlap = \"25%\"\n").unwrap();
+        assert_eq!(config.chunking.chunk_overlap, ChunkOverlap::Fraction(0.25));
+        assert_eq!(config.chunk_overlap(), 10);
+
+        // Round-trips through TOML in the same form
+        let toml_str = toml::to_string(&config).unwrap();
+        assert!(toml_str.contains("chunk_overlap = \"25%\""));
+
+        assert!(toml::from_str::<Config>("[chunking]\nchunk_overlap = \"100%\"\n").is_err());
+        assert!(toml::from_str::<Config>("[chunking]\nchunk_overlap = \"ten\"\n").is_err());
+        assert_eq!(ChunkOverlap::Fraction(0.99).resolve(1), 0);
     }
 
     #[test]
@@ -916,6 +2067,38 @@ chunk_size = 100
         assert!(dirs.contains(&"target".to_string()));
     }
 
+    #[test]
+    fn test_set_config_takes_effect_and_reset_restores() {
+        let config = crate::test_support::override_config(Config::default());
+        let loaded = Config::load().chunk_size();
+
+        let mut custom = Config::default();
+        custom.chunking.chunk_size = loaded + 17;
+        config.set(custom);
+        assert_eq!(get_config().chunk_size(), loaded + 17);
+
+        reset_config();
+        assert_eq!(get_config().chunk_size(), loaded);
+    }
+
+    #[test]
+    fn test_load_checked_reports_unparsable_env_override() {
+        // Exclusive, as other tests could load the config meanwhile
+        let _config = crate::test_support::override_config(Config::default());
+        std::env::set_var("CODE_SEARCH_FTS_WEIGHT", "heavy");
+        let checked = Config::load_checked();
+        let lenient = Config::load();
+        std::env::remove_var("CODE_SEARCH_FTS_WEIGHT");
+
+        match checked {
+            Err(CodeSearchError::InvalidConfiguration(message)) => {
+                assert!(message.contains("CODE_SEARCH_FTS_WEIGHT"), "{}", message);
+            }
+            other => panic!("expected InvalidConfiguration, got {:?}", other.map(|_| ())),
+        }
+        assert_eq!(lenient.search.fts_weight, 0.6);
+    }
+
     #[test]
     fn test_skip_files_contain_common() {
         let config = Config::default();
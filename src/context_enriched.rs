@@ -145,11 +145,11 @@ pub fn extract_imports(source: &str, language: &str) -> Vec<String> {
             // Match: from module import name
             for line in &lines {
                 let trimmed = line.trim();
-                if trimmed.starts_with("import ") {
-                    if let Some(as_pos) = trimmed.find(" as ") {
-                        imports.push(trimmed[7..as_pos].to_string());
+                if let Some(rest) = trimmed.strip_prefix("import ") {
+                    if let Some(as_pos) = rest.find(" as ") {
+                        imports.push(rest[..as_pos].to_string());
                     } else {
-                        imports.push(trimmed[7..].to_string());
+                        imports.push(rest.to_string());
                     }
                 } else if trimmed.starts_with("from ") {
                     if let Some(import_pos) = trimmed.get(5..).and_then(|s| s.find(" import")) {
@@ -166,7 +166,7 @@ pub fn extract_imports(source: &str, language: &str) -> Vec<String> {
             for line in &lines {
                 let trimmed = line.trim();
                 if trimmed.starts_with("import ") {
-                    if let Some(from_pos) = trimmed.get(7..).and_then(|s| s.find(" from")) {
+                    if let Some(_from_pos) = trimmed.get(7..).and_then(|s| s.find(" from")) {
                         let after_from = &trimmed[7 + " from".len()..];
                         if let Some(end) = after_from.find(|c: char| !c.is_alphanumeric() && c != '_' && c != '-' && c != '.' && c != '/' && c != '@') {
                             let module = &after_from[..end];
@@ -204,9 +204,9 @@ pub fn extract_imports(source: &str, language: &str) -> Vec<String> {
                     if !import.is_empty() {
                         imports.push(import.to_string());
                     }
-                } else if trimmed.starts_with("import \"") {
-                    if let Some(end) = trimmed[8..].find('"') {
-                        imports.push(trimmed[8..8 + end].to_string());
+                } else if let Some(rest) = trimmed.strip_prefix("import \"") {
+                    if let Some(end) = rest.find('"') {
+                        imports.push(rest[..end].to_string());
                     }
                 }
             }
@@ -398,13 +398,13 @@ pub fn extract_doc_comments(source: &str, language: &str) -> Vec<String> {
             let mut current_doc = String::new();
             for line in &lines {
                 let trimmed = line.trim();
-                if trimmed.starts_with("///") {
-                    let doc = trimmed[3..].trim();
+                if let Some(doc) = trimmed.strip_prefix("///") {
+                    let doc = doc.trim();
                     if !doc.is_empty() {
                         if current_doc.is_empty() {
                             current_doc = doc.to_string();
                         } else {
-                            current_doc.push_str(" ");
+                            current_doc.push(' ');
                             current_doc.push_str(doc);
                         }
                     }
@@ -422,7 +422,7 @@ pub fn extract_doc_comments(source: &str, language: &str) -> Vec<String> {
             // Match: ''' docstring '''
             let mut in_docstring = false;
             let mut current_doc = String::new();
-            let mut docstring_char = '"';
+            let mut docstring_char;
 
             for line in &lines {
                 let trimmed = line.trim();
@@ -474,12 +474,11 @@ pub fn extract_doc_comments(source: &str, language: &str) -> Vec<String> {
 
             for line in &lines {
                 let trimmed = line.trim();
-                if trimmed.starts_with("/**") {
+                if let Some(content) = trimmed.strip_prefix("/**") {
                     in_comment = true;
-                    let content = &trimmed[3..];
-                    if content.ends_with("*/") {
+                    if let Some(doc) = content.strip_suffix("*/") {
                         // Single-line comment
-                        let doc = content[..content.len() - 2].trim();
+                        let doc = doc.trim();
                         if !doc.is_empty() {
                             doc_comments.push(doc.to_string());
                         }
@@ -496,15 +495,7 @@ pub fn extract_doc_comments(source: &str, language: &str) -> Vec<String> {
                     in_comment = false;
                 } else if in_comment {
                     // Remove leading * from each line
-                    let cleaned = if trimmed.starts_with('*') {
-                        if trimmed.len() > 1 {
-                            &trimmed[1..]
-                        } else {
-                            ""
-                        }
-                    } else {
-                        trimmed
-                    };
+                    let cleaned = trimmed.strip_prefix('*').unwrap_or(trimmed);
                     if !cleaned.is_empty() {
                         current_doc.push_str(cleaned);
                         current_doc.push('\n');
@@ -597,19 +588,13 @@ pub fn extract_context(source: &str, language: &str) -> Vec<String> {
             // Match: export class Name { ...
             for line in &lines {
                 let trimmed = line.trim();
-                if trimmed.starts_with("class ") || trimmed.starts_with("export class ") {
-                    let name = if trimmed.starts_with("export class ") {
-                        if let Some(brace) = trimmed[13..].find('{') {
-                            &trimmed[13..13 + brace]
-                        } else {
-                            &trimmed[13..]
-                        }
-                    } else {
-                        if let Some(brace) = trimmed[6..].find('{') {
-                            &trimmed[6..6 + brace]
-                        } else {
-                            &trimmed[6..]
-                        }
+                let rest = trimmed
+                    .strip_prefix("export class ")
+                    .or_else(|| trimmed.strip_prefix("class "));
+                if let Some(rest) = rest {
+                    let name = match rest.find('{') {
+                        Some(brace) => &rest[..brace],
+                        None => rest,
                     };
                     context.push(format!("class {}", name.trim()));
                 }
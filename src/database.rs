@@ -1,11 +1,11 @@
-use crate::config::get_config;
+use crate::config::{get_config, Config, FtsOperator, FusionStrategy};
 use crate::error::{CodeSearchError, Result};
 use directories::ProjectDirs;
 use rusqlite::{params, Connection, OptionalExtension};
 use serde::Serialize;
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 // Legacy constants for backward compatibility
 #[deprecated(since = "0.3.0", note = "Use config.database.data_dir instead")]
@@ -17,6 +17,17 @@ pub const DB_NAME: &str = "index.db";
 const BM25_K1: f64 = 1.5;
 const BM25_B: f64 = 0.75;
 
+// Bulk insert parameters. The bundled SQLite allows up to 32766 bound
+// parameters per statement; a few hundred rows per INSERT already captures
+// nearly all of the gain over row-at-a-time execution.
+const SQLITE_MAX_PARAMS: usize = 32766;
+const INSERT_COLUMNS: usize = 12;
+const INSERT_BATCH_ROWS: usize = 256;
+const _: () = assert!(INSERT_BATCH_ROWS * INSERT_COLUMNS <= SQLITE_MAX_PARAMS);
+
+// Chunks fetched per query by `iter_chunks`
+const ITER_PAGE_ROWS: i64 = 512;
+
 #[derive(Debug, Clone)]
 pub struct Chunk {
     pub id: Option<i64>,
@@ -28,6 +39,11 @@ pub struct Chunk {
     pub language: Option<String>,
     pub embedding: Vec<f32>,
     pub hash: String,
+    /// `code`, `comment`, `doc` or `test`; see [`crate::splitter::classify_chunk`]
+    pub chunk_kind: Option<String>,
+    /// Author of most of the chunk's lines per `git blame`, when
+    /// `indexing.blame_authors` recorded one
+    pub author: Option<String>,
 }
 
 #[derive(Debug, Clone)]
@@ -39,8 +55,35 @@ pub struct SearchResult {
     pub end_line: i64,
     pub content: String,
     pub language: Option<String>,
+    /// See [`Chunk::author`]
+    pub author: Option<String>,
     pub score: f64,
     pub rank: i64,
+    /// `content` was cut at `search.max_result_chars` and ends with
+    /// [`TRUNCATION_MARKER`]
+    pub is_truncated: bool,
+    /// Other codebases holding this exact chunk (same file content and line
+    /// range), collapsed into this result by a search over all codebases
+    pub also_in: Vec<String>,
+}
+
+/// Appended to result content cut short by `search.max_result_chars`
+pub const TRUNCATION_MARKER: &str = "…";
+
+/// Cap `content` at `max_chars` characters (0 = no limit), appending
+/// [`TRUNCATION_MARKER`] when anything was cut
+pub(crate) fn cap_content(mut content: String, max_chars: usize) -> (String, bool) {
+    if max_chars == 0 {
+        return (content, false);
+    }
+    match content.char_indices().nth(max_chars) {
+        Some((cut, _)) => {
+            content.truncate(cut);
+            content.push_str(TRUNCATION_MARKER);
+            (content, true)
+        }
+        None => (content, false),
+    }
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -50,20 +93,33 @@ pub struct Stats {
     pub total_codebases: i64,
 }
 
-fn get_data_dir() -> Result<PathBuf> {
-    let proj_dirs =
-        ProjectDirs::from("com.code-search", "code-search", "code-search").ok_or_else(|| {
-            CodeSearchError::Io(std::io::Error::other("Failed to get project directories"))
-        })?;
-    let config = get_config();
-    let data_dir = proj_dirs.data_dir().join(config.data_dir());
+/// The directory holding the database: `data_dir` when given, otherwise
+/// `config`'s `database.data_dir` under the platform data directory.
+/// Created if missing.
+pub fn resolve_data_dir(data_dir: Option<&Path>, config: &Config) -> Result<PathBuf> {
+    let data_dir = match data_dir {
+        Some(dir) => dir.to_path_buf(),
+        None => {
+            let proj_dirs = ProjectDirs::from("com.code-search", "code-search", "code-search")
+                .ok_or_else(|| {
+                    CodeSearchError::Io(std::io::Error::other("Failed to get project directories"))
+                })?;
+            proj_dirs.data_dir().join(config.data_dir())
+        }
+    };
     fs::create_dir_all(&data_dir).map_err(CodeSearchError::Io)?;
     Ok(data_dir)
 }
 
+/// The database file for `data_dir` (see [`resolve_data_dir`])
+pub fn resolve_db_path(data_dir: Option<&Path>, config: &Config) -> Result<PathBuf> {
+    Ok(resolve_data_dir(data_dir, config)?.join(config.db_name()))
+}
+
+/// The database of this process: under `--data-dir` when set, else where the
+/// global config puts it
 pub fn get_db_path() -> Result<PathBuf> {
-    let data_dir = get_data_dir()?;
-    Ok(data_dir.join(get_config().db_name()))
+    resolve_db_path(crate::config::data_dir_override().as_deref(), &get_config())
 }
 
 pub fn reset_db() -> Result<()> {
@@ -75,6 +131,18 @@ pub fn reset_db() -> Result<()> {
 }
 
 fn run_migrations(conn: &Connection) -> Result<()> {
+    // A fresh database gets the current schema from init_db directly
+    let table_exists: bool = conn
+        .query_row(
+            "SELECT COUNT(*) > 0 FROM sqlite_master WHERE type = 'table' AND name = 'chunks'",
+            [],
+            |row| row.get(0),
+        )
+        .map_err(CodeSearchError::Database)?;
+    if !table_exists {
+        return Ok(());
+    }
+
     let migrations = [
         ("ALTER TABLE chunks ADD COLUMN author TEXT", "author"),
         (
@@ -85,6 +153,18 @@ fn run_migrations(conn: &Connection) -> Result<()> {
             "ALTER TABLE chunks ADD COLUMN modified_at INTEGER",
             "modified_at",
         ),
+        (
+            "ALTER TABLE chunks ADD COLUMN chunk_kind TEXT",
+            "chunk_kind",
+        ),
+        (
+            "ALTER TABLE chunks ADD COLUMN content_hash TEXT",
+            "content_hash",
+        ),
+        (
+            "ALTER TABLE chunks ADD COLUMN identifier_terms TEXT",
+            "identifier_terms",
+        ),
     ];
 
     for (sql, column) in &migrations {
@@ -101,12 +181,55 @@ fn run_migrations(conn: &Connection) -> Result<()> {
         }
     }
 
+    // The FTS update trigger used to fire on any column change, so rewriting
+    // embeddings in place also rewrote the FTS index. Drop the old definition;
+    // init_db recreates it limited to the indexed columns.
+    let stale_update_trigger: bool = conn
+        .query_row(
+            "SELECT COUNT(*) > 0 FROM sqlite_master
+             WHERE type = 'trigger' AND name = 'chunks_au' AND sql NOT LIKE '%UPDATE OF%'",
+            [],
+            |row| row.get(0),
+        )
+        .map_err(CodeSearchError::Database)?;
+    if stale_update_trigger {
+        conn.execute("DROP TRIGGER chunks_au", [])
+            .map_err(CodeSearchError::Database)?;
+    }
+
+    // The FTS triggers now index identifier_terms after the content. Rows
+    // indexed before have no terms, so the new triggers remove them cleanly.
+    for trigger in ["chunks_ai", "chunks_ad", "chunks_au"] {
+        let stale: bool = conn
+            .query_row(
+                "SELECT COUNT(*) > 0 FROM sqlite_master
+                 WHERE type = 'trigger' AND name = ?1 AND sql NOT LIKE '%identifier_terms%'",
+                params![trigger],
+                |row| row.get(0),
+            )
+            .map_err(CodeSearchError::Database)?;
+        if stale {
+            conn.execute(&format!("DROP TRIGGER {}", trigger), [])
+                .map_err(CodeSearchError::Database)?;
+        }
+    }
+
     Ok(())
 }
 
 pub fn init_db() -> Result<Connection> {
     let db_path = get_db_path()?;
-    let conn = Connection::open(&db_path).map_err(CodeSearchError::Database)?;
+    init_db_at(&db_path)
+}
+
+/// Open (creating if needed) the index database at an explicit path.
+pub fn init_db_at(db_path: &Path) -> Result<Connection> {
+    let conn = Connection::open(db_path).map_err(CodeSearchError::Database)?;
+
+    // REPLACE only fires delete triggers when recursive triggers are enabled;
+    // without this, replaced chunks would leave stale rows in chunks_fts.
+    conn.pragma_update(None, "recursive_triggers", true)
+        .map_err(CodeSearchError::Database)?;
 
     run_migrations(&conn)?;
 
@@ -125,6 +248,9 @@ pub fn init_db() -> Result<Connection> {
             author TEXT,
             created_at INTEGER,
             modified_at INTEGER,
+            chunk_kind TEXT,
+            content_hash TEXT,
+            identifier_terms TEXT,
             UNIQUE(codebase_id, file_path, start_line, end_line)
         );
 
@@ -134,6 +260,7 @@ pub fn init_db() -> Result<Connection> {
         CREATE INDEX IF NOT EXISTS idx_chunks_language ON chunks(language);
         CREATE INDEX IF NOT EXISTS idx_chunks_author ON chunks(author);
         CREATE INDEX IF NOT EXISTS idx_chunks_modified ON chunks(modified_at);
+        CREATE INDEX IF NOT EXISTS idx_chunks_kind ON chunks(chunk_kind);
 
         CREATE VIRTUAL TABLE IF NOT EXISTS chunks_fts USING fts5(
             content,
@@ -142,21 +269,28 @@ pub fn init_db() -> Result<Connection> {
             content_rowid='id'
         );
 
+        -- identifier_terms (see search.split_identifiers) is indexed after
+        -- the content so identifiers also match by their parts
         CREATE TRIGGER IF NOT EXISTS chunks_ai AFTER INSERT ON chunks BEGIN
             INSERT INTO chunks_fts(rowid, content, file_path)
-            VALUES (NEW.id, NEW.content, NEW.file_path);
+            VALUES (NEW.id, NEW.content || COALESCE(char(10) || NEW.identifier_terms, ''),
+                    NEW.file_path);
         END;
 
         CREATE TRIGGER IF NOT EXISTS chunks_ad AFTER DELETE ON chunks BEGIN
             INSERT INTO chunks_fts(chunks_fts, rowid, content, file_path)
-            VALUES ('delete', OLD.id, OLD.content, OLD.file_path);
+            VALUES ('delete', OLD.id, OLD.content || COALESCE(char(10) || OLD.identifier_terms, ''),
+                    OLD.file_path);
         END;
 
-        CREATE TRIGGER IF NOT EXISTS chunks_au AFTER UPDATE ON chunks BEGIN
+        CREATE TRIGGER IF NOT EXISTS chunks_au
+        AFTER UPDATE OF content, file_path, identifier_terms ON chunks BEGIN
             INSERT INTO chunks_fts(chunks_fts, rowid, content, file_path)
-            VALUES ('delete', OLD.id, OLD.content, OLD.file_path);
+            VALUES ('delete', OLD.id, OLD.content || COALESCE(char(10) || OLD.identifier_terms, ''),
+                    OLD.file_path);
             INSERT INTO chunks_fts(rowid, content, file_path)
-            VALUES (NEW.id, NEW.content, NEW.file_path);
+            VALUES (NEW.id, NEW.content || COALESCE(char(10) || NEW.identifier_terms, ''),
+                    NEW.file_path);
         END;
 
         -- Click-through feedback table for Learning-to-Rank
@@ -188,6 +322,80 @@ pub fn init_db() -> Result<Connection> {
         CREATE INDEX IF NOT EXISTS idx_codebases_name ON codebases(name);
         CREATE INDEX IF NOT EXISTS idx_codebases_path ON codebases(path);
 
+        -- Chunk and file counts per codebase, kept current by the triggers
+        -- below so listing codebases doesn't scan chunks
+        CREATE TABLE IF NOT EXISTS codebase_files (
+            codebase_id TEXT NOT NULL,
+            file_path TEXT NOT NULL,
+            chunk_count INTEGER NOT NULL,
+            PRIMARY KEY (codebase_id, file_path)
+        ) WITHOUT ROWID;
+
+        CREATE TABLE IF NOT EXISTS codebase_summary (
+            codebase_id TEXT PRIMARY KEY,
+            chunk_count INTEGER NOT NULL,
+            file_count INTEGER NOT NULL
+        );
+
+        CREATE TRIGGER IF NOT EXISTS chunks_summary_ai AFTER INSERT ON chunks BEGIN
+            INSERT INTO codebase_files (codebase_id, file_path, chunk_count)
+            VALUES (NEW.codebase_id, NEW.file_path, 1)
+            ON CONFLICT (codebase_id, file_path) DO UPDATE SET chunk_count = chunk_count + 1;
+            INSERT INTO codebase_summary (codebase_id, chunk_count, file_count)
+            VALUES (NEW.codebase_id, 1, 0)
+            ON CONFLICT (codebase_id) DO UPDATE SET chunk_count = chunk_count + 1;
+            UPDATE codebase_summary SET file_count = file_count + 1
+            WHERE codebase_id = NEW.codebase_id
+              AND (SELECT chunk_count FROM codebase_files
+                   WHERE codebase_id = NEW.codebase_id AND file_path = NEW.file_path) = 1;
+        END;
+
+        CREATE TRIGGER IF NOT EXISTS chunks_summary_ad AFTER DELETE ON chunks BEGIN
+            UPDATE codebase_files SET chunk_count = chunk_count - 1
+            WHERE codebase_id = OLD.codebase_id AND file_path = OLD.file_path;
+            UPDATE codebase_summary
+            SET chunk_count = chunk_count - 1,
+                file_count = file_count - (SELECT COUNT(*) FROM codebase_files
+                    WHERE codebase_id = OLD.codebase_id AND file_path = OLD.file_path
+                      AND chunk_count <= 0)
+            WHERE codebase_id = OLD.codebase_id;
+            DELETE FROM codebase_files
+            WHERE codebase_id = OLD.codebase_id AND file_path = OLD.file_path
+              AND chunk_count <= 0;
+            DELETE FROM codebase_summary
+            WHERE codebase_id = OLD.codebase_id AND chunk_count <= 0;
+        END;
+
+        CREATE TRIGGER IF NOT EXISTS chunks_summary_au
+        AFTER UPDATE OF codebase_id, file_path ON chunks
+        WHEN OLD.codebase_id IS NOT NEW.codebase_id OR OLD.file_path IS NOT NEW.file_path
+        BEGIN
+            UPDATE codebase_files SET chunk_count = chunk_count - 1
+            WHERE codebase_id = OLD.codebase_id AND file_path = OLD.file_path;
+            UPDATE codebase_summary
+            SET chunk_count = chunk_count - 1,
+                file_count = file_count - (SELECT COUNT(*) FROM codebase_files
+                    WHERE codebase_id = OLD.codebase_id AND file_path = OLD.file_path
+                      AND chunk_count <= 0)
+            WHERE codebase_id = OLD.codebase_id;
+            DELETE FROM codebase_files
+            WHERE codebase_id = OLD.codebase_id AND file_path = OLD.file_path
+              AND chunk_count <= 0;
+            DELETE FROM codebase_summary
+            WHERE codebase_id = OLD.codebase_id AND chunk_count <= 0;
+
+            INSERT INTO codebase_files (codebase_id, file_path, chunk_count)
+            VALUES (NEW.codebase_id, NEW.file_path, 1)
+            ON CONFLICT (codebase_id, file_path) DO UPDATE SET chunk_count = chunk_count + 1;
+            INSERT INTO codebase_summary (codebase_id, chunk_count, file_count)
+            VALUES (NEW.codebase_id, 1, 0)
+            ON CONFLICT (codebase_id) DO UPDATE SET chunk_count = chunk_count + 1;
+            UPDATE codebase_summary SET file_count = file_count + 1
+            WHERE codebase_id = NEW.codebase_id
+              AND (SELECT chunk_count FROM codebase_files
+                   WHERE codebase_id = NEW.codebase_id AND file_path = NEW.file_path) = 1;
+        END;
+
         -- Query popularity for query-dependent weights
         CREATE TABLE IF NOT EXISTS query_stats (
             id INTEGER PRIMARY KEY AUTOINCREMENT,
@@ -198,228 +406,1327 @@ pub fn init_db() -> Result<Connection> {
         );
 
         CREATE INDEX IF NOT EXISTS idx_query_stats_query ON query_stats(query_text);
-        "#,
-    )
-    .map_err(CodeSearchError::Database)?;
 
-    Ok(conn)
-}
+        -- Identifier frequencies per file, used for query suggestions
+        CREATE TABLE IF NOT EXISTS identifier_counts (
+            codebase_id TEXT NOT NULL,
+            file_path TEXT NOT NULL,
+            term TEXT NOT NULL,
+            frequency INTEGER NOT NULL,
+            PRIMARY KEY (codebase_id, file_path, term)
+        );
 
-pub fn insert_chunks(conn: &Connection, chunks: &[Chunk]) -> Result<i64> {
-    let tx = conn
-        .unchecked_transaction()
-        .map_err(CodeSearchError::Database)?;
+        CREATE INDEX IF NOT EXISTS idx_identifier_counts_term
+            ON identifier_counts(codebase_id, term COLLATE NOCASE);
 
-    let mut stmt = tx
-        .prepare(
-            "INSERT OR REPLACE INTO chunks (codebase_id, file_path, start_line, end_line, content, language, hash, embedding)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
-        )
-        .map_err(CodeSearchError::Database)?;
+        -- Past queries, recorded when search.record_history is enabled
+        CREATE TABLE IF NOT EXISTS search_history (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            codebase_id TEXT,
+            query TEXT NOT NULL,
+            timestamp INTEGER NOT NULL,
+            result_count INTEGER NOT NULL
+        );
 
-    let mut inserted_count = 0;
+        CREATE INDEX IF NOT EXISTS idx_search_history_codebase
+            ON search_history(codebase_id, timestamp);
 
-    for chunk in chunks {
-        let embedding_blob: Vec<u8> = chunk
-            .embedding
-            .iter()
-            .flat_map(|&f| f.to_le_bytes())
-            .collect();
+        -- Chunking parameters a codebase was first indexed with, reused by
+        -- incremental runs so all of its chunks share one chunking
+        CREATE TABLE IF NOT EXISTS codebase_chunking (
+            codebase_id TEXT PRIMARY KEY,
+            chunk_size INTEGER NOT NULL,
+            chunk_overlap INTEGER NOT NULL
+        );
+
+        -- Files touched by each commit indexed as a commit-message chunk
+        CREATE TABLE IF NOT EXISTS commit_files (
+            codebase_id TEXT NOT NULL,
+            commit_hash TEXT NOT NULL,
+            committed_at INTEGER NOT NULL,
+            file_path TEXT NOT NULL,
+            PRIMARY KEY (codebase_id, commit_hash, file_path)
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_commit_files_file
+            ON commit_files(codebase_id, file_path);
+
+        -- Database-wide settings fixed at creation, such as embedding_format
+        CREATE TABLE IF NOT EXISTS index_settings (
+            key TEXT PRIMARY KEY,
+            value TEXT NOT NULL
+        );
+        "#,
+    )
+    .map_err(CodeSearchError::Database)?;
 
-        stmt.execute(params![
-            &chunk.codebase_id,
-            &chunk.file_path,
-            &chunk.start_line,
-            &chunk.end_line,
-            &chunk.content,
-            &chunk.language,
-            &chunk.hash,
-            &embedding_blob,
-        ])
+    conn.execute_batch(crate::ann::SCHEMA)
         .map_err(CodeSearchError::Database)?;
+    crate::ann::migrate(&conn)?;
 
-        inserted_count += 1;
+    init_embedding_format(&conn)?;
+    if !codebase_summary_is_current(&conn)? {
+        refresh_codebase_summary(&conn)?;
     }
 
-    drop(stmt);
+    Ok(conn)
+}
 
-    tx.commit().map_err(CodeSearchError::Database)?;
+const CODEBASE_SUMMARY_KEY: &str = "codebase_summary";
 
-    Ok(inserted_count)
+/// Whether the `codebase_summary` counts can be trusted. They can't in a
+/// database written before the summary existed, until it is refreshed.
+fn codebase_summary_is_current(conn: &Connection) -> Result<bool> {
+    conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM index_settings WHERE key = ?1)",
+        params![CODEBASE_SUMMARY_KEY],
+        |row| row.get(0),
+    )
+    .map_err(CodeSearchError::Database)
 }
 
-pub fn delete_chunks_for_file(
-    conn: &Connection,
-    codebase_id: &str,
-    file_path: &str,
-) -> Result<i64> {
+/// Recount the per-codebase chunk and file summary from the chunks table.
+/// Triggers keep it current afterwards; this is only needed for a database
+/// that predates it.
+pub fn refresh_codebase_summary(conn: &Connection) -> Result<()> {
     let tx = conn
         .unchecked_transaction()
         .map_err(CodeSearchError::Database)?;
-
+    tx.execute_batch(
+        "DELETE FROM codebase_files;
+         DELETE FROM codebase_summary;
+         INSERT INTO codebase_files (codebase_id, file_path, chunk_count)
+             SELECT codebase_id, file_path, COUNT(*) FROM chunks
+             GROUP BY codebase_id, file_path;
+         INSERT INTO codebase_summary (codebase_id, chunk_count, file_count)
+             SELECT codebase_id, SUM(chunk_count), COUNT(*) FROM codebase_files
+             GROUP BY codebase_id;",
+    )
+    .map_err(CodeSearchError::Database)?;
     tx.execute(
-        "DELETE FROM chunks WHERE codebase_id = ?1 AND file_path = ?2",
-        params![codebase_id, file_path],
+        "INSERT OR REPLACE INTO index_settings (key, value) VALUES (?1, '1')",
+        params![CODEBASE_SUMMARY_KEY],
     )
     .map_err(CodeSearchError::Database)?;
+    tx.commit().map_err(CodeSearchError::Database)
+}
 
-    let deleted_count = tx.changes() as i64;
+/// How embedding vectors are encoded in `chunks.embedding`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmbeddingFormat {
+    /// Little-endian f32, 4 bytes per dimension
+    F32,
+    /// Little-endian IEEE half precision, 2 bytes per dimension
+    F16,
+    /// Scalar-quantized: a little-endian f32 scale followed by one signed
+    /// byte per dimension, each value being `byte * scale`
+    Int8,
+}
 
-    tx.commit().map_err(CodeSearchError::Database)?;
+impl EmbeddingFormat {
+    pub fn parse(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "f32" => Ok(Self::F32),
+            "f16" => Ok(Self::F16),
+            "int8" => Ok(Self::Int8),
+            other => Err(CodeSearchError::InvalidConfiguration(format!(
+                "Unknown embedding format '{}' (expected f32, f16 or int8)",
+                other
+            ))),
+        }
+    }
 
-    Ok(deleted_count)
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::F32 => "f32",
+            Self::F16 => "f16",
+            Self::Int8 => "int8",
+        }
+    }
+
+    /// Number of dimensions in a stored blob of `len` bytes
+    pub fn dimension(&self, len: usize) -> usize {
+        match self {
+            Self::F32 => len / 4,
+            Self::F16 => len / 2,
+            Self::Int8 => len.saturating_sub(INT8_SCALE_BYTES),
+        }
+    }
 }
 
-pub fn delete_chunks_for_codebase(conn: &Connection, codebase_id: &str) -> Result<i64> {
-    let tx = conn
-        .unchecked_transaction()
-        .map_err(CodeSearchError::Database)?;
+const EMBEDDING_FORMAT_KEY: &str = "embedding_format";
 
-    tx.execute(
-        "DELETE FROM chunks WHERE codebase_id = ?1",
-        params![codebase_id],
-    )
-    .map_err(CodeSearchError::Database)?;
+// Bytes of the per-vector scale heading an int8 embedding
+const INT8_SCALE_BYTES: usize = 4;
 
-    let deleted_count = tx.changes() as i64;
+/// Record the embedding format for a database. A database that already has
+/// embeddings keeps the format they were written in (f32 for databases
+/// older than the setting); an empty one takes the configured format.
+fn init_embedding_format(conn: &Connection) -> Result<()> {
+    let has_embeddings: bool = conn
+        .query_row(
+            "SELECT EXISTS(SELECT 1 FROM chunks WHERE length(embedding) > 0)",
+            [],
+            |row| row.get(0),
+        )
+        .map_err(CodeSearchError::Database)?;
+    let stored = stored_embedding_format(conn)?;
 
-    tx.commit().map_err(CodeSearchError::Database)?;
+    let format = match stored {
+        Some(format) if has_embeddings => format,
+        None if has_embeddings => EmbeddingFormat::F32,
+        _ => EmbeddingFormat::parse(&get_config().database.embedding_format)?,
+    };
+    if stored != Some(format) {
+        write_embedding_format(conn, format)?;
+    }
 
-    Ok(deleted_count)
+    Ok(())
 }
 
-/// Search filters for advanced filtering
-#[derive(Debug, Clone, Default)]
-pub struct SearchFilters {
-    pub language: Option<String>,
-    pub after_timestamp: Option<i64>,
-    pub author: Option<String>,
-    pub file_type: Option<String>,
-    pub imports: Option<String>,
+fn stored_embedding_format(conn: &Connection) -> Result<Option<EmbeddingFormat>> {
+    let value: Option<String> = conn
+        .query_row(
+            "SELECT value FROM index_settings WHERE key = ?1",
+            params![EMBEDDING_FORMAT_KEY],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(CodeSearchError::Database)?;
+    value.as_deref().map(EmbeddingFormat::parse).transpose()
 }
 
-pub fn fts_search(
-    conn: &Connection,
-    query: &str,
-    codebase_id: Option<&str>,
-    limit: i64,
-    filters: &SearchFilters,
-) -> Result<Vec<SearchResult>> {
-    let fts_query = query
-        .split_whitespace()
-        .filter(|word| word.len() > 1)
-        .collect::<Vec<_>>()
-        .join(" OR ");
+fn write_embedding_format(conn: &Connection, format: EmbeddingFormat) -> Result<()> {
+    conn.execute(
+        "INSERT OR REPLACE INTO index_settings (key, value) VALUES (?1, ?2)",
+        params![EMBEDDING_FORMAT_KEY, format.as_str()],
+    )
+    .map_err(CodeSearchError::Database)?;
+    Ok(())
+}
 
-    if fts_query.is_empty() {
-        return Ok(Vec::new());
+/// The format this database stores embeddings in
+pub fn embedding_format(conn: &Connection) -> Result<EmbeddingFormat> {
+    Ok(stored_embedding_format(conn)?.unwrap_or(EmbeddingFormat::F32))
+}
+
+/// Switch the embedding format of a database that has no embeddings yet
+pub fn set_embedding_format(conn: &Connection, format: EmbeddingFormat) -> Result<()> {
+    let current = embedding_format(conn)?;
+    if current == format {
+        return Ok(());
+    }
+    let has_embeddings: bool = conn
+        .query_row(
+            "SELECT EXISTS(SELECT 1 FROM chunks WHERE length(embedding) > 0)",
+            [],
+            |row| row.get(0),
+        )
+        .map_err(CodeSearchError::Database)?;
+    if has_embeddings {
+        return Err(CodeSearchError::InvalidConfiguration(format!(
+            "Embeddings are already stored as {}; reset the index to switch to {}",
+            current.as_str(),
+            format.as_str()
+        )));
     }
+    write_embedding_format(conn, format)
+}
 
-    // Build dynamic query with filters
-    let mut conditions = vec!["chunks_fts MATCH ?1".to_string()];
-    let mut params_vec: Vec<Box<dyn rusqlite::ToSql>> = vec![Box::new(fts_query.clone())];
-    let mut param_idx = 2;
+/// A transaction for one write to the chunks, or the caller's own when one
+/// is already open, so several writes can be committed (or rolled back)
+/// together
+struct WriteTx<'conn> {
+    conn: &'conn Connection,
+    tx: Option<rusqlite::Transaction<'conn>>,
+}
 
-    if let Some(cid) = codebase_id {
-        conditions.push(format!("c.codebase_id = ?{}", param_idx));
-        params_vec.push(Box::new(cid.to_string()));
-        param_idx += 1;
+impl<'conn> WriteTx<'conn> {
+    fn begin(conn: &'conn Connection) -> Result<Self> {
+        let tx = if conn.is_autocommit() {
+            Some(
+                conn.unchecked_transaction()
+                    .map_err(CodeSearchError::Database)?,
+            )
+        } else {
+            None
+        };
+        Ok(Self { conn, tx })
     }
 
-    if let Some(ref lang) = filters.language {
-        conditions.push(format!("c.language = ?{}", param_idx));
-        params_vec.push(Box::new(lang.clone()));
-        param_idx += 1;
+    fn commit(self) -> Result<()> {
+        match self.tx {
+            Some(tx) => tx.commit().map_err(CodeSearchError::Database),
+            None => Ok(()),
+        }
     }
+}
 
-    if let Some(ref author) = filters.author {
-        conditions.push(format!("c.author = ?{}", param_idx));
-        params_vec.push(Box::new(author.clone()));
-        param_idx += 1;
-    }
+impl std::ops::Deref for WriteTx<'_> {
+    type Target = Connection;
 
-    if let Some(ref file_type) = filters.file_type {
-        conditions.push(format!("c.file_path LIKE ?{}", param_idx));
-        params_vec.push(Box::new(format!("%.{}", file_type)));
-        param_idx += 1;
+    fn deref(&self) -> &Connection {
+        self.tx.as_deref().unwrap_or(self.conn)
     }
+}
 
-    if let Some(after) = filters.after_timestamp {
-        conditions.push(format!("c.modified_at > ?{}", param_idx));
-        params_vec.push(Box::new(after));
-        param_idx += 1;
+/// Insert `chunks`, returning how many were stored.
+///
+/// A file's identifier counts are rebuilt from the chunks given for it, so
+/// every chunk of a file has to be in the same call.
+pub fn insert_chunks(conn: &Connection, chunks: &[Chunk]) -> Result<i64> {
+    insert_chunks_inner(conn, chunks, get_config().search.split_identifiers)
+}
+
+/// Replace everything stored for `file_paths` with `chunks` in one
+/// transaction. Files without any chunks are just cleared.
+pub fn replace_file_chunks(
+    conn: &Connection,
+    codebase_id: &str,
+    file_paths: &[String],
+    chunks: &[Chunk],
+) -> Result<i64> {
+    let tx = WriteTx::begin(conn)?;
+    for file_path in file_paths {
+        delete_chunks_for_file(&tx, codebase_id, file_path)?;
     }
+    let inserted = insert_chunks(&tx, chunks)?;
+    tx.commit()?;
+    Ok(inserted)
+}
 
-    let where_clause = conditions.join(" AND ");
+fn insert_chunks_inner(
+    conn: &Connection,
+    chunks: &[Chunk],
+    split_identifiers: bool,
+) -> Result<i64> {
+    let tx = WriteTx::begin(conn)?;
 
-    // Use improved BM25 with explicit k1 and b parameters
-    let sql = format!(
-        r#"
-        SELECT c.id, c.codebase_id, c.file_path, c.start_line, c.end_line, c.content, c.language,
-               bm25(chunks_fts, {}, {}) as bm25_score
-        FROM chunks_fts fts
-        JOIN chunks c ON c.id = fts.rowid
-        WHERE {}
-        ORDER BY bm25_score
-        LIMIT ?{}
-        "#,
-        BM25_K1, BM25_B, where_clause, param_idx
-    );
+    let format = embedding_format(&tx)?;
+    validate_embedding_dimensions(&tx, chunks, format)?;
 
-    let mut stmt = conn.prepare(&sql).map_err(CodeSearchError::Database)?;
+    let mut codebase_ids: Vec<&str> = chunks.iter().map(|c| c.codebase_id.as_str()).collect();
+    codebase_ids.sort_unstable();
+    codebase_ids.dedup();
+    for codebase_id in &codebase_ids {
+        crate::ann::ensure_index(&tx, codebase_id)?;
+    }
 
-    params_vec.push(Box::new(limit));
+    let mut inserted_count = 0;
 
-    let params_refs: Vec<&dyn rusqlite::ToSql> = params_vec.iter().map(|p| p.as_ref()).collect();
+    for batch in chunks.chunks(INSERT_BATCH_ROWS) {
+        let embedding_blobs: Vec<Vec<u8>> = batch
+            .iter()
+            .map(|chunk| serialize_embedding(&chunk.embedding, format))
+            .collect();
+        let content_hashes: Vec<String> = batch
+            .iter()
+            .map(|chunk| chunk_content_hash(&chunk.content))
+            .collect();
+        let identifier_terms: Vec<Option<String>> = batch
+            .iter()
+            .map(|chunk| {
+                split_identifiers
+                    .then(|| crate::splitter::compound_identifier_terms(&chunk.content))
+                    .flatten()
+            })
+            .collect();
 
-    let mut results = Vec::new();
+        let mut values: Vec<&dyn rusqlite::ToSql> =
+            Vec::with_capacity(batch.len() * INSERT_COLUMNS);
+        for (((chunk, embedding_blob), content_hash), terms) in batch
+            .iter()
+            .zip(&embedding_blobs)
+            .zip(&content_hashes)
+            .zip(&identifier_terms)
+        {
+            values.push(&chunk.codebase_id);
+            values.push(&chunk.file_path);
+            values.push(&chunk.start_line);
+            values.push(&chunk.end_line);
+            values.push(&chunk.content);
+            values.push(&chunk.language);
+            values.push(&chunk.hash);
+            values.push(embedding_blob);
+            values.push(&chunk.chunk_kind);
+            values.push(content_hash);
+            values.push(terms);
+            values.push(&chunk.author);
+        }
 
-    let rows = stmt
-        .query_map(params_refs.as_slice(), |row| {
-            Ok(SearchResult {
-                chunk_id: row.get(0)?,
-                codebase_id: row.get(1)?,
-                file_path: row.get(2)?,
-                start_line: row.get(3)?,
-                end_line: row.get(4)?,
-                content: row.get(5)?,
-                language: row.get(6)?,
-                score: row.get::<_, f64>(7)?.abs(),
-                rank: 0,
-            })
-        })
-        .map_err(CodeSearchError::Database)?;
+        // Full batches share one cached statement; only the trailing partial
+        // batch needs a differently sized one.
+        let mut stmt = tx
+            .prepare_cached(&bulk_insert_sql(batch.len()))
+            .map_err(CodeSearchError::Database)?;
+        stmt.execute(rusqlite::params_from_iter(values))
+            .map_err(CodeSearchError::Database)?;
 
-    for row in rows {
-        results.push(row.map_err(CodeSearchError::Database)?);
+        inserted_count += batch.len() as i64;
     }
 
-    // Normalize BM25 scores to 0-1 range
-    let max_score: f64 = results
-        .iter()
-        .map(|r| r.score)
-        .fold(0.0, |max, s| max.max(s));
-    if max_score > 0.0 {
-        for result in &mut results {
-            result.score = result.score / max_score;
-        }
-    }
+    replace_identifier_counts(&tx, chunks)?;
 
-    for (i, result) in results.iter_mut().enumerate() {
-        result.rank = (i + 1) as i64;
+    for codebase_id in &codebase_ids {
+        crate::ann::maintain(&tx, codebase_id)?;
     }
 
-    Ok(results)
+    tx.commit()?;
+
+    Ok(inserted_count)
 }
 
-fn cosine_similarity(a: &[f32], b: &[f32]) -> f64 {
-    if a.len() != b.len() || a.is_empty() {
-        return 0.0;
-    }
-    let dot: f64 = a
-        .iter()
-        .zip(b.iter())
-        .map(|(x, y)| (*x as f64) * (*y as f64))
+/// Reject chunks whose embedding is empty or doesn't match the dimension of
+/// the codebase's stored embeddings (or, for a codebase without any yet, of
+/// the first chunk in the batch). Mixed dimensions would silently score as
+/// zero similarity.
+fn validate_embedding_dimensions(
+    conn: &Connection,
+    chunks: &[Chunk],
+    format: EmbeddingFormat,
+) -> Result<()> {
+    let mut expected: HashMap<&str, usize> = HashMap::new();
+
+    for chunk in chunks {
+        let codebase_id = chunk.codebase_id.as_str();
+        let dimension = match expected.get(codebase_id) {
+            Some(&dimension) => dimension,
+            None => {
+                let dimension =
+                    stored_dimension(conn, codebase_id, format)?.unwrap_or(chunk.embedding.len());
+                expected.insert(codebase_id, dimension);
+                dimension
+            }
+        };
+
+        if chunk.embedding.is_empty() || chunk.embedding.len() != dimension {
+            return Err(CodeSearchError::InvalidConfiguration(format!(
+                "Embedding for {}:{}-{} has dimension {} but codebase {} expects {}; \
+                 re-index with --force after changing models",
+                chunk.file_path,
+                chunk.start_line,
+                chunk.end_line,
+                chunk.embedding.len(),
+                codebase_id,
+                dimension
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+/// Dimension of the embeddings stored for a codebase, if it has any
+fn stored_dimension(
+    conn: &Connection,
+    codebase_id: &str,
+    format: EmbeddingFormat,
+) -> Result<Option<usize>> {
+    let stored: Option<i64> = conn
+        .query_row(
+            "SELECT length(embedding) FROM chunks
+             WHERE codebase_id = ?1 AND length(embedding) > 0
+             LIMIT 1",
+            params![codebase_id],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(CodeSearchError::Database)?;
+    Ok(stored.map(|len| format.dimension(len as usize)))
+}
+
+fn bulk_insert_sql(rows: usize) -> String {
+    let row = format!("({})", ["?"; INSERT_COLUMNS].join(", "));
+    format!(
+        "INSERT OR REPLACE INTO chunks (codebase_id, file_path, start_line, end_line, content, language, hash, embedding, chunk_kind, content_hash, identifier_terms, author)
+         VALUES {}",
+        vec![row.as_str(); rows].join(", ")
+    )
+}
+
+/// A page of `(id, content)` pairs for a codebase's chunks, in id order and
+/// starting after `after_id`. Pass the last id of one page to get the next.
+pub fn chunk_contents_after(
+    conn: &Connection,
+    codebase_id: &str,
+    after_id: i64,
+    limit: usize,
+) -> Result<Vec<(i64, String)>> {
+    let mut stmt = conn
+        .prepare_cached(
+            "SELECT id, content FROM chunks
+             WHERE codebase_id = ?1 AND id > ?2
+             ORDER BY id
+             LIMIT ?3",
+        )
+        .map_err(CodeSearchError::Database)?;
+
+    let page = stmt
+        .query_map(params![codebase_id, after_id, limit as i64], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })
+        .map_err(CodeSearchError::Database)?
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map_err(CodeSearchError::Database)?;
+
+    Ok(page)
+}
+
+/// Every chunk of a codebase in id order, with embeddings, read lazily a page
+/// at a time so the whole index is never held in memory
+pub fn iter_chunks<'conn>(
+    conn: &'conn Connection,
+    codebase_id: &str,
+) -> impl Iterator<Item = Result<Chunk>> + 'conn {
+    ChunkIter::new(conn, codebase_id, false)
+}
+
+/// Like [`iter_chunks`], but ordered by file path and then id, so each
+/// file's chunks come together
+pub fn iter_chunks_by_file<'conn>(
+    conn: &'conn Connection,
+    codebase_id: &str,
+) -> impl Iterator<Item = Result<Chunk>> + 'conn {
+    ChunkIter::new(conn, codebase_id, true)
+}
+
+// Keyset pagination over a cached statement, which avoids tying a borrowed
+// `Statement` and its `Rows` to the iterator's lifetime
+struct ChunkIter<'conn> {
+    conn: &'conn Connection,
+    codebase_id: String,
+    format: Option<EmbeddingFormat>,
+    /// Page in `(file_path, id)` order rather than by id alone
+    by_file: bool,
+    after_file: String,
+    after_id: i64,
+    page: VecDeque<Chunk>,
+    done: bool,
+}
+
+impl<'conn> ChunkIter<'conn> {
+    fn new(conn: &'conn Connection, codebase_id: &str, by_file: bool) -> Self {
+        Self {
+            conn,
+            codebase_id: codebase_id.to_string(),
+            format: None,
+            by_file,
+            after_file: String::new(),
+            after_id: 0,
+            page: VecDeque::new(),
+            done: false,
+        }
+    }
+
+    fn fill_page(&mut self) -> Result<()> {
+        let format = match self.format {
+            Some(format) => format,
+            None => *self.format.insert(embedding_format(self.conn)?),
+        };
+        let mut stmt = self
+            .conn
+            .prepare_cached(if self.by_file {
+                "SELECT id, file_path, start_line, end_line, content, language, embedding,
+                        hash, chunk_kind, author
+                 FROM chunks
+                 WHERE codebase_id = ?1 AND (file_path, id) > (?4, ?2)
+                 ORDER BY file_path, id
+                 LIMIT ?3"
+            } else {
+                "SELECT id, file_path, start_line, end_line, content, language, embedding,
+                        hash, chunk_kind, author
+                 FROM chunks
+                 WHERE codebase_id = ?1 AND id > ?2
+                 ORDER BY id
+                 LIMIT ?3"
+            })
+            .map_err(CodeSearchError::Database)?;
+
+        let mut rows = if self.by_file {
+            stmt.query(params![
+                self.codebase_id,
+                self.after_id,
+                ITER_PAGE_ROWS,
+                self.after_file
+            ])
+        } else {
+            stmt.query(params![self.codebase_id, self.after_id, ITER_PAGE_ROWS])
+        }
+        .map_err(CodeSearchError::Database)?;
+        while let Some(row) = rows.next().map_err(CodeSearchError::Database)? {
+            let blob: Option<Vec<u8>> = row.get(6).map_err(CodeSearchError::Database)?;
+            let chunk = (|| {
+                Ok(Chunk {
+                    id: Some(row.get(0)?),
+                    codebase_id: self.codebase_id.clone(),
+                    file_path: row.get(1)?,
+                    start_line: row.get(2)?,
+                    end_line: row.get(3)?,
+                    content: row.get(4)?,
+                    language: row.get(5)?,
+                    embedding: blob
+                        .map(|b| deserialize_embedding(&b, format))
+                        .unwrap_or_default(),
+                    hash: row.get(7)?,
+                    chunk_kind: row.get(8)?,
+                    author: row.get(9)?,
+                })
+            })()
+            .map_err(CodeSearchError::Database)?;
+            self.page.push_back(chunk);
+        }
+
+        match self.page.back() {
+            Some(last) => {
+                self.after_id = last.id.unwrap_or(self.after_id);
+                self.after_file.clone_from(&last.file_path);
+            }
+            None => self.done = true,
+        }
+        Ok(())
+    }
+}
+
+impl Iterator for ChunkIter<'_> {
+    type Item = Result<Chunk>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.page.is_empty() && !self.done {
+            if let Err(e) = self.fill_page() {
+                // An error ends the iteration rather than retrying the page
+                self.done = true;
+                return Some(Err(e));
+            }
+        }
+        self.page.pop_front().map(Ok)
+    }
+}
+
+/// Hash of a chunk's text, stored as `content_hash` so an edited file can
+/// keep the embeddings of its unchanged chunks
+pub fn chunk_content_hash(content: &str) -> String {
+    crate::manifest::hash_file_content(content.as_bytes())
+}
+
+/// Stored embeddings of a file's chunks, keyed by content hash. Chunks
+/// written before the `content_hash` column existed are left out.
+pub fn embeddings_by_content_hash(
+    conn: &Connection,
+    codebase_id: &str,
+    file_path: &str,
+) -> Result<HashMap<String, Vec<f32>>> {
+    let format = embedding_format(conn)?;
+    let mut stmt = conn
+        .prepare_cached(
+            "SELECT content_hash, embedding FROM chunks
+             WHERE codebase_id = ?1 AND file_path = ?2
+               AND content_hash IS NOT NULL AND length(embedding) > 0",
+        )
+        .map_err(CodeSearchError::Database)?;
+
+    let embeddings = stmt
+        .query_map(params![codebase_id, file_path], |row| {
+            let hash: String = row.get(0)?;
+            let blob: Vec<u8> = row.get(1)?;
+            Ok((hash, deserialize_embedding(&blob, format)))
+        })
+        .map_err(CodeSearchError::Database)?
+        .collect::<std::result::Result<HashMap<_, _>, _>>()
+        .map_err(CodeSearchError::Database)?;
+
+    Ok(embeddings)
+}
+
+/// Overwrite stored embeddings in place, keyed by chunk id. Unlike
+/// `insert_chunks` this doesn't check dimensions, so callers replacing a
+/// codebase's embeddings with a different model must update all of them.
+pub fn update_embeddings(conn: &Connection, embeddings: &[(i64, Vec<f32>)]) -> Result<()> {
+    let format = embedding_format(conn)?;
+    let mut stmt = conn
+        .prepare_cached("UPDATE chunks SET embedding = ?2 WHERE id = ?1")
+        .map_err(CodeSearchError::Database)?;
+
+    for (id, embedding) in embeddings {
+        stmt.execute(params![id, serialize_embedding(embedding, format)])
+            .map_err(CodeSearchError::Database)?;
+    }
+
+    Ok(())
+}
+
+pub fn delete_chunks_for_file(
+    conn: &Connection,
+    codebase_id: &str,
+    file_path: &str,
+) -> Result<i64> {
+    let tx = WriteTx::begin(conn)?;
+
+    tx.execute(
+        "DELETE FROM chunks WHERE codebase_id = ?1 AND file_path = ?2",
+        params![codebase_id, file_path],
+    )
+    .map_err(CodeSearchError::Database)?;
+
+    let deleted_count = tx.changes() as i64;
+
+    tx.execute(
+        "DELETE FROM identifier_counts WHERE codebase_id = ?1 AND file_path = ?2",
+        params![codebase_id, file_path],
+    )
+    .map_err(CodeSearchError::Database)?;
+
+    crate::ann::maintain(&tx, codebase_id)?;
+
+    tx.commit()?;
+
+    Ok(deleted_count)
+}
+
+/// Delete the chunks of every file whose path starts with `path_prefix`
+/// (e.g. `vendor/`) in one statement. Returns the number of chunks removed.
+pub fn delete_chunks_for_prefix(
+    conn: &Connection,
+    codebase_id: &str,
+    path_prefix: &str,
+) -> Result<i64> {
+    // LIKE ignores ASCII case, so compare the prefix itself: the manifest
+    // is pruned with a case-sensitive `starts_with`
+    let tx = WriteTx::begin(conn)?;
+
+    tx.execute(
+        "DELETE FROM chunks
+         WHERE codebase_id = ?1 AND substr(file_path, 1, length(?2)) = ?2",
+        params![codebase_id, path_prefix],
+    )
+    .map_err(CodeSearchError::Database)?;
+
+    let deleted_count = tx.changes() as i64;
+
+    tx.execute(
+        "DELETE FROM identifier_counts
+         WHERE codebase_id = ?1 AND substr(file_path, 1, length(?2)) = ?2",
+        params![codebase_id, path_prefix],
+    )
+    .map_err(CodeSearchError::Database)?;
+
+    crate::ann::maintain(&tx, codebase_id)?;
+
+    tx.commit()?;
+
+    Ok(deleted_count)
+}
+
+/// Escape `%`, `_` and `\` so a value matches literally in `LIKE ... ESCAPE '\'`
+fn escape_like(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+pub fn delete_chunks_for_codebase(conn: &Connection, codebase_id: &str) -> Result<i64> {
+    let tx = WriteTx::begin(conn)?;
+
+    tx.execute(
+        "DELETE FROM chunks WHERE codebase_id = ?1",
+        params![codebase_id],
+    )
+    .map_err(CodeSearchError::Database)?;
+
+    let deleted_count = tx.changes() as i64;
+
+    tx.execute(
+        "DELETE FROM identifier_counts WHERE codebase_id = ?1",
+        params![codebase_id],
+    )
+    .map_err(CodeSearchError::Database)?;
+
+    tx.execute(
+        "DELETE FROM codebase_chunking WHERE codebase_id = ?1",
+        params![codebase_id],
+    )
+    .map_err(CodeSearchError::Database)?;
+
+    tx.execute(
+        "DELETE FROM commit_files WHERE codebase_id = ?1",
+        params![codebase_id],
+    )
+    .map_err(CodeSearchError::Database)?;
+
+    crate::ann::clear(&tx, codebase_id)?;
+
+    tx.commit()?;
+
+    Ok(deleted_count)
+}
+
+/// Re-key everything stored for `old_codebase_id` to `new_codebase_id` and
+/// record `new_path` as its location, keeping chunks and embeddings as they
+/// are. Fails if the new id already has chunks. Returns the chunks moved.
+pub fn move_codebase(
+    conn: &Connection,
+    old_codebase_id: &str,
+    new_codebase_id: &str,
+    new_path: &str,
+) -> Result<i64> {
+    let occupied: bool = conn
+        .query_row(
+            "SELECT EXISTS(SELECT 1 FROM chunks WHERE codebase_id = ?1)",
+            params![new_codebase_id],
+            |row| row.get(0),
+        )
+        .map_err(CodeSearchError::Database)?;
+    if occupied {
+        return Err(CodeSearchError::InvalidInput(format!(
+            "{} is already indexed; delete it before moving another codebase there",
+            new_path
+        )));
+    }
+
+    let tx = conn
+        .unchecked_transaction()
+        .map_err(CodeSearchError::Database)?;
+
+    tx.execute(
+        "UPDATE chunks SET codebase_id = ?2 WHERE codebase_id = ?1",
+        params![old_codebase_id, new_codebase_id],
+    )
+    .map_err(CodeSearchError::Database)?;
+    let moved = tx.changes() as i64;
+
+    for table in [
+        "identifier_counts",
+        "codebase_chunking",
+        "commit_files",
+        "search_clicks",
+        "search_history",
+    ] {
+        tx.execute(
+            &format!(
+                "UPDATE {} SET codebase_id = ?2 WHERE codebase_id = ?1",
+                table
+            ),
+            params![old_codebase_id, new_codebase_id],
+        )
+        .map_err(CodeSearchError::Database)?;
+    }
+
+    tx.execute(
+        "UPDATE codebases SET codebase_id = ?2, path = ?3, last_updated = ?4
+         WHERE codebase_id = ?1",
+        params![
+            old_codebase_id,
+            new_codebase_id,
+            new_path,
+            chrono::Utc::now().timestamp()
+        ],
+    )
+    .map_err(CodeSearchError::Database)?;
+
+    crate::ann::rekey(&tx, old_codebase_id, new_codebase_id)?;
+
+    tx.commit().map_err(CodeSearchError::Database)?;
+
+    Ok(moved)
+}
+
+/// Clamp a requested result count into `0..=max_limit`. Returns the limit to
+/// use and whether the request was out of range. A negative limit would
+/// otherwise reach SQLite's LIMIT as "no limit" and dump the whole index.
+pub(crate) fn clamp_limit(requested: i64, max_limit: usize) -> (i64, bool) {
+    let max_limit = i64::try_from(max_limit).unwrap_or(i64::MAX);
+    let limit = requested.clamp(0, max_limit);
+    (limit, limit != requested)
+}
+
+/// [`clamp_limit`] against `search.max_limit`, warning when it clamps
+pub(crate) fn checked_limit(requested: i64) -> i64 {
+    let max_limit = get_config().search.max_limit;
+    let (limit, clamped) = clamp_limit(requested, max_limit);
+    if clamped {
+        eprintln!(
+            "Warning: result limit {} is outside 0..={} (search.max_limit); using {}",
+            requested, max_limit, limit
+        );
+    }
+    limit
+}
+
+/// Which `chunks_fts` columns a full-text query is matched against
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FtsColumns {
+    /// Chunk content and file path
+    #[default]
+    Both,
+    Content,
+    Path,
+}
+
+impl FtsColumns {
+    /// The FTS5 column the query is restricted to, if any
+    fn column(&self) -> Option<&'static str> {
+        match self {
+            FtsColumns::Both => None,
+            FtsColumns::Content => Some("content"),
+            FtsColumns::Path => Some("file_path"),
+        }
+    }
+}
+
+impl std::str::FromStr for FtsColumns {
+    type Err = CodeSearchError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "both" => Ok(FtsColumns::Both),
+            "content" => Ok(FtsColumns::Content),
+            "path" | "file_path" => Ok(FtsColumns::Path),
+            _ => Err(CodeSearchError::InvalidInput(format!(
+                "invalid search columns (expected content, path or both): {}",
+                s
+            ))),
+        }
+    }
+}
+
+/// Search filters for advanced filtering
+#[derive(Debug, Clone, Default)]
+pub struct SearchFilters {
+    pub language: Option<String>,
+    pub after_timestamp: Option<i64>,
+    pub author: Option<String>,
+    pub file_type: Option<String>,
+    pub imports: Option<String>,
+    /// Chunk kind a result must have (`code`, `comment`, `doc`, `test` or
+    /// `commit`)
+    pub kind: Option<String>,
+    /// Full-text columns query terms are matched against
+    pub fts_columns: FtsColumns,
+    /// Languages a result must be one of (`lang:rust`)
+    pub include_languages: Vec<String>,
+    /// Languages a result must not be (`-lang:json`)
+    pub exclude_languages: Vec<String>,
+    /// Path globs a result must match one of (`path:src/**`)
+    pub include_paths: Vec<String>,
+    /// Path globs a result must not match (`-path:src/gen/**`)
+    pub exclude_paths: Vec<String>,
+    /// Chunk kinds a result must be one of (`kind:doc`)
+    pub include_kinds: Vec<String>,
+    /// Chunk kinds a result must not be (`-kind:path`)
+    pub exclude_kinds: Vec<String>,
+    /// Drop results scoring below this once scored. Vector search scores
+    /// are cosine similarity (at most 1); hybrid scores are the weighted
+    /// fusion of both legs, roughly 0 to 1 with the default weights
+    pub min_score: Option<f64>,
+}
+
+impl SearchFilters {
+    /// Whether no filter restricts which chunks can match. The full-text
+    /// columns don't count, as they only choose what a query is matched
+    /// against, and neither does `min_score`, which applies to results.
+    pub fn is_unfiltered(&self) -> bool {
+        self.language.is_none()
+            && self.after_timestamp.is_none()
+            && self.author.is_none()
+            && self.file_type.is_none()
+            && self.imports.is_none()
+            && self.kind.is_none()
+            && self.include_languages.is_empty()
+            && self.exclude_languages.is_empty()
+            && self.include_paths.is_empty()
+            && self.exclude_paths.is_empty()
+            && self.include_kinds.is_empty()
+            && self.exclude_kinds.is_empty()
+    }
+
+    /// Parse a filter expression such as
+    /// `lang:rust path:src/** -path:src/gen/** -lang:json` into filters.
+    pub fn parse(expr: &str) -> Result<Self> {
+        let mut filters = Self::default();
+        filters.apply_expr(expr)?;
+        Ok(filters)
+    }
+
+    /// Add the terms of a filter expression to these filters.
+    ///
+    /// Terms are whitespace separated `key:value` pairs, negated with a
+    /// leading `-`. Supported keys are `lang`, `path` and `kind`. Repeated
+    /// include terms of the same key are alternatives; exclude terms all
+    /// apply.
+    /// Path values are globs (`*`, `?`, `**`); a value without glob
+    /// characters matches as a path prefix.
+    pub fn apply_expr(&mut self, expr: &str) -> Result<()> {
+        for term in expr.split_whitespace() {
+            let (negated, term) = match term.strip_prefix('-') {
+                Some(rest) => (true, rest),
+                None => (false, term),
+            };
+            let (key, value) = term
+                .split_once(':')
+                .filter(|(_, value)| !value.is_empty())
+                .ok_or_else(|| {
+                    CodeSearchError::InvalidInput(format!(
+                        "Invalid filter term '{}': expected key:value",
+                        term
+                    ))
+                })?;
+
+            let target = match (key, negated) {
+                ("lang" | "language", false) => &mut self.include_languages,
+                ("lang" | "language", true) => &mut self.exclude_languages,
+                ("path", false) => &mut self.include_paths,
+                ("path", true) => &mut self.exclude_paths,
+                ("kind", false) => &mut self.include_kinds,
+                ("kind", true) => &mut self.exclude_kinds,
+                _ => {
+                    return Err(CodeSearchError::InvalidInput(format!(
+                        "Unknown filter key '{}' (expected lang, path or kind)",
+                        key
+                    )))
+                }
+            };
+            let value = if key == "path" {
+                value.to_string()
+            } else {
+                value.to_lowercase()
+            };
+            target.push(value);
+        }
+        Ok(())
+    }
+}
+
+/// Translate a filter path glob into SQLite GLOB patterns, a path matching
+/// any of which matches the filter. SQLite's `*` already crosses directory
+/// separators, so `**` collapses to `*`, except that `**/` may also stand
+/// for no directory at all. A path without glob characters matches that
+/// file or anything under that directory.
+fn path_glob_to_sql(pattern: &str) -> Vec<String> {
+    if !pattern.contains(['*', '?', '[']) {
+        let path = pattern.trim_end_matches('/');
+        return vec![path.to_string(), format!("{}/*", path)];
+    }
+
+    let mut globs = vec![String::new()];
+    let mut rest = pattern;
+    while let Some(i) = rest.find("**/") {
+        let head = rest[..i].replace("**", "*");
+        globs = globs
+            .into_iter()
+            .flat_map(|glob| [format!("{}{}", glob, head), format!("{}{}*/", glob, head)])
+            .collect();
+        rest = &rest[i + 3..];
+    }
+    let tail = rest.replace("**", "*");
+    globs.into_iter().map(|glob| glob + &tail).collect()
+}
+
+/// Append SQL conditions (and their parameters) for `filters`, with chunk
+/// columns referenced through `alias` (e.g. "c.").
+fn push_filter_conditions(
+    filters: &SearchFilters,
+    alias: &str,
+    conditions: &mut Vec<String>,
+    params_vec: &mut Vec<Box<dyn rusqlite::ToSql>>,
+) {
+    let next_param = |params_vec: &mut Vec<Box<dyn rusqlite::ToSql>>, value: String| {
+        params_vec.push(Box::new(value));
+        format!("?{}", params_vec.len())
+    };
+
+    if let Some(ref lang) = filters.language {
+        let p = next_param(params_vec, lang.clone());
+        conditions.push(format!("{}language = {}", alias, p));
+    }
+
+    if let Some(ref author) = filters.author {
+        let p = next_param(params_vec, author.clone());
+        conditions.push(format!("{}author = {}", alias, p));
+    }
+
+    if let Some(ref file_type) = filters.file_type {
+        let p = next_param(params_vec, format!("%.{}", file_type));
+        conditions.push(format!("{}file_path LIKE {}", alias, p));
+    }
+
+    if let Some(ref kind) = filters.kind {
+        let p = next_param(params_vec, kind.clone());
+        conditions.push(format!("{}chunk_kind = {}", alias, p));
+    }
+
+    if let Some(after) = filters.after_timestamp {
+        params_vec.push(Box::new(after));
+        conditions.push(format!("{}modified_at > ?{}", alias, params_vec.len()));
+    }
+
+    if !filters.include_languages.is_empty() {
+        let placeholders: Vec<String> = filters
+            .include_languages
+            .iter()
+            .map(|lang| next_param(params_vec, lang.clone()))
+            .collect();
+        conditions.push(format!(
+            "{}language IN ({})",
+            alias,
+            placeholders.join(", ")
+        ));
+    }
+
+    for lang in &filters.exclude_languages {
+        let p = next_param(params_vec, lang.clone());
+        conditions.push(format!(
+            "({0}language IS NULL OR {0}language != {1})",
+            alias, p
+        ));
+    }
+
+    let mut path_matches = |path: &str| -> String {
+        let alternatives: Vec<String> = path_glob_to_sql(path)
+            .into_iter()
+            .map(|glob| {
+                let p = next_param(params_vec, glob);
+                format!("{}file_path GLOB {}", alias, p)
+            })
+            .collect();
+        alternatives.join(" OR ")
+    };
+
+    if !filters.include_paths.is_empty() {
+        let alternatives: Vec<String> = filters
+            .include_paths
+            .iter()
+            .map(|path| path_matches(path))
+            .collect();
+        conditions.push(format!("({})", alternatives.join(" OR ")));
+    }
+
+    for path in &filters.exclude_paths {
+        let matches = path_matches(path);
+        conditions.push(format!("NOT ({})", matches));
+    }
+
+    if !filters.include_kinds.is_empty() {
+        let placeholders: Vec<String> = filters
+            .include_kinds
+            .iter()
+            .map(|kind| next_param(params_vec, kind.clone()))
+            .collect();
+        conditions.push(format!(
+            "{}chunk_kind IN ({})",
+            alias,
+            placeholders.join(", ")
+        ));
+    }
+
+    for kind in &filters.exclude_kinds {
+        let p = next_param(params_vec, kind.clone());
+        conditions.push(format!(
+            "({0}chunk_kind IS NULL OR {0}chunk_kind != {1})",
+            alias, p
+        ));
+    }
+}
+
+pub fn fts_search(
+    conn: &Connection,
+    query: &str,
+    codebase_id: Option<&str>,
+    limit: i64,
+    filters: &SearchFilters,
+) -> Result<Vec<SearchResult>> {
+    fts_search_with_operator(
+        conn,
+        query,
+        codebase_id,
+        limit,
+        filters,
+        get_config().search.fts_default_operator,
+    )
+}
+
+/// [`fts_search`] with an explicit operator joining the query terms instead
+/// of `search.fts_default_operator`
+pub fn fts_search_with_operator(
+    conn: &Connection,
+    query: &str,
+    codebase_id: Option<&str>,
+    limit: i64,
+    filters: &SearchFilters,
+    operator: FtsOperator,
+) -> Result<Vec<SearchResult>> {
+    fts_search_inner(
+        conn,
+        query,
+        codebase_id,
+        limit,
+        filters,
+        operator,
+        get_config().search.split_identifiers,
+    )
+}
+
+/// Turn query text into an FTS5 expression joining its words and
+/// `"quoted phrases"` with `operator`. Every term is quoted, so FTS5 syntax
+/// in the query (`:`, `*`, `^`, `AND`, ...) is matched as text rather than
+/// parsed. With `split_identifiers`, an identifier also matches as the
+/// phrase of its parts: `("parseHTTPResponse" OR "parse HTTP Response")`.
+fn fts_match_expression(query: &str, operator: FtsOperator, split_identifiers: bool) -> String {
+    fts_query_terms(query)
+        .into_iter()
+        // Single characters and bare punctuation match too much or nothing
+        .filter(|&(term, is_phrase)| {
+            (is_phrase || term.len() > 1) && term.chars().any(char::is_alphanumeric)
+        })
+        .map(|(term, is_phrase)| {
+            let is_identifier = term.chars().all(|c| c.is_alphanumeric() || c == '_');
+            let parts = crate::splitter::identifier_parts(term);
+            if split_identifiers && !is_phrase && is_identifier && parts.len() > 1 {
+                format!("({} OR {})", fts_quote(term), fts_quote(&parts.join(" ")))
+            } else {
+                fts_quote(term)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(&format!(" {} ", operator.keyword()))
+}
+
+/// Split query text into its words and `"quoted phrases"`, the latter
+/// flagged `true`. A quote without a closing partner is dropped.
+fn fts_query_terms(query: &str) -> Vec<(&str, bool)> {
+    let mut terms = Vec::new();
+    let mut rest = query;
+    while let Some(open) = rest.find('"') {
+        terms.extend(rest[..open].split_whitespace().map(|word| (word, false)));
+        let after = &rest[open + 1..];
+        match after.find('"') {
+            Some(close) => {
+                let phrase = after[..close].trim();
+                if !phrase.is_empty() {
+                    terms.push((phrase, true));
+                }
+                rest = &after[close + 1..];
+            }
+            None => rest = after,
+        }
+    }
+    terms.extend(rest.split_whitespace().map(|word| (word, false)));
+    terms
+}
+
+/// `term` as an FTS5 string, which matches its tokens as a phrase
+fn fts_quote(term: &str) -> String {
+    format!("\"{}\"", term.replace('"', "\"\""))
+}
+
+fn fts_search_inner(
+    conn: &Connection,
+    query: &str,
+    codebase_id: Option<&str>,
+    limit: i64,
+    filters: &SearchFilters,
+    operator: FtsOperator,
+    split_identifiers: bool,
+) -> Result<Vec<SearchResult>> {
+    let limit = checked_limit(limit);
+    let fts_query = fts_match_expression(query, operator, split_identifiers);
+
+    if fts_query.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    // An FTS5 column filter scopes every term of the parenthesised query
+    let fts_query = match filters.fts_columns.column() {
+        Some(column) => format!("{{{}}} : ({})", column, fts_query),
+        None => fts_query,
+    };
+
+    // Build dynamic query with filters
+    let mut conditions = vec!["chunks_fts MATCH ?1".to_string()];
+    let mut params_vec: Vec<Box<dyn rusqlite::ToSql>> = vec![Box::new(fts_query.clone())];
+
+    if let Some(cid) = codebase_id {
+        params_vec.push(Box::new(cid.to_string()));
+        conditions.push(format!("c.codebase_id = ?{}", params_vec.len()));
+    }
+
+    push_filter_conditions(filters, "c.", &mut conditions, &mut params_vec);
+
+    let where_clause = conditions.join(" AND ");
+    let param_idx = params_vec.len() + 1;
+
+    // Use improved BM25 with explicit k1 and b parameters
+    let sql = format!(
+        r#"
+        SELECT c.id, c.codebase_id, c.file_path, c.start_line, c.end_line, c.content, c.language,
+               bm25(chunks_fts, {}, {}) as bm25_score, c.author
+        FROM chunks_fts fts
+        JOIN chunks c ON c.id = fts.rowid
+        WHERE {}
+        ORDER BY bm25_score
+        LIMIT ?{}
+        "#,
+        BM25_K1, BM25_B, where_clause, param_idx
+    );
+
+    let mut stmt = conn.prepare(&sql).map_err(CodeSearchError::Database)?;
+
+    params_vec.push(Box::new(limit));
+
+    let params_refs: Vec<&dyn rusqlite::ToSql> = params_vec.iter().map(|p| p.as_ref()).collect();
+
+    let mut results = Vec::new();
+    let max_chars = get_config().search.max_result_chars;
+
+    let rows = stmt
+        .query_map(params_refs.as_slice(), |row| {
+            let (content, is_truncated) = cap_content(row.get(5)?, max_chars);
+            Ok(SearchResult {
+                chunk_id: row.get(0)?,
+                codebase_id: row.get(1)?,
+                file_path: row.get(2)?,
+                start_line: row.get(3)?,
+                end_line: row.get(4)?,
+                content,
+                language: row.get(6)?,
+                author: row.get(8)?,
+                score: row.get::<_, f64>(7)?.abs(),
+                rank: 0,
+                is_truncated,
+                also_in: Vec::new(),
+            })
+        })
+        .map_err(CodeSearchError::Database)?;
+
+    for row in rows {
+        results.push(row.map_err(CodeSearchError::Database)?);
+    }
+
+    // Normalize BM25 scores to 0-1 range
+    let max_score: f64 = results
+        .iter()
+        .map(|r| r.score)
+        .fold(0.0, |max, s| max.max(s));
+    if max_score > 0.0 {
+        for result in &mut results {
+            result.score /= max_score;
+        }
+    }
+
+    for (i, result) in results.iter_mut().enumerate() {
+        result.rank = (i + 1) as i64;
+    }
+
+    Ok(results)
+}
+
+fn cosine_similarity(a: &[f32], b: &[f32]) -> f64 {
+    if a.len() != b.len() || a.is_empty() {
+        return 0.0;
+    }
+    let dot: f64 = a
+        .iter()
+        .zip(b.iter())
+        .map(|(x, y)| (*x as f64) * (*y as f64))
         .sum();
     let norm_a: f64 = a.iter().map(|x| (*x as f64).powi(2)).sum::<f64>().sqrt();
     let norm_b: f64 = b.iter().map(|x| (*x as f64).powi(2)).sum::<f64>().sqrt();
@@ -429,10 +1736,53 @@ fn cosine_similarity(a: &[f32], b: &[f32]) -> f64 {
     dot / (norm_a * norm_b)
 }
 
-fn deserialize_embedding(blob: &[u8]) -> Vec<f32> {
-    blob.chunks_exact(4)
-        .map(|chunk| f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
-        .collect()
+pub(crate) fn serialize_embedding(embedding: &[f32], format: EmbeddingFormat) -> Vec<u8> {
+    match format {
+        EmbeddingFormat::F32 => embedding.iter().flat_map(|&f| f.to_le_bytes()).collect(),
+        EmbeddingFormat::F16 => embedding
+            .iter()
+            .flat_map(|&f| half::f16::from_f32(f).to_le_bytes())
+            .collect(),
+        EmbeddingFormat::Int8 => quantize_int8(embedding),
+    }
+}
+
+/// Symmetric scalar quantization: the largest magnitude maps to 127
+fn quantize_int8(embedding: &[f32]) -> Vec<u8> {
+    if embedding.is_empty() {
+        return Vec::new();
+    }
+    let max_abs = embedding.iter().fold(0.0_f32, |m, &f| m.max(f.abs()));
+    let scale = if max_abs > 0.0 { max_abs / 127.0 } else { 1.0 };
+
+    let mut blob = Vec::with_capacity(INT8_SCALE_BYTES + embedding.len());
+    blob.extend_from_slice(&scale.to_le_bytes());
+    blob.extend(
+        embedding
+            .iter()
+            .map(|&f| (f / scale).round().clamp(-127.0, 127.0) as i8 as u8),
+    );
+    blob
+}
+
+pub(crate) fn deserialize_embedding(blob: &[u8], format: EmbeddingFormat) -> Vec<f32> {
+    match format {
+        EmbeddingFormat::F32 => blob
+            .chunks_exact(4)
+            .map(|chunk| f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+            .collect(),
+        EmbeddingFormat::F16 => blob
+            .chunks_exact(2)
+            .map(|chunk| half::f16::from_le_bytes([chunk[0], chunk[1]]).to_f32())
+            .collect(),
+        EmbeddingFormat::Int8 => match blob.split_first_chunk::<INT8_SCALE_BYTES>() {
+            Some((scale, values)) => {
+                let scale = f32::from_le_bytes(*scale);
+                values.iter().map(|&b| b as i8 as f32 * scale).collect()
+            }
+            None => Vec::new(),
+        },
+    }
 }
 
 pub fn vector_search(
@@ -441,68 +1791,178 @@ pub fn vector_search(
     query_embedding: &[f32],
     limit: i64,
 ) -> Result<Vec<SearchResult>> {
-    let sql = if codebase_id.is_some() {
-        "SELECT id, codebase_id, file_path, start_line, end_line, content, language, embedding FROM chunks WHERE codebase_id = ?1"
-    } else {
-        "SELECT id, codebase_id, file_path, start_line, end_line, content, language, embedding FROM chunks"
+    vector_search_filtered(
+        conn,
+        codebase_id,
+        query_embedding,
+        limit,
+        &SearchFilters::default(),
+    )
+}
+
+/// Vector search restricted to chunks matching `filters`.
+///
+/// An unfiltered search of one codebase with an ANN index is answered from
+/// the index; everything else scans the stored embeddings.
+pub fn vector_search_filtered(
+    conn: &Connection,
+    codebase_id: Option<&str>,
+    query_embedding: &[f32],
+    limit: i64,
+    filters: &SearchFilters,
+) -> Result<Vec<SearchResult>> {
+    let limit = checked_limit(limit);
+    let mut results = match codebase_id {
+        Some(codebase_id)
+            if filters.is_unfiltered() && crate::ann::has_ann_index(conn, codebase_id)? =>
+        {
+            crate::ann::ann_search(conn, codebase_id, query_embedding, limit as usize)?
+        }
+        _ => vector_search_bounded(
+            conn,
+            codebase_id,
+            query_embedding,
+            limit,
+            filters,
+            get_config().search.max_scan_embedding_bytes,
+        )?,
     };
+    if let Some(min_score) = filters.min_score {
+        results.retain(|r| r.score >= min_score);
+    }
+    Ok(results)
+}
 
-    let mut stmt = conn.prepare(sql).map_err(CodeSearchError::Database)?;
+/// A scored candidate ordered so that a max-heap of `Reverse`d entries pops
+/// the current worst: lower score, then (on ties) the later chunk id
+struct HeapEntry(SearchResult);
 
-    let mut candidates: Vec<(SearchResult, Vec<f32>)> = Vec::new();
+impl PartialEq for HeapEntry {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == std::cmp::Ordering::Equal
+    }
+}
+
+impl Eq for HeapEntry {}
+
+impl PartialOrd for HeapEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for HeapEntry {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.0
+            .score
+            .total_cmp(&other.0.score)
+            .then_with(|| other.0.chunk_id.cmp(&self.0.chunk_id))
+    }
+}
+
+/// Brute-force vector search. With `max_embedding_bytes` above zero, at most
+/// that many bytes of decoded embeddings are held at once: candidates are
+/// scored batch by batch and only the best `limit` survive each merge.
+fn vector_search_bounded(
+    conn: &Connection,
+    codebase_id: Option<&str>,
+    query_embedding: &[f32],
+    limit: i64,
+    filters: &SearchFilters,
+    max_embedding_bytes: usize,
+) -> Result<Vec<SearchResult>> {
+    let format = embedding_format(conn)?;
+    let mut conditions = Vec::new();
+    let mut params_vec: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
 
     if let Some(cid) = codebase_id {
-        let rows = stmt
-            .query_map(params![cid], |row| {
-                let embedding_blob: Vec<u8> = row.get(7)?;
-                Ok((
-                    SearchResult {
-                        chunk_id: row.get(0)?,
-                        codebase_id: row.get(1)?,
-                        file_path: row.get(2)?,
-                        start_line: row.get(3)?,
-                        end_line: row.get(4)?,
-                        content: row.get(5)?,
-                        language: row.get(6)?,
-                        score: 0.0,
-                        rank: 0,
-                    },
-                    embedding_blob,
-                ))
-            })
-            .map_err(CodeSearchError::Database)?;
+        params_vec.push(Box::new(cid.to_string()));
+        conditions.push(format!("codebase_id = ?{}", params_vec.len()));
+    }
+
+    push_filter_conditions(filters, "", &mut conditions, &mut params_vec);
+
+    let mut sql = "SELECT id, codebase_id, file_path, start_line, end_line, content, language, embedding, author FROM chunks".to_string();
+    if !conditions.is_empty() {
+        sql.push_str(" WHERE ");
+        sql.push_str(&conditions.join(" AND "));
+    }
+
+    let mut stmt = conn.prepare(&sql).map_err(CodeSearchError::Database)?;
+
+    let params_refs: Vec<&dyn rusqlite::ToSql> = params_vec.iter().map(|p| p.as_ref()).collect();
+
+    let mut candidates: Vec<(SearchResult, Vec<f32>)> = Vec::new();
+    let max_chars = get_config().search.max_result_chars;
+
+    let rows = stmt
+        .query_map(params_refs.as_slice(), |row| {
+            let embedding_blob: Vec<u8> = row.get(7)?;
+            let (content, is_truncated) = cap_content(row.get(5)?, max_chars);
+            Ok((
+                SearchResult {
+                    chunk_id: row.get(0)?,
+                    codebase_id: row.get(1)?,
+                    file_path: row.get(2)?,
+                    start_line: row.get(3)?,
+                    end_line: row.get(4)?,
+                    content,
+                    language: row.get(6)?,
+                    author: row.get(8)?,
+                    score: 0.0,
+                    rank: 0,
+                    is_truncated,
+                    also_in: Vec::new(),
+                },
+                embedding_blob,
+            ))
+        })
+        .map_err(CodeSearchError::Database)?;
+
+    if max_embedding_bytes > 0 {
+        let keep = limit.max(0) as usize;
+        let mut top: std::collections::BinaryHeap<std::cmp::Reverse<HeapEntry>> =
+            std::collections::BinaryHeap::with_capacity(keep + 1);
+        let mut batch_bytes = 0;
+        let mut merge = |batch: &mut Vec<(SearchResult, Vec<f32>)>| {
+            for (mut result, embedding) in batch.drain(..) {
+                result.score = cosine_similarity(query_embedding, &embedding);
+                top.push(std::cmp::Reverse(HeapEntry(result)));
+                if top.len() > keep {
+                    top.pop();
+                }
+            }
+        };
 
         for row in rows {
             let (result, blob) = row.map_err(CodeSearchError::Database)?;
-            let embedding = deserialize_embedding(&blob);
+            let embedding = deserialize_embedding(&blob, format);
+            let bytes = embedding.len() * std::mem::size_of::<f32>();
+            if batch_bytes + bytes > max_embedding_bytes && !candidates.is_empty() {
+                merge(&mut candidates);
+                batch_bytes = 0;
+            }
+            batch_bytes += bytes;
             candidates.push((result, embedding));
         }
-    } else {
-        let rows = stmt
-            .query_map([], |row| {
-                let embedding_blob: Vec<u8> = row.get(7)?;
-                Ok((
-                    SearchResult {
-                        chunk_id: row.get(0)?,
-                        codebase_id: row.get(1)?,
-                        file_path: row.get(2)?,
-                        start_line: row.get(3)?,
-                        end_line: row.get(4)?,
-                        content: row.get(5)?,
-                        language: row.get(6)?,
-                        score: 0.0,
-                        rank: 0,
-                    },
-                    embedding_blob,
-                ))
-            })
-            .map_err(CodeSearchError::Database)?;
+        merge(&mut candidates);
 
-        for row in rows {
-            let (result, blob) = row.map_err(CodeSearchError::Database)?;
-            let embedding = deserialize_embedding(&blob);
-            candidates.push((result, embedding));
+        // into_sorted_vec is ascending in Reverse order, i.e. best first
+        let mut scored: Vec<SearchResult> = top
+            .into_sorted_vec()
+            .into_iter()
+            .map(|std::cmp::Reverse(HeapEntry(result))| result)
+            .collect();
+        for (i, result) in scored.iter_mut().enumerate() {
+            result.rank = (i + 1) as i64;
         }
+        return Ok(scored);
+    }
+
+    for row in rows {
+        let (result, blob) = row.map_err(CodeSearchError::Database)?;
+        let embedding = deserialize_embedding(&blob, format);
+        candidates.push((result, embedding));
     }
 
     let mut scored: Vec<SearchResult> = candidates
@@ -527,6 +1987,78 @@ pub fn vector_search(
     Ok(scored)
 }
 
+/// Cosine similarity of every chunk (of one codebase, or all) to
+/// `query_embedding` as `(chunk id, score)`, best first with ties in id
+/// order. Only ids and embeddings are read.
+pub(crate) fn vector_scores(
+    conn: &Connection,
+    codebase_id: Option<&str>,
+    query_embedding: &[f32],
+) -> Result<Vec<(i64, f64)>> {
+    let format = embedding_format(conn)?;
+    let mut stmt = conn
+        .prepare_cached(
+            "SELECT id, embedding FROM chunks
+             WHERE ?1 IS NULL OR codebase_id = ?1
+             ORDER BY id",
+        )
+        .map_err(CodeSearchError::Database)?;
+
+    let mut scores = stmt
+        .query_map(params![codebase_id], |row| {
+            let blob: Option<Vec<u8>> = row.get(1)?;
+            let embedding = blob
+                .map(|b| deserialize_embedding(&b, format))
+                .unwrap_or_default();
+            Ok((row.get(0)?, cosine_similarity(query_embedding, &embedding)))
+        })
+        .map_err(CodeSearchError::Database)?
+        .collect::<std::result::Result<Vec<(i64, f64)>, _>>()
+        .map_err(CodeSearchError::Database)?;
+
+    // Stable, so equal scores stay in id order
+    scores.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+    Ok(scores)
+}
+
+/// The search result for one chunk with the given score and rank, its
+/// content capped at `search.max_result_chars`, or `None` if the chunk no
+/// longer exists
+pub(crate) fn search_result_for_chunk(
+    conn: &Connection,
+    chunk_id: i64,
+    score: f64,
+    rank: i64,
+) -> Result<Option<SearchResult>> {
+    let mut stmt = conn
+        .prepare_cached(
+            "SELECT id, codebase_id, file_path, start_line, end_line, content, language, author
+             FROM chunks WHERE id = ?1",
+        )
+        .map_err(CodeSearchError::Database)?;
+
+    let max_chars = get_config().search.max_result_chars;
+    stmt.query_row(params![chunk_id], |row| {
+        let (content, is_truncated) = cap_content(row.get(5)?, max_chars);
+        Ok(SearchResult {
+            chunk_id: row.get(0)?,
+            codebase_id: row.get(1)?,
+            file_path: row.get(2)?,
+            start_line: row.get(3)?,
+            end_line: row.get(4)?,
+            content,
+            language: row.get(6)?,
+            author: row.get(7)?,
+            score,
+            rank,
+            is_truncated,
+            also_in: Vec::new(),
+        })
+    })
+    .optional()
+    .map_err(CodeSearchError::Database)
+}
+
 pub fn hybrid_search(
     conn: &Connection,
     query_text: &str,
@@ -535,8 +2067,101 @@ pub fn hybrid_search(
     limit: i64,
     filters: &SearchFilters,
     enable_fuzzy: bool,
+) -> Result<Vec<SearchResult>> {
+    hybrid_search_with_ltr(
+        conn,
+        query_text,
+        codebase_id,
+        query_embedding,
+        limit,
+        filters,
+        enable_fuzzy,
+        get_config().search.enable_ltr,
+    )
+}
+
+/// [`hybrid_search`], choosing whether recorded clicks on a query's
+/// results boost them (`search.enable_ltr` in the config)
+#[allow(clippy::too_many_arguments)]
+pub fn hybrid_search_with_ltr(
+    conn: &Connection,
+    query_text: &str,
+    codebase_id: Option<&str>,
+    query_embedding: &[f32],
+    limit: i64,
+    filters: &SearchFilters,
+    enable_fuzzy: bool,
+    enable_ltr: bool,
+) -> Result<Vec<SearchResult>> {
+    hybrid_search_recorded(
+        conn,
+        query_text,
+        codebase_id,
+        query_embedding,
+        limit,
+        filters,
+        enable_fuzzy,
+        enable_ltr,
+        get_config().search.record_history,
+    )
+}
+
+/// [`hybrid_search_inner`], counted in the query stats and recorded as
+/// [`record_finished_search`] does
+#[allow(clippy::too_many_arguments)]
+fn hybrid_search_recorded(
+    conn: &Connection,
+    query_text: &str,
+    codebase_id: Option<&str>,
+    query_embedding: &[f32],
+    limit: i64,
+    filters: &SearchFilters,
+    enable_fuzzy: bool,
+    enable_ltr: bool,
+    record_history: bool,
+) -> Result<Vec<SearchResult>> {
+    let started = crate::metrics::start_timer();
+    let results = hybrid_search_inner(
+        conn,
+        query_text,
+        codebase_id,
+        query_embedding,
+        limit,
+        filters,
+        enable_fuzzy,
+        enable_ltr,
+    )?;
+
+    // Record search for query stats
+    let _ = record_search(conn, query_text, results.len() as i64);
+
+    record_finished_search(
+        conn,
+        started,
+        query_text,
+        codebase_id,
+        limit,
+        results.len(),
+        record_history,
+    );
+    Ok(results)
+}
+
+/// The ranking of [`hybrid_search_with_ltr`] without writing anything:
+/// no query stats, history or metrics
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn hybrid_search_inner(
+    conn: &Connection,
+    query_text: &str,
+    codebase_id: Option<&str>,
+    query_embedding: &[f32],
+    limit: i64,
+    filters: &SearchFilters,
+    enable_fuzzy: bool,
+    enable_ltr: bool,
 ) -> Result<Vec<SearchResult>> {
     let config = get_config();
+    let limit = checked_limit(limit);
     let mut fts_weight = config.fts_weight();
     let mut vector_weight = config.vector_weight();
 
@@ -556,25 +2181,36 @@ pub fn hybrid_search(
         }
     }
 
-    let fts_limit = limit * 3; // Get more results for fusion
+    // Get more results for fusion, within the ceiling so it isn't reported
+    let fts_limit = (limit * 3).min(config.search.max_limit as i64);
 
-    let mut fts_results = fts_search(conn, query_text, codebase_id, fts_limit, filters)?;
+    // The cutoff applies to fused scores, not to either leg's own
+    let leg_filters = SearchFilters {
+        min_score: None,
+        ..filters.clone()
+    };
+    let mut fts_results = fts_search(conn, query_text, codebase_id, fts_limit, &leg_filters)?;
 
-    let mut vector_results = vector_search(conn, codebase_id, query_embedding, fts_limit)?;
+    let mut vector_results =
+        vector_search_filtered(conn, codebase_id, query_embedding, fts_limit, &leg_filters)?;
 
     // Get LTR click boosts
-    let click_boosts = get_click_boosts(conn, query_text).unwrap_or_default();
+    let click_boosts = if enable_ltr {
+        get_click_boosts(conn, query_text).unwrap_or_default()
+    } else {
+        HashMap::new()
+    };
 
     // Apply LTR boosting to FTS results
     for result in &mut fts_results {
         if let Some(boost) = click_boosts.get(&result.chunk_id) {
-            result.score = result.score + boost * 0.3;
+            result.score += boost * 0.3;
         }
 
         // Apply language weight
         if let Some(ref lang) = result.language {
             if let Some(weight) = language_weights.get(lang) {
-                result.score = result.score * (1.0 + weight * 0.2);
+                result.score *= 1.0 + weight * 0.2;
             }
         }
     }
@@ -582,41 +2218,144 @@ pub fn hybrid_search(
     // Apply LTR boosting and language weights to vector results
     for result in &mut vector_results {
         if let Some(boost) = click_boosts.get(&result.chunk_id) {
-            result.score = result.score + boost * 0.3;
+            result.score += boost * 0.3;
         }
 
         if let Some(ref lang) = result.language {
             if let Some(weight) = language_weights.get(lang) {
-                result.score = result.score * (1.0 + weight * 0.2);
+                result.score *= 1.0 + weight * 0.2;
             }
         }
     }
 
-    // Coverage scoring: measure how many query terms are covered
-    let query_terms: Vec<&str> = query_text.split_whitespace().collect();
+    let mut combined = fuse_results(
+        query_text,
+        fts_results,
+        vector_results,
+        (fts_weight, vector_weight),
+        config.search.fusion_strategy,
+        enable_fuzzy,
+    );
+
+    combined.sort_by(|a, b| {
+        b.score
+            .partial_cmp(&a.score)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+    if codebase_id.is_none() && config.search.dedup_across_codebases {
+        combined = dedup_across_codebases(conn, combined)?;
+    }
+    if let Some(min_score) = filters.min_score {
+        combined.retain(|r| r.score >= min_score);
+    }
+    combined.truncate(limit as usize);
+
+    for (i, result) in combined.iter_mut().enumerate() {
+        result.rank = (i + 1) as i64;
+    }
+
+    Ok(combined)
+}
+
+/// [`vector_search_filtered`] as a search in its own right: recorded in the
+/// search history and reported to the metrics sink like [`hybrid_search`]
+pub fn vector_only_search(
+    conn: &Connection,
+    query_text: &str,
+    codebase_id: Option<&str>,
+    query_embedding: &[f32],
+    limit: i64,
+    filters: &SearchFilters,
+) -> Result<Vec<SearchResult>> {
+    let started = crate::metrics::start_timer();
+    let results = vector_search_filtered(conn, codebase_id, query_embedding, limit, filters)?;
+    record_finished_search(
+        conn,
+        started,
+        query_text,
+        codebase_id,
+        limit,
+        results.len(),
+        get_config().search.record_history,
+    );
+    Ok(results)
+}
+
+/// What every search entry point does once it has its results: append them
+/// to the search history when `record_history`, and report them to the
+/// metrics sink. History is best-effort: a failed write must not fail the
+/// search.
+pub(crate) fn record_finished_search(
+    conn: &Connection,
+    started: Option<std::time::Instant>,
+    query_text: &str,
+    codebase_id: Option<&str>,
+    limit: i64,
+    result_count: usize,
+    record_history: bool,
+) {
+    if record_history {
+        let _ = record_search_history(conn, codebase_id, query_text, result_count as i64);
+    }
+    crate::metrics::report_search(
+        started,
+        query_text,
+        codebase_id,
+        checked_limit(limit),
+        result_count,
+    );
+}
 
+/// Combine full-text and vector results, each best first, into one list
+/// scored by `fusion`. Chunks found by both searches appear once. The
+/// result is unsorted.
+fn fuse_results(
+    query_text: &str,
+    mut fts_results: Vec<SearchResult>,
+    mut vector_results: Vec<SearchResult>,
+    (fts_weight, vector_weight): (f64, f64),
+    fusion: FusionStrategy,
+    enable_fuzzy: bool,
+) -> Vec<SearchResult> {
     // Store rankings for RRF before consuming results
     let fts_ranks: Vec<_> = fts_results.iter().map(|r| r.chunk_id).collect();
     let vector_ranks: Vec<_> = vector_results.iter().map(|r| r.chunk_id).collect();
 
-    let mut seen_ids: std::collections::HashSet<i64> = std::collections::HashSet::new();
-    let mut combined: Vec<SearchResult> = Vec::new();
-
-    for mut result in fts_results {
-        if seen_ids.insert(result.chunk_id) {
-            // Calculate coverage score
-            let coverage = calculate_coverage(query_text, &result.content);
-            result.score = fts_weight * (result.score + coverage * 0.2);
-            combined.push(result);
+    match fusion {
+        // BM25 magnitudes depend on the corpus and query; scale them into
+        // 0..1 like cosine similarity before weighting
+        FusionStrategy::Rrf => normalize_fts_scores(&mut fts_results),
+        // Both legs onto the same 0..=1 range, so the weights alone decide
+        // how much each counts
+        FusionStrategy::LinearBlend => {
+            min_max_normalize(&mut fts_results);
+            min_max_normalize(&mut vector_results);
         }
     }
 
-    for mut result in vector_results {
-        if seen_ids.insert(result.chunk_id) {
-            // Calculate coverage score
-            let coverage = calculate_coverage(query_text, &result.content);
-            result.score = vector_weight * (result.score + coverage * 0.2);
-            combined.push(result);
+    // A chunk found by both searches sums its weighted scores
+    let mut positions: HashMap<i64, usize> = HashMap::new();
+    let mut combined: Vec<SearchResult> = Vec::new();
+    let weighted = fts_results
+        .into_iter()
+        .map(|r| (r, fts_weight))
+        .chain(vector_results.into_iter().map(|r| (r, vector_weight)));
+
+    for (mut result, weight) in weighted {
+        result.score = match fusion {
+            FusionStrategy::Rrf => {
+                // Calculate coverage score
+                let coverage = calculate_coverage(query_text, &result.content);
+                weight * (result.score + coverage * 0.2)
+            }
+            FusionStrategy::LinearBlend => weight * result.score,
+        };
+        match positions.get(&result.chunk_id) {
+            Some(&i) => combined[i].score += result.score,
+            None => {
+                positions.insert(result.chunk_id, combined.len());
+                combined.push(result);
+            }
         }
     }
 
@@ -625,31 +2364,104 @@ pub fn hybrid_search(
         apply_fuzzy_boost(query_text, &mut combined, 2);
     }
 
-    // RRF (Reciprocal Rank Fusion) for combining rankings
-    let rrf_results = reciprocal_rank_fusion_ids(&fts_ranks, &vector_ranks, 60.0);
+    if fusion == FusionStrategy::Rrf {
+        // RRF (Reciprocal Rank Fusion) for combining rankings
+        let rrf_results = reciprocal_rank_fusion_ids(&fts_ranks, &vector_ranks, 60.0);
 
-    // Merge RRF scores with original scores
-    for result in &mut combined {
-        if let Some(rrf_score) = rrf_results.get(&result.chunk_id) {
-            result.score = result.score * 0.7 + rrf_score * 0.3;
+        // Merge RRF scores with original scores
+        for result in &mut combined {
+            if let Some(rrf_score) = rrf_results.get(&result.chunk_id) {
+                result.score = result.score * 0.7 + rrf_score * 0.3;
+            }
         }
     }
 
-    combined.sort_by(|a, b| {
-        b.score
-            .partial_cmp(&a.score)
-            .unwrap_or(std::cmp::Ordering::Equal)
-    });
-    combined.truncate(limit as usize);
+    combined
+}
 
-    for (i, result) in combined.iter_mut().enumerate() {
-        result.rank = (i + 1) as i64;
+/// Collapse results that are the same chunk of the same file content (equal
+/// file hash and line range) in different codebases, keeping the best-scored
+/// copy and listing the other codebases in its `also_in`. `results` must be
+/// sorted best first.
+fn dedup_across_codebases(
+    conn: &Connection,
+    results: Vec<SearchResult>,
+) -> Result<Vec<SearchResult>> {
+    if results.len() < 2 {
+        return Ok(results);
+    }
+
+    let placeholders = vec!["?"; results.len()].join(", ");
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT id, hash FROM chunks WHERE id IN ({})",
+            placeholders
+        ))
+        .map_err(CodeSearchError::Database)?;
+    let hashes: HashMap<i64, String> = stmt
+        .query_map(
+            rusqlite::params_from_iter(results.iter().map(|r| r.chunk_id)),
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .map_err(CodeSearchError::Database)?
+        .collect::<std::result::Result<_, _>>()
+        .map_err(CodeSearchError::Database)?;
+
+    let mut kept: Vec<SearchResult> = Vec::with_capacity(results.len());
+    let mut first_copy: HashMap<(&str, i64, i64), usize> = HashMap::new();
+    for result in results {
+        let Some(hash) = hashes.get(&result.chunk_id) else {
+            kept.push(result);
+            continue;
+        };
+        let key = (hash.as_str(), result.start_line, result.end_line);
+        match first_copy.get(&key) {
+            Some(&i) if kept[i].codebase_id != result.codebase_id => {
+                let original = &mut kept[i];
+                if !original.also_in.contains(&result.codebase_id) {
+                    original.also_in.push(result.codebase_id);
+                }
+            }
+            Some(_) => kept.push(result),
+            None => {
+                first_copy.insert(key, kept.len());
+                kept.push(result);
+            }
+        }
+    }
+    Ok(kept)
+}
+
+/// Scale full-text scores (BM25 magnitudes, higher is better) by the best
+/// one, into 0..=1
+fn normalize_fts_scores(results: &mut [SearchResult]) {
+    let best = results.iter().map(|r| r.score).fold(0.0_f64, f64::max);
+    if best > 0.0 {
+        for result in results {
+            result.score /= best;
+        }
     }
+}
 
-    // Record search for query stats
-    let _ = record_search(conn, query_text, combined.len() as i64);
-
-    Ok(combined)
+/// Rescale scores linearly so the worst is 0 and the best 1; when all are
+/// equal they all become 1
+fn min_max_normalize(results: &mut [SearchResult]) {
+    let min = results
+        .iter()
+        .map(|r| r.score)
+        .fold(f64::INFINITY, f64::min);
+    let max = results
+        .iter()
+        .map(|r| r.score)
+        .fold(f64::NEG_INFINITY, f64::max);
+    let range = max - min;
+    for result in results {
+        result.score = if range > 0.0 {
+            (result.score - min) / range
+        } else {
+            1.0
+        };
+    }
 }
 
 /// Calculate query term coverage in content
@@ -669,6 +2481,7 @@ fn calculate_coverage(query: &str, content: &str) -> f64 {
 }
 
 /// Reciprocal Rank Fusion to combine multiple rankings
+#[allow(dead_code)]
 fn reciprocal_rank_fusion(
     fts_results: Vec<SearchResult>,
     vector_results: Vec<SearchResult>,
@@ -733,6 +2546,218 @@ pub fn get_codebase_stats(conn: &Connection, codebase_id: &str) -> Result<Option
     }
 }
 
+/// Files listed in [`Breakdown::top_files`]
+pub const BREAKDOWN_TOP_FILES: usize = 10;
+
+/// How one codebase's chunks divide up by language and by file
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Breakdown {
+    pub codebase_id: String,
+    pub total_chunks: i64,
+    pub total_files: i64,
+    /// Mean chunk length in lines, 0 for an empty codebase
+    pub avg_chunk_lines: f64,
+    /// Mean chunk length in characters, 0 for an empty codebase
+    pub avg_chunk_chars: f64,
+    /// One entry per language, most chunks first
+    pub languages: Vec<LanguageBreakdown>,
+    /// The [`BREAKDOWN_TOP_FILES`] files with the most chunks, most first
+    pub top_files: Vec<FileBreakdown>,
+    /// File-name chunks, one per indexed file. These and commit chunks are
+    /// left out of every count above, which describe source text only.
+    pub path_chunks: i64,
+    /// Commit-message chunks from the git history
+    pub commit_chunks: i64,
+}
+
+/// Chunks cut from file contents, as opposed to file names and commits
+const SOURCE_CHUNKS: &str = "COALESCE(chunk_kind, '') NOT IN ('path', 'commit')";
+
+/// A language's share of a [`Breakdown`]
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LanguageBreakdown {
+    /// `None` for chunks with no detected language
+    pub language: Option<String>,
+    pub chunk_count: i64,
+    pub file_count: i64,
+    pub avg_chunk_lines: f64,
+}
+
+/// A file's entry in [`Breakdown::top_files`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FileBreakdown {
+    pub file_path: String,
+    pub language: Option<String>,
+    pub chunk_count: i64,
+}
+
+/// Chunk counts for a codebase grouped by language and by file, with
+/// average chunk sizes. File-name and commit chunks are counted apart.
+pub fn get_codebase_breakdown(conn: &Connection, codebase_id: &str) -> Result<Breakdown> {
+    let (total_chunks, total_files, avg_chunk_lines, avg_chunk_chars) = conn
+        .query_row(
+            &format!(
+                "SELECT COUNT(*), COUNT(DISTINCT file_path),
+                        COALESCE(AVG(end_line - start_line + 1), 0),
+                        COALESCE(AVG(LENGTH(content)), 0)
+                 FROM chunks WHERE codebase_id = ?1 AND {}",
+                SOURCE_CHUNKS
+            ),
+            params![codebase_id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+        )
+        .map_err(CodeSearchError::Database)?;
+
+    let (path_chunks, commit_chunks) = conn
+        .query_row(
+            "SELECT COALESCE(SUM(chunk_kind = 'path'), 0), COALESCE(SUM(chunk_kind = 'commit'), 0)
+             FROM chunks WHERE codebase_id = ?1",
+            params![codebase_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .map_err(CodeSearchError::Database)?;
+
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT language, COUNT(*), COUNT(DISTINCT file_path), AVG(end_line - start_line + 1)
+             FROM chunks WHERE codebase_id = ?1 AND {}
+             GROUP BY language
+             ORDER BY COUNT(*) DESC, language",
+            SOURCE_CHUNKS
+        ))
+        .map_err(CodeSearchError::Database)?;
+    let languages = stmt
+        .query_map(params![codebase_id], |row| {
+            Ok(LanguageBreakdown {
+                language: row.get(0)?,
+                chunk_count: row.get(1)?,
+                file_count: row.get(2)?,
+                avg_chunk_lines: row.get(3)?,
+            })
+        })
+        .map_err(CodeSearchError::Database)?
+        .collect::<rusqlite::Result<Vec<_>>>()
+        .map_err(CodeSearchError::Database)?;
+
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT file_path, MAX(language), COUNT(*)
+             FROM chunks WHERE codebase_id = ?1 AND {}
+             GROUP BY file_path
+             ORDER BY COUNT(*) DESC, file_path
+             LIMIT ?2",
+            SOURCE_CHUNKS
+        ))
+        .map_err(CodeSearchError::Database)?;
+    let top_files = stmt
+        .query_map(params![codebase_id, BREAKDOWN_TOP_FILES as i64], |row| {
+            Ok(FileBreakdown {
+                file_path: row.get(0)?,
+                language: row.get(1)?,
+                chunk_count: row.get(2)?,
+            })
+        })
+        .map_err(CodeSearchError::Database)?
+        .collect::<rusqlite::Result<Vec<_>>>()
+        .map_err(CodeSearchError::Database)?;
+
+    Ok(Breakdown {
+        codebase_id: codebase_id.to_string(),
+        total_chunks,
+        total_files,
+        avg_chunk_lines,
+        avg_chunk_chars,
+        languages,
+        top_files,
+        path_chunks,
+        commit_chunks,
+    })
+}
+
+/// Counts for one codebase taken from each place that tracks its chunks.
+/// The triggers keep these in step, so any disagreement points at a trigger
+/// bug or a partial write.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct StatsVerification {
+    pub codebase_id: String,
+    /// Rows in `chunks`
+    pub chunk_count: i64,
+    /// Distinct file paths in `chunks`
+    pub file_count: i64,
+    /// Chunks that have a row in the `chunks_fts` index
+    pub fts_count: i64,
+    /// Chunk and file counts held in `codebase_summary`, `None` when the
+    /// summary has no row for this codebase
+    pub summary_chunk_count: Option<i64>,
+    pub summary_file_count: Option<i64>,
+}
+
+impl StatsVerification {
+    /// Human-readable descriptions of every count that disagrees with the
+    /// chunks table; empty when the codebase is consistent.
+    pub fn mismatches(&self) -> Vec<String> {
+        let mut mismatches = Vec::new();
+        if self.fts_count != self.chunk_count {
+            mismatches.push(format!(
+                "full-text index has {} rows for {} chunks",
+                self.fts_count, self.chunk_count
+            ));
+        }
+        let summary = (
+            self.summary_chunk_count.unwrap_or(0),
+            self.summary_file_count.unwrap_or(0),
+        );
+        if summary != (self.chunk_count, self.file_count) {
+            mismatches.push(format!(
+                "summary records {} chunks in {} files, table has {} chunks in {} files",
+                summary.0, summary.1, self.chunk_count, self.file_count
+            ));
+        }
+        mismatches
+    }
+
+    pub fn is_consistent(&self) -> bool {
+        self.mismatches().is_empty()
+    }
+}
+
+/// Recount a codebase's chunks, its full-text rows and its summary row
+/// independently so they can be compared.
+///
+/// `chunks_fts` is an external-content table, so selecting from it reads
+/// through to `chunks`; the FTS count is taken from its `docsize` shadow
+/// table, which holds one row per indexed document.
+pub fn verify_stats(conn: &Connection, codebase_id: &str) -> Result<StatsVerification> {
+    let (chunk_count, file_count, fts_count) = conn
+        .query_row(
+            "SELECT COUNT(*), COUNT(DISTINCT c.file_path), COUNT(d.id)
+             FROM chunks c
+             LEFT JOIN chunks_fts_docsize d ON d.id = c.id
+             WHERE c.codebase_id = ?1",
+            params![codebase_id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .map_err(CodeSearchError::Database)?;
+
+    let summary = conn
+        .query_row(
+            "SELECT chunk_count, file_count FROM codebase_summary WHERE codebase_id = ?1",
+            params![codebase_id],
+            |row| Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?)),
+        )
+        .optional()
+        .map_err(CodeSearchError::Database)?;
+
+    Ok(StatsVerification {
+        codebase_id: codebase_id.to_string(),
+        chunk_count,
+        file_count,
+        fts_count,
+        summary_chunk_count: summary.map(|(chunks, _)| chunks),
+        summary_file_count: summary.map(|(_, files)| files),
+    })
+}
+
 pub fn get_global_stats(conn: &Connection) -> Result<Option<Stats>> {
     let mut stmt = conn
         .prepare(
@@ -763,15 +2788,240 @@ pub fn get_global_stats(conn: &Connection) -> Result<Option<Stats>> {
     }
 }
 
-pub fn list_indexed_codebases(conn: &Connection) -> Result<Vec<(String, i64, i64)>> {
+/// Number of chunks stored for each file of a codebase, ordered by path
+pub fn chunk_counts_by_file(conn: &Connection, codebase_id: &str) -> Result<Vec<(String, i64)>> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT file_path, COUNT(*) FROM chunks
+             WHERE codebase_id = ?1
+             GROUP BY file_path
+             ORDER BY file_path",
+        )
+        .map_err(CodeSearchError::Database)?;
+
+    let counts = stmt
+        .query_map(params![codebase_id], |row| Ok((row.get(0)?, row.get(1)?)))
+        .map_err(CodeSearchError::Database)?
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map_err(CodeSearchError::Database)?;
+
+    Ok(counts)
+}
+
+/// Chunks stored for one file of a codebase
+pub fn file_chunk_count(conn: &Connection, codebase_id: &str, file_path: &str) -> Result<i64> {
+    conn.query_row(
+        "SELECT COUNT(*) FROM chunks WHERE codebase_id = ?1 AND file_path = ?2",
+        params![codebase_id, file_path],
+        |row| row.get(0),
+    )
+    .map_err(CodeSearchError::Database)
+}
+
+/// Chunks of `file_path` whose `[start_line, end_line]` contains `line`,
+/// ordered by start line. Overlapping chunks are all returned.
+pub fn chunks_at(
+    conn: &Connection,
+    codebase_id: &str,
+    file_path: &str,
+    line: i64,
+) -> Result<Vec<Chunk>> {
+    let format = embedding_format(conn)?;
+    let mut stmt = conn
+        .prepare_cached(&format!(
+            "SELECT {} FROM chunks
+             WHERE codebase_id = ?1 AND file_path = ?2
+               AND start_line <= ?3 AND end_line >= ?3
+             ORDER BY start_line, end_line",
+            chunk_columns(true)
+        ))
+        .map_err(CodeSearchError::Database)?;
+
+    let chunks = stmt
+        .query_map(params![codebase_id, file_path, line], |row| {
+            chunk_from_row(row, format)
+        })
+        .map_err(CodeSearchError::Database)?
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map_err(CodeSearchError::Database)?;
+
+    Ok(chunks)
+}
+
+/// The chunk with id `chunk_id`, if any. Its embedding is only read and
+/// decoded `with_embedding`; otherwise it is left empty.
+pub fn get_chunk(conn: &Connection, chunk_id: i64, with_embedding: bool) -> Result<Option<Chunk>> {
+    let format = embedding_format(conn)?;
+    conn.query_row(
+        &format!(
+            "SELECT {} FROM chunks WHERE id = ?1",
+            chunk_columns(with_embedding)
+        ),
+        params![chunk_id],
+        |row| chunk_from_row(row, format),
+    )
+    .optional()
+    .map_err(CodeSearchError::Database)
+}
+
+/// Up to `n` chunks of `file_path` on either side of the chunk starting at
+/// `start_line`, ordered by start line: the nearest `n` starting before it,
+/// then the nearest `n` starting after it. Chunks starting at `start_line`
+/// itself, and the file's line-0 `path` chunk, are left out. Embeddings are
+/// read as for [`get_chunk`].
+pub fn get_adjacent_chunks(
+    conn: &Connection,
+    codebase_id: &str,
+    file_path: &str,
+    start_line: i64,
+    n: usize,
+    with_embedding: bool,
+) -> Result<Vec<Chunk>> {
+    let format = embedding_format(conn)?;
+    let columns = chunk_columns(with_embedding);
+    let side = |condition: &str, order: &str| -> Result<Vec<Chunk>> {
+        let mut stmt = conn
+            .prepare_cached(&format!(
+                "SELECT {} FROM chunks
+                 WHERE codebase_id = ?1 AND file_path = ?2 AND start_line > 0
+                   AND {}
+                 ORDER BY start_line {}, end_line {}
+                 LIMIT ?4",
+                columns, condition, order, order
+            ))
+            .map_err(CodeSearchError::Database)?;
+        let chunks = stmt
+            .query_map(
+                params![codebase_id, file_path, start_line, n as i64],
+                |row| chunk_from_row(row, format),
+            )
+            .map_err(CodeSearchError::Database)?
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(CodeSearchError::Database)?;
+        Ok(chunks)
+    };
+
+    let mut chunks = side("start_line < ?3", "DESC")?;
+    chunks.reverse();
+    chunks.extend(side("start_line > ?3", "ASC")?);
+    Ok(chunks)
+}
+
+/// The columns [`chunk_from_row`] reads, with the embedding replaced by
+/// `NULL` unless `with_embedding`, so its blob is never read
+fn chunk_columns(with_embedding: bool) -> String {
+    format!(
+        "id, codebase_id, file_path, start_line, end_line, content, language, {}, \
+         hash, chunk_kind, author",
+        if with_embedding { "embedding" } else { "NULL" }
+    )
+}
+
+fn chunk_from_row(row: &rusqlite::Row<'_>, format: EmbeddingFormat) -> rusqlite::Result<Chunk> {
+    let blob: Option<Vec<u8>> = row.get(7)?;
+    Ok(Chunk {
+        id: Some(row.get(0)?),
+        codebase_id: row.get(1)?,
+        file_path: row.get(2)?,
+        start_line: row.get(3)?,
+        end_line: row.get(4)?,
+        content: row.get(5)?,
+        language: row.get(6)?,
+        embedding: blob
+            .map(|b| deserialize_embedding(&b, format))
+            .unwrap_or_default(),
+        hash: row.get(8)?,
+        chunk_kind: row.get(9)?,
+        author: row.get(10)?,
+    })
+}
+
+/// Replace the recorded commit-to-file links of a codebase with `links`, given
+/// as `(commit hash, commit time, touched files)`
+pub fn replace_commit_links(
+    conn: &Connection,
+    codebase_id: &str,
+    links: &[(&str, i64, &[String])],
+) -> Result<()> {
+    let tx = WriteTx::begin(conn)?;
+    tx.execute(
+        "DELETE FROM commit_files WHERE codebase_id = ?1",
+        params![codebase_id],
+    )
+    .map_err(CodeSearchError::Database)?;
+    {
+        let mut stmt = tx
+            .prepare(
+                "INSERT OR IGNORE INTO commit_files (codebase_id, commit_hash, committed_at, file_path)
+                 VALUES (?1, ?2, ?3, ?4)",
+            )
+            .map_err(CodeSearchError::Database)?;
+        for (commit_hash, committed_at, files) in links {
+            for file_path in files.iter() {
+                stmt.execute(params![codebase_id, commit_hash, committed_at, file_path])
+                    .map_err(CodeSearchError::Database)?;
+            }
+        }
+    }
+    tx.commit()?;
+    Ok(())
+}
+
+/// Files an indexed commit touched, ordered by path
+pub fn files_touched_by_commit(
+    conn: &Connection,
+    codebase_id: &str,
+    commit_hash: &str,
+) -> Result<Vec<String>> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT file_path FROM commit_files
+             WHERE codebase_id = ?1 AND commit_hash = ?2
+             ORDER BY file_path",
+        )
+        .map_err(CodeSearchError::Database)?;
+    let files = stmt
+        .query_map(params![codebase_id, commit_hash], |row| row.get(0))
+        .map_err(CodeSearchError::Database)?
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map_err(CodeSearchError::Database)?;
+    Ok(files)
+}
+
+/// Hashes of the indexed commits that touched `file_path`, newest first
+pub fn commits_touching_file(
+    conn: &Connection,
+    codebase_id: &str,
+    file_path: &str,
+) -> Result<Vec<String>> {
     let mut stmt = conn
         .prepare(
-            "SELECT codebase_id, COUNT(*) as chunks, COUNT(DISTINCT file_path) as files
-             FROM chunks
-             GROUP BY codebase_id
-             ORDER BY codebase_id",
+            "SELECT commit_hash FROM commit_files
+             WHERE codebase_id = ?1 AND file_path = ?2
+             ORDER BY committed_at DESC, commit_hash",
         )
         .map_err(CodeSearchError::Database)?;
+    let commits = stmt
+        .query_map(params![codebase_id, file_path], |row| row.get(0))
+        .map_err(CodeSearchError::Database)?
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map_err(CodeSearchError::Database)?;
+    Ok(commits)
+}
+
+/// `(codebase id, chunk count, file count)` for every codebase with chunks
+pub fn list_indexed_codebases(conn: &Connection) -> Result<Vec<(String, i64, i64)>> {
+    let sql = if codebase_summary_is_current(conn)? {
+        "SELECT codebase_id, chunk_count, file_count
+         FROM codebase_summary
+         ORDER BY codebase_id"
+    } else {
+        "SELECT codebase_id, COUNT(*) as chunks, COUNT(DISTINCT file_path) as files
+         FROM chunks
+         GROUP BY codebase_id
+         ORDER BY codebase_id"
+    };
+    let mut stmt = conn.prepare(sql).map_err(CodeSearchError::Database)?;
 
     let rows = stmt
         .query_map([], |row| {
@@ -819,6 +3069,81 @@ pub fn register_codebase(
     Ok(())
 }
 
+/// Record the embedding model a codebase's chunks were embedded with
+pub fn set_codebase_model(conn: &Connection, codebase_id: &str, model: &str) -> Result<()> {
+    conn.execute(
+        "UPDATE codebases SET model = ?2, last_updated = ?3 WHERE codebase_id = ?1",
+        params![codebase_id, model, chrono::Utc::now().timestamp()],
+    )
+    .map_err(CodeSearchError::Database)?;
+
+    Ok(())
+}
+
+/// Fail when `model` is not the model a codebase was embedded with, or
+/// now embeds to another dimension (such as after changing
+/// `model.embedding_dim`), since its query embeddings can't be compared
+/// with the stored ones. Codebases with no recorded model or embeddings
+/// pass.
+pub fn check_codebase_model(conn: &Connection, codebase_id: &str, model: &str) -> Result<()> {
+    use crate::embedding::ModelType;
+
+    let recorded = get_codebase_metadata(conn, codebase_id)?.and_then(|m| m.model);
+    match recorded {
+        Some(recorded) if ModelType::parse(&recorded) != ModelType::parse(model) => {
+            return Err(CodeSearchError::InvalidConfiguration(format!(
+                "codebase is indexed with model '{}' but the search uses '{}'; \
+                 search with --model {} or re-index with --force --model {}",
+                recorded, model, recorded, model
+            )));
+        }
+        _ => {}
+    }
+
+    let dimension = ModelType::parse(model).dimension();
+    match stored_dimension(conn, codebase_id, embedding_format(conn)?)? {
+        Some(stored) if stored != dimension => Err(CodeSearchError::InvalidConfiguration(format!(
+            "codebase is indexed with {}-dimensional embeddings but model '{}' now \
+             produces {}; re-index with --force",
+            stored, model, dimension
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// The `(chunk_size, chunk_overlap)` a codebase is pinned to, if any
+pub fn get_chunking_params(conn: &Connection, codebase_id: &str) -> Result<Option<(usize, usize)>> {
+    conn.query_row(
+        "SELECT chunk_size, chunk_overlap FROM codebase_chunking WHERE codebase_id = ?1",
+        params![codebase_id],
+        |row| {
+            Ok((
+                row.get::<_, i64>(0)? as usize,
+                row.get::<_, i64>(1)? as usize,
+            ))
+        },
+    )
+    .optional()
+    .map_err(CodeSearchError::Database)
+}
+
+/// Pin the chunking parameters used for a codebase's chunks
+pub fn set_chunking_params(
+    conn: &Connection,
+    codebase_id: &str,
+    chunk_size: usize,
+    chunk_overlap: usize,
+) -> Result<()> {
+    conn.execute(
+        "INSERT OR REPLACE INTO codebase_chunking (codebase_id, chunk_size, chunk_overlap)
+         VALUES (?1, ?2, ?3)",
+        params![codebase_id, chunk_size as i64, chunk_overlap as i64],
+    )
+    .map_err(CodeSearchError::Database)?;
+
+    Ok(())
+}
+
 pub fn get_codebase_metadata(
     conn: &Connection,
     codebase_id: &str,
@@ -852,16 +3177,21 @@ pub fn get_codebase_metadata(
 }
 
 pub fn list_codebases_with_metadata(conn: &Connection) -> Result<Vec<CodebaseMetadata>> {
-    let mut stmt = conn
-        .prepare(
-            "SELECT c.codebase_id, c.name, c.path, c.indexed_at, c.last_updated, c.model, c.tags,
-                    COUNT(ch.id) as chunk_count, COUNT(DISTINCT ch.file_path) as file_count
-             FROM codebases c
-             LEFT JOIN chunks ch ON c.codebase_id = ch.codebase_id
-             GROUP BY c.codebase_id
-             ORDER BY c.name",
-        )
-        .map_err(CodeSearchError::Database)?;
+    let sql = if codebase_summary_is_current(conn)? {
+        "SELECT c.codebase_id, c.name, c.path, c.indexed_at, c.last_updated, c.model, c.tags,
+                COALESCE(s.chunk_count, 0), COALESCE(s.file_count, 0)
+         FROM codebases c
+         LEFT JOIN codebase_summary s ON c.codebase_id = s.codebase_id
+         ORDER BY c.name"
+    } else {
+        "SELECT c.codebase_id, c.name, c.path, c.indexed_at, c.last_updated, c.model, c.tags,
+                COUNT(ch.id) as chunk_count, COUNT(DISTINCT ch.file_path) as file_count
+         FROM codebases c
+         LEFT JOIN chunks ch ON c.codebase_id = ch.codebase_id
+         GROUP BY c.codebase_id
+         ORDER BY c.name"
+    };
+    let mut stmt = conn.prepare(sql).map_err(CodeSearchError::Database)?;
 
     let codebases = stmt
         .query_map([], |row| {
@@ -884,6 +3214,48 @@ pub fn list_codebases_with_metadata(conn: &Connection) -> Result<Vec<CodebaseMet
     Ok(codebases)
 }
 
+/// Find the indexed codebase a user meant by a label or partial path.
+///
+/// An exact label (name) or path match wins; otherwise `needle` is matched
+/// case-insensitively as a substring of each codebase's name and path.
+/// Several matches are an error listing the candidates.
+pub fn resolve_codebase(conn: &Connection, needle: &str) -> Result<CodebaseMetadata> {
+    let codebases = list_codebases_with_metadata(conn)?;
+
+    let exact: Vec<&CodebaseMetadata> = codebases
+        .iter()
+        .filter(|cb| cb.name == needle || cb.path == needle)
+        .collect();
+    if exact.len() == 1 {
+        return Ok(exact[0].clone());
+    }
+
+    let lowered = needle.to_lowercase();
+    let matches: Vec<&CodebaseMetadata> = codebases
+        .iter()
+        .filter(|cb| {
+            cb.name.to_lowercase().contains(&lowered) || cb.path.to_lowercase().contains(&lowered)
+        })
+        .collect();
+
+    match matches.len() {
+        0 => Err(CodeSearchError::CodebaseNotIndexed(needle.to_string())),
+        1 => Ok(matches[0].clone()),
+        _ => {
+            let candidates: Vec<String> = matches
+                .iter()
+                .map(|cb| format!("  {} ({})", cb.name, cb.path))
+                .collect();
+            Err(CodeSearchError::InvalidInput(format!(
+                "'{}' matches {} codebases, be more specific:\n{}",
+                needle,
+                matches.len(),
+                candidates.join("\n")
+            )))
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize)]
 pub struct CodebaseMetadata {
     pub codebase_id: String,
@@ -897,6 +3269,142 @@ pub struct CodebaseMetadata {
     pub file_count: i64,
 }
 
+// ============== Identifier Suggestions ==============
+
+/// Shortest identifier worth suggesting
+const MIN_IDENTIFIER_LEN: usize = 3;
+
+/// A suggested completion for a partially typed query
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TermSuggestion {
+    pub term: String,
+    pub frequency: i64,
+}
+
+/// Count identifier-like tokens (`[A-Za-z_][A-Za-z0-9_]*`) in `text`
+fn count_identifiers(text: &str, counts: &mut HashMap<String, i64>) {
+    let mut start = None;
+    for (i, c) in text.char_indices() {
+        let is_ident_char = c.is_ascii_alphanumeric() || c == '_';
+        match start {
+            None if c.is_ascii_alphabetic() || c == '_' => start = Some(i),
+            Some(s) if !is_ident_char => {
+                add_identifier(&text[s..i], counts);
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    if let Some(s) = start {
+        add_identifier(&text[s..], counts);
+    }
+}
+
+fn add_identifier(token: &str, counts: &mut HashMap<String, i64>) {
+    if token.len() >= MIN_IDENTIFIER_LEN && !token.chars().all(|c| c == '_') {
+        *counts.entry(token.to_string()).or_insert(0) += 1;
+    }
+}
+
+/// Rebuild the identifier frequencies of every file touched by `chunks`
+/// from all of the file's stored chunks, so a file inserted over several
+/// calls is still counted whole.
+///
+/// Chunks of a file may overlap, so lines already covered by an earlier
+/// chunk are skipped to avoid counting them twice.
+fn replace_identifier_counts(conn: &Connection, chunks: &[Chunk]) -> Result<()> {
+    let mut files: Vec<(&str, &str)> = chunks
+        .iter()
+        .map(|chunk| (chunk.codebase_id.as_str(), chunk.file_path.as_str()))
+        .collect();
+    files.sort_unstable();
+    files.dedup();
+
+    let mut chunks_stmt = conn
+        .prepare_cached(
+            "SELECT start_line, end_line, content FROM chunks
+             WHERE codebase_id = ?1 AND file_path = ?2
+             ORDER BY start_line",
+        )
+        .map_err(CodeSearchError::Database)?;
+    let mut delete_stmt = conn
+        .prepare_cached("DELETE FROM identifier_counts WHERE codebase_id = ?1 AND file_path = ?2")
+        .map_err(CodeSearchError::Database)?;
+    let mut insert_stmt = conn
+        .prepare_cached(
+            "INSERT INTO identifier_counts (codebase_id, file_path, term, frequency)
+             VALUES (?1, ?2, ?3, ?4)",
+        )
+        .map_err(CodeSearchError::Database)?;
+
+    for (codebase_id, file_path) in files {
+        let mut rows = chunks_stmt
+            .query(params![codebase_id, file_path])
+            .map_err(CodeSearchError::Database)?;
+        let mut counts = HashMap::new();
+        let mut covered_until = 0;
+        while let Some(row) = rows.next().map_err(CodeSearchError::Database)? {
+            let start_line: i64 = row.get(0).map_err(CodeSearchError::Database)?;
+            let end_line: i64 = row.get(1).map_err(CodeSearchError::Database)?;
+            let content: String = row.get(2).map_err(CodeSearchError::Database)?;
+            let skip = (covered_until - start_line + 1).max(0) as usize;
+            for line in content.lines().skip(skip) {
+                count_identifiers(line, &mut counts);
+            }
+            covered_until = covered_until.max(end_line);
+        }
+
+        delete_stmt
+            .execute(params![codebase_id, file_path])
+            .map_err(CodeSearchError::Database)?;
+        for (term, frequency) in &counts {
+            insert_stmt
+                .execute(params![codebase_id, file_path, term, frequency])
+                .map_err(CodeSearchError::Database)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Suggest indexed identifiers starting with `prefix` (case-insensitive),
+/// most frequent first.
+///
+/// Frequencies are collected by `insert_chunks`, so codebases indexed before
+/// suggestions were introduced return nothing until they are re-indexed.
+pub fn suggest_terms(
+    conn: &Connection,
+    codebase_id: &str,
+    prefix: &str,
+    limit: i64,
+) -> Result<Vec<TermSuggestion>> {
+    let pattern = format!("{}%", escape_like(prefix));
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT term, SUM(frequency) AS total
+             FROM identifier_counts
+             WHERE codebase_id = ?1 AND term LIKE ?2 ESCAPE '\\'
+             GROUP BY term
+             ORDER BY total DESC, term ASC
+             LIMIT ?3",
+        )
+        .map_err(CodeSearchError::Database)?;
+
+    let suggestions = stmt
+        .query_map(params![codebase_id, pattern, limit], |row| {
+            Ok(TermSuggestion {
+                term: row.get(0)?,
+                frequency: row.get(1)?,
+            })
+        })
+        .map_err(CodeSearchError::Database)?
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map_err(CodeSearchError::Database)?;
+
+    Ok(suggestions)
+}
+
 // ============== Learning-to-Rank Functions ==============
 
 /// Record a click event for Learning-to-Rank
@@ -966,6 +3474,77 @@ pub fn record_search(conn: &Connection, query_text: &str, result_count: i64) ->
     Ok(())
 }
 
+/// A past search recorded in the search_history table
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchHistoryEntry {
+    pub codebase_id: Option<String>,
+    pub query: String,
+    pub timestamp: i64,
+    pub result_count: i64,
+}
+
+/// Append a search to the search history
+pub fn record_search_history(
+    conn: &Connection,
+    codebase_id: Option<&str>,
+    query_text: &str,
+    result_count: i64,
+) -> Result<()> {
+    // Don't wait on a locked database; dropping a history row is preferable
+    // to delaying the search response. The caller's timeout is put back.
+    let previous: u64 = conn
+        .query_row("PRAGMA busy_timeout", [], |row| row.get(0))
+        .map_err(CodeSearchError::Database)?;
+    conn.busy_timeout(std::time::Duration::ZERO)
+        .map_err(CodeSearchError::Database)?;
+    let inserted = conn.execute(
+        "INSERT INTO search_history (codebase_id, query, timestamp, result_count)
+         VALUES (?1, ?2, ?3, ?4)",
+        params![
+            codebase_id,
+            query_text,
+            chrono::Utc::now().timestamp(),
+            result_count
+        ],
+    );
+    conn.busy_timeout(std::time::Duration::from_millis(previous))
+        .map_err(CodeSearchError::Database)?;
+    inserted.map_err(CodeSearchError::Database)?;
+
+    Ok(())
+}
+
+/// Most recent searches first, optionally restricted to one codebase
+pub fn get_search_history(
+    conn: &Connection,
+    codebase_id: Option<&str>,
+    limit: i64,
+) -> Result<Vec<SearchHistoryEntry>> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT codebase_id, query, timestamp, result_count FROM search_history
+             WHERE ?1 IS NULL OR codebase_id = ?1
+             ORDER BY timestamp DESC, id DESC
+             LIMIT ?2",
+        )
+        .map_err(CodeSearchError::Database)?;
+
+    let entries = stmt
+        .query_map(params![codebase_id, limit], |row| {
+            Ok(SearchHistoryEntry {
+                codebase_id: row.get(0)?,
+                query: row.get(1)?,
+                timestamp: row.get(2)?,
+                result_count: row.get(3)?,
+            })
+        })
+        .map_err(CodeSearchError::Database)?
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map_err(CodeSearchError::Database)?;
+
+    Ok(entries)
+}
+
 /// Get click boost scores for a given query
 pub fn get_click_boosts(conn: &Connection, query_text: &str) -> Result<HashMap<i64, f64>> {
     let mut stmt = conn
@@ -1064,11 +3643,11 @@ pub fn levenshtein_distance(s1: &str, s2: &str) -> usize {
 
     let mut matrix = vec![vec![0usize; len2 + 1]; len1 + 1];
 
-    for i in 0..=len1 {
-        matrix[i][0] = i;
+    for (i, row) in matrix.iter_mut().enumerate() {
+        row[0] = i;
     }
-    for j in 0..=len2 {
-        matrix[0][j] = j;
+    for (j, cell) in matrix[0].iter_mut().enumerate() {
+        *cell = j;
     }
 
     for i in 1..=len1 {
@@ -1196,6 +3775,1521 @@ pub fn apply_fuzzy_boost(query: &str, results: &mut [SearchResult], max_edit_dis
         }
 
         // Apply fuzzy boost to score
-        result.score = result.score + fuzzy_score * 0.2;
+        result.score += fuzzy_score * 0.2;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_chunk(codebase_id: &str, i: usize, content: String) -> Chunk {
+        Chunk {
+            id: None,
+            codebase_id: codebase_id.to_string(),
+            file_path: format!("src/file_{}.rs", i / 10),
+            start_line: (i % 10) as i64 * 50 + 1,
+            end_line: (i % 10) as i64 * 50 + 50,
+            content,
+            language: Some("rust".to_string()),
+            embedding: vec![i as f32, 1.0, 0.5],
+            hash: format!("{:016x}", i),
+            chunk_kind: Some("code".to_string()),
+            author: None,
+        }
+    }
+
+    #[test]
+    fn test_global_search_dedups_vendored_chunk() {
+        let dir = tempfile::tempdir().unwrap();
+        let conn = init_db_at(&dir.path().join("index.db")).unwrap();
+
+        let vendored = |codebase_id: &str| Chunk {
+            file_path: format!("{}/vendor/left_pad.js", codebase_id),
+            hash: "00000000feedf00d".to_string(),
+            ..test_chunk(
+                codebase_id,
+                0,
+                "function vendored_left_pad() {}".to_string(),
+            )
+        };
+        insert_chunks(
+            &conn,
+            &[
+                vendored("app_one"),
+                vendored("app_two"),
+                test_chunk("app_two", 1, "function own_left_pad() {}".to_string()),
+            ],
+        )
+        .unwrap();
+
+        let search = |codebase_id: Option<&str>| {
+            hybrid_search_inner(
+                &conn,
+                "left_pad vendored_left_pad own_left_pad",
+                codebase_id,
+                &[0.0, 1.0, 0.5],
+                10,
+                &SearchFilters::default(),
+                false,
+                true,
+            )
+            .unwrap()
+        };
+
+        let global = search(None);
+        assert_eq!(global.len(), 2);
+        let vendored_hits: Vec<&SearchResult> = global
+            .iter()
+            .filter(|r| r.content.contains("vendored_left_pad"))
+            .collect();
+        assert_eq!(vendored_hits.len(), 1);
+        let hit = vendored_hits[0];
+        let mut sources = vec![hit.codebase_id.clone()];
+        sources.extend(hit.also_in.iter().cloned());
+        sources.sort();
+        assert_eq!(sources, vec!["app_one", "app_two"]);
+        assert_eq!(
+            global.iter().map(|r| r.rank).collect::<Vec<_>>(),
+            vec![1, 2]
+        );
+
+        // A single-codebase search has nothing to collapse
+        let scoped = search(Some("app_two"));
+        assert_eq!(scoped.len(), 2);
+        assert!(scoped.iter().all(|r| r.also_in.is_empty()));
+    }
+
+    #[test]
+    fn test_bounded_vector_search_matches_unbounded_top_k() {
+        let dir = tempfile::tempdir().unwrap();
+        let conn = init_db_at(&dir.path().join("index.db")).unwrap();
+
+        let chunks: Vec<Chunk> = (0..200)
+            .map(|i| Chunk {
+                embedding: vec![(i as f32 * 0.37).sin(), (i as f32 * 0.11).cos(), 0.5],
+                ..test_chunk("bounded", i, format!("chunk {}", i))
+            })
+            .collect();
+        insert_chunks(&conn, &chunks).unwrap();
+
+        let query = [0.3, 0.9, 0.1];
+        let search = |max_bytes| {
+            vector_search_bounded(
+                &conn,
+                Some("bounded"),
+                &query,
+                7,
+                &SearchFilters::default(),
+                max_bytes,
+            )
+            .unwrap()
+            .into_iter()
+            .map(|r| (r.chunk_id, r.rank))
+            .collect::<Vec<_>>()
+        };
+
+        let unbounded = search(0);
+        assert_eq!(unbounded.len(), 7);
+        // Four 3-dimensional embeddings per batch, so fifty merges
+        assert_eq!(search(48), unbounded);
+        // Smaller than a single embedding still scans one at a time
+        assert_eq!(search(1), unbounded);
+    }
+
+    #[test]
+    fn test_chunks_at_returns_overlapping_chunks() {
+        let dir = tempfile::tempdir().unwrap();
+        let conn = init_db_at(&dir.path().join("index.db")).unwrap();
+
+        let chunk = |start, end| Chunk {
+            start_line: start,
+            end_line: end,
+            ..test_chunk("cursor", 0, format!("lines {}-{}", start, end))
+        };
+        insert_chunks(&conn, &[chunk(1, 50), chunk(41, 90), chunk(81, 130)]).unwrap();
+
+        let at = |line| -> Vec<(i64, i64)> {
+            chunks_at(&conn, "cursor", "src/file_0.rs", line)
+                .unwrap()
+                .into_iter()
+                .map(|c| (c.start_line, c.end_line))
+                .collect()
+        };
+        assert_eq!(at(45), vec![(1, 50), (41, 90)]);
+        assert_eq!(at(50), vec![(1, 50), (41, 90)]);
+        assert_eq!(at(60), vec![(41, 90)]);
+        assert!(at(200).is_empty());
+
+        let found = chunks_at(&conn, "cursor", "src/file_0.rs", 10).unwrap();
+        assert_eq!(found[0].content, "lines 1-50");
+        assert_eq!(found[0].embedding, vec![0.0, 1.0, 0.5]);
+        assert!(chunks_at(&conn, "cursor", "src/other.rs", 10)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_get_chunk_and_adjacent_chunks() {
+        let dir = tempfile::tempdir().unwrap();
+        let conn = init_db_at(&dir.path().join("index.db")).unwrap();
+
+        // A 60-line file in six chunks, inserted out of order, plus its
+        // path chunk and a chunk of another file
+        let chunk = |start: i64, end: i64| Chunk {
+            start_line: start,
+            end_line: end,
+            ..test_chunk("adjacent", 0, format!("lines {}-{}", start, end))
+        };
+        let path_chunk = Chunk {
+            chunk_kind: Some("path".to_string()),
+            ..chunk(0, 0)
+        };
+        let other_file = Chunk {
+            file_path: "src/other.rs".to_string(),
+            ..chunk(21, 30)
+        };
+        insert_chunks(
+            &conn,
+            &[
+                chunk(41, 50),
+                chunk(1, 10),
+                chunk(21, 30),
+                path_chunk,
+                chunk(51, 60),
+                chunk(11, 20),
+                chunk(31, 40),
+                other_file,
+            ],
+        )
+        .unwrap();
+
+        let id = chunks_at(&conn, "adjacent", "src/file_0.rs", 25).unwrap()[0]
+            .id
+            .unwrap();
+        let found = get_chunk(&conn, id, false).unwrap().unwrap();
+        assert_eq!(found.content, "lines 21-30");
+        assert_eq!(found.file_path, "src/file_0.rs");
+        assert!(found.embedding.is_empty());
+        let found = get_chunk(&conn, id, true).unwrap().unwrap();
+        assert_eq!(found.embedding, vec![0.0, 1.0, 0.5]);
+        assert!(get_chunk(&conn, id + 1000, true).unwrap().is_none());
+
+        let adjacent = |start_line, n| -> Vec<i64> {
+            get_adjacent_chunks(&conn, "adjacent", "src/file_0.rs", start_line, n, false)
+                .unwrap()
+                .into_iter()
+                .map(|c| {
+                    assert!(c.embedding.is_empty());
+                    c.start_line
+                })
+                .collect()
+        };
+        assert_eq!(adjacent(21, 1), vec![11, 31]);
+        assert_eq!(adjacent(21, 2), vec![1, 11, 31, 41]);
+        // Near either end there is less on one side
+        assert_eq!(adjacent(1, 2), vec![11, 21]);
+        assert_eq!(adjacent(51, 3), vec![21, 31, 41]);
+        assert!(adjacent(21, 0).is_empty());
+
+        let with_embeddings =
+            get_adjacent_chunks(&conn, "adjacent", "src/file_0.rs", 21, 1, true).unwrap();
+        assert!(with_embeddings.iter().all(|c| c.embedding.len() == 3));
+    }
+
+    #[test]
+    fn test_iter_chunks_streams_every_chunk() {
+        let dir = tempfile::tempdir().unwrap();
+        let conn = init_db_at(&dir.path().join("index.db")).unwrap();
+
+        // Several pages plus a partial one, with another codebase interleaved
+        let total = ITER_PAGE_ROWS as usize * 2 + 7;
+        let chunks: Vec<Chunk> = (0..total)
+            .flat_map(|i| {
+                [
+                    test_chunk("streamed", i, format!("fn streamed_{}() {{}}", i)),
+                    test_chunk("neighbour", i, format!("fn neighbour_{}() {{}}", i)),
+                ]
+            })
+            .collect();
+        insert_chunks(&conn, &chunks).unwrap();
+
+        let streamed: Vec<Chunk> = iter_chunks(&conn, "streamed")
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(streamed.len(), total);
+        assert!(streamed.iter().all(|c| c.codebase_id == "streamed"));
+        assert!(streamed.windows(2).all(|w| w[0].id < w[1].id));
+        assert_eq!(streamed[0].content, "fn streamed_0() {}");
+        assert_eq!(
+            streamed[total - 1].embedding,
+            vec![(total - 1) as f32, 1.0, 0.5]
+        );
+
+        assert_eq!(iter_chunks(&conn, "missing").count(), 0);
+    }
+
+    #[test]
+    fn test_insert_chunks_bulk_is_fts_searchable() {
+        let dir = tempfile::tempdir().unwrap();
+        let conn = init_db_at(&dir.path().join("index.db")).unwrap();
+
+        // Spans several full batches plus a partial one
+        let total = INSERT_BATCH_ROWS * 3 + 17;
+        let chunks: Vec<Chunk> = (0..total)
+            .map(|i| test_chunk("bulk", i, format!("fn token{}() {{ shared_marker }}", i)))
+            .collect();
+
+        assert_eq!(insert_chunks(&conn, &chunks).unwrap(), total as i64);
+
+        let stored: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM chunks WHERE codebase_id = 'bulk'",
+                [],
+                |r| r.get(0),
+            )
+            .unwrap();
+        assert_eq!(stored, total as i64);
+
+        let fts_rows: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM chunks_fts WHERE chunks_fts MATCH 'shared_marker'",
+                [],
+                |r| r.get(0),
+            )
+            .unwrap();
+        assert_eq!(fts_rows, total as i64);
+
+        let filters = SearchFilters::default();
+        let last = format!("token{}", total - 1);
+        let results = fts_search(&conn, &last, Some("bulk"), 10, &filters).unwrap();
+        assert_eq!(results.len(), 1);
+        assert!(results[0].content.contains(&last));
+    }
+
+    #[test]
+    fn test_parse_filter_expression() {
+        let filters =
+            SearchFilters::parse("lang:Rust path:src/** -path:src/gen/** -lang:json").unwrap();
+        assert_eq!(filters.include_languages, vec!["rust"]);
+        assert_eq!(filters.exclude_languages, vec!["json"]);
+        assert_eq!(filters.include_paths, vec!["src/**"]);
+        assert_eq!(filters.exclude_paths, vec!["src/gen/**"]);
+
+        assert!(SearchFilters::parse("").unwrap().include_paths.is_empty());
+        assert!(SearchFilters::parse("lang").is_err());
+        assert!(SearchFilters::parse("path:").is_err());
+        assert!(SearchFilters::parse("owner:me").is_err());
+    }
+
+    #[test]
+    fn test_path_glob_to_sql() {
+        assert_eq!(path_glob_to_sql("src"), vec!["src", "src/*"]);
+        assert_eq!(path_glob_to_sql("src/"), vec!["src", "src/*"]);
+        assert_eq!(path_glob_to_sql("src/**"), vec!["src/*"]);
+        assert_eq!(
+            path_glob_to_sql("src/**/*.rs"),
+            vec!["src/*.rs", "src/*/*.rs"]
+        );
+        assert_eq!(
+            path_glob_to_sql("**/gen/**/*.rs"),
+            vec!["gen/*.rs", "gen/*/*.rs", "*/gen/*.rs", "*/gen/*/*.rs"]
+        );
+    }
+
+    #[test]
+    fn test_filter_expression_applies_to_searches() {
+        let dir = tempfile::tempdir().unwrap();
+        let conn = init_db_at(&dir.path().join("index.db")).unwrap();
+
+        let chunk = |i: usize, path: &str, language: &str| Chunk {
+            file_path: path.to_string(),
+            language: Some(language.to_string()),
+            ..test_chunk("filters", i * 10, "fn filtered_marker() {}".to_string())
+        };
+        insert_chunks(
+            &conn,
+            &[
+                chunk(0, "src/lib.rs", "rust"),
+                chunk(1, "src/gen/bindings.rs", "rust"),
+                chunk(2, "src/schema.json", "json"),
+                Chunk {
+                    chunk_kind: Some("test".to_string()),
+                    author: None,
+                    ..chunk(3, "tests/it.rs", "rust")
+                },
+                chunk(4, "srcfoo/main.rs", "rust"),
+            ],
+        )
+        .unwrap();
+
+        let filters =
+            SearchFilters::parse("lang:rust path:src/** -path:src/gen/** -lang:json").unwrap();
+
+        let paths = |results: Vec<SearchResult>| {
+            let mut paths: Vec<String> = results.into_iter().map(|r| r.file_path).collect();
+            paths.sort();
+            paths
+        };
+
+        let fts = fts_search(&conn, "filtered_marker", Some("filters"), 10, &filters).unwrap();
+        assert_eq!(paths(fts), vec!["src/lib.rs"]);
+
+        let vector =
+            vector_search_filtered(&conn, Some("filters"), &[1.0, 1.0, 0.5], 10, &filters).unwrap();
+        assert_eq!(paths(vector), vec!["src/lib.rs"]);
+
+        // Path values without glob characters match a file or directory,
+        // not every path sharing the prefix
+        let filters = SearchFilters::parse("path:src -lang:json").unwrap();
+        let fts = fts_search(&conn, "filtered_marker", Some("filters"), 10, &filters).unwrap();
+        assert_eq!(paths(fts), vec!["src/gen/bindings.rs", "src/lib.rs"]);
+        let filters = SearchFilters::parse("-path:src -path:tests/it.rs").unwrap();
+        let fts = fts_search(&conn, "filtered_marker", Some("filters"), 10, &filters).unwrap();
+        assert_eq!(paths(fts), vec!["srcfoo/main.rs"]);
+
+        // `**/` also matches no directory
+        let filters = SearchFilters::parse("path:src/**/*.rs").unwrap();
+        let fts = fts_search(&conn, "filtered_marker", Some("filters"), 10, &filters).unwrap();
+        assert_eq!(paths(fts), vec!["src/gen/bindings.rs", "src/lib.rs"]);
+
+        let filters = SearchFilters {
+            kind: Some("test".to_string()),
+            ..Default::default()
+        };
+        let fts = fts_search(&conn, "filtered_marker", Some("filters"), 10, &filters).unwrap();
+        assert_eq!(paths(fts), vec!["tests/it.rs"]);
+    }
+
+    #[test]
+    fn test_language_list_filters_mixed_codebase() {
+        let dir = tempfile::tempdir().unwrap();
+        let conn = init_db_at(&dir.path().join("index.db")).unwrap();
+
+        let files = [
+            ("src/lib.rs", "rust"),
+            ("cmd/main.go", "go"),
+            ("tools/gen.py", "python"),
+            ("web/app.ts", "typescript"),
+        ];
+        let chunks: Vec<Chunk> = files
+            .iter()
+            .enumerate()
+            .map(|(i, (path, language))| Chunk {
+                file_path: path.to_string(),
+                language: Some(language.to_string()),
+                ..test_chunk("polyglot", i * 10, "polyglot_marker here".to_string())
+            })
+            .collect();
+        insert_chunks(&conn, &chunks).unwrap();
+
+        let languages = |results: Vec<SearchResult>| {
+            let mut languages: Vec<String> =
+                results.into_iter().filter_map(|r| r.language).collect();
+            languages.sort();
+            languages
+        };
+        let search = |filters: &SearchFilters| {
+            hybrid_search(
+                &conn,
+                "polyglot_marker",
+                Some("polyglot"),
+                &[1.0, 1.0, 0.5],
+                10,
+                filters,
+                false,
+            )
+            .unwrap()
+        };
+
+        assert_eq!(search(&SearchFilters::default()).len(), 4);
+
+        let filters = SearchFilters {
+            include_languages: vec!["rust".to_string(), "go".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(languages(search(&filters)), vec!["go", "rust"]);
+        let vector =
+            vector_search_filtered(&conn, Some("polyglot"), &[1.0, 1.0, 0.5], 10, &filters)
+                .unwrap();
+        assert_eq!(languages(vector), vec!["go", "rust"]);
+    }
+
+    #[test]
+    fn test_click_boosts_follow_ltr_toggle() {
+        let dir = tempfile::tempdir().unwrap();
+        let conn = init_db_at(&dir.path().join("index.db")).unwrap();
+        insert_chunks(
+            &conn,
+            &[
+                test_chunk("ltr", 0, "fn clicked_marker() {}".to_string()),
+                test_chunk("ltr", 1, "fn clicked_marker_too() {}".to_string()),
+            ],
+        )
+        .unwrap();
+
+        let score_of = |enable_ltr: bool, chunk_id: i64| {
+            hybrid_search_inner(
+                &conn,
+                "clicked_marker",
+                Some("ltr"),
+                &[0.0, 1.0, 0.5],
+                10,
+                &SearchFilters::default(),
+                false,
+                enable_ltr,
+            )
+            .unwrap()
+            .into_iter()
+            .find(|r| r.chunk_id == chunk_id)
+            .unwrap()
+            .score
+        };
+
+        let clicked: i64 = conn
+            .query_row(
+                "SELECT id FROM chunks WHERE content = 'fn clicked_marker_too() {}'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        for _ in 0..3 {
+            record_click(&conn, "clicked_marker", clicked, 1, Some("ltr")).unwrap();
+        }
+
+        // Clicks also shift the query's weights, so both scores are taken
+        // after recording them
+        assert!(score_of(true, clicked) > score_of(false, clicked));
+    }
+
+    #[test]
+    fn test_search_history_follows_toggle() {
+        let dir = tempfile::tempdir().unwrap();
+        let conn = init_db_at(&dir.path().join("index.db")).unwrap();
+        insert_chunks(
+            &conn,
+            &[test_chunk(
+                "history",
+                0,
+                "fn history_marker() {}".to_string(),
+            )],
+        )
+        .unwrap();
+
+        let search = |record_history: bool| {
+            hybrid_search_recorded(
+                &conn,
+                "history_marker",
+                Some("history"),
+                &[0.0, 1.0, 0.5],
+                10,
+                &SearchFilters::default(),
+                false,
+                true,
+                record_history,
+            )
+            .unwrap()
+        };
+
+        search(false);
+        assert!(get_search_history(&conn, Some("history"), 10)
+            .unwrap()
+            .is_empty());
+
+        // Recording skips the wait on a locked database, then restores the
+        // connection's own timeout
+        conn.busy_timeout(std::time::Duration::from_millis(1234))
+            .unwrap();
+
+        let results = search(true);
+        let history = get_search_history(&conn, Some("history"), 10).unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].query, "history_marker");
+        assert_eq!(history[0].codebase_id.as_deref(), Some("history"));
+        assert_eq!(history[0].result_count, results.len() as i64);
+        let timeout: i64 = conn
+            .query_row("PRAGMA busy_timeout", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(timeout, 1234);
+        assert!(get_search_history(&conn, Some("other"), 10)
+            .unwrap()
+            .is_empty());
+
+        // Vector-only searches are recorded the same way
+        let mut config = crate::config::Config::default();
+        config.search.record_history = true;
+        let _config = crate::test_support::override_config(config);
+        let results = vector_only_search(
+            &conn,
+            "history marker by meaning",
+            Some("history"),
+            &[0.0, 1.0, 0.5],
+            10,
+            &SearchFilters::default(),
+        )
+        .unwrap();
+        let history = get_search_history(&conn, Some("history"), 10).unwrap();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].query, "history marker by meaning");
+        assert_eq!(history[0].result_count, results.len() as i64);
+    }
+
+    #[test]
+    fn test_check_codebase_model_compares_stored_dimension() {
+        let dir = tempfile::tempdir().unwrap();
+        let conn = init_db_at(&dir.path().join("index.db")).unwrap();
+        let dimension = crate::embedding::get_model_dimension("minilm");
+        let chunk = |codebase_id: &str, dimension: usize| Chunk {
+            embedding: vec![0.5; dimension],
+            ..test_chunk(codebase_id, 0, "fn resized() {}".to_string())
+        };
+        insert_chunks(&conn, &[chunk("same", dimension)]).unwrap();
+        insert_chunks(&conn, &[chunk("resized", dimension / 2)]).unwrap();
+
+        assert!(check_codebase_model(&conn, "same", "minilm").is_ok());
+        assert!(check_codebase_model(&conn, "empty", "minilm").is_ok());
+        let err = check_codebase_model(&conn, "resized", "minilm").unwrap_err();
+        assert!(
+            matches!(err, CodeSearchError::InvalidConfiguration(ref m) if m.contains("re-index")),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_insert_chunks_rejects_wrong_embedding_dimension() {
+        let dir = tempfile::tempdir().unwrap();
+        let conn = init_db_at(&dir.path().join("index.db")).unwrap();
+        insert_chunks(&conn, &[test_chunk("dims", 0, "fn a() {}".to_string())]).unwrap();
+
+        let count = |conn: &Connection| -> i64 {
+            conn.query_row(
+                "SELECT COUNT(*) FROM chunks WHERE codebase_id = 'dims'",
+                [],
+                |r| r.get(0),
+            )
+            .unwrap()
+        };
+
+        // Stored chunks are 3-dimensional, so a 4-dimensional one is rejected
+        // and nothing from the batch is written
+        let mut wrong = test_chunk("dims", 1, "fn b() {}".to_string());
+        wrong.embedding.push(0.0);
+        let batch = [test_chunk("dims", 2, "fn c() {}".to_string()), wrong];
+        assert!(matches!(
+            insert_chunks(&conn, &batch),
+            Err(CodeSearchError::InvalidConfiguration(_))
+        ));
+        assert_eq!(count(&conn), 1);
+
+        let mut empty = test_chunk("dims", 3, "fn d() {}".to_string());
+        empty.embedding.clear();
+        assert!(insert_chunks(&conn, &[empty]).is_err());
+
+        // Within a new codebase the first chunk sets the dimension
+        let mut mixed = vec![test_chunk("fresh", 0, "fn e() {}".to_string())];
+        mixed.push(Chunk {
+            embedding: vec![1.0; 8],
+            ..test_chunk("fresh", 1, "fn f() {}".to_string())
+        });
+        assert!(insert_chunks(&conn, &mixed).is_err());
+    }
+
+    #[test]
+    fn test_insert_chunks_replace_keeps_fts_consistent() {
+        let dir = tempfile::tempdir().unwrap();
+        let conn = init_db_at(&dir.path().join("index.db")).unwrap();
+
+        insert_chunks(
+            &conn,
+            &[test_chunk("replace", 0, "old_content".to_string())],
+        )
+        .unwrap();
+        insert_chunks(
+            &conn,
+            &[test_chunk("replace", 0, "new_content".to_string())],
+        )
+        .unwrap();
+
+        let filters = SearchFilters::default();
+        assert!(
+            fts_search(&conn, "old_content", Some("replace"), 10, &filters)
+                .unwrap()
+                .is_empty()
+        );
+        assert_eq!(
+            fts_search(&conn, "new_content", Some("replace"), 10, &filters)
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+
+    #[cfg(not(feature = "onnx"))]
+    #[test]
+    fn test_vector_ranking_with_canned_embeddings() {
+        use crate::embedding::{canned, get_embedding_with_model, get_query_embedding_with_model};
+
+        let dir = tempfile::tempdir().unwrap();
+        let conn = init_db_at(&dir.path().join("index.db")).unwrap();
+
+        let docs = [
+            ("fn canned_alpha() {}", vec![1.0, 0.0, 0.0]),
+            ("fn canned_beta() {}", vec![0.0, 1.0, 0.0]),
+            ("fn canned_gamma() {}", vec![0.0, 0.0, 1.0]),
+        ];
+        for (text, embedding) in &docs {
+            canned::register(text, embedding.clone());
+        }
+        canned::register("canned ranking query", vec![0.3, 0.9, 0.0]);
+
+        let chunks: Vec<Chunk> = docs
+            .iter()
+            .enumerate()
+            .map(|(i, (text, _))| Chunk {
+                embedding: get_embedding_with_model(text, "minilm"),
+                ..test_chunk("canned", i * 10, text.to_string())
+            })
+            .collect();
+        insert_chunks(&conn, &chunks).unwrap();
+
+        let query = get_query_embedding_with_model("canned ranking query", "minilm");
+        let ranked: Vec<String> = vector_search(&conn, Some("canned"), &query, 3)
+            .unwrap()
+            .into_iter()
+            .map(|r| r.content)
+            .collect();
+        assert_eq!(
+            ranked,
+            vec![
+                "fn canned_beta() {}",
+                "fn canned_alpha() {}",
+                "fn canned_gamma() {}"
+            ]
+        );
+    }
+
+    #[cfg(not(feature = "onnx"))]
+    #[test]
+    fn test_min_score_drops_unrelated_results() {
+        use crate::embedding::{canned, get_embedding_with_model, get_query_embedding_with_model};
+
+        let dir = tempfile::tempdir().unwrap();
+        let conn = init_db_at(&dir.path().join("index.db")).unwrap();
+
+        let docs = [
+            ("fn min_score_parse() {}", vec![1.0, 0.0, 0.0]),
+            ("fn min_score_render() {}", vec![0.0, 1.0, 0.0]),
+        ];
+        for (text, embedding) in &docs {
+            canned::register(text, embedding.clone());
+        }
+        canned::register("weather forecast for tomorrow", vec![0.0, 0.0, 1.0]);
+        canned::register("min score parsing", vec![0.9, 0.1, 0.0]);
+
+        let chunks: Vec<Chunk> = docs
+            .iter()
+            .enumerate()
+            .map(|(i, (text, _))| Chunk {
+                embedding: get_embedding_with_model(text, "minilm"),
+                ..test_chunk("min_score", i * 10, text.to_string())
+            })
+            .collect();
+        insert_chunks(&conn, &chunks).unwrap();
+
+        let filters = SearchFilters {
+            min_score: Some(0.3),
+            ..Default::default()
+        };
+        let unrelated = "weather forecast for tomorrow";
+        let embedding = get_query_embedding_with_model(unrelated, "minilm");
+        // Without a cutoff the noise still fills the limit
+        assert_eq!(
+            vector_search(&conn, Some("min_score"), &embedding, 5)
+                .unwrap()
+                .len(),
+            2
+        );
+        assert!(
+            vector_search_filtered(&conn, Some("min_score"), &embedding, 5, &filters)
+                .unwrap()
+                .is_empty()
+        );
+        assert!(hybrid_search(
+            &conn,
+            unrelated,
+            Some("min_score"),
+            &embedding,
+            5,
+            &filters,
+            false
+        )
+        .unwrap()
+        .is_empty());
+
+        let related = "min score parsing";
+        let embedding = get_query_embedding_with_model(related, "minilm");
+        let results =
+            vector_search_filtered(&conn, Some("min_score"), &embedding, 5, &filters).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].content, "fn min_score_parse() {}");
+        let results = hybrid_search(
+            &conn,
+            related,
+            Some("min_score"),
+            &embedding,
+            5,
+            &filters,
+            false,
+        )
+        .unwrap();
+        assert_eq!(results[0].content, "fn min_score_parse() {}");
+        assert!(results.iter().all(|r| r.score >= 0.3));
+    }
+
+    #[test]
+    fn test_f16_storage_scores_close_to_f32() {
+        let dir = tempfile::tempdir().unwrap();
+        let full = init_db_at(&dir.path().join("f32.db")).unwrap();
+        let compact = init_db_at(&dir.path().join("f16.db")).unwrap();
+        set_embedding_format(&compact, EmbeddingFormat::F16).unwrap();
+
+        let chunks: Vec<Chunk> = (0..40)
+            .map(|i| Chunk {
+                embedding: (0..64)
+                    .map(|j| ((i * 64 + j) as f32 * 0.731).sin())
+                    .collect(),
+                ..test_chunk("precision", i, format!("fn item_{}() {{}}", i))
+            })
+            .collect();
+        insert_chunks(&full, &chunks).unwrap();
+        insert_chunks(&compact, &chunks).unwrap();
+
+        let stored: i64 = compact
+            .query_row("SELECT length(embedding) FROM chunks LIMIT 1", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(stored, 64 * 2);
+
+        let query = &chunks[7].embedding;
+        let scores = |conn: &Connection| -> HashMap<String, f64> {
+            vector_search(conn, Some("precision"), query, 40)
+                .unwrap()
+                .into_iter()
+                .map(|r| (r.content, r.score))
+                .collect()
+        };
+        let (exact, approx) = (scores(&full), scores(&compact));
+        assert_eq!(exact.len(), 40);
+        for (content, score) in &exact {
+            assert!(
+                (score - approx[content]).abs() < 1e-3,
+                "{}: f32 {} vs f16 {}",
+                content,
+                score,
+                approx[content]
+            );
+        }
+
+        // The format is fixed once embeddings are stored
+        assert!(set_embedding_format(&compact, EmbeddingFormat::F32).is_err());
+    }
+
+    #[test]
+    fn test_int8_storage_round_trips_within_tolerance() {
+        let embedding: Vec<f32> = (0..384).map(|j| (j as f32 * 0.173).cos() * 0.2).collect();
+        let blob = serialize_embedding(&embedding, EmbeddingFormat::Int8);
+        assert_eq!(blob.len(), 384 + INT8_SCALE_BYTES);
+        assert_eq!(EmbeddingFormat::Int8.dimension(blob.len()), 384);
+
+        let restored = deserialize_embedding(&blob, EmbeddingFormat::Int8);
+        assert_eq!(restored.len(), 384);
+        assert!(1.0 - cosine_similarity(&embedding, &restored) < 1e-3);
+        assert!(serialize_embedding(&[], EmbeddingFormat::Int8).is_empty());
+        assert_eq!(
+            deserialize_embedding(
+                &serialize_embedding(&[0.0; 4], EmbeddingFormat::Int8),
+                EmbeddingFormat::Int8
+            ),
+            vec![0.0; 4]
+        );
+
+        // vector_search dequantizes on read
+        let dir = tempfile::tempdir().unwrap();
+        let conn = init_db_at(&dir.path().join("int8.db")).unwrap();
+        set_embedding_format(&conn, EmbeddingFormat::Int8).unwrap();
+        let chunks: Vec<Chunk> = (0..20)
+            .map(|i| Chunk {
+                embedding: (0..384)
+                    .map(|j| ((i * 384 + j) as f32 * 0.731).sin())
+                    .collect(),
+                ..test_chunk("quantized", i, format!("fn item_{}() {{}}", i))
+            })
+            .collect();
+        insert_chunks(&conn, &chunks).unwrap();
+        let results = vector_search(&conn, Some("quantized"), &chunks[11].embedding, 3).unwrap();
+        assert_eq!(results[0].content, "fn item_11() {}");
+        assert!((results[0].score - 1.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_verify_stats_reports_missing_fts_row() {
+        let dir = tempfile::tempdir().unwrap();
+        let conn = init_db_at(&dir.path().join("index.db")).unwrap();
+
+        let chunks: Vec<Chunk> = (0..6)
+            .map(|i| test_chunk("verified", i, format!("fn v{}() {{}}", i)))
+            .collect();
+        insert_chunks(&conn, &chunks).unwrap();
+
+        let verification = verify_stats(&conn, "verified").unwrap();
+        assert_eq!(verification.chunk_count, 6);
+        assert_eq!(verification.fts_count, 6);
+        assert_eq!(verification.summary_chunk_count, Some(6));
+        assert!(
+            verification.is_consistent(),
+            "{:?}",
+            verification.mismatches()
+        );
+
+        // Remove one document from the index behind the triggers' back
+        let (id, content, file_path): (i64, String, String) = conn
+            .query_row(
+                "SELECT id, content || COALESCE(char(10) || identifier_terms, ''), file_path
+                 FROM chunks WHERE codebase_id = 'verified' ORDER BY id LIMIT 1",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .unwrap();
+        conn.execute(
+            "INSERT INTO chunks_fts(chunks_fts, rowid, content, file_path)
+             VALUES ('delete', ?1, ?2, ?3)",
+            params![id, content, file_path],
+        )
+        .unwrap();
+
+        let verification = verify_stats(&conn, "verified").unwrap();
+        assert_eq!(verification.chunk_count, 6);
+        assert_eq!(verification.fts_count, 5);
+        assert!(!verification.is_consistent());
+        let mismatches = verification.mismatches();
+        assert_eq!(mismatches.len(), 1);
+        assert!(
+            mismatches[0].contains("5 rows for 6 chunks"),
+            "{}",
+            mismatches[0]
+        );
+
+        // A codebase with nothing indexed is trivially consistent
+        assert!(verify_stats(&conn, "absent").unwrap().is_consistent());
+    }
+
+    #[test]
+    fn test_codebase_breakdown_groups_by_language_and_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let conn = init_db_at(&dir.path().join("index.db")).unwrap();
+
+        let fixture = [
+            ("src/lib.rs", Some("rust")),
+            ("src/lib.rs", Some("rust")),
+            ("src/lib.rs", Some("rust")),
+            ("src/main.rs", Some("rust")),
+            ("tools/gen.py", Some("python")),
+            ("tools/gen.py", Some("python")),
+            ("NOTES", None),
+        ];
+        let chunks: Vec<Chunk> = fixture
+            .iter()
+            .enumerate()
+            .map(|(i, (file_path, language))| Chunk {
+                file_path: file_path.to_string(),
+                language: language.map(str::to_string),
+                ..test_chunk("mixed", i, format!("mixed chunk {}", i))
+            })
+            .collect();
+        insert_chunks(&conn, &chunks).unwrap();
+        insert_chunks(&conn, &[test_chunk("other", 0, "elsewhere".to_string())]).unwrap();
+
+        let breakdown = get_codebase_breakdown(&conn, "mixed").unwrap();
+        assert_eq!(breakdown.total_chunks, 7);
+        assert_eq!(breakdown.total_files, 4);
+        assert_eq!(breakdown.avg_chunk_lines, 50.0);
+        assert_eq!(breakdown.avg_chunk_chars, 13.0);
+
+        let languages: Vec<(Option<&str>, i64, i64)> = breakdown
+            .languages
+            .iter()
+            .map(|l| (l.language.as_deref(), l.chunk_count, l.file_count))
+            .collect();
+        assert_eq!(
+            languages,
+            vec![(Some("rust"), 4, 2), (Some("python"), 2, 1), (None, 1, 1)]
+        );
+
+        let top: Vec<(&str, i64)> = breakdown
+            .top_files
+            .iter()
+            .map(|f| (f.file_path.as_str(), f.chunk_count))
+            .collect();
+        assert_eq!(
+            top,
+            vec![
+                ("src/lib.rs", 3),
+                ("tools/gen.py", 2),
+                ("NOTES", 1),
+                ("src/main.rs", 1)
+            ]
+        );
+        assert_eq!(breakdown.top_files[0].language.as_deref(), Some("rust"));
+
+        let empty = get_codebase_breakdown(&conn, "absent").unwrap();
+        assert_eq!(empty.total_chunks, 0);
+        assert!(empty.languages.is_empty() && empty.top_files.is_empty());
+    }
+
+    #[test]
+    fn test_codebase_breakdown_counts_path_and_commit_chunks_apart() {
+        let dir = tempfile::tempdir().unwrap();
+        let conn = init_db_at(&dir.path().join("index.db")).unwrap();
+
+        let chunk = |i: usize, file_path: &str, kind: &str, language: Option<&str>| Chunk {
+            file_path: file_path.to_string(),
+            chunk_kind: Some(kind.to_string()),
+            language: language.map(str::to_string),
+            ..test_chunk("kinds", i, format!("{} chunk {}", kind, i))
+        };
+        insert_chunks(
+            &conn,
+            &[
+                chunk(0, "src/lib.rs", "code", Some("rust")),
+                chunk(1, "src/lib.rs", "path", None),
+                chunk(2, "commit:0a1b2c", "commit", None),
+                chunk(3, "commit:3d4e5f", "commit", None),
+            ],
+        )
+        .unwrap();
+
+        let breakdown = get_codebase_breakdown(&conn, "kinds").unwrap();
+        assert_eq!(breakdown.total_chunks, 1);
+        assert_eq!(breakdown.total_files, 1);
+        assert_eq!(breakdown.path_chunks, 1);
+        assert_eq!(breakdown.commit_chunks, 2);
+        let languages: Vec<(Option<&str>, i64)> = breakdown
+            .languages
+            .iter()
+            .map(|l| (l.language.as_deref(), l.chunk_count))
+            .collect();
+        assert_eq!(languages, vec![(Some("rust"), 1)]);
+        let top: Vec<(&str, i64)> = breakdown
+            .top_files
+            .iter()
+            .map(|f| (f.file_path.as_str(), f.chunk_count))
+            .collect();
+        assert_eq!(top, vec![("src/lib.rs", 1)]);
+    }
+
+    #[test]
+    fn test_codebase_summary_tracks_chunk_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let conn = init_db_at(&dir.path().join("index.db")).unwrap();
+
+        let scanned = |conn: &Connection| -> Vec<(String, i64, i64)> {
+            conn.prepare(
+                "SELECT codebase_id, COUNT(*), COUNT(DISTINCT file_path) FROM chunks
+                 GROUP BY codebase_id ORDER BY codebase_id",
+            )
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .unwrap()
+            .collect::<std::result::Result<_, _>>()
+            .unwrap()
+        };
+        let check = |conn: &Connection| {
+            let summary = list_indexed_codebases(conn).unwrap();
+            assert_eq!(summary, scanned(conn));
+            summary
+        };
+
+        // 25 chunks over files 0..=2 in each of two codebases
+        for codebase_id in ["left", "right"] {
+            let chunks: Vec<Chunk> = (0..25)
+                .map(|i| test_chunk(codebase_id, i, format!("fn f{}() {{}}", i)))
+                .collect();
+            insert_chunks(&conn, &chunks).unwrap();
+        }
+        assert_eq!(
+            check(&conn),
+            vec![("left".to_string(), 25, 3), ("right".to_string(), 25, 3)]
+        );
+
+        // Re-inserting the same ranges replaces rather than adds
+        insert_chunks(&conn, &[test_chunk("left", 3, "fn again() {}".to_string())]).unwrap();
+        delete_chunks_for_file(&conn, "left", "src/file_2.rs").unwrap();
+        assert_eq!(check(&conn)[0], ("left".to_string(), 20, 2));
+
+        conn.execute(
+            "UPDATE chunks SET file_path = 'src/moved.rs'
+             WHERE codebase_id = 'right' AND file_path = 'src/file_0.rs' AND start_line = 1",
+            [],
+        )
+        .unwrap();
+        move_codebase(&conn, "right", "renamed", "/renamed").unwrap();
+        assert_eq!(check(&conn)[1], ("renamed".to_string(), 25, 4));
+
+        delete_chunks_for_codebase(&conn, "left").unwrap();
+        assert_eq!(check(&conn), vec![("renamed".to_string(), 25, 4)]);
+
+        // A summary not known to be current is bypassed, then rebuilt
+        conn.execute(
+            "DELETE FROM index_settings WHERE key = 'codebase_summary'",
+            [],
+        )
+        .unwrap();
+        conn.execute("DELETE FROM codebase_summary", []).unwrap();
+        check(&conn);
+        refresh_codebase_summary(&conn).unwrap();
+        assert_eq!(check(&conn), vec![("renamed".to_string(), 25, 4)]);
+    }
+
+    #[test]
+    fn test_resolve_codebase_by_substring() {
+        let dir = tempfile::tempdir().unwrap();
+        let conn = init_db_at(&dir.path().join("index.db")).unwrap();
+        register_codebase(&conn, "a", "backend", "/src/acme-backend", None, None).unwrap();
+        register_codebase(&conn, "b", "frontend", "/src/acme-frontend", None, None).unwrap();
+
+        assert_eq!(resolve_codebase(&conn, "back").unwrap().codebase_id, "a");
+        assert_eq!(
+            resolve_codebase(&conn, "frontend").unwrap().codebase_id,
+            "b"
+        );
+
+        match resolve_codebase(&conn, "acme") {
+            Err(CodeSearchError::InvalidInput(msg)) => {
+                assert!(msg.contains("/src/acme-backend"));
+                assert!(msg.contains("/src/acme-frontend"));
+            }
+            other => panic!("expected ambiguity error, got {:?}", other),
+        }
+        assert!(matches!(
+            resolve_codebase(&conn, "mobile"),
+            Err(CodeSearchError::CodebaseNotIndexed(_))
+        ));
+    }
+
+    #[test]
+    fn test_oversized_result_content_is_capped() {
+        let dir = tempfile::tempdir().unwrap();
+        let conn = init_db_at(&dir.path().join("index.db")).unwrap();
+        let max_chars = get_config().search.max_result_chars;
+        assert!(max_chars > 0);
+
+        let minified = format!("var oversized_marker=1;{}", "a=b+c;".repeat(max_chars));
+        insert_chunks(
+            &conn,
+            &[
+                test_chunk("capped", 0, minified),
+                test_chunk("capped", 10, "let oversized_marker = 2;".to_string()),
+            ],
+        )
+        .unwrap();
+
+        let check = |results: Vec<SearchResult>| {
+            assert_eq!(results.len(), 2);
+            for result in results {
+                if result.chunk_id == 1 {
+                    assert!(result.is_truncated);
+                    assert!(result.content.ends_with(TRUNCATION_MARKER));
+                    assert_eq!(result.content.chars().count(), max_chars + 1);
+                } else {
+                    assert!(!result.is_truncated);
+                    assert_eq!(result.content, "let oversized_marker = 2;");
+                }
+            }
+        };
+        check(
+            fts_search(
+                &conn,
+                "oversized_marker",
+                Some("capped"),
+                10,
+                &SearchFilters::default(),
+            )
+            .unwrap(),
+        );
+        check(vector_search(&conn, Some("capped"), &[0.0, 1.0, 0.5], 10).unwrap());
+    }
+
+    #[test]
+    fn test_limit_clamped_to_ceiling() {
+        assert_eq!(clamp_limit(10, 1000), (10, false));
+        assert_eq!(clamp_limit(1000, 1000), (1000, false));
+        assert_eq!(clamp_limit(5000, 1000), (1000, true));
+        assert_eq!(clamp_limit(-1, 1000), (0, true));
+
+        // A negative limit no longer reaches SQLite as "no limit"
+        let dir = tempfile::tempdir().unwrap();
+        let conn = init_db_at(&dir.path().join("index.db")).unwrap();
+        let chunks: Vec<Chunk> = (0..5)
+            .map(|i| test_chunk("ceiling", i, "fn clamp_marker() {}".to_string()))
+            .collect();
+        insert_chunks(&conn, &chunks).unwrap();
+        let filters = SearchFilters::default();
+        let fts = |limit| {
+            fts_search(&conn, "clamp_marker", Some("ceiling"), limit, &filters)
+                .unwrap()
+                .len()
+        };
+        assert_eq!(fts(-1), 0);
+        assert_eq!(fts(3), 3);
+        assert_eq!(fts(i64::MAX), 5);
+        assert!(vector_search(&conn, Some("ceiling"), &[0.0, 1.0, 0.5], -1)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_split_identifiers_match_camel_case_parts() {
+        let dir = tempfile::tempdir().unwrap();
+        let conn = init_db_at(&dir.path().join("index.db")).unwrap();
+        let chunks = |codebase_id: &str| {
+            vec![
+                test_chunk(
+                    codebase_id,
+                    0,
+                    "fn parseHTTPResponse(raw: &[u8]) -> Response { todo!() }".to_string(),
+                ),
+                test_chunk(codebase_id, 1, "fn render_page() {}".to_string()),
+            ]
+        };
+        insert_chunks_inner(&conn, &chunks("plain"), false).unwrap();
+        insert_chunks_inner(&conn, &chunks("split"), true).unwrap();
+
+        let search = |codebase_id, query, split| {
+            fts_search_inner(
+                &conn,
+                query,
+                Some(codebase_id),
+                10,
+                &SearchFilters::default(),
+                FtsOperator::And,
+                split,
+            )
+            .unwrap()
+            .into_iter()
+            .map(|r| r.start_line)
+            .collect::<Vec<_>>()
+        };
+
+        // Without splitting, the identifier is one opaque token
+        assert!(search("plain", "http response", false).is_empty());
+        assert!(search("plain", "HttpResponse", true).is_empty());
+
+        // Its parts, or another identifier made of them, now find it
+        let camel_chunk = vec![chunks("split")[0].start_line];
+        assert_eq!(search("split", "http response", true), camel_chunk);
+        assert_eq!(search("split", "HttpResponse", true), camel_chunk);
+        assert_eq!(search("split", "parseHTTPResponse", true), camel_chunk);
+        assert_eq!(search("plain", "parseHTTPResponse", true), camel_chunk);
+
+        // Deleting removes the identifier terms from the FTS index as well
+        delete_chunks_for_codebase(&conn, "split").unwrap();
+        assert!(search("split", "http response", true).is_empty());
+    }
+
+    #[test]
+    fn test_hybrid_sums_keyword_and_semantic_scores() {
+        let dir = tempfile::tempdir().unwrap();
+        let conn = init_db_at(&dir.path().join("index.db")).unwrap();
+        let query_embedding = vec![1.0, 0.0, 0.0];
+        let chunk = |i, content: &str, embedding: Vec<f32>| Chunk {
+            embedding,
+            ..test_chunk("fused", i, content.to_string())
+        };
+        insert_chunks(
+            &conn,
+            &[
+                // Strong keyword match, unrelated meaning
+                chunk(0, "retry retry retry backoff", vec![0.0, 1.0, 0.0]),
+                // Same meaning, no keyword
+                chunk(1, "fn attempt_again_later() {}", vec![1.0, 0.0, 0.0]),
+                // Both signals
+                chunk(2, "fn retry_with_backoff() { retry }", vec![0.9, 0.1, 0.0]),
+                chunk(3, "fn unrelated() {}", vec![0.0, 0.0, 1.0]),
+            ],
+        )
+        .unwrap();
+
+        let results = hybrid_search(
+            &conn,
+            "retry",
+            Some("fused"),
+            &query_embedding,
+            4,
+            &SearchFilters::default(),
+            false,
+        )
+        .unwrap();
+        let order: Vec<i64> = results.iter().map(|r| r.start_line).collect();
+        assert_eq!(order[0], 101, "chunk matching both signals ranks first");
+        assert!(results.iter().all(|r| r.score <= 2.0));
+
+        // Keyword-only hits keep their BM25 order instead of tying
+        let mut fts =
+            fts_search(&conn, "retry", Some("fused"), 10, &SearchFilters::default()).unwrap();
+        normalize_fts_scores(&mut fts);
+        assert_eq!(fts[0].score, 1.0);
+        assert!(fts[1].score < 1.0);
+    }
+
+    #[test]
+    fn test_linear_blend_reorders_compressed_cosines() {
+        let result = |chunk_id, score| SearchResult {
+            chunk_id,
+            codebase_id: "blend".to_string(),
+            file_path: format!("src/{}.rs", chunk_id),
+            start_line: 1,
+            end_line: 10,
+            content: String::new(),
+            language: None,
+            author: None,
+            score,
+            rank: 0,
+            is_truncated: false,
+            also_in: Vec::new(),
+        };
+        // Chunk 1 dominates on BM25; on cosine all three sit close together
+        let fts = vec![result(1, 10.0), result(2, 5.0), result(3, 4.0)];
+        let vector = vec![result(3, 0.80), result(2, 0.79), result(1, 0.70)];
+
+        let order = |fusion| -> Vec<i64> {
+            let mut fused = fuse_results(
+                "query",
+                fts.clone(),
+                vector.clone(),
+                (0.5, 0.5),
+                fusion,
+                false,
+            );
+            fused.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap());
+            fused.iter().map(|r| r.chunk_id).collect()
+        };
+
+        // Raw cosines barely move the BM25 order...
+        assert_eq!(order(FusionStrategy::Rrf), vec![1, 2, 3]);
+        // ...while on a shared 0..=1 scale chunk 2, second on both, wins
+        assert_eq!(order(FusionStrategy::LinearBlend)[0], 2);
+
+        let mut scores = vector.clone();
+        min_max_normalize(&mut scores);
+        let scores: Vec<f64> = scores.iter().map(|r| r.score).collect();
+        assert_eq!(scores[0], 1.0);
+        assert_eq!(scores[2], 0.0);
+        let mut equal = vec![result(1, 3.0), result(2, 3.0)];
+        min_max_normalize(&mut equal);
+        assert!(equal.iter().all(|r| r.score == 1.0));
+    }
+
+    #[test]
+    fn test_fts_column_restriction() {
+        let dir = tempfile::tempdir().unwrap();
+        let conn = init_db_at(&dir.path().join("index.db")).unwrap();
+
+        insert_chunks(
+            &conn,
+            &[
+                Chunk {
+                    file_path: "src/scheduler/mod.rs".to_string(),
+                    ..test_chunk("columns", 0, "fn tick() {}".to_string())
+                },
+                Chunk {
+                    file_path: "src/timer.rs".to_string(),
+                    ..test_chunk("columns", 1, "// the scheduler wakes here".to_string())
+                },
+            ],
+        )
+        .unwrap();
+
+        let paths = |columns: FtsColumns| {
+            let filters = SearchFilters {
+                fts_columns: columns,
+                ..Default::default()
+            };
+            let mut paths: Vec<String> =
+                fts_search(&conn, "scheduler", Some("columns"), 10, &filters)
+                    .unwrap()
+                    .into_iter()
+                    .map(|r| r.file_path)
+                    .collect();
+            paths.sort();
+            paths
+        };
+
+        assert_eq!(
+            paths(FtsColumns::Both),
+            vec!["src/scheduler/mod.rs", "src/timer.rs"]
+        );
+        assert_eq!(paths(FtsColumns::Content), vec!["src/timer.rs"]);
+        assert_eq!(paths(FtsColumns::Path), vec!["src/scheduler/mod.rs"]);
+        assert_eq!("path".parse::<FtsColumns>().unwrap(), FtsColumns::Path);
+        assert!("title".parse::<FtsColumns>().is_err());
+    }
+
+    #[test]
+    fn test_fts_and_operator_requires_every_term() {
+        let dir = tempfile::tempdir().unwrap();
+        let conn = init_db_at(&dir.path().join("index.db")).unwrap();
+        insert_chunks(
+            &conn,
+            &[
+                test_chunk(
+                    "ops",
+                    0,
+                    "fn open_socket() { retry connection }".to_string(),
+                ),
+                test_chunk(
+                    "ops",
+                    10,
+                    "fn close_socket() { drop connection }".to_string(),
+                ),
+                test_chunk("ops", 20, "fn backoff() { retry later }".to_string()),
+            ],
+        )
+        .unwrap();
+
+        let search = |operator| {
+            fts_search_with_operator(
+                &conn,
+                "retry connection",
+                Some("ops"),
+                10,
+                &SearchFilters::default(),
+                operator,
+            )
+            .unwrap()
+        };
+        assert_eq!(search(FtsOperator::Or).len(), 3);
+        let both = search(FtsOperator::And);
+        assert_eq!(both.len(), 1);
+        assert!(both[0].content.contains("open_socket"));
+    }
+
+    #[test]
+    fn test_fts_query_syntax_is_matched_as_text() {
+        let dir = tempfile::tempdir().unwrap();
+        let conn = init_db_at(&dir.path().join("index.db")).unwrap();
+        insert_chunks(
+            &conn,
+            &[
+                test_chunk(
+                    "quoting",
+                    0,
+                    "let reader = std::io::stdin(); retry connection".to_string(),
+                ),
+                test_chunk("quoting", 10, "fn connection() { retry later }".to_string()),
+            ],
+        )
+        .unwrap();
+
+        let search = |query: &str, operator| -> Vec<String> {
+            fts_search_with_operator(
+                &conn,
+                query,
+                Some("quoting"),
+                10,
+                &SearchFilters::default(),
+                operator,
+            )
+            .unwrap()
+            .into_iter()
+            .map(|r| r.file_path)
+            .collect()
+        };
+        let first = || vec!["src/file_0.rs".to_string()];
+
+        // A colon would otherwise name a column
+        assert_eq!(search("std::io", FtsOperator::Or), first());
+        // A quoted phrase needs its words together and in order
+        assert_eq!(search("\"retry connection\"", FtsOperator::Or), first());
+        assert_eq!(search("retry connection", FtsOperator::Or).len(), 2);
+        assert_eq!(
+            search("\"connection retry\" later", FtsOperator::Or).len(),
+            1
+        );
+        // A stray quote is dropped, and the words around it still match
+        assert_eq!(search("stdin \"", FtsOperator::Or), first());
+        assert_eq!(search("\"stdin reader", FtsOperator::And), first());
+        // Operators, prefixes and bare punctuation are plain text
+        assert!(search("NOT ^ * : AND", FtsOperator::Or).is_empty());
+
+        assert_eq!(
+            fts_match_expression("say \"hi there\" x a:b", FtsOperator::And, false),
+            "\"say\" AND \"hi there\" AND \"a:b\""
+        );
+        assert_eq!(
+            fts_match_expression("parseHTTPResponse", FtsOperator::Or, true),
+            "(\"parseHTTPResponse\" OR \"parse HTTP Response\")"
+        );
+    }
+
+    #[test]
+    fn test_suggest_terms_ranked_by_frequency() {
+        let dir = tempfile::tempdir().unwrap();
+        let conn = init_db_at(&dir.path().join("index.db")).unwrap();
+
+        let mut first = test_chunk(
+            "suggest",
+            0,
+            "let cfg = parse_config(path);\nparse_config(other);\nlet parser = Parser::new();"
+                .to_string(),
+        );
+        first.start_line = 1;
+        first.end_line = 3;
+        // Line 3 overlaps the first chunk and must not be counted twice
+        let mut second = test_chunk(
+            "suggest",
+            1,
+            "let parser = Parser::new();\nparse_config(args);\nparse_args(parser);".to_string(),
+        );
+        second.start_line = 3;
+        second.end_line = 5;
+        second.file_path = first.file_path.clone();
+        let other_codebase = test_chunk("elsewhere", 2, "parse_everything()".to_string());
+
+        insert_chunks(&conn, &[first, second.clone(), other_codebase]).unwrap();
+
+        let ranked = || -> Vec<(String, i64)> {
+            suggest_terms(&conn, "suggest", "pars", 10)
+                .unwrap()
+                .into_iter()
+                .map(|s| (s.term, s.frequency))
+                .collect()
+        };
+        let expected: Vec<(String, i64)> = [
+            ("parse_config", 3),
+            ("parser", 2),
+            ("Parser", 1),
+            ("parse_args", 1),
+        ]
+        .into_iter()
+        .map(|(term, frequency)| (term.to_string(), frequency))
+        .collect();
+        assert_eq!(ranked(), expected);
+
+        // A later insert of part of the file still counts all of it
+        insert_chunks(&conn, &[second]).unwrap();
+        assert_eq!(ranked(), expected);
+
+        assert_eq!(suggest_terms(&conn, "suggest", "pars", 2).unwrap().len(), 2);
+        assert!(suggest_terms(&conn, "suggest", "parse%", 10)
+            .unwrap()
+            .is_empty());
+
+        let file_path = test_chunk("suggest", 0, String::new()).file_path;
+        delete_chunks_for_file(&conn, "suggest", &file_path).unwrap();
+        assert!(suggest_terms(&conn, "suggest", "pars", 10)
+            .unwrap()
+            .is_empty());
     }
 }
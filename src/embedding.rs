@@ -1,7 +1,19 @@
-use crate::config::get_config;
-use crate::error::Result;
+//! Text embeddings from the ONNX models, or from a deterministic hash
+//! without the `onnx` feature. With the `remote` feature, the `remote` model
+//! posts texts to an OpenAI-compatible embeddings API instead.
+//!
+//! To embed many texts, resolve an [`EmbeddingModel`] once and reuse it; it
+//! can be shared across threads. The free `*_with_model` functions parse the
+//! model name and look up its embedder on every call, and return zero
+//! vectors instead of errors.
+
+use crate::config::{get_config, ModelConfig};
+use crate::error::{CodeSearchError, Result};
 use sha2::{Digest, Sha256};
-use std::sync::OnceLock;
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::sync::{Condvar, Mutex, MutexGuard, OnceLock, PoisonError};
+use std::time::{Duration, Instant};
 
 pub const DEFAULT_MODEL: &str = "minilm";
 
@@ -12,12 +24,28 @@ pub struct CustomModelConfig {
     pub embedding_dim: usize,
 }
 
+/// An OpenAI-compatible embeddings API, from `model.endpoint`,
+/// `model.remote_model` and `model.embedding_dim`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RemoteModelConfig {
+    /// Full URL of the API, e.g. `https://api.openai.com/v1/embeddings`
+    pub endpoint: String,
+    /// Model name sent with each request, for servers that host several
+    pub model: Option<String>,
+    pub embedding_dim: usize,
+}
+
+/// Environment variable whose value, when set, is sent to a remote
+/// embeddings API as a bearer token
+pub const REMOTE_API_KEY_ENV: &str = "CODE_SEARCH_API_KEY";
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum ModelType {
     MiniLM,
     Nomic,
     Nemotron,
     Custom(CustomModelConfig),
+    Remote(RemoteModelConfig),
 }
 
 impl std::str::FromStr for ModelType {
@@ -42,6 +70,22 @@ impl std::str::FromStr for ModelType {
                     Err("Custom model requires model_path and embedding_dim in config".to_string())
                 }
             }
+            "remote" => {
+                let config = get_config();
+                match (&config.model.endpoint, config.embedding_dim()) {
+                    (Some(endpoint), Some(embedding_dim)) => {
+                        Ok(ModelType::Remote(RemoteModelConfig {
+                            endpoint: endpoint.clone(),
+                            model: config.model.remote_model.clone(),
+                            embedding_dim,
+                        }))
+                    }
+                    _ => {
+                        Err("Remote model requires endpoint and embedding_dim in config"
+                            .to_string())
+                    }
+                }
+            }
             _ => Ok(ModelType::MiniLM),
         }
     }
@@ -55,12 +99,47 @@ impl ModelType {
         }
     }
 
-    pub fn dimension(&self) -> usize {
+    /// Length of the vectors the model itself produces
+    pub fn native_dimension(&self) -> usize {
         match self {
             ModelType::MiniLM => 384,
             ModelType::Nomic => 768,
             ModelType::Nemotron => 2048,
             ModelType::Custom(config) => config.embedding_dim,
+            ModelType::Remote(config) => config.embedding_dim,
+        }
+    }
+
+    /// Length of the embeddings returned and stored: the native length, or
+    /// for Nomic the Matryoshka prefix `model.embedding_dim` asks for
+    pub fn dimension(&self) -> usize {
+        let native = self.native_dimension();
+        match self {
+            ModelType::Nomic => get_config()
+                .model
+                .embedding_dim
+                .filter(|&dim| dim > 0)
+                .map_or(native, |dim| dim.min(native)),
+            _ => native,
+        }
+    }
+
+    /// Whether a prefix of the model's embeddings is itself a usable
+    /// embedding (Matryoshka representation learning)
+    pub fn supports_truncation(&self) -> bool {
+        matches!(self, ModelType::Nomic)
+    }
+
+    /// Longest input in tokens the model attends to; text past it is cut
+    /// off before embedding
+    pub fn max_tokens(&self) -> usize {
+        match self {
+            ModelType::MiniLM => 256,
+            ModelType::Nomic => 8192,
+            ModelType::Nemotron => 8192,
+            ModelType::Custom(_) => 512,
+            // The OpenAI embedding models' limit
+            ModelType::Remote(_) => 8191,
         }
     }
 
@@ -69,7 +148,7 @@ impl ModelType {
             ModelType::MiniLM => "",
             ModelType::Nomic => "search_document: ",
             ModelType::Nemotron => "passage: ",
-            ModelType::Custom(_) => "",
+            ModelType::Custom(_) | ModelType::Remote(_) => "",
         }
     }
 
@@ -78,11 +157,255 @@ impl ModelType {
             ModelType::MiniLM => "",
             ModelType::Nomic => "search_query: ",
             ModelType::Nemotron => "query: ",
-            ModelType::Custom(_) => "",
+            ModelType::Custom(_) | ModelType::Remote(_) => "",
         }
     }
 }
 
+/// Source of model files, abstracted so downloads can be retried and tested
+/// without the network. The ONNX backend fetches from the HuggingFace Hub.
+pub trait ModelFileFetcher {
+    /// Fetch `file` from the model repository and return its local path
+    fn fetch(&self, file: &str) -> std::result::Result<PathBuf, String>;
+}
+
+/// Retry policy for model file downloads
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Attempts after the first failure
+    pub retries: u32,
+    /// Delay before the first retry; doubled after each further failure
+    pub initial_backoff: Duration,
+    /// Give up once this point is reached, whatever retries remain
+    pub deadline: Option<Instant>,
+}
+
+impl RetryPolicy {
+    /// Policy from the model config, with the load timeout counted from now
+    pub fn from_config(config: &ModelConfig) -> Self {
+        Self {
+            retries: config.download_retries,
+            initial_backoff: Duration::from_millis(config.download_backoff_ms),
+            deadline: config
+                .load_timeout_secs
+                .map(|secs| Instant::now() + Duration::from_secs(secs)),
+        }
+    }
+}
+
+/// Fetch a model file, retrying failures with exponential backoff. Fails with
+/// the last error once the retries are spent or the next wait would pass the
+/// policy's deadline.
+pub fn fetch_with_retry(
+    fetcher: &dyn ModelFileFetcher,
+    file: &str,
+    policy: &RetryPolicy,
+) -> Result<PathBuf> {
+    let mut backoff = policy.initial_backoff;
+    let mut attempt = 0;
+
+    loop {
+        attempt += 1;
+        let error = match fetcher.fetch(file) {
+            Ok(path) => return Ok(path),
+            Err(error) => error,
+        };
+
+        if attempt > policy.retries {
+            return Err(CodeSearchError::EmbeddingModelLoad(format!(
+                "Failed to download {} after {} attempt(s): {}",
+                file, attempt, error
+            )));
+        }
+        if let Some(deadline) = policy.deadline {
+            if Instant::now() + backoff >= deadline {
+                return Err(CodeSearchError::EmbeddingModelLoad(format!(
+                    "Timed out downloading {} after {} attempt(s): {}",
+                    file, attempt, error
+                )));
+            }
+        }
+
+        std::thread::sleep(backoff);
+        backoff = backoff.saturating_mul(2);
+    }
+}
+
+/// A bounded set of interchangeable values, created on demand up to `max`.
+/// ONNX sessions need exclusive access to run, so each encode checks one
+/// out instead of every thread queueing on a single session.
+#[cfg_attr(not(feature = "onnx"), allow(dead_code))]
+pub(crate) struct Pool<T> {
+    state: Mutex<PoolState<T>>,
+    returned: Condvar,
+    max: usize,
+}
+
+struct PoolState<T> {
+    idle: Vec<T>,
+    created: usize,
+}
+
+/// A value checked out of a [`Pool`]; goes back to it when dropped
+pub(crate) struct PoolGuard<'a, T> {
+    pool: &'a Pool<T>,
+    value: Option<T>,
+}
+
+#[cfg_attr(not(feature = "onnx"), allow(dead_code))]
+impl<T> Pool<T> {
+    /// A pool holding `first`, which may grow to `max` values (at least 1)
+    pub fn with_first(first: T, max: usize) -> Self {
+        Self {
+            state: Mutex::new(PoolState {
+                idle: vec![first],
+                created: 1,
+            }),
+            returned: Condvar::new(),
+            max: max.max(1),
+        }
+    }
+
+    /// Check out an idle value, or make one with `create` if fewer than
+    /// `max` exist, or else wait for one to be returned
+    pub fn get(&self, create: impl FnOnce() -> Result<T>) -> Result<PoolGuard<'_, T>> {
+        let mut state = self.lock();
+        loop {
+            if let Some(value) = state.idle.pop() {
+                return Ok(PoolGuard {
+                    pool: self,
+                    value: Some(value),
+                });
+            }
+            if state.created < self.max {
+                state.created += 1;
+                break;
+            }
+            state = self
+                .returned
+                .wait(state)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+        drop(state);
+
+        match create() {
+            Ok(value) => Ok(PoolGuard {
+                pool: self,
+                value: Some(value),
+            }),
+            Err(e) => {
+                self.lock().created -= 1;
+                self.returned.notify_one();
+                Err(e)
+            }
+        }
+    }
+
+    /// Values made so far, idle or checked out
+    #[cfg(test)]
+    pub fn created(&self) -> usize {
+        self.lock().created
+    }
+
+    fn lock(&self) -> MutexGuard<'_, PoolState<T>> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<T> std::ops::Deref for PoolGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.value.as_ref().expect("value present until drop")
+    }
+}
+
+impl<T> std::ops::DerefMut for PoolGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.value.as_mut().expect("value present until drop")
+    }
+}
+
+impl<T> Drop for PoolGuard<'_, T> {
+    fn drop(&mut self) {
+        if let Some(value) = self.value.take() {
+            self.pool.lock().idle.push(value);
+            self.pool.returned.notify_one();
+        }
+    }
+}
+
+/// A query embedding kept so repeating the search skips the model
+struct CachedQuery {
+    model_type: ModelType,
+    prefixed_text: String,
+    embedding: Vec<f32>,
+}
+
+/// Recently embedded queries, least recently used first, bounded by
+/// `model.query_cache_size`
+static QUERY_CACHE: Mutex<VecDeque<CachedQuery>> = Mutex::new(VecDeque::new());
+
+fn query_cache() -> MutexGuard<'static, VecDeque<CachedQuery>> {
+    QUERY_CACHE.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// `text` embedded as a query by `model_type`, from the cache if it was
+/// embedded recently
+fn cached_query_embedding(model_type: &ModelType, text: &str) -> Result<Vec<f32>> {
+    let capacity = get_config().model.query_cache_size;
+    let prefix = model_type.query_prefix();
+    let prefixed_text = format!("{}{}", prefix, text);
+    // A vector of another length was cached under an earlier embedding_dim
+    let dimension = model_type.dimension();
+    let matches = |entry: &CachedQuery| {
+        entry.model_type == *model_type
+            && entry.prefixed_text == prefixed_text
+            && entry.embedding.len() == dimension
+    };
+
+    if capacity > 0 {
+        let mut cache = query_cache();
+        if let Some(entry) = cache
+            .iter()
+            .position(matches)
+            .and_then(|position| cache.remove(position))
+        {
+            let embedding = entry.embedding.clone();
+            cache.push_back(entry);
+            return Ok(embedding);
+        }
+    }
+
+    let embedding = get_embedder(model_type).get_embedding_with_prefix(text, prefix)?;
+    if capacity > 0 {
+        let mut cache = query_cache();
+        // Another thread may have embedded the same query meanwhile
+        cache.retain(|entry| !matches(entry));
+        while cache.len() >= capacity {
+            cache.pop_front();
+        }
+        cache.push_back(CachedQuery {
+            model_type: model_type.clone(),
+            prefixed_text,
+            embedding: embedding.clone(),
+        });
+    }
+    Ok(embedding)
+}
+
+/// Drop cached queries for `model_type`, whose vectors may change when it
+/// is (re)loaded
+#[cfg_attr(not(feature = "onnx"), allow(dead_code))]
+fn invalidate_query_cache(model_type: &ModelType) {
+    query_cache().retain(|entry| entry.model_type != *model_type);
+}
+
+/// Drop every cached query embedding
+pub fn clear_query_cache() {
+    query_cache().clear();
+}
+
 static ONNX_AVAILABLE: OnceLock<bool> = OnceLock::new();
 
 fn check_onnx_available() -> bool {
@@ -101,21 +424,45 @@ fn check_onnx_available() -> bool {
 #[cfg(feature = "onnx")]
 mod onnx_backend {
     use super::*;
+    use crate::config::ExecutionProvider;
     use crate::error::CodeSearchError;
     use hf_hub::api::sync::Api;
     use ndarray::Array2;
-    use ort::session::{builder::GraphOptimizationLevel, Session};
+    use ort::ep::ExecutionProvider as _;
+    use ort::session::builder::{GraphOptimizationLevel, SessionBuilder};
+    use ort::session::Session;
     use ort::value::Tensor;
+    use std::path::{Path, PathBuf};
     use std::sync::{Arc, RwLock};
     use tokenizers::Tokenizer;
 
     pub struct LoadedModel {
-        session: Session,
+        model_type: ModelType,
+        /// Sessions over the same model file, so threads can run inference
+        /// concurrently; the tokenizer is shared by all of them
+        sessions: Pool<Session>,
+        model_path: PathBuf,
         tokenizer: Tokenizer,
+        /// Provider the sessions actually run on, after any fallback
+        execution_provider: ExecutionProvider,
+        /// Intra-op threads per session, splitting the cores between them
+        intra_threads: usize,
+    }
+
+    struct HubFetcher {
+        repo: hf_hub::api::sync::ApiRepo,
+    }
+
+    impl ModelFileFetcher for HubFetcher {
+        fn fetch(&self, file: &str) -> std::result::Result<std::path::PathBuf, String> {
+            self.repo.get(file).map_err(|e| e.to_string())
+        }
     }
 
     impl LoadedModel {
         pub fn new(model_type: ModelType) -> Result<Self> {
+            let policy = RetryPolicy::from_config(&get_config().model);
+
             let api = Api::new().map_err(|e| {
                 CodeSearchError::EmbeddingModelLoad(format!(
                     "Failed to initialize HuggingFace API: {}",
@@ -123,37 +470,22 @@ mod onnx_backend {
                 ))
             })?;
 
-            let repo = api.model(model_type.repo_id().to_string());
-
-            let model_path = repo.get("onnx/model.onnx").map_err(|e| {
-                CodeSearchError::EmbeddingModelLoad(format!("Failed to download model: {}", e))
-            })?;
+            let fetcher = HubFetcher {
+                repo: api.model(model_type.repo_id().to_string()),
+            };
 
-            let tokenizer_path = repo.get("tokenizer.json").map_err(|e| {
-                CodeSearchError::EmbeddingModelLoad(format!("Failed to download tokenizer: {}", e))
-            })?;
+            let model_path = fetch_with_retry(&fetcher, "onnx/model.onnx", &policy)?;
+            let tokenizer_path = fetch_with_retry(&fetcher, "tokenizer.json", &policy)?;
 
-            let session = Session::builder()
-                .map_err(|e| {
-                    CodeSearchError::EmbeddingModelLoad(format!(
-                        "Failed to create session builder: {}",
-                        e
-                    ))
-                })?
-                .with_optimization_level(GraphOptimizationLevel::Level3)
-                .map_err(|e| {
-                    CodeSearchError::EmbeddingModelLoad(format!(
-                        "Failed to set optimization level: {}",
-                        e
-                    ))
-                })?
-                .commit_from_file(&model_path)
-                .map_err(|e| {
-                    CodeSearchError::EmbeddingModelLoad(format!(
-                        "Failed to load model from file: {}",
-                        e
-                    ))
-                })?;
+            let (max_sessions, intra_threads) =
+                session_layout(get_config().model.inference_sessions, num_cpus::get());
+            // The first session settles the provider; later ones reuse it
+            // rather than retrying (and warning about) a failed one
+            let (session, execution_provider) = build_session(
+                &model_path,
+                get_config().model.execution_provider,
+                intra_threads,
+            )?;
 
             let tokenizer = Tokenizer::from_file(&tokenizer_path)
                 .map_err(|e| {
@@ -161,10 +493,17 @@ mod onnx_backend {
                 })?
                 .into();
 
-            Ok(Self { session, tokenizer })
+            Ok(Self {
+                model_type,
+                sessions: Pool::with_first(session, max_sessions),
+                model_path,
+                tokenizer,
+                execution_provider,
+                intra_threads,
+            })
         }
 
-        pub fn encode(&mut self, text: &str) -> Result<Vec<f32>> {
+        pub fn encode(&self, text: &str) -> Result<Vec<f32>> {
             let encoding = self.tokenizer.encode(text, true).map_err(|e| {
                 CodeSearchError::EmbeddingInference(format!("Tokenization failed: {}", e))
             })?;
@@ -219,8 +558,15 @@ mod onnx_backend {
                     ))
                 })?;
 
-            let outputs = self
-                .session
+            let mut session = self.sessions.get(|| {
+                build_session(
+                    &self.model_path,
+                    self.execution_provider,
+                    self.intra_threads,
+                )
+                .map(|(session, _)| session)
+            })?;
+            let outputs = session
                 .run(ort::inputs![
                     "input_ids" => input_ids_tensor,
                     "attention_mask" => attention_mask_tensor,
@@ -243,14 +589,91 @@ mod onnx_backend {
             let embedding = mean_pool(data, &attention_mask, seq_len_out, hidden_size);
             let normalized = l2_normalize(&embedding);
 
-            Ok(normalized)
+            Ok(truncate_embedding(&normalized, self.model_type.dimension()))
         }
 
-        pub fn encode_batch(&mut self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        pub fn encode_batch(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
             texts.iter().map(|text| self.encode(text)).collect()
         }
     }
 
+    /// Intra-op threads each session keeps when the session count is chosen
+    /// automatically, so a lone query still runs on several cores
+    const AUTO_THREADS_PER_SESSION: usize = 4;
+
+    /// Inference sessions for one model and the intra-op threads each gets
+    /// out of `cores`. `configured` 0 picks as many sessions as leave every
+    /// one [`AUTO_THREADS_PER_SESSION`] threads.
+    pub(super) fn session_layout(configured: usize, cores: usize) -> (usize, usize) {
+        let cores = cores.max(1);
+        let sessions = if configured == 0 {
+            (cores / AUTO_THREADS_PER_SESSION).max(1)
+        } else {
+            configured
+        };
+        (sessions, (cores / sessions).max(1))
+    }
+
+    /// Open a session over `model_path` on `provider`, returning the
+    /// provider it actually runs on
+    fn build_session(
+        model_path: &Path,
+        provider: ExecutionProvider,
+        intra_threads: usize,
+    ) -> Result<(Session, ExecutionProvider)> {
+        let mut builder = Session::builder()
+            .map_err(|e| {
+                CodeSearchError::EmbeddingModelLoad(format!(
+                    "Failed to create session builder: {}",
+                    e
+                ))
+            })?
+            .with_optimization_level(GraphOptimizationLevel::Level3)
+            .map_err(|e| {
+                CodeSearchError::EmbeddingModelLoad(format!(
+                    "Failed to set optimization level: {}",
+                    e
+                ))
+            })?
+            .with_intra_threads(intra_threads)
+            .map_err(|e| {
+                CodeSearchError::EmbeddingModelLoad(format!(
+                    "Failed to set intra-op threads: {}",
+                    e
+                ))
+            })?;
+        let execution_provider = register_execution_provider(&mut builder, provider);
+        let session = builder.commit_from_file(model_path).map_err(|e| {
+            CodeSearchError::EmbeddingModelLoad(format!("Failed to load model from file: {}", e))
+        })?;
+        Ok((session, execution_provider))
+    }
+
+    /// Register `provider` on the session, returning the provider the session
+    /// will run on: `provider` itself, or the CPU if it couldn't be set up
+    fn register_execution_provider(
+        builder: &mut SessionBuilder,
+        provider: ExecutionProvider,
+    ) -> ExecutionProvider {
+        let registered = match provider {
+            ExecutionProvider::Cpu => return provider,
+            ExecutionProvider::Cuda => ort::ep::CUDA::default().register(builder),
+            ExecutionProvider::CoreMl => ort::ep::CoreML::default().register(builder),
+            ExecutionProvider::DirectMl => ort::ep::DirectML::default().register(builder),
+        };
+        match registered {
+            Ok(()) => provider,
+            Err(e) => {
+                eprintln!(
+                    "Warning: execution provider '{}' is unavailable ({}); running on the CPU",
+                    provider.as_str(),
+                    e
+                );
+                ExecutionProvider::Cpu
+            }
+        }
+    }
+
     fn mean_pool(
         hidden_state: &[f32],
         attention_mask: &[i64],
@@ -307,6 +730,7 @@ mod onnx_backend {
             if write_guard.is_none() {
                 let model = LoadedModel::new(self.model_type.clone())?;
                 *write_guard = Some(model);
+                invalidate_query_cache(&self.model_type);
             }
 
             Ok(())
@@ -314,11 +738,12 @@ mod onnx_backend {
 
         pub fn get_embedding_with_prefix(&self, text: &str, prefix: &str) -> Result<Vec<f32>> {
             self.ensure_loaded()?;
-            let mut guard = self
+            // A read lock: the model's session pool handles concurrent encodes
+            let guard = self
                 .model
-                .write()
+                .read()
                 .map_err(|e| CodeSearchError::EmbeddingInference(format!("Lock error: {}", e)))?;
-            let model = guard.as_mut().ok_or_else(|| {
+            let model = guard.as_ref().ok_or_else(|| {
                 CodeSearchError::EmbeddingInference("Model not loaded".to_string())
             })?;
             let prefixed_text = format!("{}{}", prefix, text);
@@ -332,11 +757,11 @@ mod onnx_backend {
             is_query: bool,
         ) -> Result<Vec<Vec<f32>>> {
             self.ensure_loaded()?;
-            let mut guard = self
+            let guard = self
                 .model
-                .write()
+                .read()
                 .map_err(|e| CodeSearchError::EmbeddingInference(format!("Lock error: {}", e)))?;
-            let model = guard.as_mut().ok_or_else(|| {
+            let model = guard.as_ref().ok_or_else(|| {
                 CodeSearchError::EmbeddingInference("Model not loaded".to_string())
             })?;
 
@@ -372,6 +797,22 @@ mod onnx_backend {
                 Err(_) => false,
             }
         }
+
+        pub fn backend_info(&self) -> Result<BackendInfo> {
+            self.ensure_loaded()?;
+            let guard = self
+                .model
+                .read()
+                .map_err(|e| CodeSearchError::EmbeddingModelLoad(format!("Lock error: {}", e)))?;
+            let execution_provider = guard
+                .as_ref()
+                .map(|model| model.execution_provider)
+                .unwrap_or_default();
+            Ok(BackendInfo {
+                backend: "onnx",
+                execution_provider,
+            })
+        }
     }
 
     impl ModelType {
@@ -381,6 +822,7 @@ mod onnx_backend {
                 ModelType::Nomic => "nomic-ai/nomic-embed-text-v1.5",
                 ModelType::Nemotron => "nvidia/llama-nemotron-embed-vl-1b-v2",
                 ModelType::Custom(config) => &config.model_path,
+                ModelType::Remote(config) => &config.endpoint,
             }
         }
     }
@@ -404,11 +846,7 @@ mod fallback_backend {
         }
 
         pub fn get_embedding_with_prefix(&self, text: &str, prefix: &str) -> Result<Vec<f32>> {
-            let prefixed_text = format!("{}{}", prefix, text);
-            Ok(hash_to_embedding(
-                &prefixed_text,
-                self.model_type.dimension(),
-            ))
+            Ok(self.embed_one(text, prefix))
         }
 
         pub fn get_embeddings_batch(
@@ -425,13 +863,24 @@ mod fallback_backend {
 
             Ok(texts
                 .iter()
-                .map(|text| {
-                    let prefixed_text = format!("{}{}", prefix, text);
-                    hash_to_embedding(&prefixed_text, self.model_type.dimension())
-                })
+                .map(|text| self.embed_one(text, prefix))
                 .collect())
         }
 
+        fn embed_one(&self, text: &str, prefix: &str) -> Vec<f32> {
+            let prefixed_text = format!("{}{}", prefix, text);
+            #[cfg(test)]
+            encode_counts::record(&prefixed_text);
+
+            #[cfg(any(test, feature = "canned-embeddings"))]
+            if let Some(embedding) = canned::lookup(text) {
+                return embedding;
+            }
+
+            let embedding = hash_to_embedding(&prefixed_text, self.model_type.native_dimension());
+            truncate_embedding(&embedding, self.model_type.dimension())
+        }
+
         pub fn check_available(&self) -> bool {
             true
         }
@@ -439,6 +888,51 @@ mod fallback_backend {
         pub fn is_loaded(&self) -> bool {
             true
         }
+
+        /// The configured execution provider doesn't apply to hashing
+        pub fn backend_info(&self) -> Result<BackendInfo> {
+            Ok(BackendInfo {
+                backend: "hash",
+                execution_provider: crate::config::ExecutionProvider::Cpu,
+            })
+        }
+    }
+}
+
+/// Fixed embeddings for chosen texts, so ranking tests can work with known
+/// vectors instead of reverse-engineering hash outputs. Only the fallback
+/// backend consults them; a registered text gets the same vector as a
+/// document and as a query, whatever the model.
+#[cfg(all(not(feature = "onnx"), any(test, feature = "canned-embeddings")))]
+pub mod canned {
+    use std::collections::HashMap;
+    use std::sync::{Mutex, OnceLock};
+
+    static CANNED: OnceLock<Mutex<HashMap<String, Vec<f32>>>> = OnceLock::new();
+
+    fn registry() -> std::sync::MutexGuard<'static, HashMap<String, Vec<f32>>> {
+        CANNED
+            .get_or_init(Default::default)
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Make `text` embed as `embedding` until it is cleared
+    pub fn register(text: &str, embedding: Vec<f32>) {
+        registry().insert(text.to_string(), embedding);
+        super::query_cache().retain(|entry| {
+            entry.prefixed_text != format!("{}{}", entry.model_type.query_prefix(), text)
+        });
+    }
+
+    /// Drop every registered embedding
+    pub fn clear() {
+        registry().clear();
+        super::clear_query_cache();
+    }
+
+    pub(super) fn lookup(text: &str) -> Option<Vec<f32>> {
+        registry().get(text).cloned()
     }
 }
 
@@ -463,6 +957,36 @@ fn hash_to_embedding(text: &str, dim: usize) -> Vec<f32> {
     l2_normalize(&embedding)
 }
 
+/// The first `dim` values of `embedding`, re-normalized to unit length; an
+/// embedding no longer than `dim` is returned as is
+pub fn truncate_embedding(embedding: &[f32], dim: usize) -> Vec<f32> {
+    if embedding.len() <= dim {
+        return embedding.to_vec();
+    }
+    l2_normalize(&embedding[..dim])
+}
+
+/// Reject a `model.embedding_dim` that `model_type` can't produce: for
+/// Nomic it may only shorten the native embedding
+pub fn check_embedding_dim(model_type: &ModelType, embedding_dim: Option<usize>) -> Result<()> {
+    let Some(dim) = embedding_dim else {
+        return Ok(());
+    };
+    if dim == 0 {
+        return Err(CodeSearchError::InvalidConfiguration(
+            "model.embedding_dim must be greater than 0".to_string(),
+        ));
+    }
+    if model_type.supports_truncation() && dim > model_type.native_dimension() {
+        return Err(CodeSearchError::InvalidConfiguration(format!(
+            "model.embedding_dim {} is larger than the model's native {} dimensions",
+            dim,
+            model_type.native_dimension()
+        )));
+    }
+    Ok(())
+}
+
 fn l2_normalize(embedding: &[f32]) -> Vec<f32> {
     let norm: f32 = embedding.iter().map(|v| v * v).sum::<f32>().sqrt();
     if norm > 0.0 {
@@ -472,57 +996,403 @@ fn l2_normalize(embedding: &[f32]) -> Vec<f32> {
     }
 }
 
+#[cfg(feature = "remote")]
+mod remote_backend {
+    use super::*;
+    use serde::{Deserialize, Serialize};
+
+    /// Longest one request to the API may take
+    const REQUEST_TIMEOUT: Duration = Duration::from_secs(120);
+
+    #[derive(Serialize)]
+    struct EmbeddingsRequest<'a> {
+        input: &'a [String],
+        #[serde(skip_serializing_if = "Option::is_none")]
+        model: Option<&'a str>,
+    }
+
+    #[derive(Deserialize)]
+    struct EmbeddingsResponse {
+        data: Vec<EmbeddingData>,
+    }
+
+    #[derive(Deserialize)]
+    struct EmbeddingData {
+        embedding: Vec<f32>,
+        /// Position of the input this embeds; servers may answer out of order
+        #[serde(default)]
+        index: Option<usize>,
+    }
+
+    pub struct RemoteEmbedder {
+        config: RemoteModelConfig,
+        agent: ureq::Agent,
+    }
+
+    impl RemoteEmbedder {
+        pub fn new(config: RemoteModelConfig) -> Self {
+            Self {
+                config,
+                agent: ureq::AgentBuilder::new().timeout(REQUEST_TIMEOUT).build(),
+            }
+        }
+
+        /// Nothing is loaded locally; a bad endpoint shows on the first request
+        pub fn ensure_loaded(&self) -> Result<()> {
+            Ok(())
+        }
+
+        pub fn get_embedding_with_prefix(&self, text: &str, prefix: &str) -> Result<Vec<f32>> {
+            let mut embeddings = self.post(&[format!("{}{}", prefix, text)])?;
+            Ok(embeddings.remove(0))
+        }
+
+        pub fn get_embeddings_batch(
+            &self,
+            texts: &[String],
+            batch_size: usize,
+            _is_query: bool,
+        ) -> Result<Vec<Vec<f32>>> {
+            let mut all_embeddings = Vec::with_capacity(texts.len());
+            for chunk in texts.chunks(batch_size.max(1)) {
+                all_embeddings.extend(self.post(chunk)?);
+            }
+            Ok(all_embeddings)
+        }
+
+        /// Embed `texts` in one request. Vectors longer than the configured
+        /// dimension are shortened with [`truncate_embedding`].
+        fn post(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+            let endpoint = &self.config.endpoint;
+            let mut request = self.agent.post(endpoint);
+            if let Ok(key) = std::env::var(REMOTE_API_KEY_ENV) {
+                request = request.set("Authorization", &format!("Bearer {}", key));
+            }
+            let response = request
+                .send_json(EmbeddingsRequest {
+                    input: texts,
+                    model: self.config.model.as_deref(),
+                })
+                .map_err(|e| {
+                    CodeSearchError::EmbeddingInference(match e {
+                        ureq::Error::Status(status, response) => format!(
+                            "{} answered {}: {}",
+                            endpoint,
+                            status,
+                            response.into_string().unwrap_or_default().trim()
+                        ),
+                        e => format!("Request to {} failed: {}", endpoint, e),
+                    })
+                })?;
+            let mut data = response
+                .into_json::<EmbeddingsResponse>()
+                .map_err(|e| {
+                    CodeSearchError::EmbeddingInference(format!(
+                        "Unexpected response from {}: {}",
+                        endpoint, e
+                    ))
+                })?
+                .data;
+
+            if data.len() != texts.len() {
+                return Err(CodeSearchError::EmbeddingInference(format!(
+                    "{} returned {} embeddings for {} inputs",
+                    endpoint,
+                    data.len(),
+                    texts.len()
+                )));
+            }
+            if data.iter().all(|item| item.index.is_some()) {
+                data.sort_by_key(|item| item.index);
+            }
+
+            let dim = self.config.embedding_dim;
+            data.into_iter()
+                .map(|item| {
+                    if item.embedding.len() < dim {
+                        return Err(CodeSearchError::EmbeddingInference(format!(
+                            "{} returned {}-dimensional embeddings, fewer than the {} \
+                             of model.embedding_dim",
+                            endpoint,
+                            item.embedding.len(),
+                            dim
+                        )));
+                    }
+                    Ok(truncate_embedding(&item.embedding, dim))
+                })
+                .collect()
+        }
+
+        pub fn check_available(&self) -> bool {
+            true
+        }
+
+        pub fn is_loaded(&self) -> bool {
+            true
+        }
+
+        /// The execution provider is the server's concern
+        pub fn backend_info(&self) -> Result<BackendInfo> {
+            Ok(BackendInfo {
+                backend: "remote",
+                execution_provider: crate::config::ExecutionProvider::Cpu,
+            })
+        }
+    }
+}
+
+/// Stands in for the HTTP backend in builds without it, failing every call
+#[cfg(not(feature = "remote"))]
+mod remote_backend {
+    use super::*;
+
+    pub struct RemoteEmbedder;
+
+    impl RemoteEmbedder {
+        pub fn new(_config: RemoteModelConfig) -> Self {
+            Self
+        }
+
+        fn unavailable() -> CodeSearchError {
+            CodeSearchError::EmbeddingModelLoad(
+                "the remote model needs code-search built with the `remote` feature".to_string(),
+            )
+        }
+
+        pub fn ensure_loaded(&self) -> Result<()> {
+            Err(Self::unavailable())
+        }
+
+        pub fn get_embedding_with_prefix(&self, _text: &str, _prefix: &str) -> Result<Vec<f32>> {
+            Err(Self::unavailable())
+        }
+
+        pub fn get_embeddings_batch(
+            &self,
+            _texts: &[String],
+            _batch_size: usize,
+            _is_query: bool,
+        ) -> Result<Vec<Vec<f32>>> {
+            Err(Self::unavailable())
+        }
+
+        pub fn check_available(&self) -> bool {
+            false
+        }
+
+        pub fn is_loaded(&self) -> bool {
+            false
+        }
+
+        pub fn backend_info(&self) -> Result<BackendInfo> {
+            Err(Self::unavailable())
+        }
+    }
+}
+
 #[cfg(feature = "onnx")]
 use onnx_backend::GlobalEmbedder;
 
 #[cfg(not(feature = "onnx"))]
 use fallback_backend::GlobalEmbedder;
 
-static MINILM_EMBEDDER: OnceLock<GlobalEmbedder> = OnceLock::new();
-static NOMIC_EMBEDDER: OnceLock<GlobalEmbedder> = OnceLock::new();
-static NEMOTRON_EMBEDDER: OnceLock<GlobalEmbedder> = OnceLock::new();
-static CUSTOM_EMBEDDER: OnceLock<GlobalEmbedder> = OnceLock::new();
+use remote_backend::RemoteEmbedder;
 
-fn get_embedder(model_type: &ModelType) -> &'static GlobalEmbedder {
-    match model_type {
-        ModelType::MiniLM => MINILM_EMBEDDER.get_or_init(|| GlobalEmbedder::new(ModelType::MiniLM)),
-        ModelType::Nomic => NOMIC_EMBEDDER.get_or_init(|| GlobalEmbedder::new(ModelType::Nomic)),
-        ModelType::Nemotron => {
-            NEMOTRON_EMBEDDER.get_or_init(|| GlobalEmbedder::new(ModelType::Nemotron))
+/// What computes a model's embeddings: the build's local backend, or for
+/// the `remote` model an embeddings API
+enum Embedder {
+    Local(GlobalEmbedder),
+    Remote(RemoteEmbedder),
+}
+
+impl Embedder {
+    fn ensure_loaded(&self) -> Result<()> {
+        match self {
+            Embedder::Local(embedder) => embedder.ensure_loaded(),
+            Embedder::Remote(embedder) => embedder.ensure_loaded(),
+        }
+    }
+
+    fn get_embedding_with_prefix(&self, text: &str, prefix: &str) -> Result<Vec<f32>> {
+        match self {
+            Embedder::Local(embedder) => embedder.get_embedding_with_prefix(text, prefix),
+            Embedder::Remote(embedder) => embedder.get_embedding_with_prefix(text, prefix),
+        }
+    }
+
+    fn get_embeddings_batch(
+        &self,
+        texts: &[String],
+        batch_size: usize,
+        is_query: bool,
+    ) -> Result<Vec<Vec<f32>>> {
+        match self {
+            Embedder::Local(embedder) => embedder.get_embeddings_batch(texts, batch_size, is_query),
+            Embedder::Remote(embedder) => {
+                embedder.get_embeddings_batch(texts, batch_size, is_query)
+            }
+        }
+    }
+
+    fn check_available(&self) -> bool {
+        match self {
+            Embedder::Local(embedder) => embedder.check_available(),
+            Embedder::Remote(embedder) => embedder.check_available(),
+        }
+    }
+
+    fn is_loaded(&self) -> bool {
+        match self {
+            Embedder::Local(embedder) => embedder.is_loaded(),
+            Embedder::Remote(embedder) => embedder.is_loaded(),
+        }
+    }
+
+    fn backend_info(&self) -> Result<BackendInfo> {
+        match self {
+            Embedder::Local(embedder) => embedder.backend_info(),
+            Embedder::Remote(embedder) => embedder.backend_info(),
         }
+    }
+}
+
+/// How often the fallback backend has embedded each prefixed text, so tests
+/// can tell cache hits from fresh embeddings
+#[cfg(all(test, not(feature = "onnx")))]
+mod encode_counts {
+    use std::collections::HashMap;
+    use std::sync::{Mutex, PoisonError};
+
+    static COUNTS: Mutex<Option<HashMap<String, usize>>> = Mutex::new(None);
+
+    pub fn record(prefixed_text: &str) {
+        let mut counts = COUNTS.lock().unwrap_or_else(PoisonError::into_inner);
+        *counts
+            .get_or_insert_with(HashMap::new)
+            .entry(prefixed_text.to_string())
+            .or_default() += 1;
+    }
+
+    pub fn get(prefixed_text: &str) -> usize {
+        let counts = COUNTS.lock().unwrap_or_else(PoisonError::into_inner);
+        counts
+            .as_ref()
+            .and_then(|counts| counts.get(prefixed_text).copied())
+            .unwrap_or(0)
+    }
+}
+
+static MINILM_EMBEDDER: OnceLock<Embedder> = OnceLock::new();
+static NOMIC_EMBEDDER: OnceLock<Embedder> = OnceLock::new();
+static NEMOTRON_EMBEDDER: OnceLock<Embedder> = OnceLock::new();
+static CUSTOM_EMBEDDER: OnceLock<Embedder> = OnceLock::new();
+static REMOTE_EMBEDDER: OnceLock<Embedder> = OnceLock::new();
+
+fn get_embedder(model_type: &ModelType) -> &'static Embedder {
+    let local = |model_type: ModelType| Embedder::Local(GlobalEmbedder::new(model_type));
+    match model_type {
+        ModelType::MiniLM => MINILM_EMBEDDER.get_or_init(|| local(ModelType::MiniLM)),
+        ModelType::Nomic => NOMIC_EMBEDDER.get_or_init(|| local(ModelType::Nomic)),
+        ModelType::Nemotron => NEMOTRON_EMBEDDER.get_or_init(|| local(ModelType::Nemotron)),
         ModelType::Custom(config) => {
-            CUSTOM_EMBEDDER.get_or_init(|| GlobalEmbedder::new(ModelType::Custom(config.clone())))
+            CUSTOM_EMBEDDER.get_or_init(|| local(ModelType::Custom(config.clone())))
+        }
+        ModelType::Remote(config) => {
+            REMOTE_EMBEDDER.get_or_init(|| Embedder::Remote(RemoteEmbedder::new(config.clone())))
         }
     }
 }
 
+/// Which backend computes a model's embeddings, and on what hardware
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BackendInfo {
+    /// `onnx`, `hash` for the deterministic fallback built without it, or
+    /// `remote` for an embeddings API
+    pub backend: &'static str,
+    /// Provider in use, which is the CPU when the configured one failed
+    pub execution_provider: crate::config::ExecutionProvider,
+}
+
+/// A model resolved once for reuse: its name is parsed and its embedder
+/// looked up when the handle is made, not on every call. `Send + Sync`, so
+/// parallel workers can share one by reference.
 #[derive(Clone)]
 pub struct EmbeddingModel {
+    name: String,
     model_type: ModelType,
+    embedder: &'static Embedder,
 }
 
 impl EmbeddingModel {
+    /// Resolve and load `model_name`, or the default model for `None`
     pub fn new(model_name: Option<&str>) -> Result<Self> {
-        let model_type = ModelType::parse(model_name.unwrap_or(DEFAULT_MODEL));
-        let embedder = get_embedder(&model_type);
-        embedder.ensure_loaded()?;
-        Ok(Self { model_type })
+        let model = Self::resolve(model_name.unwrap_or(DEFAULT_MODEL));
+        check_embedding_dim(&model.model_type, get_config().model.embedding_dim)?;
+        model.embedder.ensure_loaded()?;
+        Ok(model)
+    }
+
+    /// Resolve `model_name` without loading it; the first embedding loads
+    /// it, and a load failure comes back from that call
+    pub fn resolve(model_name: &str) -> Self {
+        let model_type = ModelType::parse(model_name);
+        Self {
+            name: model_name.to_string(),
+            embedder: get_embedder(&model_type),
+            model_type,
+        }
+    }
+
+    /// The name this model was resolved from
+    pub fn name(&self) -> &str {
+        &self.name
     }
 
+    pub fn model_type(&self) -> &ModelType {
+        &self.model_type
+    }
+
+    /// Embed `text` as a document, with the model's document prefix
     pub fn embed(&self, text: &str) -> Result<Vec<f32>> {
-        let embedder = get_embedder(&self.model_type);
-        embedder.get_embedding_with_prefix(text, self.model_type.document_prefix())
+        self.embedder
+            .get_embedding_with_prefix(text, self.model_type.document_prefix())
+    }
+
+    /// Embed `text` as a search query, with the model's query prefix
+    pub fn embed_query(&self, text: &str) -> Result<Vec<f32>> {
+        cached_query_embedding(&self.model_type, text)
     }
 
+    /// Embed `texts` as documents, like [`EmbeddingModel::embed`]
     pub fn embed_batch(&self, texts: &[&str]) -> Result<Vec<Vec<f32>>> {
-        let embedder = get_embedder(&self.model_type);
+        self.embed_texts(texts, false)
+    }
+
+    /// Embed `texts` as search queries, like [`EmbeddingModel::embed_query`]
+    pub fn embed_query_batch(&self, texts: &[&str]) -> Result<Vec<Vec<f32>>> {
+        self.embed_texts(texts, true)
+    }
+
+    /// Embed `texts` as documents, at most `batch_size` per model call
+    pub fn embed_documents(&self, texts: &[String], batch_size: usize) -> Result<Vec<Vec<f32>>> {
+        self.embedder
+            .get_embeddings_batch(texts, batch_size.max(1), false)
+    }
+
+    fn embed_texts(&self, texts: &[&str], is_query: bool) -> Result<Vec<Vec<f32>>> {
         let texts: Vec<String> = texts.iter().map(|s| s.to_string()).collect();
-        embedder.get_embeddings_batch(&texts, texts.len(), false)
+        self.embedder
+            .get_embeddings_batch(&texts, texts.len().max(1), is_query)
     }
 
     pub fn embedding_dimension(&self) -> usize {
         self.model_type.dimension()
     }
+
+    /// The backend and execution provider this model runs on
+    pub fn backend_info(&self) -> Result<BackendInfo> {
+        self.embedder.backend_info()
+    }
 }
 
 pub fn get_embedding(text: &str) -> Vec<f32> {
@@ -544,11 +1414,7 @@ pub fn get_query_embedding(text: &str) -> Vec<f32> {
 
 pub fn get_query_embedding_with_model(text: &str, model: &str) -> Vec<f32> {
     let model_type = ModelType::parse(model);
-    let embedder = get_embedder(&model_type);
-    let prefix = model_type.query_prefix();
-    embedder
-        .get_embedding_with_prefix(text, prefix)
-        .unwrap_or_else(|_| vec![0.0; model_type.dimension()])
+    cached_query_embedding(&model_type, text).unwrap_or_else(|_| vec![0.0; model_type.dimension()])
 }
 
 pub fn get_embeddings_batch(texts: &[String], batch_size: usize, is_query: bool) -> Vec<Vec<f32>> {
@@ -589,6 +1455,7 @@ pub fn ensure_model_available() -> Result<()> {
 
 pub fn ensure_model_available_with_model(model: &str) -> Result<()> {
     let model_type = ModelType::parse(model);
+    check_embedding_dim(&model_type, get_config().model.embedding_dim)?;
     let embedder = get_embedder(&model_type);
     embedder.ensure_loaded()
 }
@@ -636,6 +1503,7 @@ mod tests {
 
     #[test]
     fn test_model_dimensions() {
+        let _config = crate::test_support::read_config();
         assert_eq!(ModelType::MiniLM.dimension(), 384);
         assert_eq!(ModelType::Nomic.dimension(), 768);
         assert_eq!(ModelType::Nemotron.dimension(), 2048);
@@ -659,6 +1527,22 @@ mod tests {
         assert!((normalized[1] - 0.8).abs() < 1e-6);
     }
 
+    #[test]
+    fn test_truncate_embedding_keeps_renormalized_prefix() {
+        let full = l2_normalize(&[3.0, 4.0, 12.0]);
+        let truncated = truncate_embedding(&full, 2);
+        assert_eq!(truncated.len(), 2);
+        assert!((truncated[0] - 0.6).abs() < 1e-6);
+        assert!((truncated[1] - 0.8).abs() < 1e-6);
+        assert_eq!(truncate_embedding(&full, 3), full);
+        assert_eq!(truncate_embedding(&full, 8), full);
+
+        assert!(check_embedding_dim(&ModelType::Nomic, Some(512)).is_ok());
+        assert!(check_embedding_dim(&ModelType::Nomic, Some(769)).is_err());
+        assert!(check_embedding_dim(&ModelType::Nomic, Some(0)).is_err());
+        assert!(check_embedding_dim(&ModelType::MiniLM, None).is_ok());
+    }
+
     #[test]
     fn test_l2_normalize_zero_vector() {
         let embedding = vec![0.0, 0.0, 0.0];
@@ -675,6 +1559,7 @@ mod tests {
 
     #[test]
     fn test_get_model_dimension() {
+        let _config = crate::test_support::read_config();
         assert_eq!(get_model_dimension("minilm"), 384);
         assert_eq!(get_model_dimension("nomic"), 768);
         assert_eq!(get_model_dimension("nemotron"), 2048);
@@ -705,10 +1590,446 @@ mod tests {
 
     #[test]
     fn test_fallback_embedding() {
+        let _config = crate::test_support::read_config();
         let emb = get_embedding("test query");
         assert_eq!(emb.len(), 384);
 
         let query_emb = get_query_embedding("test query");
         assert_eq!(query_emb.len(), 384);
     }
+
+    #[test]
+    fn test_nomic_query_and_document_embeddings_differ() {
+        let _config = crate::test_support::read_config();
+        let model = EmbeddingModel::new(Some("nomic")).unwrap();
+        let text = "parse the config file";
+
+        let document = model.embed(text).unwrap();
+        let query = model.embed_query(text).unwrap();
+        assert_eq!(query.len(), ModelType::Nomic.dimension());
+        assert_ne!(query, document);
+
+        let queries = model.embed_query_batch(&[text]).unwrap();
+        let documents = model.embed_batch(&[text]).unwrap();
+        assert_eq!(queries, vec![query]);
+        assert_eq!(documents, vec![document]);
+    }
+
+    #[test]
+    #[cfg(not(feature = "onnx"))]
+    fn test_repeated_query_embedding_hits_the_cache() {
+        let _config = crate::test_support::read_config();
+        let query = "where is the query cache evicted";
+        let prefixed = format!("{}{}", ModelType::Nomic.query_prefix(), query);
+
+        let first = get_query_embedding_with_model(query, "nomic");
+        let second = get_query_embedding_with_model(query, "nomic");
+        assert_eq!(first, second);
+        assert_eq!(encode_counts::get(&prefixed), 1);
+
+        clear_query_cache();
+        assert_eq!(get_query_embedding_with_model(query, "nomic"), first);
+        assert_eq!(encode_counts::get(&prefixed), 2);
+    }
+
+    #[test]
+    fn test_one_embedding_model_embeds_a_batch_across_threads() {
+        use rayon::prelude::*;
+
+        let model = EmbeddingModel::new(Some("minilm")).unwrap();
+        let texts: Vec<String> = (0..10)
+            .map(|i| format!("fn reused_model_{}() {{}}", i))
+            .collect();
+
+        let batched = model.embed_documents(&texts, 4).unwrap();
+        assert_eq!(batched.len(), texts.len());
+        assert!(batched
+            .iter()
+            .all(|e| e.len() == model.embedding_dimension()));
+
+        let one_by_one: Vec<Vec<f32>> = texts
+            .par_iter()
+            .map(|text| model.embed(text).unwrap())
+            .collect();
+        // Padding within a batch may shift the ONNX outputs slightly
+        for (batch, single) in batched.iter().zip(&one_by_one) {
+            assert!(batch.iter().zip(single).all(|(a, b)| (a - b).abs() < 1e-4));
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "onnx")]
+    fn test_session_layout_keeps_threads_per_session() {
+        use super::onnx_backend::session_layout;
+
+        // Automatic: a few sessions, each still multi-threaded
+        assert_eq!(session_layout(0, 16), (4, 4));
+        assert_eq!(session_layout(0, 8), (2, 4));
+        assert_eq!(session_layout(0, 2), (1, 2));
+        assert_eq!(session_layout(0, 0), (1, 1));
+        // An explicit count splits the cores between its sessions
+        assert_eq!(session_layout(16, 16), (16, 1));
+        assert_eq!(session_layout(3, 8), (3, 2));
+    }
+
+    #[test]
+    #[cfg(not(feature = "onnx"))]
+    fn test_fallback_backend_info_ignores_execution_provider() {
+        use crate::config::ExecutionProvider;
+
+        let _config = crate::test_support::read_config();
+        let model = EmbeddingModel::new(None).unwrap();
+        let info = model.backend_info().unwrap();
+        assert_eq!(info.backend, "hash");
+        assert_eq!(info.execution_provider, ExecutionProvider::Cpu);
+        assert_eq!("CoreML".parse(), Ok(ExecutionProvider::CoreMl));
+        assert!("tpu".parse::<ExecutionProvider>().is_err());
+    }
+
+    #[test]
+    #[cfg(not(feature = "remote"))]
+    fn test_remote_model_needs_remote_feature() {
+        let embedder = get_embedder(&ModelType::Remote(RemoteModelConfig {
+            endpoint: "http://localhost:8080/v1/embeddings".to_string(),
+            model: None,
+            embedding_dim: 1536,
+        }));
+        let err = embedder.ensure_loaded().unwrap_err().to_string();
+        assert!(err.contains("`remote` feature"), "{}", err);
+        assert!(embedder.get_embedding_with_prefix("text", "").is_err());
+        assert!(!embedder.check_available());
+    }
+
+    /// Fails the first `failures` fetches, then succeeds
+    struct FlakyFetcher {
+        failures: u32,
+        attempts: std::cell::Cell<u32>,
+    }
+
+    impl ModelFileFetcher for FlakyFetcher {
+        fn fetch(&self, file: &str) -> std::result::Result<PathBuf, String> {
+            self.attempts.set(self.attempts.get() + 1);
+            if self.attempts.get() <= self.failures {
+                Err(format!("connection reset ({})", self.attempts.get()))
+            } else {
+                Ok(PathBuf::from(file))
+            }
+        }
+    }
+
+    fn policy(retries: u32) -> RetryPolicy {
+        RetryPolicy {
+            retries,
+            initial_backoff: Duration::ZERO,
+            deadline: None,
+        }
+    }
+
+    #[test]
+    fn test_pool_runs_checkouts_concurrently() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Barrier;
+
+        // All four threads hold a value at once: each waits at the barrier
+        // while holding one, so this only finishes if none are serialized
+        let pool = Pool::with_first(0usize, 4);
+        let barrier = Barrier::new(4);
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    let _value = pool.get(|| Ok(0)).unwrap();
+                    barrier.wait();
+                });
+            }
+        });
+        assert_eq!(pool.created(), 4);
+
+        // Beyond the limit, threads wait for a returned value
+        let pool = Pool::with_first(0usize, 2);
+        let active = AtomicUsize::new(0);
+        let peak = AtomicUsize::new(0);
+        std::thread::scope(|scope| {
+            for _ in 0..8 {
+                scope.spawn(|| {
+                    let mut value = pool.get(|| Ok(0)).unwrap();
+                    let now = active.fetch_add(1, Ordering::SeqCst) + 1;
+                    peak.fetch_max(now, Ordering::SeqCst);
+                    *value += 1;
+                    std::thread::sleep(Duration::from_millis(5));
+                    active.fetch_sub(1, Ordering::SeqCst);
+                });
+            }
+        });
+        assert!(peak.load(Ordering::SeqCst) <= 2);
+        assert_eq!(pool.created(), 2);
+
+        // A failed create frees its slot
+        let pool = Pool::with_first(0usize, 2);
+        let _held = pool.get(|| Ok(0)).unwrap();
+        assert!(pool
+            .get(|| Err(CodeSearchError::Other("no".to_string())))
+            .is_err());
+        assert_eq!(pool.created(), 1);
+        assert!(pool.get(|| Ok(1)).is_ok());
+    }
+
+    #[test]
+    fn test_fetch_retries_configured_times_before_failing() {
+        let fetcher = FlakyFetcher {
+            failures: u32::MAX,
+            attempts: Default::default(),
+        };
+        let err = fetch_with_retry(&fetcher, "tokenizer.json", &policy(3)).unwrap_err();
+        assert_eq!(fetcher.attempts.get(), 4);
+        let message = err.to_string();
+        assert!(message.contains("tokenizer.json after 4 attempt(s)"));
+        assert!(message.contains("connection reset (4)"));
+
+        // A transient failure within the budget recovers
+        let fetcher = FlakyFetcher {
+            failures: 2,
+            attempts: Default::default(),
+        };
+        let path = fetch_with_retry(&fetcher, "onnx/model.onnx", &policy(3)).unwrap();
+        assert_eq!(path, PathBuf::from("onnx/model.onnx"));
+        assert_eq!(fetcher.attempts.get(), 3);
+    }
+
+    #[test]
+    fn test_fetch_stops_at_load_deadline() {
+        let fetcher = FlakyFetcher {
+            failures: u32::MAX,
+            attempts: Default::default(),
+        };
+        let policy = RetryPolicy {
+            retries: 10,
+            initial_backoff: Duration::from_secs(60),
+            deadline: Some(Instant::now() + Duration::from_secs(1)),
+        };
+        let err = fetch_with_retry(&fetcher, "onnx/model.onnx", &policy).unwrap_err();
+        assert_eq!(fetcher.attempts.get(), 1);
+        assert!(err.to_string().contains("Timed out"));
+    }
+
+    fn dot(a: &[f32], b: &[f32]) -> f32 {
+        a.iter().zip(b).map(|(x, y)| x * y).sum()
+    }
+
+    #[test]
+    fn test_truncated_nomic_index_and_query_score_consistently() {
+        use crate::config::Config;
+        use crate::database::{init_db_at, insert_chunks, vector_search, Chunk};
+
+        let doc_text = "fn open_session(user: &User) -> Session {}";
+        let query_text = "open a user session";
+
+        let mut config = Config::default();
+        config.model.model_type = "nomic".to_string();
+        config.model.embedding_dim = Some(256);
+        let guard = crate::test_support::override_config(config.clone());
+
+        let model = EmbeddingModel::new(Some("nomic")).unwrap();
+        assert_eq!(get_model_dimension("nomic"), 256);
+        let doc = model.embed(doc_text).unwrap();
+        let query = model.embed_query(query_text).unwrap();
+        assert_eq!(doc.len(), 256);
+        assert_eq!(query.len(), 256);
+
+        let dir = tempfile::tempdir().unwrap();
+        let conn = init_db_at(&dir.path().join("index.db")).unwrap();
+        insert_chunks(
+            &conn,
+            &[Chunk {
+                id: None,
+                codebase_id: "matryoshka".to_string(),
+                file_path: "src/session.rs".to_string(),
+                start_line: 1,
+                end_line: 1,
+                content: doc_text.to_string(),
+                language: Some("rust".to_string()),
+                embedding: doc.clone(),
+                hash: "000000000000beef".to_string(),
+                chunk_kind: Some("code".to_string()),
+                author: None,
+            }],
+        )
+        .unwrap();
+        let stored: i64 = conn
+            .query_row("SELECT length(embedding) FROM chunks", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(stored, 256 * 4);
+
+        let results = vector_search(&conn, Some("matryoshka"), &query, 1).unwrap();
+        assert!((results[0].score - dot(&doc, &query) as f64).abs() < 1e-5);
+
+        // The same vectors as cutting the full-length embeddings down
+        guard.set(Config::default());
+        let full_doc = model.embed(doc_text).unwrap();
+        let full_query = model.embed_query(query_text).unwrap();
+        assert_eq!(full_doc.len(), 768);
+        assert_eq!(full_query.len(), 768);
+        let expected = dot(
+            &truncate_embedding(&full_doc, 256),
+            &truncate_embedding(&full_query, 256),
+        );
+        assert!((results[0].score - expected as f64).abs() < 1e-5);
+
+        config.model.embedding_dim = Some(1024);
+        guard.set(config);
+        assert!(EmbeddingModel::new(Some("nomic")).is_err());
+    }
+
+    /// The remote embedder is created once per process, so this is the only
+    /// test that loads one
+    #[cfg(feature = "remote")]
+    mod remote {
+        use super::*;
+        use crate::config::Config;
+        use serde_json::{json, Value};
+        use std::io::{BufRead, BufReader, Read, Write};
+        use std::net::TcpListener;
+        use std::sync::{Arc, Mutex};
+
+        /// What the mock server embeds `text` as: four values, one more than the
+        /// configured dimension
+        fn mock_embedding(text: &str) -> Vec<f32> {
+            vec![text.len() as f32, 1.0, 2.0, 3.0]
+        }
+
+        /// A request the mock server received: its Authorization header and body
+        type Received = Arc<Mutex<Vec<(Option<String>, Value)>>>;
+
+        /// Serve an OpenAI-style `/v1/embeddings` API on a free port, answering
+        /// out of order with each item's `index`. The input `fail` gets a 500 and
+        /// `short` a vector shorter than asked for.
+        fn start_mock_server() -> (String, Received) {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let endpoint = format!("http://{}/v1/embeddings", listener.local_addr().unwrap());
+            let received: Received = Arc::default();
+            let log = Arc::clone(&received);
+
+            std::thread::spawn(move || {
+                for stream in listener.incoming() {
+                    let mut stream = stream.unwrap();
+                    let mut reader = BufReader::new(stream.try_clone().unwrap());
+                    let mut content_length = 0;
+                    let mut authorization = None;
+                    loop {
+                        let mut line = String::new();
+                        reader.read_line(&mut line).unwrap();
+                        let line = line.trim_end();
+                        if line.is_empty() {
+                            break;
+                        }
+                        if let Some((name, value)) = line.split_once(": ") {
+                            match name.to_ascii_lowercase().as_str() {
+                                "content-length" => content_length = value.parse().unwrap(),
+                                "authorization" => authorization = Some(value.to_string()),
+                                _ => {}
+                            }
+                        }
+                    }
+                    let mut body = vec![0; content_length];
+                    reader.read_exact(&mut body).unwrap();
+                    let request: Value = serde_json::from_slice(&body).unwrap();
+                    log.lock().unwrap().push((authorization, request.clone()));
+
+                    let inputs: Vec<&str> = request["input"]
+                        .as_array()
+                        .unwrap()
+                        .iter()
+                        .map(|input| input.as_str().unwrap())
+                        .collect();
+                    let (status, response) = if inputs.contains(&"fail") {
+                        (
+                            "500 Internal Server Error",
+                            json!({"error": "model overloaded"}),
+                        )
+                    } else {
+                        let data: Vec<Value> = inputs
+                            .iter()
+                            .enumerate()
+                            .rev()
+                            .map(|(index, text)| {
+                                let mut embedding = mock_embedding(text);
+                                if *text == "short" {
+                                    embedding.truncate(2);
+                                }
+                                json!({"object": "embedding", "index": index, "embedding": embedding})
+                            })
+                            .collect();
+                        ("200 OK", json!({"object": "list", "data": data}))
+                    };
+                    let response = response.to_string();
+                    write!(
+                        stream,
+                        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\
+                         Connection: close\r\n\r\n{}",
+                        status,
+                        response.len(),
+                        response
+                    )
+                    .unwrap();
+                }
+            });
+
+            (endpoint, received)
+        }
+
+        #[test]
+        fn test_remote_model_embeds_through_openai_style_api() {
+            let (endpoint, received) = start_mock_server();
+            let mut config = Config::default();
+            config.model.model_type = "remote".to_string();
+            config.model.endpoint = Some(endpoint);
+            config.model.remote_model = Some("mock-embed".to_string());
+            config.model.embedding_dim = Some(3);
+            let _config = crate::test_support::override_config(config);
+            std::env::set_var(REMOTE_API_KEY_ENV, "secret-key");
+
+            let model = EmbeddingModel::new(Some("remote")).unwrap();
+            assert_eq!(model.backend_info().unwrap().backend, "remote");
+            assert_eq!(model.embedding_dimension(), 3);
+
+            // Five texts at two per request, each answer put back in input order
+            let texts: Vec<String> = ["a", "bb", "ccc", "dddd", "eeeee"]
+                .iter()
+                .map(|text| text.to_string())
+                .collect();
+            let embeddings = model.embed_documents(&texts, 2).unwrap();
+            let expected: Vec<Vec<f32>> = texts
+                .iter()
+                .map(|text| truncate_embedding(&mock_embedding(text), 3))
+                .collect();
+            assert_eq!(embeddings, expected);
+
+            {
+                let received = received.lock().unwrap();
+                let batch_sizes: Vec<usize> = received
+                    .iter()
+                    .map(|(_, request)| request["input"].as_array().unwrap().len())
+                    .collect();
+                assert_eq!(batch_sizes, vec![2, 2, 1]);
+                for (authorization, request) in received.iter() {
+                    assert_eq!(authorization.as_deref(), Some("Bearer secret-key"));
+                    assert_eq!(request["model"], "mock-embed");
+                }
+            }
+
+            let query = model.embed_query("find the parser").unwrap();
+            assert_eq!(
+                query,
+                truncate_embedding(&mock_embedding("find the parser"), 3)
+            );
+
+            let err = model.embed("fail").unwrap_err().to_string();
+            assert!(
+                err.contains("500") && err.contains("model overloaded"),
+                "{}",
+                err
+            );
+            let err = model.embed("short").unwrap_err().to_string();
+            assert!(err.contains("fewer than the 3"), "{}", err);
+        }
+    }
 }
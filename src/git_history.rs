@@ -0,0 +1,403 @@
+//! Commit-message ingestion (`git` feature).
+//!
+//! Recent commits of the repository around a codebase are read with the
+//! `git` command line and stored as chunks of kind `commit`, one per commit,
+//! under the pseudo path `commit:<hash>`. The files each commit touched are
+//! recorded in the `commit_files` table so a hit can be tied back to code.
+
+use crate::database::{delete_chunks_for_prefix, insert_chunks, replace_commit_links, Chunk};
+use crate::embedding::get_embeddings_batch_with_model;
+use crate::error::{CodeSearchError, Result};
+use rusqlite::Connection;
+use std::path::Path;
+use std::process::Command;
+
+/// File path prefix of commit-message chunks
+pub const COMMIT_PATH_PREFIX: &str = "commit:";
+
+// Separators passed to `git log --format`; neither occurs in commit text
+const RECORD_SEPARATOR: char = '\u{1e}';
+const FIELD_SEPARATOR: char = '\u{1f}';
+
+/// A commit read from `git log`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Commit {
+    pub hash: String,
+    pub author: String,
+    /// Commit time, seconds since the Unix epoch
+    pub timestamp: i64,
+    pub message: String,
+    /// Touched files, relative to the codebase root
+    pub files: Vec<String>,
+}
+
+impl Commit {
+    /// Text embedded and indexed for the commit
+    fn chunk_content(&self) -> String {
+        format!(
+            "{}\n\nAuthor: {}\nFiles: {}",
+            self.message,
+            self.author,
+            self.files.join(", ")
+        )
+    }
+}
+
+/// Read the newest commits touching `codebase_path`, newest first. At most
+/// `max_commits` are returned, and none older than `since` (any date `git
+/// log --since` accepts). Merge commits are left out.
+pub fn read_commits(
+    codebase_path: &Path,
+    max_commits: Option<usize>,
+    since: Option<&str>,
+) -> Result<Vec<Commit>> {
+    let mut command = Command::new("git");
+    command.arg("-C").arg(codebase_path).args([
+        "log",
+        "--no-merges",
+        "--relative",
+        "--name-only",
+        "--format=%x1e%H%x1f%an%x1f%at%x1f%B%x1f",
+    ]);
+    if let Some(n) = max_commits {
+        command.arg(format!("--max-count={}", n));
+    }
+    if let Some(since) = since {
+        command.arg(format!("--since={}", since));
+    }
+    command.args(["--", "."]);
+
+    let output = command
+        .output()
+        .map_err(|e| CodeSearchError::Git(format!("failed to run git: {}", e)))?;
+    if !output.status.success() {
+        return Err(CodeSearchError::Git(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+
+    Ok(parse_log(&String::from_utf8_lossy(&output.stdout)))
+}
+
+fn parse_log(log: &str) -> Vec<Commit> {
+    log.split(RECORD_SEPARATOR)
+        .filter_map(|record| {
+            let mut fields = record.splitn(5, FIELD_SEPARATOR);
+            let hash = fields.next()?.trim().to_string();
+            let author = fields.next()?.to_string();
+            let timestamp = fields.next()?.trim().parse().ok()?;
+            let message = fields.next()?.trim().to_string();
+            let files = fields
+                .next()
+                .unwrap_or("")
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty())
+                .map(str::to_string)
+                .collect();
+            Some(Commit {
+                hash,
+                author,
+                timestamp,
+                message,
+                files,
+            })
+        })
+        .collect()
+}
+
+/// Replace the commit-message chunks of a codebase with its recent history
+/// (bounded by `max_commits` and `since`, see [`read_commits`]), embedded
+/// with `model`. Returns the number of commits indexed.
+pub fn index_commits(
+    conn: &Connection,
+    codebase_path: &Path,
+    codebase_id: &str,
+    model: &str,
+    max_commits: Option<usize>,
+    since: Option<&str>,
+) -> Result<usize> {
+    let commits = read_commits(codebase_path, max_commits, since)?;
+
+    delete_chunks_for_prefix(conn, codebase_id, COMMIT_PATH_PREFIX)?;
+
+    let contents: Vec<String> = commits.iter().map(Commit::chunk_content).collect();
+    let embeddings = get_embeddings_batch_with_model(
+        &contents,
+        crate::config::get_config().batch_size(),
+        false,
+        model,
+    );
+    let chunks: Vec<Chunk> = commits
+        .iter()
+        .zip(contents)
+        .zip(embeddings)
+        .map(|((commit, content), embedding)| Chunk {
+            id: None,
+            codebase_id: codebase_id.to_string(),
+            file_path: format!("{}{}", COMMIT_PATH_PREFIX, commit.hash),
+            start_line: 1,
+            end_line: content.lines().count() as i64,
+            content,
+            language: Some("commit".to_string()),
+            embedding,
+            hash: commit.hash.chars().take(16).collect(),
+            chunk_kind: Some("commit".to_string()),
+            author: None,
+        })
+        .collect();
+    insert_chunks(conn, &chunks)?;
+
+    let links: Vec<(&str, i64, &[String])> = commits
+        .iter()
+        .map(|c| (c.hash.as_str(), c.timestamp, c.files.as_slice()))
+        .collect();
+    replace_commit_links(conn, codebase_id, &links)?;
+
+    Ok(commits.len())
+}
+
+/// The author of each line of `file_path`, in line order, from `git blame`.
+/// Lines not committed yet have no author. Fails when the file isn't
+/// tracked in a git repository.
+pub fn blame_line_authors(file_path: &Path) -> Result<Vec<Option<String>>> {
+    let dir = file_path.parent().unwrap_or(Path::new("."));
+    let name = file_path.file_name().unwrap_or(file_path.as_os_str());
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["blame", "--line-porcelain", "--"])
+        .arg(name)
+        .output()
+        .map_err(|e| CodeSearchError::Git(format!("failed to run git: {}", e)))?;
+    if !output.status.success() {
+        return Err(CodeSearchError::Git(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+
+    Ok(parse_blame(&String::from_utf8_lossy(&output.stdout)))
+}
+
+fn parse_blame(porcelain: &str) -> Vec<Option<String>> {
+    let mut authors = Vec::new();
+    let mut author = None;
+    for line in porcelain.lines() {
+        if let Some(name) = line.strip_prefix("author ") {
+            author = (name != "Not Committed Yet").then(|| name.to_string());
+        } else if line.starts_with('\t') {
+            authors.push(author.take());
+        }
+    }
+    authors
+}
+
+/// The author of most of lines `start..=end` (1-based) in `line_authors`,
+/// the one with the earliest line on a tie
+pub fn majority_author(
+    line_authors: &[Option<String>],
+    start: usize,
+    end: usize,
+) -> Option<String> {
+    let lines = line_authors.get(start.max(1) - 1..end.min(line_authors.len()))?;
+    let mut counts: Vec<(&str, usize)> = Vec::new();
+    for author in lines.iter().flatten() {
+        match counts.iter_mut().find(|(name, _)| *name == author) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((author, 1)),
+        }
+    }
+    // max_by_key keeps the last of equal maxima, so walk in reverse
+    counts
+        .into_iter()
+        .rev()
+        .max_by_key(|(_, count)| *count)
+        .map(|(name, _)| name.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::{
+        commits_touching_file, files_touched_by_commit, fts_search, init_db, SearchFilters,
+    };
+    use crate::indexing::{Indexer, IndexingOptions};
+    use crate::manifest::get_codebase_hash;
+    use std::fs;
+
+    fn git(dir: &Path, args: &[&str]) -> String {
+        let output = Command::new("git")
+            .arg("-C")
+            .arg(dir)
+            .args(["-c", "user.name=Test", "-c", "user.email=test@example.com"])
+            .args(args)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "git {:?} failed", args);
+        String::from_utf8(output.stdout).unwrap().trim().to_string()
+    }
+
+    #[test]
+    fn test_commit_messages_searchable_and_linked() {
+        let _data_dir = crate::test_support::use_temp_data_dir();
+
+        let dir = tempfile::tempdir().unwrap();
+        git(dir.path(), &["init", "-q"]);
+        fs::write(dir.path().join("upload.rs"), "fn upload() {}\n").unwrap();
+        git(dir.path(), &["add", "."]);
+        git(
+            dir.path(),
+            &["commit", "-q", "-m", "Retry flaky uploads with backoff"],
+        );
+        let first = git(dir.path(), &["rev-parse", "HEAD"]);
+        fs::write(dir.path().join("cache.rs"), "fn evict() {}\n").unwrap();
+        git(dir.path(), &["add", "."]);
+        git(
+            dir.path(),
+            &["commit", "-q", "-m", "Document the cache eviction policy"],
+        );
+        let second = git(dir.path(), &["rev-parse", "HEAD"]);
+
+        let stats = Indexer::new(IndexingOptions {
+            commit_limit: Some(10),
+            ..Default::default()
+        })
+        .index_codebase(dir.path())
+        .unwrap();
+        assert_eq!(stats.commits_indexed, 2);
+
+        let codebase_id = get_codebase_hash(&dir.path().canonicalize().unwrap());
+        let conn = init_db().unwrap();
+        let commit_kind = SearchFilters {
+            kind: Some("commit".to_string()),
+            ..Default::default()
+        };
+        let hits = fts_search(&conn, "eviction", Some(&codebase_id), 5, &commit_kind).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].file_path, format!("commit:{}", second));
+        assert_eq!(
+            files_touched_by_commit(&conn, &codebase_id, &second).unwrap(),
+            vec!["cache.rs"]
+        );
+        assert_eq!(
+            commits_touching_file(&conn, &codebase_id, "upload.rs").unwrap(),
+            vec![first.clone()]
+        );
+
+        // A smaller limit replaces the ingested history with the newest commits
+        let stats = Indexer::new(IndexingOptions {
+            commit_limit: Some(1),
+            ..Default::default()
+        })
+        .index_codebase(dir.path())
+        .unwrap();
+        assert_eq!(stats.commits_indexed, 1);
+        assert!(
+            fts_search(&conn, "uploads", Some(&codebase_id), 5, &commit_kind)
+                .unwrap()
+                .is_empty()
+        );
+        assert!(commits_touching_file(&conn, &codebase_id, "upload.rs")
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_parse_log_splits_message_and_files() {
+        let log = "\u{1e}abc123\u{1f}Ann\u{1f}1700000000\u{1f}Fix parser\n\nLonger body\n\u{1f}\n\nsrc/a.rs\nsrc/b.rs\n";
+        let commits = parse_log(log);
+        assert_eq!(commits.len(), 1);
+        assert_eq!(commits[0].hash, "abc123");
+        assert_eq!(commits[0].author, "Ann");
+        assert_eq!(commits[0].timestamp, 1_700_000_000);
+        assert_eq!(commits[0].message, "Fix parser\n\nLonger body");
+        assert_eq!(commits[0].files, vec!["src/a.rs", "src/b.rs"]);
+    }
+
+    #[test]
+    fn test_blame_authors_recorded_and_filterable() {
+        let _data_dir = crate::test_support::use_temp_data_dir();
+
+        let dir = tempfile::tempdir().unwrap();
+        git(dir.path(), &["init", "-q"]);
+        fs::write(
+            dir.path().join("queue.rs"),
+            "fn enqueue_job() {}\nfn dequeue_job() {}\nfn drain_jobs() {}\n",
+        )
+        .unwrap();
+        git(dir.path(), &["add", "."]);
+        git(
+            dir.path(),
+            &[
+                "commit",
+                "-q",
+                "--author=Ann <ann@example.com>",
+                "-m",
+                "Queue",
+            ],
+        );
+        // Bob's one-line edit leaves Ann the author of most of queue.rs
+        fs::write(
+            dir.path().join("queue.rs"),
+            "fn enqueue_job() {}\nfn dequeue_job_fast() {}\nfn drain_jobs() {}\n",
+        )
+        .unwrap();
+        fs::write(dir.path().join("timer.rs"), "fn schedule_job() {}\n").unwrap();
+        git(dir.path(), &["add", "."]);
+        git(
+            dir.path(),
+            &[
+                "commit",
+                "-q",
+                "--author=Bob <bob@example.com>",
+                "-m",
+                "Timer",
+            ],
+        );
+
+        Indexer::new(IndexingOptions {
+            blame_authors: Some(true),
+            ..Default::default()
+        })
+        .index_codebase(dir.path())
+        .unwrap();
+
+        let codebase_id = get_codebase_hash(&dir.path().canonicalize().unwrap());
+        let conn = init_db().unwrap();
+        let by = |author: &str| {
+            let filters = SearchFilters {
+                author: Some(author.to_string()),
+                ..Default::default()
+            };
+            fts_search(&conn, "job", Some(&codebase_id), 10, &filters)
+                .unwrap()
+                .into_iter()
+                .map(|r| (r.file_path, r.author))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            by("Ann"),
+            vec![("queue.rs".to_string(), Some("Ann".to_string()))]
+        );
+        assert_eq!(
+            by("Bob"),
+            vec![("timer.rs".to_string(), Some("Bob".to_string()))]
+        );
+    }
+
+    #[test]
+    fn test_majority_author_skips_uncommitted_lines() {
+        let porcelain = "aaa 1 1 1\nauthor Ann\nauthor-mail <ann@example.com>\n\tfn a() {}\n\
+                         000 2 2 1\nauthor Not Committed Yet\n\tfn b() {}\n\
+                         bbb 3 3 1\nauthor Bob\n\tfn c() {}\n";
+        let authors = parse_blame(porcelain);
+        assert_eq!(
+            authors,
+            vec![Some("Ann".to_string()), None, Some("Bob".to_string())]
+        );
+        assert_eq!(majority_author(&authors, 1, 3), Some("Ann".to_string()));
+        assert_eq!(majority_author(&authors, 2, 3), Some("Bob".to_string()));
+        assert_eq!(majority_author(&authors, 2, 2), None);
+        assert_eq!(majority_author(&authors, 4, 9), None);
+    }
+}
@@ -1,33 +1,178 @@
-use ignore::gitignore::GitignoreBuilder;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
 use ignore::WalkBuilder;
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use std::sync::RwLock;
 
+/// Gitignore-syntax rules at the codebase root that only code search reads
+pub const SEARCH_IGNORE_FILE: &str = ".code-search-ignore";
+
 pub struct GitignoreMatcher {
     codebase_path: PathBuf,
-    gitignores: RwLock<HashMap<PathBuf, ignore::gitignore::Gitignore>>,
+    /// Directory the gitignore hierarchy is anchored at. This is the enclosing
+    /// git repository root when root detection is enabled and one is found,
+    /// otherwise the codebase path itself.
+    root: PathBuf,
+    gitignores: RwLock<HashMap<PathBuf, Gitignore>>,
+    /// Rules consulted when no `.gitignore` under the root decides a path,
+    /// highest precedence first
+    fallbacks: Vec<Gitignore>,
+    /// The codebase's `.code-search-ignore`, consulted before everything
+    /// else so it can both exclude tracked files and re-include ignored ones
+    search_ignore: Option<Gitignore>,
+}
+
+/// Which ignore-rule sources a [`GitignoreMatcher`] reads
+#[derive(Debug, Clone)]
+pub struct GitignoreOptions {
+    /// Anchor the rules at the enclosing git repository root, so files
+    /// above the codebase apply
+    pub detect_git_root: bool,
+    /// Read `.ignore` files next to `.gitignore` files; in the same
+    /// directory `.ignore` rules win
+    pub ignore_files: bool,
+    /// Read the repository's `.git/info/exclude`, below every `.gitignore`
+    pub git_exclude: bool,
+    /// Read the nearest `.gitignore` above the root and then the global
+    /// excludes file (`core.excludesFile`), below everything else
+    pub global_gitignore: bool,
+}
+
+impl Default for GitignoreOptions {
+    fn default() -> Self {
+        Self {
+            detect_git_root: true,
+            ignore_files: true,
+            git_exclude: true,
+            global_gitignore: false,
+        }
+    }
+}
+
+impl GitignoreOptions {
+    /// The sources enabled in the `[indexing]` config
+    pub fn from_config(indexing: &crate::config::IndexingConfig) -> Self {
+        Self {
+            detect_git_root: indexing.detect_git_root,
+            ignore_files: indexing.use_ignore_files,
+            git_exclude: indexing.use_git_exclude,
+            global_gitignore: indexing.use_global_gitignore,
+        }
+    }
 }
 
 impl GitignoreMatcher {
+    /// Create a matcher for `codebase_path`, detecting the enclosing git
+    /// repository root so that `.gitignore` files above the codebase apply.
     pub fn new<P: AsRef<Path>>(codebase_path: P) -> Result<Self, std::io::Error> {
+        Self::with_root_detection(codebase_path, true)
+    }
+
+    /// Create a matcher, optionally walking upward from `codebase_path` to the
+    /// nearest ancestor containing `.git` and loading the `.gitignore` files of
+    /// every directory in between.
+    pub fn with_root_detection<P: AsRef<Path>>(
+        codebase_path: P,
+        detect_git_root: bool,
+    ) -> Result<Self, std::io::Error> {
+        Self::with_options(
+            codebase_path,
+            &GitignoreOptions {
+                detect_git_root,
+                ..Default::default()
+            },
+        )
+    }
+
+    /// Create a matcher reading the rule sources enabled in `options`.
+    ///
+    /// As in git, the `.gitignore` (and `.ignore`) closest to a path decides
+    /// it; `.git/info/exclude`, the nearest `.gitignore` above the root and
+    /// the global excludes file follow, in that order, only for paths those
+    /// leave undecided.
+    pub fn with_options<P: AsRef<Path>>(
+        codebase_path: P,
+        options: &GitignoreOptions,
+    ) -> Result<Self, std::io::Error> {
         let codebase_path = codebase_path.as_ref().canonicalize()?;
 
+        let root = if options.detect_git_root {
+            find_git_root(&codebase_path).unwrap_or_else(|| codebase_path.clone())
+        } else {
+            codebase_path.clone()
+        };
+
         let mut gitignores = HashMap::new();
 
-        if let Err(e) = Self::load_gitignores_recursive(&codebase_path, &mut gitignores) {
+        if let Err(e) =
+            Self::load_gitignores_recursive(&codebase_path, options.ignore_files, &mut gitignores)
+        {
             eprintln!("Warning: Error loading .gitignore files: {}", e);
         }
 
+        for dir in codebase_path.ancestors().skip(1) {
+            if !dir.starts_with(&root) {
+                break;
+            }
+            if let Some(gitignore) = build_dir_rules(dir, options.ignore_files) {
+                gitignores.insert(dir.to_path_buf(), gitignore);
+            }
+        }
+
+        let mut fallbacks = Vec::new();
+        if options.git_exclude {
+            if let Some(path) = git_dir(&root).map(|dir| dir.join("info").join("exclude")) {
+                if path.is_file() {
+                    fallbacks.extend(build_gitignore(&root, &path));
+                }
+            }
+        }
+        if options.global_gitignore {
+            let ancestor = root
+                .ancestors()
+                .skip(1)
+                .find(|dir| dir.join(".gitignore").is_file());
+            if let Some(dir) = ancestor {
+                fallbacks.extend(build_gitignore(dir, &dir.join(".gitignore")));
+            }
+            if let Some(path) = global_excludes_file(&root).filter(|p| p.is_file()) {
+                fallbacks.extend(build_gitignore(&root, &path));
+            }
+        }
+
+        let search_ignore = build_search_ignore(&codebase_path);
+
         Ok(Self {
             codebase_path,
+            root,
             gitignores: RwLock::new(gitignores),
+            fallbacks,
+            search_ignore,
         })
     }
 
+    /// A matcher reading only the codebase's `.code-search-ignore`, for
+    /// when gitignore filtering is off; `None` when there is no such file
+    pub fn search_ignore_only<P: AsRef<Path>>(
+        codebase_path: P,
+    ) -> Result<Option<Self>, std::io::Error> {
+        let codebase_path = codebase_path.as_ref().canonicalize()?;
+        let Some(search_ignore) = build_search_ignore(&codebase_path) else {
+            return Ok(None);
+        };
+        Ok(Some(Self {
+            root: codebase_path.clone(),
+            codebase_path,
+            gitignores: RwLock::new(HashMap::new()),
+            fallbacks: Vec::new(),
+            search_ignore: Some(search_ignore),
+        }))
+    }
+
     fn load_gitignores_recursive(
         base_path: &Path,
-        gitignores: &mut HashMap<PathBuf, ignore::gitignore::Gitignore>,
+        ignore_files: bool,
+        gitignores: &mut HashMap<PathBuf, Gitignore>,
     ) -> Result<(), std::io::Error> {
         for entry in WalkBuilder::new(base_path)
             .hidden(false)
@@ -39,19 +184,15 @@ impl GitignoreMatcher {
             let entry = entry.map_err(|e| std::io::Error::other(format!("Walk error: {}", e)))?;
             let path = entry.path();
 
-            if path.file_name() == Some(std::ffi::OsStr::new(".gitignore")) {
+            let is_rules_file = path.file_name() == Some(std::ffi::OsStr::new(".gitignore"))
+                || (ignore_files && path.file_name() == Some(std::ffi::OsStr::new(".ignore")));
+            if is_rules_file {
                 if let Some(parent) = path.parent() {
-                    let mut builder = GitignoreBuilder::new(parent);
-
-                    if let Ok(content) = std::fs::read_to_string(path) {
-                        for line in content.lines() {
-                            let _ = builder.add_line(Some(path.to_path_buf()), line);
+                    if !gitignores.contains_key(parent) {
+                        if let Some(gitignore) = build_dir_rules(parent, ignore_files) {
+                            gitignores.insert(parent.to_path_buf(), gitignore);
                         }
                     }
-
-                    if let Ok(gitignore) = builder.build() {
-                        gitignores.insert(parent.to_path_buf(), gitignore);
-                    }
                 }
             }
         }
@@ -60,7 +201,7 @@ impl GitignoreMatcher {
     }
 
     #[allow(dead_code)]
-    fn get_gitignore_for_path(&self, file_path: &Path) -> Option<ignore::gitignore::Gitignore> {
+    fn get_gitignore_for_path(&self, file_path: &Path) -> Option<Gitignore> {
         let absolute_path = if file_path.is_absolute() {
             file_path.to_path_buf()
         } else {
@@ -103,108 +244,79 @@ impl GitignoreMatcher {
     pub fn is_ignored<P: AsRef<Path>>(&self, file_path: P) -> bool {
         let file_path = file_path.as_ref();
 
-        let absolute_path = if file_path.is_absolute() {
-            file_path.to_path_buf()
+        // Collecting the components drops trailing separators ("target/"),
+        // which would otherwise stop directory patterns from matching.
+        let absolute_path: PathBuf = if file_path.is_absolute() {
+            file_path.components().collect()
         } else {
-            self.codebase_path.join(file_path)
+            self.codebase_path.join(file_path).components().collect()
         };
 
-        if !absolute_path.starts_with(&self.codebase_path) {
+        if !absolute_path.starts_with(&self.codebase_path) || absolute_path == self.codebase_path {
             return false;
         }
 
-        let relative_path = match absolute_path.strip_prefix(&self.codebase_path) {
-            Ok(p) => p,
-            Err(_) => return false,
-        };
-
         let is_dir = absolute_path.is_dir();
 
-        let gitignores = self.gitignores.read().ok();
-        let gitignores = match gitignores {
-            Some(g) => g,
-            None => return false,
-        };
+        if let Some(search_ignore) = &self.search_ignore {
+            match search_ignore.matched_path_or_any_parents(&absolute_path, is_dir) {
+                ignore::Match::Ignore(_) => return true,
+                ignore::Match::Whitelist(_) => return false,
+                ignore::Match::None => {}
+            }
+        }
 
-        let mut parent = if is_dir {
-            &absolute_path
-        } else if let Some(p) = absolute_path.parent() {
-            p
-        } else {
-            return false;
+        let gitignores = match self.gitignores.read() {
+            Ok(g) => g,
+            Err(_) => return false,
         };
 
-        loop {
-            if let Some(gitignore) = gitignores.get(parent) {
-                match gitignore.matched(relative_path, is_dir) {
+        // Deeper .gitignore files take precedence, so check from the file's
+        // directory upward to the repository root and stop at the first match.
+        let mut dir = absolute_path.parent();
+        while let Some(current) = dir {
+            if let Some(gitignore) = gitignores.get(current) {
+                match gitignore.matched_path_or_any_parents(&absolute_path, is_dir) {
                     ignore::Match::Ignore(_) => return true,
                     ignore::Match::Whitelist(_) => return false,
                     ignore::Match::None => {}
                 }
             }
 
-            if parent == self.codebase_path {
+            if current == self.root {
                 break;
             }
-
-            match parent.parent() {
-                Some(p) if p.starts_with(&self.codebase_path) => parent = p,
-                _ => break,
-            }
+            dir = current.parent().filter(|p| p.starts_with(&self.root));
         }
 
-        if !is_dir {
-            if let Some(dir_path) = relative_path.parent() {
-                if dir_path != Path::new("") {
-                    let mut current = self.codebase_path.clone();
-                    for component in dir_path.components() {
-                        current.push(component);
-                        if self.check_path_ignored(&gitignores, &current, dir_path, true) {
-                            return true;
-                        }
-                    }
-                }
+        for gitignore in &self.fallbacks {
+            match gitignore.matched_path_or_any_parents(&absolute_path, is_dir) {
+                ignore::Match::Ignore(_) => return true,
+                ignore::Match::Whitelist(_) => return false,
+                ignore::Match::None => {}
             }
         }
 
         false
     }
 
-    fn check_path_ignored(
-        &self,
-        gitignores: &HashMap<PathBuf, ignore::gitignore::Gitignore>,
-        absolute_path: &Path,
-        relative_path: &Path,
-        is_dir: bool,
-    ) -> bool {
-        let mut parent = if is_dir {
-            absolute_path
-        } else if let Some(p) = absolute_path.parent() {
-            p
-        } else {
+    /// Whether `.code-search-ignore`, rather than a gitignore source,
+    /// excludes `file_path`
+    pub fn is_search_ignored<P: AsRef<Path>>(&self, file_path: P) -> bool {
+        let Some(search_ignore) = &self.search_ignore else {
             return false;
         };
-
-        loop {
-            if let Some(gitignore) = gitignores.get(parent) {
-                match gitignore.matched(relative_path, is_dir) {
-                    ignore::Match::Ignore(_) => return true,
-                    ignore::Match::Whitelist(_) => return false,
-                    ignore::Match::None => {}
-                }
-            }
-
-            if parent == self.codebase_path {
-                break;
-            }
-
-            match parent.parent() {
-                Some(p) if p.starts_with(&self.codebase_path) => parent = p,
-                _ => break,
-            }
+        let absolute_path: PathBuf = self
+            .codebase_path
+            .join(file_path.as_ref())
+            .components()
+            .collect();
+        if !absolute_path.starts_with(&self.codebase_path) {
+            return false;
         }
-
-        false
+        search_ignore
+            .matched_path_or_any_parents(&absolute_path, absolute_path.is_dir())
+            .is_ignore()
     }
 
     pub fn filter_paths(&self, paths: &[PathBuf]) -> Vec<PathBuf> {
@@ -220,6 +332,102 @@ impl GitignoreMatcher {
     pub fn codebase_path(&self) -> &Path {
         &self.codebase_path
     }
+
+    /// The directory gitignore rules are resolved against (the git repository
+    /// root when one was detected).
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+}
+
+/// Find the nearest ancestor of `path` (including `path` itself) that
+/// contains a `.git` entry. `.git` may be a file for worktrees and submodules.
+pub fn find_git_root(path: &Path) -> Option<PathBuf> {
+    path.ancestors()
+        .find(|dir| dir.join(".git").exists())
+        .map(Path::to_path_buf)
+}
+
+/// The user's global excludes file: `core.excludesFile` as git reads it for
+/// `repo`, else git's default of `$XDG_CONFIG_HOME/git/ignore` (or
+/// `~/.config/git/ignore`)
+pub fn global_excludes_file(repo: &Path) -> Option<PathBuf> {
+    let configured = std::process::Command::new("git")
+        .args(["config", "--path", "--get", "core.excludesFile"])
+        .current_dir(repo)
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        .filter(|path| !path.is_empty());
+    if let Some(path) = configured {
+        return Some(PathBuf::from(path));
+    }
+
+    let config_home = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(config_home.join("git").join("ignore"))
+}
+
+/// The git directory of the repository at `root`: `root/.git`, or the
+/// directory a `.git` file (worktrees, submodules) points to
+fn git_dir(root: &Path) -> Option<PathBuf> {
+    let dot_git = root.join(".git");
+    if dot_git.is_dir() {
+        return Some(dot_git);
+    }
+    let content = std::fs::read_to_string(&dot_git).ok()?;
+    let target = content.trim().strip_prefix("gitdir:")?.trim();
+    Some(root.join(target))
+}
+
+/// The rules of `codebase_path`'s `.code-search-ignore`, if it has one
+fn build_search_ignore(codebase_path: &Path) -> Option<Gitignore> {
+    let path = codebase_path.join(SEARCH_IGNORE_FILE);
+    if !path.is_file() {
+        return None;
+    }
+    build_gitignore(codebase_path, &path)
+}
+
+fn build_gitignore(dir: &Path, gitignore_path: &Path) -> Option<Gitignore> {
+    let mut builder = GitignoreBuilder::new(dir);
+    add_rules_file(&mut builder, gitignore_path);
+    builder.build().ok()
+}
+
+/// The rules of `dir`'s `.gitignore` and, with `ignore_files`, its
+/// `.ignore`, or `None` when it has neither. `.ignore` lines come last so
+/// they win, as later lines of one file do.
+fn build_dir_rules(dir: &Path, ignore_files: bool) -> Option<Gitignore> {
+    let mut names = vec![".gitignore"];
+    if ignore_files {
+        names.push(".ignore");
+    }
+    let files: Vec<PathBuf> = names
+        .into_iter()
+        .map(|name| dir.join(name))
+        .filter(|path| path.is_file())
+        .collect();
+    if files.is_empty() {
+        return None;
+    }
+
+    let mut builder = GitignoreBuilder::new(dir);
+    for path in &files {
+        add_rules_file(&mut builder, path);
+    }
+    builder.build().ok()
+}
+
+fn add_rules_file(builder: &mut GitignoreBuilder, path: &Path) {
+    if let Ok(content) = std::fs::read_to_string(path) {
+        for line in content.lines() {
+            let _ = builder.add_line(Some(path.to_path_buf()), line);
+        }
+    }
 }
 
 #[cfg(test)]
@@ -363,4 +571,231 @@ mod tests {
         assert!(matcher.is_ignored("src/generated"));
         assert!(matcher.is_ignored("src/generated/code.rs"));
     }
+
+    #[test]
+    fn test_parent_gitignore_applies_to_subdirectory() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = dir.path();
+
+        fs::create_dir_all(repo.join(".git")).unwrap();
+        fs::create_dir_all(repo.join("src/gen")).unwrap();
+
+        let mut gitignore = File::create(repo.join(".gitignore")).unwrap();
+        writeln!(gitignore, "src/gen/").unwrap();
+        writeln!(gitignore, "*.log").unwrap();
+
+        File::create(repo.join("src/lib.rs")).unwrap();
+        File::create(repo.join("src/debug.log")).unwrap();
+        File::create(repo.join("src/gen/bindings.rs")).unwrap();
+
+        let matcher = GitignoreMatcher::new(repo.join("src")).unwrap();
+
+        assert_eq!(matcher.root(), repo.canonicalize().unwrap());
+        assert!(!matcher.is_ignored("lib.rs"));
+        assert!(matcher.is_ignored("debug.log"));
+        assert!(matcher.is_ignored("gen"));
+        assert!(matcher.is_ignored("gen/bindings.rs"));
+    }
+
+    #[test]
+    fn test_root_detection_disabled() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = dir.path();
+
+        fs::create_dir_all(repo.join(".git")).unwrap();
+        fs::create_dir_all(repo.join("src/gen")).unwrap();
+
+        let mut gitignore = File::create(repo.join(".gitignore")).unwrap();
+        writeln!(gitignore, "src/gen/").unwrap();
+
+        File::create(repo.join("src/gen/bindings.rs")).unwrap();
+
+        let matcher = GitignoreMatcher::with_root_detection(repo.join("src"), false).unwrap();
+
+        assert_eq!(matcher.root(), matcher.codebase_path());
+        assert!(!matcher.is_ignored("gen/bindings.rs"));
+    }
+
+    #[test]
+    fn test_nested_whitelist_overrides_parent() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = dir.path();
+
+        fs::create_dir_all(repo.join(".git")).unwrap();
+        fs::create_dir_all(repo.join("src")).unwrap();
+
+        let mut root_gitignore = File::create(repo.join(".gitignore")).unwrap();
+        writeln!(root_gitignore, "*.gen.rs").unwrap();
+
+        let mut src_gitignore = File::create(repo.join("src/.gitignore")).unwrap();
+        writeln!(src_gitignore, "!keep.gen.rs").unwrap();
+
+        File::create(repo.join("src/keep.gen.rs")).unwrap();
+        File::create(repo.join("src/drop.gen.rs")).unwrap();
+
+        let matcher = GitignoreMatcher::new(repo.join("src")).unwrap();
+
+        assert!(!matcher.is_ignored("keep.gen.rs"));
+        assert!(matcher.is_ignored("drop.gen.rs"));
+    }
+
+    #[test]
+    fn test_ancestor_gitignore_above_repo_root() {
+        let dir = tempfile::tempdir().unwrap();
+        let parent = dir.path();
+        let repo = parent.join("repo");
+
+        fs::create_dir_all(repo.join(".git")).unwrap();
+        let mut parent_gitignore = File::create(parent.join(".gitignore")).unwrap();
+        writeln!(parent_gitignore, "*.log").unwrap();
+        writeln!(parent_gitignore, "scratch/").unwrap();
+        let mut repo_gitignore = File::create(repo.join(".gitignore")).unwrap();
+        writeln!(repo_gitignore, "!keep.log").unwrap();
+
+        fs::create_dir_all(repo.join("scratch")).unwrap();
+        File::create(repo.join("debug.log")).unwrap();
+        File::create(repo.join("keep.log")).unwrap();
+        File::create(repo.join("scratch/notes.rs")).unwrap();
+        File::create(repo.join("main.rs")).unwrap();
+
+        let matcher = GitignoreMatcher::new(&repo).unwrap();
+        assert!(!matcher.is_ignored("debug.log"));
+        assert!(!matcher.is_ignored("scratch/notes.rs"));
+
+        let options = GitignoreOptions {
+            global_gitignore: true,
+            ..Default::default()
+        };
+        let matcher = GitignoreMatcher::with_options(&repo, &options).unwrap();
+        assert!(matcher.is_ignored("debug.log"));
+        assert!(matcher.is_ignored("scratch/notes.rs"));
+        // The repository's own .gitignore is closer, so its whitelist wins
+        assert!(!matcher.is_ignored("keep.log"));
+        assert!(!matcher.is_ignored("main.rs"));
+    }
+
+    #[test]
+    fn test_ignore_file_and_git_exclude() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = dir.path();
+
+        fs::create_dir_all(repo.join(".git/info")).unwrap();
+        fs::create_dir_all(repo.join("src")).unwrap();
+        let mut exclude = File::create(repo.join(".git/info/exclude")).unwrap();
+        writeln!(exclude, "*.local").unwrap();
+        writeln!(exclude, "*.snap").unwrap();
+        let mut gitignore = File::create(repo.join(".gitignore")).unwrap();
+        writeln!(gitignore, "!keep.snap").unwrap();
+        writeln!(gitignore, "*.bak").unwrap();
+        let mut ignore = File::create(repo.join("src/.ignore")).unwrap();
+        writeln!(ignore, "fixtures.rs").unwrap();
+        writeln!(ignore, "!wanted.bak").unwrap();
+
+        for file in [
+            "a.local",
+            "b.snap",
+            "keep.snap",
+            "src/fixtures.rs",
+            "src/old.bak",
+        ] {
+            File::create(repo.join(file)).unwrap();
+        }
+        File::create(repo.join("src/wanted.bak")).unwrap();
+
+        let matcher = GitignoreMatcher::new(repo).unwrap();
+        assert!(matcher.is_ignored("a.local"));
+        assert!(matcher.is_ignored("b.snap"));
+        // .gitignore outranks .git/info/exclude
+        assert!(!matcher.is_ignored("keep.snap"));
+        assert!(matcher.is_ignored("src/fixtures.rs"));
+        assert!(matcher.is_ignored("src/old.bak"));
+        // .ignore in the same (here a deeper) directory wins over .gitignore
+        assert!(!matcher.is_ignored("src/wanted.bak"));
+
+        let options = GitignoreOptions {
+            ignore_files: false,
+            git_exclude: false,
+            ..Default::default()
+        };
+        let matcher = GitignoreMatcher::with_options(repo, &options).unwrap();
+        assert!(!matcher.is_ignored("a.local"));
+        assert!(!matcher.is_ignored("src/fixtures.rs"));
+        assert!(matcher.is_ignored("src/wanted.bak"));
+    }
+
+    #[test]
+    fn test_global_excludes_file_from_git_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = dir.path().join("repo");
+        fs::create_dir_all(&repo).unwrap();
+        let git = |args: &[&str]| {
+            let status = std::process::Command::new("git")
+                .arg("-C")
+                .arg(&repo)
+                .args(args)
+                .status()
+                .unwrap();
+            assert!(status.success(), "git {:?} failed", args);
+        };
+        git(&["init", "-q"]);
+        // Repository config outranks the user's, so the test never reads it
+        let excludes = dir.path().join("excludes");
+        let mut rules = File::create(&excludes).unwrap();
+        writeln!(rules, "*.swp").unwrap();
+        git(&["config", "core.excludesFile", excludes.to_str().unwrap()]);
+
+        File::create(repo.join("main.rs.swp")).unwrap();
+        File::create(repo.join("main.rs")).unwrap();
+
+        assert_eq!(global_excludes_file(&repo), Some(excludes));
+        let matcher = GitignoreMatcher::new(&repo).unwrap();
+        assert!(!matcher.is_ignored("main.rs.swp"));
+
+        let options = GitignoreOptions {
+            global_gitignore: true,
+            ..Default::default()
+        };
+        let matcher = GitignoreMatcher::with_options(&repo, &options).unwrap();
+        assert!(matcher.is_ignored("main.rs.swp"));
+        assert!(!matcher.is_ignored("main.rs"));
+    }
+
+    #[test]
+    fn test_search_ignore_excludes_and_reincludes() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = dir.path();
+
+        fs::create_dir_all(repo.join(".git")).unwrap();
+        fs::create_dir_all(repo.join("fixtures")).unwrap();
+        let mut gitignore = File::create(repo.join(".gitignore")).unwrap();
+        writeln!(gitignore, "generated/").unwrap();
+        let mut search_ignore = File::create(repo.join(SEARCH_IGNORE_FILE)).unwrap();
+        writeln!(search_ignore, "fixtures/").unwrap();
+        writeln!(search_ignore, "!generated/api.rs").unwrap();
+        fs::create_dir_all(repo.join("generated")).unwrap();
+        for file in [
+            "fixtures/big.json",
+            "generated/api.rs",
+            "generated/other.rs",
+        ] {
+            File::create(repo.join(file)).unwrap();
+        }
+
+        let matcher = GitignoreMatcher::new(repo).unwrap();
+        assert!(matcher.is_ignored("fixtures/big.json"));
+        assert!(matcher.is_search_ignored("fixtures/big.json"));
+        // A negation re-includes a gitignored file
+        assert!(!matcher.is_ignored("generated/api.rs"));
+        assert!(matcher.is_ignored("generated/other.rs"));
+        assert!(!matcher.is_search_ignored("generated/other.rs"));
+
+        let matcher = GitignoreMatcher::search_ignore_only(repo).unwrap().unwrap();
+        assert!(matcher.is_ignored("fixtures/big.json"));
+        assert!(!matcher.is_ignored("generated/other.rs"));
+
+        fs::remove_file(repo.join(SEARCH_IGNORE_FILE)).unwrap();
+        assert!(GitignoreMatcher::search_ignore_only(repo)
+            .unwrap()
+            .is_none());
+    }
 }
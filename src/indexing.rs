@@ -1,48 +1,46 @@
 use crate::config::get_config;
-use crate::database::{delete_chunks_for_file, get_codebase_stats, init_db, insert_chunks, Chunk};
-use crate::embedding::{
-    get_embedding_with_model, get_embeddings_batch_with_model, zero_embedding_with_model,
+use crate::database::{
+    chunk_content_hash, chunk_contents_after, delete_chunks_for_file, delete_chunks_for_prefix,
+    embeddings_by_content_hash, get_codebase_stats, init_db, init_db_at, insert_chunks,
+    replace_file_chunks, resolve_db_path, set_codebase_model, update_embeddings, Chunk,
 };
+use crate::embedding::{zero_embedding_with_model, EmbeddingModel};
 use crate::error::{CodeSearchError, Result};
-use crate::gitignore::GitignoreMatcher;
+use crate::gitignore::{GitignoreMatcher, GitignoreOptions};
 use crate::manifest::{
-    get_codebase_hash, get_manifest_path, hash_file_content, load_manifest_internal,
-    save_manifest_internal, Changes,
+    get_codebase_hash, get_manifest_path, hash_file_content, load_index_params,
+    load_manifest_internal, resolve_manifest_dir, save_index_params, save_manifest_internal,
+    size_limit, ChangeKind, Changes, IndexParams, Manifest, ManifestEntry,
+};
+use crate::splitter::{
+    file_name_chunk, normalize_whitespace, read_source, resolve_chunk_params,
+    split_file_with_threshold,
 };
-use crate::splitter::split_file;
 use indicatif::{ProgressBar, ProgressStyle};
 use rayon::prelude::*;
+use rusqlite::Connection;
 use sha2::{Digest, Sha256};
-use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
 use std::time::Instant;
 
 // Helper function to get extensions from config
-fn get_extensions() -> Vec<&'static str> {
-    get_config()
-        .extensions()
-        .iter()
-        .map(|s| s.as_str())
-        .collect()
+fn get_extensions() -> Vec<String> {
+    get_config().extensions().to_vec()
 }
 
 // Helper function to get skip dirs from config
-fn get_skip_dirs() -> Vec<&'static str> {
-    get_config()
-        .skip_dirs()
-        .iter()
-        .map(|s| s.as_str())
-        .collect()
+fn get_skip_dirs() -> Vec<String> {
+    get_config().skip_dirs().to_vec()
 }
 
 // Helper function to get skip files from config
-fn get_skip_files() -> Vec<&'static str> {
-    get_config()
-        .skip_files()
-        .iter()
-        .map(|s| s.as_str())
-        .collect()
+fn get_skip_files() -> Vec<String> {
+    get_config().skip_files().to_vec()
 }
 
 // Helper function to get batch size from config
@@ -50,14 +48,104 @@ fn get_batch_size() -> usize {
     get_config().batch_size()
 }
 
+/// What indexing does when a file cannot be read or split
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ErrorPolicy {
+    /// Leave the file out and index the rest
+    #[default]
+    Skip,
+    /// Fail the run with the first file error, leaving the index as it was
+    Abort,
+}
+
+/// A step of an indexing run, reported through `IndexingOptions::progress`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IndexingProgress {
+    /// Walking the codebase for added, modified and removed files
+    Scanning,
+    /// `done` of the `total` files to index have their chunks embedded and
+    /// inserted
+    Embedding { done: usize, total: usize },
+    /// Every chunk is stored; the manifest and codebase metadata are written
+    Inserting,
+    /// The run finished
+    Done,
+}
+
+/// Receives [`IndexingProgress`] events in place of the built-in progress
+/// bars, e.g. to drive a GUI or LSP progress report
+#[derive(Clone)]
+pub struct ProgressCallback(Arc<dyn Fn(IndexingProgress) + Send + Sync>);
+
+impl ProgressCallback {
+    pub fn new(callback: impl Fn(IndexingProgress) + Send + Sync + 'static) -> Self {
+        Self(Arc::new(callback))
+    }
+}
+
+impl std::fmt::Debug for ProgressCallback {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("ProgressCallback")
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct IndexingOptions {
     pub chunk_size: Option<usize>,
     pub chunk_overlap: Option<usize>,
+    /// Embed files of at most this many lines as one whole-file chunk.
+    /// `None` uses `chunking.whole_file_max_lines` from the config.
+    pub whole_file_max_lines: Option<usize>,
     pub force: bool,
     pub verbose: bool,
+    /// Scan for added, modified and removed files and return the projected
+    /// stats, without embedding or writing to the index or manifest
+    pub dry_run: bool,
     pub use_gitignore: bool,
+    /// With `use_gitignore`, also apply the nearest `.gitignore` above the
+    /// repository root and the user's global git excludes file, as
+    /// `indexing.use_global_gitignore` in the config does
+    pub use_global_gitignore: bool,
     pub model_name: Option<String>,
+    /// Name to register the codebase under; `--codebase` accepts it in place
+    /// of a path. `None` keeps the existing label, or the directory name.
+    pub label: Option<String>,
+    /// Record which files were removed from the index (and how many chunks
+    /// each lost) in `IndexingStats::removed_files`. Implied by `verbose`.
+    pub report_removed: bool,
+    /// Sort the files to index by path, and each file's chunks by line,
+    /// before insertion so identical inputs get identical rowids.
+    /// `None` uses `indexing.deterministic_order` from the config.
+    pub deterministic_order: Option<bool>,
+    /// Add a `path` chunk per file holding its path split into words.
+    /// `None` uses `indexing.index_file_names` from the config.
+    pub index_file_names: Option<bool>,
+    /// Index files that aren't valid UTF-8 with replacement characters.
+    /// `None` uses `indexing.index_non_utf8` from the config.
+    pub index_non_utf8: Option<bool>,
+    /// Follow symlinks, indexing a file reachable under several paths once.
+    /// `None` uses `indexing.follow_symlinks` from the config.
+    pub follow_symlinks: Option<bool>,
+    /// Skip files larger than this many bytes; `Some(0)` lifts the limit.
+    /// `None` uses `indexing.max_file_bytes` from the config.
+    pub max_file_bytes: Option<u64>,
+    /// Record each chunk's main author from `git blame`. Needs the `git`
+    /// feature. `None` uses `indexing.blame_authors` from the config.
+    pub blame_authors: Option<bool>,
+    /// Skip unreadable files (the default) or fail the run on the first one
+    pub on_error: ErrorPolicy,
+    /// Directory holding the database and manifests to index into. `None`
+    /// uses `--data-dir` when set, else the configured location.
+    pub data_dir: Option<PathBuf>,
+    /// Also index the newest commit messages, at most this many, as `commit`
+    /// chunks. Needs the `git` feature.
+    pub commit_limit: Option<usize>,
+    /// Only index commit messages since this date (anything `git log
+    /// --since` accepts). Needs the `git` feature.
+    pub commits_since: Option<String>,
+    /// Called at each step of `index_codebase`; the progress bars are not
+    /// drawn when set
+    pub progress: Option<ProgressCallback>,
 }
 
 impl Default for IndexingOptions {
@@ -65,32 +153,128 @@ impl Default for IndexingOptions {
         Self {
             chunk_size: None,
             chunk_overlap: None,
+            whole_file_max_lines: None,
             force: false,
             verbose: false,
+            dry_run: false,
             use_gitignore: true,
+            use_global_gitignore: false,
             model_name: None,
+            label: None,
+            report_removed: false,
+            deterministic_order: None,
+            index_file_names: None,
+            index_non_utf8: None,
+            follow_symlinks: None,
+            max_file_bytes: None,
+            blame_authors: None,
+            data_dir: None,
+            on_error: ErrorPolicy::Skip,
+            commit_limit: None,
+            commits_since: None,
+            progress: None,
         }
     }
 }
 
-#[derive(Debug, Default)]
+impl IndexingOptions {
+    /// Whether a chunk size or overlap was given, which then holds for every
+    /// language over `chunking.per_language`
+    fn overrides_chunking(&self) -> bool {
+        self.chunk_size.is_some() || self.chunk_overlap.is_some()
+    }
+}
+
+#[derive(Debug, Default, serde::Serialize)]
 pub struct IndexingStats {
     pub files_indexed: usize,
     pub files_skipped: usize,
     pub files_removed: usize,
     pub chunks_created: usize,
     pub chunks_removed: usize,
+    /// Chunks of re-indexed files whose text was unchanged, so their stored
+    /// embedding was kept instead of computed again
+    pub chunks_reused: usize,
+    /// Chunks stored with a zero embedding because the model returned fewer
+    /// embeddings than chunks under `ErrorPolicy::Skip`
+    pub chunks_zero_embedded: usize,
+    /// Commit messages indexed through `IndexingOptions::commit_limit` or
+    /// `commits_since`
+    pub commits_indexed: usize,
     pub duration_ms: u64,
+    /// Per-file removal detail, present only when requested through
+    /// `IndexingOptions::report_removed` or `verbose`
+    pub removed_files: Option<Vec<RemovedFile>>,
+    /// Chunks embedded this run whose text was longer than the model's
+    /// input limit, so only their beginning shaped the embedding
+    pub chunks_truncated: usize,
+    /// Files with at least `indexing.truncation_warning_threshold` such
+    /// chunks, with advice on chunking them
+    pub truncated_files: Vec<TruncatedFile>,
+    /// Projected by `IndexingOptions::dry_run`; nothing was embedded or
+    /// written, so `chunks_created` is 0
+    pub dry_run: bool,
+}
+
+/// A file many of whose chunks were too long for the embedding model
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct TruncatedFile {
+    pub file_path: String,
+    pub chunks_truncated: usize,
+    pub suggestion: String,
+}
+
+/// A file whose chunks were dropped from the index during an indexing run
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct RemovedFile {
+    pub file_path: String,
+    pub chunks_removed: usize,
 }
 
 impl std::fmt::Display for IndexingStats {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        writeln!(f, "Indexing completed:")?;
+        if self.dry_run {
+            writeln!(f, "Dry run, nothing written:")?;
+        } else {
+            writeln!(f, "Indexing completed:")?;
+        }
         writeln!(f, "  Files indexed: {}", self.files_indexed)?;
         writeln!(f, "  Files skipped: {}", self.files_skipped)?;
         writeln!(f, "  Files removed: {}", self.files_removed)?;
         writeln!(f, "  Chunks created: {}", self.chunks_created)?;
         writeln!(f, "  Chunks removed: {}", self.chunks_removed)?;
+        if self.chunks_reused > 0 {
+            writeln!(f, "  Chunks reused: {}", self.chunks_reused)?;
+        }
+        if self.chunks_zero_embedded > 0 {
+            writeln!(
+                f,
+                "  Chunks with zero embeddings: {}",
+                self.chunks_zero_embedded
+            )?;
+        }
+        if self.chunks_truncated > 0 {
+            writeln!(
+                f,
+                "  Chunks over the model input limit: {}",
+                self.chunks_truncated
+            )?;
+            for truncated in &self.truncated_files {
+                writeln!(f, "    - {}", truncated.suggestion)?;
+            }
+        }
+        if self.commits_indexed > 0 {
+            writeln!(f, "  Commits indexed: {}", self.commits_indexed)?;
+        }
+        if let Some(ref removed_files) = self.removed_files {
+            for removed in removed_files {
+                writeln!(
+                    f,
+                    "    - {} ({} chunks)",
+                    removed.file_path, removed.chunks_removed
+                )?;
+            }
+        }
         writeln!(f, "  Duration: {}ms", self.duration_ms)
     }
 }
@@ -108,11 +292,17 @@ impl Indexer {
         let start = Instant::now();
         let codebase_path = codebase_path.as_ref().canonicalize()?;
         let codebase_id = get_codebase_hash(&codebase_path);
+        let global_config = get_config();
         let model = self
             .config
             .model_name
             .as_deref()
-            .unwrap_or(get_config().model_name());
+            .unwrap_or(global_config.model_name());
+
+        // Reject an overlap that would not leave room for progress before
+        // touching the index
+        let requested = resolve_chunk_params(self.config.chunk_size, self.config.chunk_overlap)?;
+        let blame_authors = self.blame_authors()?;
 
         if self.config.verbose {
             println!("Codebase ID: {}", codebase_id);
@@ -120,138 +310,454 @@ impl Indexer {
             println!("Model: {}", model);
         }
 
-        let conn = init_db()?;
+        let conn = self.open_db()?;
+        let manifest_path = self.manifest_path(&codebase_id)?;
+
+        // Chunks made with other settings are stale even where the content
+        // is unchanged, so a settings change re-chunks every file
+        let mut params = IndexParams::new(requested.0, requested.1, model);
+        if !self.config.overrides_chunking() {
+            params.per_language = resolved_per_language();
+        }
+        if let Some(lines) = self.config.whole_file_max_lines {
+            params.whole_file_max_lines = lines;
+        }
+        params.global_gitignore = self.gitignore_options().global_gitignore;
+        params.max_file_bytes = self.max_file_bytes();
+        let params_changed = !self.config.force
+            && index_params_changed(&conn, &codebase_id, &manifest_path, &params)?;
+        let force = self.config.force || params_changed;
+        if params_changed {
+            eprintln!(
+                "Note: {} was indexed with other chunking or model settings; \
+                 re-chunking every file",
+                codebase_path.display()
+            );
+        }
+
+        let mut stats = IndexingStats::default();
+        if self.config.report_removed || self.config.verbose {
+            stats.removed_files = Some(Vec::new());
+        }
+
+        if self.config.dry_run {
+            self.project_changes(&conn, &codebase_path, &codebase_id, force, &mut stats)?;
+            stats.duration_ms = start.elapsed().as_millis() as u64;
+            self.report(IndexingProgress::Done);
+            return Ok(stats);
+        }
+
+        // Under `ErrorPolicy::Abort` every write of the run shares one
+        // transaction, so a failure anywhere rolls all of them back
+        let run_tx = match self.config.on_error {
+            ErrorPolicy::Abort => Some(
+                conn.unchecked_transaction()
+                    .map_err(CodeSearchError::Database)?,
+            ),
+            ErrorPolicy::Skip => None,
+        };
 
-        if self.config.force {
+        if force {
             if self.config.verbose {
-                println!("Force flag set, removing existing index...");
+                println!("Re-indexing every file, removing existing index...");
             }
+            let per_file = crate::database::chunk_counts_by_file(&conn, &codebase_id)?;
             let removed = crate::database::delete_chunks_for_codebase(&conn, &codebase_id)?;
+            stats.chunks_removed += removed as usize;
+            stats.files_removed += per_file.len();
+            if let Some(ref mut removed_files) = stats.removed_files {
+                removed_files.extend(per_file.into_iter().map(|(file_path, chunks)| RemovedFile {
+                    file_path,
+                    chunks_removed: chunks as usize,
+                }));
+            }
             if self.config.verbose {
                 println!("Removed {} existing chunks", removed);
             }
         }
 
-        let gitignore_matcher = if self.config.use_gitignore {
-            Some(GitignoreMatcher::new(&codebase_path)?)
-        } else {
-            None
-        };
+        // Pinned for reindex_file, so single files match the rest
+        let (chunk_size, chunk_overlap) = requested;
+        crate::database::set_chunking_params(&conn, &codebase_id, chunk_size, chunk_overlap)?;
 
-        let manifest_path = get_manifest_path()?.join(format!("{}.json", codebase_id));
-        let existing_manifest = if manifest_path.exists() {
+        let existing_manifest = if manifest_path.exists() && !force {
             load_manifest_internal(&manifest_path)?
         } else {
             HashMap::new()
         };
 
-        let changes = if self.config.force {
-            get_all_files(
-                &codebase_path,
-                gitignore_matcher.as_ref(),
-                self.config.verbose,
-            )?
-        } else {
-            get_changes_with_gitignore(
-                &codebase_path,
-                &existing_manifest,
-                gitignore_matcher.as_ref(),
-                self.config.verbose,
-            )?
-        };
+        let changes = self.detect_changes(&codebase_path, &existing_manifest, force)?;
+        stats.files_skipped += changes.too_large.len();
 
-        let mut stats = IndexingStats::default();
+        // Per-file counts for removed directories have to be read before
+        // their chunks go in one prefix delete
+        let dir_file_counts: HashMap<String, i64> =
+            if stats.removed_files.is_some() && !changes.removed_dirs.is_empty() {
+                crate::database::chunk_counts_by_file(&conn, &codebase_id)?
+                    .into_iter()
+                    .collect()
+            } else {
+                HashMap::new()
+            };
+
+        for dir in &changes.removed_dirs {
+            if self.config.verbose {
+                println!("Removing directory: {}", dir);
+            }
+            let deleted = delete_chunks_for_prefix(&conn, &codebase_id, dir)?;
+            stats.chunks_removed += deleted as usize;
+            for file_path in changes
+                .removed
+                .iter()
+                .filter(|p| p.starts_with(dir.as_str()))
+            {
+                stats.files_removed += 1;
+                if let Some(ref mut removed_files) = stats.removed_files {
+                    removed_files.push(RemovedFile {
+                        file_path: file_path.clone(),
+                        chunks_removed: dir_file_counts.get(file_path).copied().unwrap_or(0)
+                            as usize,
+                    });
+                }
+            }
+        }
 
-        for file_path in &changes.removed {
+        for file_path in changes.removed_outside_dirs() {
             if self.config.verbose {
                 println!("Removing: {}", file_path);
             }
             let deleted = delete_chunks_for_file(&conn, &codebase_id, file_path)?;
             stats.chunks_removed += deleted as usize;
             stats.files_removed += 1;
+            if let Some(ref mut removed_files) = stats.removed_files {
+                removed_files.push(RemovedFile {
+                    file_path: file_path.clone(),
+                    chunks_removed: deleted as usize,
+                });
+            }
+        }
+
+        if self.config.commit_limit.is_some() || self.config.commits_since.is_some() {
+            stats.commits_indexed =
+                self.index_commits(&conn, &codebase_path, &codebase_id, model)?;
         }
 
-        let files_to_index: Vec<(String, String)> =
+        let mut files_to_index: Vec<(String, ManifestEntry)> =
             changes.added.into_iter().chain(changes.modified).collect();
+        let deterministic = self
+            .config
+            .deterministic_order
+            .unwrap_or(get_config().indexing.deterministic_order);
+        if deterministic {
+            files_to_index.sort_by(|a, b| a.0.cmp(&b.0));
+        }
 
         stats.files_indexed = files_to_index.len();
 
+        // Files only touched get fresh entries, so later scans stop
+        // re-hashing them
+        let mut new_manifest = existing_manifest;
+        let touched = !changes.touched.is_empty();
+        new_manifest.extend(changes.touched);
+
         if files_to_index.is_empty() {
+            if let Some(tx) = run_tx {
+                tx.commit().map_err(CodeSearchError::Database)?;
+            }
+            if touched || force {
+                save_manifest_internal(&manifest_path, &new_manifest)?;
+            }
+            save_index_params(&manifest_path, &params)?;
             if self.config.verbose {
                 println!("No files to index");
             }
             stats.duration_ms = start.elapsed().as_millis() as u64;
+            crate::metrics::report_index(&codebase_id, &stats);
+            self.report(IndexingProgress::Done);
             return Ok(stats);
         }
 
-        let pb = if !self.config.verbose {
-            let pb = ProgressBar::new(files_to_index.len() as u64);
-            pb.set_style(
+        for (rel_path, _entry) in &files_to_index {
+            new_manifest.remove(rel_path);
+        }
+
+        // Left unset unless given, so split_file applies per-language sizes
+        let (chunk_size, chunk_overlap) = if self.config.overrides_chunking() {
+            (Some(chunk_size), Some(chunk_overlap))
+        } else {
+            (None, None)
+        };
+        let whole_file_max_lines = self.config.whole_file_max_lines;
+        let verbose = self.config.verbose;
+
+        // Embeddings already stored for a window's files are read as it is
+        // split, so chunks whose text didn't change skip the model. The
+        // producer thread reads over its own connection; a forced run has
+        // deleted them all already.
+        let stored_conn = if force {
+            None
+        } else {
+            // A run transaction that spilled to disk would lock that
+            // reader out until it commits
+            if run_tx.is_some() {
+                conn.pragma_update(None, "cache_spill", false)
+                    .map_err(CodeSearchError::Database)?;
+            }
+            Some(Mutex::new(self.open_db_read_only()?))
+        };
+
+        let index_file_names = self
+            .config
+            .index_file_names
+            .unwrap_or(get_config().indexing.index_file_names);
+        let index_non_utf8 = self.index_non_utf8();
+        let max_tokens = crate::embedding::ModelType::parse(model).max_tokens();
+        let normalize = get_config().indexing.normalize_whitespace;
+        let truncation_threshold = get_config().indexing.truncation_warning_threshold;
+        let on_error = self.config.on_error;
+
+        let (file_pb, insert_pb) = if verbose || self.config.progress.is_some() {
+            (None, None)
+        } else {
+            let file_pb = ProgressBar::new(files_to_index.len() as u64);
+            file_pb.set_style(
                 ProgressStyle::default_bar()
                     .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} ({eta})")
                     .unwrap()
                     .progress_chars("#>-"),
             );
-            Some(pb)
-        } else {
-            None
+            let insert_pb = ProgressBar::new_spinner();
+            insert_pb.set_style(
+                ProgressStyle::default_spinner()
+                    .template("{spinner:.yellow} {msg}")
+                    .unwrap(),
+            );
+            (Some(file_pb), Some(insert_pb))
         };
 
-        let mut new_manifest = existing_manifest;
+        // Files are split a window at a time on the producer thread and
+        // their chunks packed into embedding batches that a pool of workers
+        // embeds while earlier batches are inserted here. A file never spans
+        // two batches, so its chunks are replaced in one insert.
+        let batch_size = get_batch_size().max(1);
+        let indexing_config = &get_config().indexing;
+        let workers = if deterministic {
+            1
+        } else {
+            indexing_config.embedding_workers
+        };
+        let mut windows = files_to_index.chunks(FILES_PER_WINDOW);
+        let mut split: std::collections::VecDeque<(String, Vec<Chunk>)> = Default::default();
+        let mut batch = FileBatch::default();
+        let mut chunks_reused = 0;
+        let mut chunks_truncated = 0;
+        let mut truncated_files = Vec::new();
+        let mut file_error = None;
+        let produce = std::iter::from_fn(|| loop {
+            if batch.chunks.len() >= batch_size {
+                return Some(std::mem::take(&mut batch));
+            }
+            if let Some((rel_path, chunks)) = split.pop_front() {
+                batch.files.push(rel_path);
+                batch.chunks.extend(chunks);
+                continue;
+            }
+            let Some(files) = windows.next() else {
+                return (!batch.files.is_empty()).then(|| std::mem::take(&mut batch));
+            };
+            let stored: Vec<HashMap<String, Vec<f32>>> = match &stored_conn {
+                Some(stored_conn) => {
+                    let stored_conn = stored_conn.lock().unwrap_or_else(|e| e.into_inner());
+                    let stored = files
+                        .iter()
+                        .map(|(rel_path, _entry)| {
+                            embeddings_by_content_hash(&stored_conn, &codebase_id, rel_path)
+                        })
+                        .collect::<Result<_>>();
+                    match stored {
+                        Ok(stored) => stored,
+                        Err(e) => {
+                            file_error = Some(e);
+                            return None;
+                        }
+                    }
+                }
+                None => vec![HashMap::new(); files.len()],
+            };
+            // Collecting a parallel iterator keeps the order of files_to_index
+            let processed: Vec<Result<ProcessedFile>> = files
+                .par_iter()
+                .zip(&stored)
+                .map(|((rel_path, entry), stored)| {
+                    let full_path = codebase_path.join(rel_path);
+                    process_file(
+                        &full_path,
+                        rel_path,
+                        &codebase_id,
+                        &entry.hash,
+                        chunk_size,
+                        chunk_overlap,
+                        whole_file_max_lines,
+                        index_file_names,
+                        index_non_utf8,
+                        blame_authors,
+                        stored,
+                    )
+                })
+                .collect();
+            for ((rel_path, _entry), result) in files.iter().zip(processed) {
+                let mut file = match result {
+                    Ok(file) => file,
+                    Err(e) => match on_error {
+                        ErrorPolicy::Skip => {
+                            if verbose {
+                                eprintln!("Skipping file {} ({})", rel_path, e);
+                            }
+                            // Its old chunks are still removed
+                            split.push_back((rel_path.clone(), Vec::new()));
+                            continue;
+                        }
+                        ErrorPolicy::Abort => {
+                            file_error = Some(e);
+                            return None;
+                        }
+                    },
+                };
+                chunks_reused += file.reused;
+                let truncated = count_truncated(&file.chunks, max_tokens, normalize);
+                chunks_truncated += truncated;
+                if truncation_threshold > 0 && truncated >= truncation_threshold {
+                    let language = file.chunks.first().and_then(|c| c.language.as_deref());
+                    let (size_key, lines_per_chunk) = match language
+                        .filter(|_| chunk_size.is_none())
+                        .and_then(|language| params.per_language.get(language))
+                    {
+                        Some(&(size, _)) => (
+                            format!("chunking.per_language.{}.chunk_size", language.unwrap()),
+                            size,
+                        ),
+                        None => ("chunking.chunk_size".to_string(), params.chunk_size),
+                    };
+                    let suggestion = truncation_suggestion(
+                        rel_path,
+                        language,
+                        truncated,
+                        model,
+                        max_tokens,
+                        &size_key,
+                        lines_per_chunk,
+                    );
+                    if verbose {
+                        eprintln!("{}", suggestion);
+                    }
+                    truncated_files.push(TruncatedFile {
+                        file_path: rel_path.clone(),
+                        chunks_truncated: truncated,
+                        suggestion,
+                    });
+                }
+                if deterministic {
+                    sort_chunks_by_line(&mut file.chunks);
+                }
+                split.push_back((rel_path.clone(), file.chunks));
+            }
+        });
+
+        let zero_filled = AtomicUsize::new(0);
+        let embedding_model = EmbeddingModel::resolve(model);
+        let embed = |batch: &mut FileBatch| -> Result<()> {
+            let zeroed = embed_missing(
+                &mut batch.chunks,
+                &embedding_model,
+                batch_size,
+                normalize,
+                on_error,
+            )?;
+            zero_filled.fetch_add(zeroed, Ordering::Relaxed);
+            Ok(())
+        };
 
-        for (rel_path, _hash) in &files_to_index {
-            new_manifest.remove(rel_path);
+        let progress = self.config.progress.clone();
+        let total_files = files_to_index.len();
+        let mut files_done = 0;
+        if let Some(ref callback) = progress {
+            (callback.0)(IndexingProgress::Embedding {
+                done: 0,
+                total: total_files,
+            });
         }
-
-        let chunk_size = self.config.chunk_size;
-        let chunk_overlap = self.config.chunk_overlap;
-        let verbose = self.config.verbose;
-        let model_owned = model.to_string();
-
-        let all_chunks: Vec<Vec<Chunk>> = files_to_index
-            .par_iter()
-            .filter_map(|(rel_path, hash)| {
-                let full_path = codebase_path.join(rel_path);
-                process_file(
-                    &full_path,
-                    rel_path,
-                    &codebase_id,
-                    hash,
-                    &model_owned,
-                    chunk_size,
-                    chunk_overlap,
-                    verbose,
-                )
-                .ok()
-            })
-            .collect();
-
-        for chunks in all_chunks {
-            if !chunks.is_empty() {
-                let inserted = insert_chunks(&conn, &chunks)?;
-                stats.chunks_created += inserted as usize;
+        let consume = |batch: FileBatch| -> Result<()> {
+            // A modified file may now split into fewer or shifted chunks,
+            // which INSERT OR REPLACE on matching line ranges would leave
+            // behind, so its old chunks go first
+            let inserted = replace_file_chunks(&conn, &codebase_id, &batch.files, &batch.chunks)?;
+            stats.chunks_created += inserted as usize;
+            files_done += batch.files.len();
+            if let Some(ref callback) = progress {
+                (callback.0)(IndexingProgress::Embedding {
+                    done: files_done,
+                    total: total_files,
+                });
             }
-            if let Some(ref pb) = pb {
-                pb.inc(1);
+            if let Some(ref pb) = file_pb {
+                pb.inc(batch.files.len() as u64);
             }
-        }
+            if let Some(ref pb) = insert_pb {
+                pb.set_message(format!(
+                    "{} chunks embedded and inserted",
+                    stats.chunks_created
+                ));
+            }
+            Ok(())
+        };
 
-        if let Some(pb) = pb {
-            pb.finish_and_clear();
+        let result = run_embedding_pipeline(
+            produce,
+            embed,
+            consume,
+            workers,
+            indexing_config.pipeline_buffer,
+        )
+        .and_then(|()| file_error.map_or(Ok(()), Err));
+        for pb in [file_pb, insert_pb].into_iter().flatten() {
+            if result.is_ok() {
+                pb.finish_and_clear();
+            } else {
+                pb.abandon();
+            }
+        }
+        result?;
+        stats.chunks_reused += chunks_reused;
+        stats.chunks_truncated += chunks_truncated;
+        stats.truncated_files.extend(truncated_files);
+        stats.chunks_zero_embedded = zero_filled.into_inner();
+        self.report(IndexingProgress::Inserting);
+
+        if let Some(tx) = run_tx {
+            tx.commit().map_err(CodeSearchError::Database)?;
         }
 
-        for (rel_path, hash) in &files_to_index {
-            new_manifest.insert(rel_path.clone(), hash.clone());
+        for (rel_path, entry) in &files_to_index {
+            new_manifest.insert(rel_path.clone(), entry.clone());
         }
 
         save_manifest_internal(&manifest_path, &new_manifest)?;
-
-        // Register codebase metadata
-        let codebase_name = codebase_path
-            .file_name()
-            .and_then(|n| n.to_str())
-            .unwrap_or("unknown")
-            .to_string();
+        save_index_params(&manifest_path, &params)?;
+
+        // Register codebase metadata, keeping an earlier label unless replaced
+        let existing_name = crate::database::get_codebase_metadata(&conn, &codebase_id)
+            .ok()
+            .flatten()
+            .map(|m| m.name);
+        let codebase_name = match (&self.config.label, existing_name) {
+            (Some(label), _) => label.clone(),
+            (None, Some(name)) => name,
+            (None, None) => codebase_path
+                .file_name()
+                .and_then(|n| n.to_str())
+                .unwrap_or("unknown")
+                .to_string(),
+        };
         let codebase_path_str = codebase_path.to_string_lossy().to_string();
 
         if let Err(e) = crate::database::register_codebase(
@@ -267,56 +773,477 @@ impl Indexer {
             }
         }
 
+        stats.duration_ms = start.elapsed().as_millis() as u64;
+        crate::metrics::report_index(&codebase_id, &stats);
+        self.report(IndexingProgress::Done);
+        Ok(stats)
+    }
+
+    fn report(&self, progress: IndexingProgress) {
+        if let Some(ref callback) = self.config.progress {
+            (callback.0)(progress);
+        }
+    }
+
+    /// Re-index one file of a codebase, as after an editor saves it: its
+    /// chunks are replaced and its manifest entry updated, leaving the rest
+    /// of the codebase alone. A file that is gone, or that indexing would
+    /// skip, just loses its chunks and entry. Returns the chunks created.
+    pub fn reindex_file<P: AsRef<Path>>(
+        &mut self,
+        codebase_path: P,
+        rel_path: &str,
+    ) -> Result<usize> {
+        let stats = self.index_files(codebase_path, &[rel_path.to_string()])?;
+        Ok(stats.chunks_created)
+    }
+
+    /// Re-index just `rel_paths` of a codebase, as when CI already knows
+    /// which files changed, without walking the tree. Each file is handled
+    /// as by [`reindex_file`](Self::reindex_file). Paths may be relative to
+    /// the root or absolute, but one outside the root fails the call before
+    /// anything is written. A file that cannot be processed is skipped or
+    /// fails the call as `on_error` says, as in a full run.
+    pub fn index_files<P: AsRef<Path>>(
+        &mut self,
+        codebase_path: P,
+        rel_paths: &[String],
+    ) -> Result<IndexingStats> {
+        let start = Instant::now();
+        let codebase_path = codebase_path.as_ref().canonicalize()?;
+        let codebase_id = get_codebase_hash(&codebase_path);
+        // Index paths are relative to the root, whatever form was given
+        let rel_paths = rel_paths
+            .iter()
+            .map(|rel_path| relative_to_root(&codebase_path, rel_path))
+            .collect::<Result<Vec<_>>>()?;
+        let global_config = get_config();
+        let model = self
+            .config
+            .model_name
+            .as_deref()
+            .unwrap_or(global_config.model_name());
+        let blame_authors = self.blame_authors()?;
+
+        let conn = self.open_db()?;
+        let manifest_path = self.manifest_path(&codebase_id)?;
+        let mut manifest = if manifest_path.exists() {
+            load_manifest_internal(&manifest_path)?
+        } else {
+            HashMap::new()
+        };
+        let matcher = self.gitignore_matcher(&codebase_path)?;
+
+        // Keep the chunking the rest of the codebase was indexed with
+        let (chunk_size, chunk_overlap) =
+            match crate::database::get_chunking_params(&conn, &codebase_id)? {
+                Some(pinned) => pinned,
+                None => {
+                    let requested =
+                        resolve_chunk_params(self.config.chunk_size, self.config.chunk_overlap)?;
+                    crate::database::set_chunking_params(
+                        &conn,
+                        &codebase_id,
+                        requested.0,
+                        requested.1,
+                    )?;
+                    requested
+                }
+            };
+        // Per-language sizes apply as in a full run when nothing overrode
+        // the configured chunking
+        let (chunk_size, chunk_overlap) = if !self.config.overrides_chunking()
+            && (chunk_size, chunk_overlap) == resolve_chunk_params(None, None)?
+        {
+            (None, None)
+        } else {
+            (Some(chunk_size), Some(chunk_overlap))
+        };
+        let index_file_names = self
+            .config
+            .index_file_names
+            .unwrap_or(global_config.indexing.index_file_names);
+        let index_non_utf8 = self.index_non_utf8();
+        let embedding_model = EmbeddingModel::resolve(model);
+
+        // As in a full run, `ErrorPolicy::Abort` rolls back every write
+        let run_tx = match self.config.on_error {
+            ErrorPolicy::Abort => Some(
+                conn.unchecked_transaction()
+                    .map_err(CodeSearchError::Database)?,
+            ),
+            ErrorPolicy::Skip => None,
+        };
+
+        let mut stats = IndexingStats::default();
+        for rel_path in &rel_paths {
+            let full_path = codebase_path.join(rel_path);
+            let gitignored = matcher
+                .as_ref()
+                .is_some_and(|matcher| matcher.is_ignored(&full_path));
+            let too_large = too_large(&full_path, self.max_file_bytes()).is_some();
+            if !full_path.is_file() || gitignored || too_large || skip_reason(rel_path).is_some() {
+                let removed = delete_chunks_for_file(&conn, &codebase_id, rel_path)?;
+                if manifest.remove(rel_path).is_some() || removed > 0 {
+                    stats.files_removed += 1;
+                    stats.chunks_removed += removed as usize;
+                } else {
+                    stats.files_skipped += 1;
+                }
+                if self.config.verbose {
+                    println!("Removed {} ({} chunks)", rel_path, removed);
+                }
+                continue;
+            }
+
+            let stored = embeddings_by_content_hash(&conn, &codebase_id, rel_path)?;
+            let processed = fs::metadata(&full_path)
+                .and_then(|metadata| Ok((metadata, fs::read(&full_path)?)))
+                .map_err(CodeSearchError::from)
+                .and_then(|(metadata, content)| {
+                    let hash = hash_file_content(&content);
+                    let file = process_file(
+                        &full_path,
+                        rel_path,
+                        &codebase_id,
+                        &hash,
+                        chunk_size,
+                        chunk_overlap,
+                        self.config.whole_file_max_lines,
+                        index_file_names,
+                        index_non_utf8,
+                        blame_authors,
+                        &stored,
+                    )?;
+                    Ok((ManifestEntry::new(hash, &metadata), file))
+                });
+            let (entry, mut file) = match processed {
+                Ok(processed) => processed,
+                Err(e) if self.config.on_error == ErrorPolicy::Skip => {
+                    if self.config.verbose {
+                        eprintln!("Skipping file {} ({})", rel_path, e);
+                    }
+                    // Its old chunks are still removed
+                    let removed = delete_chunks_for_file(&conn, &codebase_id, rel_path)?;
+                    manifest.remove(rel_path);
+                    stats.files_skipped += 1;
+                    stats.chunks_removed += removed as usize;
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+            stats.chunks_zero_embedded += embed_missing(
+                &mut file.chunks,
+                &embedding_model,
+                get_batch_size().max(1),
+                global_config.indexing.normalize_whitespace,
+                self.config.on_error,
+            )?;
+
+            delete_chunks_for_file(&conn, &codebase_id, rel_path)?;
+            let inserted = if file.chunks.is_empty() {
+                0
+            } else {
+                insert_chunks(&conn, &file.chunks)? as usize
+            };
+            manifest.insert(rel_path.clone(), entry);
+            stats.files_indexed += 1;
+            stats.chunks_created += inserted;
+            stats.chunks_reused += file.reused;
+
+            if self.config.verbose {
+                println!(
+                    "Re-indexed {} ({} chunks, {} embeddings reused)",
+                    rel_path, inserted, file.reused
+                );
+            }
+        }
+        if let Some(tx) = run_tx {
+            tx.commit().map_err(CodeSearchError::Database)?;
+        }
+        save_manifest_internal(&manifest_path, &manifest)?;
+
+        // A codebase first indexed this way still needs its metadata
+        if crate::database::get_codebase_metadata(&conn, &codebase_id)?.is_none() {
+            let codebase_name = match &self.config.label {
+                Some(label) => label.clone(),
+                None => codebase_path
+                    .file_name()
+                    .and_then(|n| n.to_str())
+                    .unwrap_or("unknown")
+                    .to_string(),
+            };
+            crate::database::register_codebase(
+                &conn,
+                &codebase_id,
+                &codebase_name,
+                &codebase_path.to_string_lossy(),
+                Some(model),
+                None,
+            )?;
+        }
+
         stats.duration_ms = start.elapsed().as_millis() as u64;
         Ok(stats)
     }
 
+    /// The storage location this indexer writes to: its own `data_dir`,
+    /// else the process-wide `--data-dir`, else the configured one
+    fn data_dir(&self) -> Option<PathBuf> {
+        self.config
+            .data_dir
+            .clone()
+            .or_else(crate::config::data_dir_override)
+    }
+
+    fn open_db(&self) -> Result<Connection> {
+        let db_path = resolve_db_path(self.data_dir().as_deref(), &get_config())?;
+        init_db_at(&db_path)
+    }
+
+    /// A second connection to the same database, for reads off the thread
+    /// that owns the one from [`open_db`](Self::open_db)
+    fn open_db_read_only(&self) -> Result<Connection> {
+        let db_path = resolve_db_path(self.data_dir().as_deref(), &get_config())?;
+        Connection::open_with_flags(&db_path, rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY)
+            .map_err(CodeSearchError::Database)
+    }
+
+    fn manifest_path(&self, codebase_id: &str) -> Result<PathBuf> {
+        Ok(resolve_manifest_dir(self.data_dir().as_deref())?.join(format!("{}.json", codebase_id)))
+    }
+
+    /// Walk the codebase for files added, modified or removed since
+    /// `manifest` was written; every file counts as added with `force`
+    fn detect_changes(
+        &self,
+        codebase_path: &Path,
+        manifest: &Manifest,
+        force: bool,
+    ) -> Result<Changes> {
+        let gitignore_matcher = self.gitignore_matcher(codebase_path)?;
+        let follow_symlinks = self
+            .config
+            .follow_symlinks
+            .unwrap_or(get_config().indexing.follow_symlinks);
+        let max_file_bytes = self.max_file_bytes();
+        self.report(IndexingProgress::Scanning);
+        if force {
+            get_all_files(
+                codebase_path,
+                gitignore_matcher.as_ref(),
+                follow_symlinks,
+                max_file_bytes,
+                self.config.verbose,
+            )
+        } else {
+            get_changes_with_gitignore(
+                codebase_path,
+                manifest,
+                gitignore_matcher.as_ref(),
+                follow_symlinks,
+                max_file_bytes,
+                self.config.verbose,
+            )
+        }
+    }
+
+    /// Fill `stats` with what a run would index and remove, reading the
+    /// index and manifest but writing neither
+    fn project_changes(
+        &self,
+        conn: &Connection,
+        codebase_path: &Path,
+        codebase_id: &str,
+        force: bool,
+        stats: &mut IndexingStats,
+    ) -> Result<()> {
+        let manifest_path = self.manifest_path(codebase_id)?;
+        let manifest = if manifest_path.exists() {
+            load_manifest_internal(&manifest_path)?
+        } else {
+            HashMap::new()
+        };
+        let changes = self.detect_changes(codebase_path, &manifest, force)?;
+
+        let per_file = crate::database::chunk_counts_by_file(conn, codebase_id)?;
+        let removed: Vec<(String, i64)> = if force {
+            per_file
+        } else {
+            let counts: HashMap<String, i64> = per_file.into_iter().collect();
+            changes
+                .removed
+                .iter()
+                .map(|path| (path.clone(), counts.get(path).copied().unwrap_or(0)))
+                .collect()
+        };
+        for (file_path, chunks) in removed {
+            stats.files_removed += 1;
+            stats.chunks_removed += chunks as usize;
+            if let Some(ref mut removed_files) = stats.removed_files {
+                removed_files.push(RemovedFile {
+                    file_path,
+                    chunks_removed: chunks as usize,
+                });
+            }
+        }
+
+        stats.files_indexed = changes.added.len() + changes.modified.len();
+        stats.files_skipped += changes.too_large.len();
+        stats.dry_run = true;
+        Ok(())
+    }
+
+    /// The size limit for indexed files, if any
+    fn max_file_bytes(&self) -> Option<u64> {
+        size_limit(
+            self.config
+                .max_file_bytes
+                .unwrap_or(get_config().indexing.max_file_bytes),
+        )
+    }
+
+    fn index_non_utf8(&self) -> bool {
+        self.config
+            .index_non_utf8
+            .unwrap_or(get_config().indexing.index_non_utf8)
+    }
+
+    /// The gitignore rules for `codebase_path`, or `None` with gitignore
+    /// filtering off
+    fn gitignore_matcher(&self, codebase_path: &Path) -> Result<Option<GitignoreMatcher>> {
+        ignore_matcher(
+            codebase_path,
+            self.config.use_gitignore,
+            &self.gitignore_options(),
+        )
+    }
+
+    fn gitignore_options(&self) -> GitignoreOptions {
+        let mut options = GitignoreOptions::from_config(&get_config().indexing);
+        options.global_gitignore |= self.config.use_global_gitignore;
+        options
+    }
+
+    /// Whether to record chunk authors, failing when that is asked for in a
+    /// build without the `git` feature
+    fn blame_authors(&self) -> Result<bool> {
+        let blame = self
+            .config
+            .blame_authors
+            .unwrap_or(get_config().indexing.blame_authors);
+        if blame && !cfg!(feature = "git") {
+            return Err(CodeSearchError::InvalidConfiguration(
+                "recording authors with git blame requires the `git` feature".to_string(),
+            ));
+        }
+        Ok(blame)
+    }
+
+    #[cfg(feature = "git")]
+    fn index_commits(
+        &self,
+        conn: &Connection,
+        codebase_path: &Path,
+        codebase_id: &str,
+        model: &str,
+    ) -> Result<usize> {
+        let indexed = crate::git_history::index_commits(
+            conn,
+            codebase_path,
+            codebase_id,
+            model,
+            self.config.commit_limit,
+            self.config.commits_since.as_deref(),
+        )?;
+        if self.config.verbose {
+            println!("Indexed {} commit message(s)", indexed);
+        }
+        Ok(indexed)
+    }
+
+    #[cfg(not(feature = "git"))]
+    fn index_commits(
+        &self,
+        _conn: &Connection,
+        _codebase_path: &Path,
+        _codebase_id: &str,
+        _model: &str,
+    ) -> Result<usize> {
+        Err(CodeSearchError::InvalidConfiguration(
+            "indexing commit messages requires the `git` feature".to_string(),
+        ))
+    }
+
     pub fn get_stats<P: AsRef<Path>>(
         &self,
         codebase_path: P,
     ) -> Result<Option<crate::database::Stats>> {
         let codebase_path = codebase_path.as_ref().canonicalize()?;
         let codebase_id = get_codebase_hash(&codebase_path);
-        let conn = init_db()?;
+        let conn = self.open_db()?;
         get_codebase_stats(&conn, &codebase_id)
     }
 }
 
+/// Chunks of one file, ready for the embedding pipeline
+struct ProcessedFile {
+    chunks: Vec<Chunk>,
+    /// Embeddings taken over from the stored chunks
+    reused: usize,
+}
+
+/// Read and split one file. Chunks whose content hash is in `stored` take
+/// that embedding; the rest are left empty for the embedding pipeline.
 #[allow(clippy::too_many_arguments)]
 fn process_file(
     file_path: &Path,
     rel_path: &str,
     codebase_id: &str,
     hash: &str,
-    model: &str,
     chunk_size: Option<usize>,
     chunk_overlap: Option<usize>,
-    verbose: bool,
-) -> Result<Vec<Chunk>> {
-    let content = match fs::read_to_string(file_path) {
-        Ok(c) => c,
-        Err(e) => {
-            if verbose {
-                eprintln!("Skipping file {} (read error: {})", file_path.display(), e);
-            }
-            return Ok(Vec::new());
-        }
+    whole_file_max_lines: Option<usize>,
+    index_file_names: bool,
+    index_non_utf8: bool,
+    blame_authors: bool,
+    stored: &HashMap<String, Vec<f32>>,
+) -> Result<ProcessedFile> {
+    let mut processed = ProcessedFile {
+        chunks: Vec::new(),
+        reused: 0,
     };
+    let content =
+        read_source(file_path, index_non_utf8).map_err(|_| CodeSearchError::FileRead {
+            path: file_path.to_string_lossy().to_string(),
+        })?;
 
     if content.is_empty() {
-        return Ok(Vec::new());
+        return Ok(processed);
     }
 
-    let code_chunks = split_file(rel_path, &content, chunk_size, chunk_overlap);
+    let mut code_chunks = split_file_with_threshold(
+        rel_path,
+        &content,
+        chunk_size,
+        chunk_overlap,
+        whole_file_max_lines,
+    );
 
     if code_chunks.is_empty() {
-        return Ok(Vec::new());
+        return Ok(processed);
+    }
+    if index_file_names {
+        code_chunks.push(file_name_chunk(rel_path));
     }
 
-    let chunks: Vec<Chunk> = code_chunks
+    let mut chunks: Vec<Chunk> = code_chunks
         .into_iter()
         .map(|chunk| {
-            let embedding = get_embedding_with_model(&chunk.content, model);
+            let embedding = stored
+                .get(&chunk_content_hash(&chunk.content))
+                .cloned()
+                .unwrap_or_default();
             Chunk {
                 id: None,
                 codebase_id: codebase_id.to_string(),
@@ -327,26 +1254,382 @@ fn process_file(
                 language: Some(chunk.language),
                 embedding,
                 hash: hash.to_string(),
+                chunk_kind: Some(chunk.kind),
+                author: None,
             }
         })
         .collect();
 
-    Ok(chunks)
+    if blame_authors {
+        assign_authors(file_path, &mut chunks);
+    }
+
+    processed.reused = chunks.iter().filter(|c| !c.embedding.is_empty()).count();
+    processed.chunks = chunks;
+
+    Ok(processed)
 }
 
-fn get_all_files(
+/// Give each chunk the author of most of its lines, from one `git blame` of
+/// the file. A file git doesn't track keeps authorless chunks.
+#[cfg(feature = "git")]
+fn assign_authors(file_path: &Path, chunks: &mut [Chunk]) {
+    let Ok(line_authors) = crate::git_history::blame_line_authors(file_path) else {
+        return;
+    };
+    for chunk in chunks {
+        chunk.author = crate::git_history::majority_author(
+            &line_authors,
+            chunk.start_line as usize,
+            chunk.end_line as usize,
+        );
+    }
+}
+
+#[cfg(not(feature = "git"))]
+fn assign_authors(_file_path: &Path, _chunks: &mut [Chunk]) {}
+
+/// The text embedded for each chunk that has no embedding yet: its content,
+/// passed through [`normalize_whitespace`] when `normalize` is set
+fn embedding_inputs(chunks: &[Chunk], normalize: bool) -> Vec<String> {
+    chunks
+        .iter()
+        .filter(|c| c.embedding.is_empty())
+        .map(|c| embedding_input(&c.content, normalize))
+        .collect()
+}
+
+/// Chunks still to be embedded whose text, by the ~4 characters per token
+/// estimate, is longer than `max_tokens`
+fn count_truncated(chunks: &[Chunk], max_tokens: usize, normalize: bool) -> usize {
+    embedding_inputs(chunks, normalize)
+        .iter()
+        .filter(|text| crate::context_enriched::estimate_tokens(text) > max_tokens)
+        .count()
+}
+
+/// Advice for a file `truncated` of whose chunks ran past the model's limit
+fn truncation_suggestion(
+    rel_path: &str,
+    language: Option<&str>,
+    truncated: usize,
+    model: &str,
+    max_tokens: usize,
+    size_key: &str,
+    chunk_size: usize,
+) -> String {
+    let language = language
+        .map(|lang| format!(" ({} files)", lang))
+        .unwrap_or_default();
+    format!(
+        "{}: {} chunks are longer than the {} token limit of {}; lower {} \
+         below {} lines or index with a model that takes longer inputs{}",
+        rel_path, truncated, max_tokens, model, size_key, chunk_size, language
+    )
+}
+
+fn embedding_input(content: &str, normalize: bool) -> String {
+    if normalize {
+        normalize_whitespace(content)
+    } else {
+        content.to_string()
+    }
+}
+
+/// Embed the chunks that have no embedding yet in one batched model call.
+/// Returns the number of chunks zero-filled after a short result.
+fn embed_missing(
+    chunks: &mut [Chunk],
+    model: &EmbeddingModel,
+    batch_size: usize,
+    normalize: bool,
+    on_error: ErrorPolicy,
+) -> Result<usize> {
+    let contents = embedding_inputs(chunks, normalize);
+    if contents.is_empty() {
+        return Ok(0);
+    }
+    // A model that can't load leaves zero vectors, found only by full-text
+    // search, rather than failing the run
+    let embeddings = model
+        .embed_documents(&contents, batch_size)
+        .unwrap_or_else(|_| vec![vec![0.0; model.embedding_dimension()]; contents.len()]);
+    assign_embeddings(
+        chunks.iter_mut().filter(|c| c.embedding.is_empty()),
+        embeddings,
+        model.name(),
+        on_error,
+    )
+}
+
+fn sort_chunks_by_line(chunks: &mut [Chunk]) {
+    chunks.sort_by_key(|c| (c.start_line, c.end_line));
+}
+
+/// Give each chunk its embedding from a batch result, in order. If the model
+/// returned fewer embeddings than chunks, `Abort` fails and `Skip` stores
+/// zero embeddings for the rest, which only full-text search can then find.
+/// Returns the number of chunks zero-filled.
+fn assign_embeddings<'a>(
+    chunks: impl IntoIterator<Item = &'a mut Chunk>,
+    embeddings: Vec<Vec<f32>>,
+    model: &str,
+    on_error: ErrorPolicy,
+) -> Result<usize> {
+    let returned = embeddings.len();
+    let mut embeddings = embeddings.into_iter();
+    let mut zero_filled = 0;
+    for chunk in chunks {
+        chunk.embedding = match embeddings.next() {
+            Some(embedding) => embedding,
+            None if on_error == ErrorPolicy::Abort => {
+                return Err(CodeSearchError::EmbeddingInference(format!(
+                    "model '{}' returned only {} embeddings for a batch; none for {}:{}",
+                    model, returned, chunk.file_path, chunk.start_line
+                )));
+            }
+            None => {
+                zero_filled += 1;
+                zero_embedding_with_model(model)
+            }
+        };
+    }
+    Ok(zero_filled)
+}
+
+/// Files split on the indexing producer at a time, in parallel
+const FILES_PER_WINDOW: usize = 64;
+
+/// The chunks of whole files, embedded and stored together
+#[derive(Default)]
+struct FileBatch {
+    /// Every file the batch replaces, including ones left without chunks
+    files: Vec<String>,
+    chunks: Vec<Chunk>,
+}
+
+/// Embed the batches yielded by `produce` on `workers` threads and hand each
+/// embedded batch to `consume` on the calling thread. Both channels hold at
+/// most `buffer` batches, so about `2 * buffer + workers + 2` batches are
+/// alive at once however large the input is. With one worker, batches reach
+/// `consume` in the order they were produced.
+///
+/// The first error from `embed` or `consume` stops the pipeline and is
+/// returned.
+fn run_embedding_pipeline<B, P, E, C>(
+    produce: P,
+    embed: E,
+    mut consume: C,
+    workers: usize,
+    buffer: usize,
+) -> Result<()>
+where
+    B: Send,
+    P: Iterator<Item = B> + Send,
+    E: Fn(&mut B) -> Result<()> + Sync,
+    C: FnMut(B) -> Result<()>,
+{
+    let (batch_tx, batch_rx) = mpsc::sync_channel::<B>(buffer.max(1));
+    let (done_tx, done_rx) = mpsc::sync_channel::<Result<B>>(buffer.max(1));
+    let batch_rx = Mutex::new(batch_rx);
+    let cancelled = AtomicBool::new(false);
+
+    std::thread::scope(|scope| {
+        let cancelled = &cancelled;
+        scope.spawn(move || {
+            for batch in produce {
+                if cancelled.load(Ordering::Relaxed) || batch_tx.send(batch).is_err() {
+                    break;
+                }
+            }
+        });
+
+        for _ in 0..workers.max(1) {
+            let done_tx = done_tx.clone();
+            let batch_rx = &batch_rx;
+            let embed = &embed;
+            scope.spawn(move || loop {
+                let next = batch_rx.lock().unwrap_or_else(|e| e.into_inner()).recv();
+                let Ok(mut batch) = next else {
+                    break;
+                };
+                // Once cancelled, keep draining so the producer never blocks
+                if cancelled.load(Ordering::Relaxed) {
+                    continue;
+                }
+                let embedded = embed(&mut batch).map(|()| batch);
+                if done_tx.send(embedded).is_err() {
+                    cancelled.store(true, Ordering::Relaxed);
+                }
+            });
+        }
+        drop(done_tx);
+
+        for embedded in done_rx {
+            if let Err(e) = embedded.and_then(&mut consume) {
+                cancelled.store(true, Ordering::Relaxed);
+                return Err(e);
+            }
+        }
+        Ok(())
+    })
+}
+
+/// Regular files under `codebase_path`. With `follow_symlinks`, symlinked
+/// files and directories are walked too, and a file reachable under several
+/// paths is listed once.
+fn walk_files(codebase_path: &Path, follow_symlinks: bool) -> Vec<walkdir::DirEntry> {
+    let entries: Vec<walkdir::DirEntry> = walkdir::WalkDir::new(codebase_path)
+        .follow_links(follow_symlinks)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .collect();
+
+    if follow_symlinks {
+        dedup_symlinked(entries)
+    } else {
+        entries
+    }
+}
+
+/// Keep one entry per real file among `entries`: the one at the file's real
+/// path when the walk reached it, otherwise the one with the smallest path.
+/// Entries whose real path cannot be resolved are dropped.
+fn dedup_symlinked(entries: Vec<walkdir::DirEntry>) -> Vec<walkdir::DirEntry> {
+    let mut kept: HashMap<PathBuf, usize> = HashMap::new();
+    for (i, entry) in entries.iter().enumerate() {
+        let Ok(real) = entry.path().canonicalize() else {
+            continue;
+        };
+        match kept.entry(real) {
+            Entry::Vacant(slot) => {
+                slot.insert(i);
+            }
+            Entry::Occupied(mut slot) => {
+                let current = entries[*slot.get()].path();
+                let real = slot.key().as_path();
+                if entry.path() == real || (current != real && entry.path() < current) {
+                    slot.insert(i);
+                }
+            }
+        }
+    }
+
+    let kept: HashSet<usize> = kept.into_values().collect();
+    entries
+        .into_iter()
+        .enumerate()
+        .filter(|(i, _)| kept.contains(i))
+        .map(|(_, entry)| entry)
+        .collect()
+}
+
+/// The `chunking.per_language` sizes and overlaps in lines
+fn resolved_per_language() -> BTreeMap<String, (usize, usize)> {
+    get_config()
+        .chunking
+        .per_language
+        .iter()
+        .map(|(language, params)| {
+            let overlap = params.chunk_overlap.resolve(params.chunk_size);
+            (language.clone(), (params.chunk_size, overlap))
+        })
+        .collect()
+}
+
+/// Whether the codebase's chunks were made with other settings than
+/// `params`. Without recorded parameters, as for manifests written before
+/// they were, only the chunking pinned in the index can be compared.
+fn index_params_changed(
+    conn: &Connection,
+    codebase_id: &str,
+    manifest_path: &Path,
+    params: &IndexParams,
+) -> Result<bool> {
+    Ok(match load_index_params(manifest_path)? {
+        Some(recorded) => !recorded.produces_same_chunks(params),
+        None => crate::database::get_chunking_params(conn, codebase_id)?
+            .is_some_and(|pinned| pinned != (params.chunk_size, params.chunk_overlap)),
+    })
+}
+
+/// `path`, relative to `root` or absolute, as a path relative to the
+/// canonical `root`. `..` is resolved without touching the filesystem, so
+/// a file that is gone still maps to its entry; an existing file is also
+/// checked through its symlinks.
+fn relative_to_root(root: &Path, path: &str) -> Result<String> {
+    let outside = || {
+        CodeSearchError::InvalidInput(format!(
+            "{} is outside the codebase root {}",
+            path,
+            root.display()
+        ))
+    };
+    let mut full = PathBuf::new();
+    for component in root.join(path).components() {
+        match component {
+            std::path::Component::ParentDir => {
+                full.pop();
+            }
+            std::path::Component::CurDir => {}
+            component => full.push(component),
+        }
+    }
+    let rel = full.strip_prefix(root).map_err(|_| outside())?;
+    if rel.as_os_str().is_empty() {
+        return Err(outside());
+    }
+    if let Ok(real) = full.canonicalize() {
+        if !real.starts_with(root) {
+            return Err(outside());
+        }
+    }
+    Ok(rel.to_string_lossy().to_string())
+}
+
+/// The ignore rules for `codebase_path`: its gitignore sources when
+/// `use_gitignore` is set, and its `.code-search-ignore` either way
+fn ignore_matcher(
+    codebase_path: &Path,
+    use_gitignore: bool,
+    options: &GitignoreOptions,
+) -> Result<Option<GitignoreMatcher>> {
+    if use_gitignore {
+        Ok(Some(GitignoreMatcher::with_options(
+            codebase_path,
+            options,
+        )?))
+    } else {
+        Ok(GitignoreMatcher::search_ignore_only(codebase_path)?)
+    }
+}
+
+/// `SkipReason::TooLarge` if the file at `path` is larger than `limit` bytes
+fn too_large(path: &Path, limit: Option<u64>) -> Option<SkipReason> {
+    let limit = limit?;
+    let size = fs::metadata(path).ok()?.len();
+    (size > limit).then_some(SkipReason::TooLarge { size, limit })
+}
+
+/// Whether the file at `entry` is larger than `limit` bytes
+fn exceeds_size_limit(entry: &walkdir::DirEntry, limit: Option<u64>) -> bool {
+    match (limit, entry.metadata()) {
+        (Some(limit), Ok(metadata)) => metadata.len() > limit,
+        _ => false,
+    }
+}
+
+fn get_all_files(
     codebase_path: &Path,
     gitignore_matcher: Option<&GitignoreMatcher>,
+    follow_symlinks: bool,
+    max_file_bytes: Option<u64>,
     verbose: bool,
 ) -> Result<Changes> {
     let mut changes = Changes::default();
-    let mut current_files: HashMap<String, String> = HashMap::new();
 
-    for entry in walkdir::WalkDir::new(codebase_path)
-        .into_iter()
-        .filter_map(|e| e.ok())
-        .filter(|e| e.file_type().is_file())
-    {
+    for entry in walk_files(codebase_path, follow_symlinks) {
         let file_path = entry.path();
         let rel_path = match file_path.strip_prefix(codebase_path) {
             Ok(p) => p.to_string_lossy().to_string(),
@@ -363,14 +1646,20 @@ fn get_all_files(
             continue;
         }
 
-        if let Ok(content) = fs::read(file_path) {
-            let hash = hash_file_content(&content);
-            current_files.insert(rel_path.clone(), hash.clone());
-            changes.added.push((rel_path.clone(), hash));
-
+        if exceeds_size_limit(&entry, max_file_bytes) {
             if verbose {
-                println!("Found: {}", rel_path);
+                println!("Skipping (too large): {}", rel_path);
             }
+            changes.too_large.push(rel_path);
+            continue;
+        }
+
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+        let recorded = changes.record(rel_path.clone(), &metadata, None, || fs::read(file_path));
+        if recorded.is_ok() && verbose {
+            println!("Found: {}", rel_path);
         }
     }
 
@@ -379,18 +1668,16 @@ fn get_all_files(
 
 fn get_changes_with_gitignore(
     codebase_path: &Path,
-    manifest: &HashMap<String, String>,
+    manifest: &Manifest,
     gitignore_matcher: Option<&GitignoreMatcher>,
+    follow_symlinks: bool,
+    max_file_bytes: Option<u64>,
     verbose: bool,
 ) -> Result<Changes> {
     let mut changes = Changes::default();
-    let mut current_files: HashMap<String, String> = HashMap::new();
+    let mut current_files: HashSet<String> = HashSet::new();
 
-    for entry in walkdir::WalkDir::new(codebase_path)
-        .into_iter()
-        .filter_map(|e| e.ok())
-        .filter(|e| e.file_type().is_file())
-    {
+    for entry in walk_files(codebase_path, follow_symlinks) {
         let file_path = entry.path();
         let rel_path = match file_path.strip_prefix(codebase_path) {
             Ok(p) => p.to_string_lossy().to_string(),
@@ -407,46 +1694,60 @@ fn get_changes_with_gitignore(
             continue;
         }
 
-        if let Ok(content) = fs::read(file_path) {
-            let hash = hash_file_content(&content);
-            current_files.insert(rel_path.clone(), hash.clone());
+        if exceeds_size_limit(&entry, max_file_bytes) {
+            if verbose {
+                println!("Skipping (too large): {}", rel_path);
+            }
+            changes.too_large.push(rel_path);
+            continue;
+        }
 
-            if let Some(old_hash) = manifest.get(&rel_path) {
-                if old_hash != &hash {
-                    changes.modified.push((rel_path.clone(), hash));
-                    if verbose {
-                        println!("Modified: {}", rel_path);
-                    }
-                }
-            } else {
-                changes.added.push((rel_path.clone(), hash));
-                if verbose {
-                    println!("Added: {}", rel_path);
-                }
+        // Metadata first, so a write racing the read can't pair new
+        // metadata with the old content's hash
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+        let old = manifest.get(&rel_path);
+        let Ok(kind) = changes.record(rel_path.clone(), &metadata, old, || fs::read(file_path))
+        else {
+            continue;
+        };
+        if verbose {
+            match kind {
+                Some(ChangeKind::Added) => println!("Added: {}", rel_path),
+                Some(ChangeKind::Modified) => println!("Modified: {}", rel_path),
+                _ => {}
             }
         }
+        current_files.insert(rel_path);
     }
 
     for path in manifest.keys() {
-        if !current_files.contains_key(path) {
+        if !current_files.contains(path) {
             changes.removed.push(path.clone());
             if verbose {
                 println!("Removed: {}", path);
             }
         }
     }
+    changes.coalesce_removals(&current_files);
 
     Ok(changes)
 }
 
 fn should_skip_file(rel_path: &str) -> bool {
+    skip_reason(rel_path).is_some()
+}
+
+/// Which of the configured path filters leaves `rel_path` out, if any
+fn skip_reason(rel_path: &str) -> Option<SkipReason> {
     let skip_dirs = get_skip_dirs();
     let skip_files = get_skip_files();
     let extensions = get_extensions();
 
     for dir in skip_dirs {
         if rel_path.starts_with(&format!("{}/", dir)) || rel_path.contains(&format!("/{}/", dir)) {
-            return true;
+            return Some(SkipReason::SkippedDir(dir.to_string()));
         }
     }
 
@@ -457,21 +1758,71 @@ fn should_skip_file(rel_path: &str) -> bool {
         if skip_file.starts_with('*') {
             let ext = skip_file.trim_start_matches('*');
             if file_name.ends_with(ext) {
-                return true;
+                return Some(SkipReason::SkippedFile(skip_file.to_string()));
             }
         } else if file_name == skip_file {
-            return true;
+            return Some(SkipReason::SkippedFile(skip_file.to_string()));
         }
     }
 
     if let Some(ext) = path.extension().and_then(|e| e.to_str()) {
         let ext_with_dot = format!(".{}", ext.to_lowercase());
-        if !extensions.contains(&ext_with_dot.as_str()) {
-            return true;
+        if !extensions.contains(&ext_with_dot) {
+            return Some(SkipReason::Extension(ext_with_dot));
         }
     }
 
-    false
+    None
+}
+
+/// Why indexing leaves a file out
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[serde(tag = "reason", content = "detail", rename_all = "snake_case")]
+pub enum SkipReason {
+    /// There is no such file in the codebase
+    Missing,
+    /// Inside a directory named in `indexing.skip_dirs`
+    SkippedDir(String),
+    /// Matches an `indexing.skip_files` entry
+    SkippedFile(String),
+    /// Has an extension missing from `indexing.extensions`
+    Extension(String),
+    /// Matched by a `.gitignore` rule
+    Gitignored,
+    /// Matched by a `.code-search-ignore` rule
+    SearchIgnored,
+    /// Cannot be read as UTF-8 text
+    Unreadable(String),
+    /// Empty, so it splits into no chunks
+    Empty,
+    /// Larger than the `indexing.max_file_bytes` limit
+    TooLarge { size: u64, limit: u64 },
+}
+
+impl std::fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SkipReason::Missing => write!(f, "no such file in the codebase"),
+            SkipReason::SkippedDir(dir) => {
+                write!(f, "inside '{}', which is in indexing.skip_dirs", dir)
+            }
+            SkipReason::SkippedFile(pattern) => {
+                write!(f, "matches '{}' in indexing.skip_files", pattern)
+            }
+            SkipReason::Extension(ext) => {
+                write!(f, "extension {} is not in indexing.extensions", ext)
+            }
+            SkipReason::Gitignored => write!(f, "ignored by a .gitignore rule"),
+            SkipReason::SearchIgnored => write!(f, "ignored by a .code-search-ignore rule"),
+            SkipReason::Unreadable(error) => write!(f, "unreadable: {}", error),
+            SkipReason::Empty => write!(f, "empty, so it has no chunks"),
+            SkipReason::TooLarge { size, limit } => write!(
+                f,
+                "{} bytes, over the indexing.max_file_bytes limit of {}",
+                size, limit
+            ),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -488,13 +1839,16 @@ pub fn scan_codebase(
 ) -> Result<Vec<FileHash>> {
     let codebase_path = codebase_path.canonicalize().map_err(CodeSearchError::Io)?;
     let skip_dirs = get_skip_dirs();
+    let follow_symlinks = get_config().indexing.follow_symlinks;
+    let max_file_bytes = size_limit(get_config().indexing.max_file_bytes);
 
     let entries: Vec<walkdir::DirEntry> = walkdir::WalkDir::new(&codebase_path)
+        .follow_links(follow_symlinks)
         .into_iter()
         .filter_entry(|e| {
             if e.file_type().is_dir() {
                 if let Some(name) = e.file_name().to_str() {
-                    if skip_dirs.contains(&name) {
+                    if skip_dirs.iter().any(|dir| dir == name) {
                         return false;
                     }
                 }
@@ -504,6 +1858,11 @@ pub fn scan_codebase(
         .filter_map(|e| e.ok())
         .filter(|e| e.file_type().is_file())
         .collect();
+    let entries = if follow_symlinks {
+        dedup_symlinked(entries)
+    } else {
+        entries
+    };
 
     let file_hashes: Vec<FileHash> = entries
         .par_iter()
@@ -521,7 +1880,7 @@ pub fn scan_codebase(
                 Err(_) => return None,
             };
 
-            if should_skip_file(&relative_path) {
+            if should_skip_file(&relative_path) || exceeds_size_limit(entry, max_file_bytes) {
                 return None;
             }
 
@@ -552,298 +1911,1889 @@ pub fn compute_file_hash(content: &[u8]) -> String {
     hex::encode(result)[..16].to_string()
 }
 
+#[deprecated(note = "use `Indexer::index_codebase` with `IndexingOptions::force`")]
 pub fn index_codebase<P: AsRef<Path>>(
     codebase_path: P,
     model: &str,
     force_reindex: bool,
 ) -> Result<IndexingStats> {
-    let start = Instant::now();
-    let codebase_path = codebase_path
-        .as_ref()
-        .canonicalize()
-        .map_err(CodeSearchError::Io)?;
+    #[allow(deprecated)]
+    index_codebase_with_policy(codebase_path, model, force_reindex, ErrorPolicy::default())
+}
+
+/// [`index_codebase`], with `on_error` deciding what a short embedding batch
+/// does: `Abort` fails the run, `Skip` zero-fills the missing embeddings and
+/// counts them in `IndexingStats::chunks_zero_embedded`.
+#[deprecated(note = "use `Indexer::index_codebase` with `IndexingOptions::on_error`")]
+pub fn index_codebase_with_policy<P: AsRef<Path>>(
+    codebase_path: P,
+    model: &str,
+    force_reindex: bool,
+    on_error: ErrorPolicy,
+) -> Result<IndexingStats> {
+    Indexer::new(IndexingOptions {
+        force: force_reindex,
+        model_name: Some(model.to_string()),
+        on_error,
+        ..Default::default()
+    })
+    .index_codebase(codebase_path)
+}
+
+pub fn list_indexed_codebases() -> Result<Vec<CodebaseInfo>> {
+    let conn = init_db()?;
+    let codebases = crate::database::list_indexed_codebases(&conn)?
+        .into_iter()
+        .map(|(codebase_id, chunk_count, file_count)| CodebaseInfo {
+            codebase_id,
+            chunk_count,
+            file_count,
+        })
+        .collect();
+
+    Ok(codebases)
+}
+
+/// Chunks read and re-embedded per round trip in [`reembed_codebase`]
+const REEMBED_PAGE_SIZE: usize = 256;
+
+/// Recompute every stored embedding of a codebase with `model`, reusing the
+/// chunk contents already in the database instead of re-walking and
+/// re-splitting its files. The codebase's recorded model is updated and any
+/// ANN index rebuilt. Returns the number of chunks re-embedded.
+pub fn reembed_codebase(conn: &Connection, codebase_id: &str, model: &str) -> Result<usize> {
+    // Fail before touching the index if the model cannot load
+    let embedding_model = EmbeddingModel::new(Some(model))?;
+
+    let tx = conn
+        .unchecked_transaction()
+        .map_err(CodeSearchError::Database)?;
+
+    let normalize = get_config().indexing.normalize_whitespace;
+    let mut reembedded = 0;
+    let mut after_id = 0;
+    loop {
+        let page = chunk_contents_after(&tx, codebase_id, after_id, REEMBED_PAGE_SIZE)?;
+        let last_id = match page.last() {
+            Some(&(id, _)) => id,
+            None => break,
+        };
+
+        let contents: Vec<String> = page
+            .iter()
+            .map(|(_, content)| embedding_input(content, normalize))
+            .collect();
+        let embeddings = embedding_model.embed_documents(&contents, get_batch_size())?;
+        if embeddings.len() != page.len() {
+            return Err(CodeSearchError::EmbeddingInference(format!(
+                "expected {} embeddings, got {}",
+                page.len(),
+                embeddings.len()
+            )));
+        }
+
+        let updates: Vec<(i64, Vec<f32>)> =
+            page.iter().map(|(id, _)| *id).zip(embeddings).collect();
+        update_embeddings(&tx, &updates)?;
+
+        reembedded += page.len();
+        after_id = last_id;
+    }
+
+    set_codebase_model(&tx, codebase_id, model)?;
+    tx.commit().map_err(CodeSearchError::Database)?;
+
+    // The chunks are unchanged, so only the model needs updating for the
+    // next index run not to re-chunk everything
+    let manifest_path = get_manifest_path()?.join(format!("{}.json", codebase_id));
+    if let Some(mut params) = load_index_params(&manifest_path)? {
+        params.model = model.to_string();
+        params.embedding_dim = embedding_model.model_type().dimension();
+        save_index_params(&manifest_path, &params)?;
+    }
+
+    if crate::ann::has_ann_index(conn, codebase_id)? {
+        crate::ann::rebuild_ann_index(conn, codebase_id)?;
+    }
+
+    Ok(reembedded)
+}
+
+/// What indexing does with one file of a codebase, from [`explain_file`]
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct FileExplanation {
+    /// Path relative to the codebase root, as stored in the index
+    pub file_path: String,
+    /// Why indexing leaves the file out; `None` when it would be indexed
+    pub skipped: Option<SkipReason>,
+    /// Chunks the index currently holds for the file. Zero for a file that
+    /// isn't skipped means it hasn't been indexed since it appeared.
+    pub chunks: i64,
+}
+
+/// Explain why `rel_path` in `codebase_path` is or isn't indexed, applying
+/// the checks indexing makes in the order it makes them: the `.gitignore`
+/// rules, the configured directory, file name and extension filters, and
+/// whether the file reads as non-empty UTF-8 text.
+pub fn explain_file<P: AsRef<Path>>(
+    conn: &Connection,
+    codebase_path: P,
+    rel_path: &str,
+) -> Result<FileExplanation> {
+    let codebase_path = codebase_path.as_ref().canonicalize()?;
     let codebase_id = get_codebase_hash(&codebase_path);
+    let full_path = codebase_path.join(rel_path);
+    // Index paths are relative to the root, whatever form was given
+    let rel_path = full_path
+        .strip_prefix(&codebase_path)
+        .map(|p| p.to_string_lossy().to_string())
+        .unwrap_or_else(|_| rel_path.to_string());
+    let chunks = crate::database::file_chunk_count(conn, &codebase_id, &rel_path)?;
+
+    let indexing = &get_config().indexing;
+    let (gitignore_options, max_file_bytes) = indexed_selection(&codebase_id)?;
+    let ignored = || -> Result<Option<SkipReason>> {
+        let matcher = ignore_matcher(&codebase_path, indexing.use_gitignore, &gitignore_options)?;
+        Ok(matcher
+            .filter(|matcher| matcher.is_ignored(&full_path))
+            .map(|matcher| {
+                if matcher.is_search_ignored(&rel_path) {
+                    SkipReason::SearchIgnored
+                } else {
+                    SkipReason::Gitignored
+                }
+            }))
+    };
 
-    println!("Scanning codebase: {}", codebase_path.display());
+    let skipped = if !full_path.is_file() {
+        Some(SkipReason::Missing)
+    } else if let Some(reason) = ignored()? {
+        Some(reason)
+    } else if let Some(reason) = skip_reason(&rel_path) {
+        Some(reason)
+    } else if let Some(reason) = too_large(&full_path, max_file_bytes) {
+        Some(reason)
+    } else {
+        match read_source(&full_path, indexing.index_non_utf8) {
+            Ok(content) if content.is_empty() => Some(SkipReason::Empty),
+            Ok(_) => None,
+            Err(e) if e.kind() == std::io::ErrorKind::InvalidData => {
+                Some(SkipReason::Unreadable("not valid UTF-8 text".to_string()))
+            }
+            Err(e) => Some(SkipReason::Unreadable(e.to_string())),
+        }
+    };
 
-    let pb = ProgressBar::new_spinner();
-    pb.set_style(
-        ProgressStyle::default_spinner()
-            .template("{spinner:.green} {msg}")
-            .unwrap(),
-    );
-    pb.set_message("Scanning files...");
-    pb.enable_steady_tick(std::time::Duration::from_millis(100));
+    Ok(FileExplanation {
+        file_path: rel_path,
+        skipped,
+        chunks,
+    })
+}
 
-    let gitignore_matcher = GitignoreMatcher::new(&codebase_path)?;
-    let file_hashes = scan_codebase(&codebase_path, Some(&gitignore_matcher))?;
-    pb.finish_with_message(format!("Found {} files", file_hashes.len()));
+/// How `codebase_id` was last indexed picks its files: the config's
+/// ignore-rule sources, with the global rules and the size limit as the
+/// last run had them
+fn indexed_selection(codebase_id: &str) -> Result<(GitignoreOptions, Option<u64>)> {
+    let indexing = &get_config().indexing;
+    let mut options = GitignoreOptions::from_config(indexing);
+    let mut max_file_bytes = size_limit(indexing.max_file_bytes);
+    let manifest_path = get_manifest_path()?.join(format!("{}.json", codebase_id));
+    if let Some(params) = load_index_params(&manifest_path)? {
+        options.global_gitignore = params.global_gitignore;
+        max_file_bytes = params.max_file_bytes;
+    }
+    Ok((options, max_file_bytes))
+}
 
+/// Files of an indexed codebase that were added, modified or removed on disk
+/// since it was last indexed, found with the same filters indexing applies
+pub fn stale_files<P: AsRef<Path>>(codebase_path: P) -> Result<Changes> {
+    let codebase_path = codebase_path.as_ref().canonicalize()?;
+    let codebase_id = get_codebase_hash(&codebase_path);
     let manifest_path = get_manifest_path()?.join(format!("{}.json", codebase_id));
-    let mut manifest = if force_reindex || !manifest_path.exists() {
-        HashMap::new()
+    let manifest = if manifest_path.exists() {
+        load_manifest_internal(&manifest_path)?
     } else {
-        load_manifest_internal(&manifest_path).unwrap_or_default()
+        HashMap::new()
     };
 
-    let changes = detect_changes(&file_hashes, &manifest);
+    let indexing = &get_config().indexing;
+    let (gitignore_options, max_file_bytes) = indexed_selection(&codebase_id)?;
+    let gitignore_matcher =
+        ignore_matcher(&codebase_path, indexing.use_gitignore, &gitignore_options)?;
+    get_changes_with_gitignore(
+        &codebase_path,
+        &manifest,
+        gitignore_matcher.as_ref(),
+        indexing.follow_symlinks,
+        max_file_bytes,
+        false,
+    )
+}
 
-    let mut stats = IndexingStats {
-        files_indexed: 0,
-        files_skipped: file_hashes.len() - changes.added.len() - changes.modified.len(),
-        ..Default::default()
+/// Point the index of a codebase whose directory moved from `old_path` to
+/// `new_path` at its new location, without re-splitting or re-embedding.
+/// `old_path` need not exist any more: it is matched against the recorded
+/// codebase paths (and labels) when it doesn't. Returns the chunks moved.
+pub fn relocate_codebase<P: AsRef<Path>>(
+    conn: &Connection,
+    old_path: &str,
+    new_path: P,
+) -> Result<i64> {
+    let new_path = new_path.as_ref().canonicalize()?;
+    let new_id = get_codebase_hash(&new_path);
+
+    let old_id = match Path::new(old_path).canonicalize() {
+        Ok(canonical) => get_codebase_hash(&canonical),
+        Err(_) => {
+            let absolute = std::path::absolute(old_path)?;
+            let id = get_codebase_hash(&absolute);
+            if get_codebase_stats(conn, &id)?.is_some() {
+                id
+            } else {
+                crate::database::resolve_codebase(conn, old_path)?.codebase_id
+            }
+        }
     };
+    if old_id == new_id {
+        return Err(CodeSearchError::InvalidInput(format!(
+            "{} and {} are the same codebase",
+            old_path,
+            new_path.display()
+        )));
+    }
+    if get_codebase_stats(conn, &old_id)?.is_none() {
+        return Err(CodeSearchError::CodebaseNotIndexed(old_path.to_string()));
+    }
 
-    if changes.added.is_empty() && changes.modified.is_empty() && changes.removed.is_empty() {
-        println!("No changes detected. Index is up to date.");
-        stats.duration_ms = start.elapsed().as_millis() as u64;
-        return Ok(stats);
+    let moved =
+        crate::database::move_codebase(conn, &old_id, &new_id, &new_path.to_string_lossy())?;
+    crate::manifest::rename_manifest(&old_id, &new_id)?;
+    Ok(moved)
+}
+
+/// Remove every file whose path relative to the codebase root starts with
+/// `path_prefix` (e.g. `vendor/`) from the index and its manifest. Files
+/// still on disk come back on the next index run unless they are excluded.
+/// Returns the chunks removed.
+pub fn delete_path<P: AsRef<Path>>(
+    conn: &Connection,
+    codebase_path: P,
+    path_prefix: &str,
+) -> Result<i64> {
+    let codebase_path = codebase_path.as_ref().canonicalize()?;
+    let codebase_id = get_codebase_hash(&codebase_path);
+    let path_prefix = path_prefix.trim_start_matches("./");
+    if path_prefix.is_empty() {
+        return Err(CodeSearchError::InvalidInput(
+            "empty path prefix; use `delete` to remove the whole codebase".to_string(),
+        ));
+    }
+    if get_codebase_stats(conn, &codebase_id)?.is_none() {
+        return Err(CodeSearchError::CodebaseNotIndexed(
+            codebase_path.display().to_string(),
+        ));
     }
 
-    println!(
-        "Changes: {} added, {} modified, {} removed",
-        changes.added.len(),
-        changes.modified.len(),
-        changes.removed.len()
-    );
+    let deleted = delete_chunks_for_prefix(conn, &codebase_id, path_prefix)?;
+    crate::manifest::prune_manifest_prefix(&codebase_id, path_prefix)?;
+    Ok(deleted)
+}
 
-    let conn = init_db()?;
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct CodebaseInfo {
+    pub codebase_id: String,
+    pub chunk_count: i64,
+    pub file_count: i64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    let delete_pb = ProgressBar::new(changes.removed.len() as u64);
-    delete_pb.set_style(
-        ProgressStyle::default_bar()
-            .template("{bar:40.cyan/blue} {pos}/{len} {msg}")
+    #[test]
+    fn test_should_skip_file() {
+        assert!(should_skip_file(".git/config"));
+        assert!(should_skip_file("node_modules/package/index.js"));
+        assert!(should_skip_file("target/debug/main"));
+        assert!(should_skip_file("image.png"));
+        assert!(should_skip_file("archive.zip"));
+
+        assert!(!should_skip_file("src/main.rs"));
+        assert!(!should_skip_file("lib.py"));
+        assert!(!should_skip_file("index.js"));
+    }
+
+    #[test]
+    fn test_default_config() {
+        let config = IndexingOptions::default();
+        assert!(!config.force);
+        assert!(!config.verbose);
+        assert!(config.use_gitignore);
+    }
+
+    #[test]
+    fn test_get_all_files_honours_repo_root_gitignore() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = dir.path();
+
+        fs::create_dir_all(repo.join(".git")).unwrap();
+        fs::create_dir_all(repo.join("src/gen")).unwrap();
+        fs::write(repo.join(".gitignore"), "src/gen/\n").unwrap();
+        fs::write(repo.join("src/lib.rs"), "pub fn hello() {}\n").unwrap();
+        fs::write(repo.join("src/gen/bindings.rs"), "pub fn generated() {}\n").unwrap();
+
+        let src = repo.join("src").canonicalize().unwrap();
+        let matcher = GitignoreMatcher::new(&src).unwrap();
+        let changes = get_all_files(&src, Some(&matcher), false, None, false).unwrap();
+
+        let files: Vec<&str> = changes.added.iter().map(|(p, _)| p.as_str()).collect();
+        assert_eq!(files, vec!["lib.rs"]);
+    }
+
+    #[test]
+    fn test_indexing_a_subdirectory_honours_repo_root_gitignore() {
+        let _data_dir = crate::test_support::use_temp_data_dir();
+
+        let dir = tempfile::tempdir().unwrap();
+        let repo = dir.path();
+        fs::create_dir_all(repo.join(".git")).unwrap();
+        fs::create_dir_all(repo.join("src/gen")).unwrap();
+        fs::write(repo.join(".gitignore"), "src/gen/\n").unwrap();
+        fs::write(repo.join("src/lib.rs"), "pub fn hello() {}\n").unwrap();
+        fs::write(repo.join("src/gen/bindings.rs"), "pub fn generated() {}\n").unwrap();
+
+        let src = repo.join("src");
+        let stats = Indexer::new(IndexingOptions::default())
+            .index_codebase(&src)
+            .unwrap();
+        assert_eq!(stats.files_indexed, 1);
+
+        let codebase_id = get_codebase_hash(&src.canonicalize().unwrap());
+        let conn = init_db().unwrap();
+        let files: Vec<String> = crate::database::chunk_counts_by_file(&conn, &codebase_id)
             .unwrap()
-            .progress_chars("=>-"),
-    );
-    delete_pb.set_message("Removing deleted files...");
+            .into_iter()
+            .map(|(path, _)| path)
+            .collect();
+        assert_eq!(files, vec!["lib.rs"]);
+    }
 
-    for file_path in &changes.removed {
-        delete_chunks_for_file(&conn, &codebase_id, file_path)?;
-        manifest.remove(file_path);
-        stats.files_removed += 1;
-        delete_pb.inc(1);
+    #[test]
+    fn test_removed_file_report_matches_counts() {
+        let _data_dir = crate::test_support::use_temp_data_dir();
+
+        let dir = tempfile::tempdir().unwrap();
+        let long_file = (1..=120)
+            .map(|i| format!("fn item_{}() {{}}", i))
+            .collect::<Vec<_>>()
+            .join("\n");
+        fs::write(dir.path().join("a.rs"), &long_file).unwrap();
+        fs::write(dir.path().join("b.rs"), "fn b() {}\n").unwrap();
+        fs::write(dir.path().join("c.rs"), "fn c() {}\n").unwrap();
+
+        let mut indexer = Indexer::new(IndexingOptions::default());
+        let stats = indexer.index_codebase(dir.path()).unwrap();
+        assert!(stats.removed_files.is_none());
+
+        fs::remove_file(dir.path().join("c.rs")).unwrap();
+        let mut indexer = Indexer::new(IndexingOptions {
+            report_removed: true,
+            ..Default::default()
+        });
+        let stats = indexer.index_codebase(dir.path()).unwrap();
+        let removed = stats.removed_files.as_ref().unwrap();
+        assert_eq!(removed.len(), stats.files_removed);
+        assert_eq!(removed[0].file_path, "c.rs");
+
+        let mut indexer = Indexer::new(IndexingOptions {
+            force: true,
+            report_removed: true,
+            ..Default::default()
+        });
+        let stats = indexer.index_codebase(dir.path()).unwrap();
+        let removed = stats.removed_files.as_ref().unwrap();
+        let mut paths: Vec<&str> = removed.iter().map(|r| r.file_path.as_str()).collect();
+        paths.sort();
+        assert_eq!(paths, vec!["a.rs", "b.rs"]);
+        assert_eq!(removed.len(), stats.files_removed);
+        assert_eq!(
+            removed.iter().map(|r| r.chunks_removed).sum::<usize>(),
+            stats.chunks_removed
+        );
+        assert!(removed.iter().any(|r| r.chunks_removed > 1));
     }
-    delete_pb.finish();
 
-    let files_to_index: Vec<_> = changes
-        .added
-        .iter()
-        .chain(changes.modified.iter())
-        .collect();
+    #[test]
+    fn test_dry_run_projects_changes_without_writing() {
+        let _data_dir = crate::test_support::use_temp_data_dir();
+
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("kept.rs"), "fn dry_run_kept() {}\n").unwrap();
+        fs::write(dir.path().join("edited.rs"), "fn dry_run_edited() {}\n").unwrap();
+        fs::write(dir.path().join("gone.rs"), "fn dry_run_gone() {}\n").unwrap();
+        Indexer::new(IndexingOptions::default())
+            .index_codebase(dir.path())
+            .unwrap();
+
+        let conn = init_db().unwrap();
+        let codebase_id = get_codebase_hash(&dir.path().canonicalize().unwrap());
+        let manifest_path = get_manifest_path()
+            .unwrap()
+            .join(format!("{}.json", codebase_id));
+        let chunks_before = crate::database::chunk_counts_by_file(&conn, &codebase_id).unwrap();
+        let manifest_before = fs::read(&manifest_path).unwrap();
 
-    if files_to_index.is_empty() {
-        save_manifest_internal(&manifest_path, &manifest)?;
-        stats.duration_ms = start.elapsed().as_millis() as u64;
-        return Ok(stats);
+        fs::write(
+            dir.path().join("edited.rs"),
+            "fn dry_run_edited_again() {}\n",
+        )
+        .unwrap();
+        fs::write(dir.path().join("added.rs"), "fn dry_run_added() {}\n").unwrap();
+        fs::remove_file(dir.path().join("gone.rs")).unwrap();
+
+        let stats = Indexer::new(IndexingOptions {
+            dry_run: true,
+            report_removed: true,
+            ..Default::default()
+        })
+        .index_codebase(dir.path())
+        .unwrap();
+        assert!(stats.dry_run);
+        assert_eq!(stats.files_indexed, 2);
+        assert_eq!(stats.files_removed, 1);
+        assert_eq!(stats.chunks_removed, 1);
+        assert_eq!(stats.chunks_created, 0);
+        assert_eq!(stats.removed_files.unwrap()[0].file_path, "gone.rs");
+
+        assert_eq!(
+            crate::database::chunk_counts_by_file(&conn, &codebase_id).unwrap(),
+            chunks_before
+        );
+        assert_eq!(fs::read(&manifest_path).unwrap(), manifest_before);
     }
 
-    let process_pb = ProgressBar::new(files_to_index.len() as u64);
-    process_pb.set_style(
-        ProgressStyle::default_bar()
-            .template("{bar:40.green/blue} {pos}/{len} {msg}")
+    #[test]
+    fn test_delete_path_removes_only_the_prefix() {
+        let _data_dir = crate::test_support::use_temp_data_dir();
+
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("third_party/dep/src")).unwrap();
+        fs::create_dir_all(dir.path().join("src")).unwrap();
+        fs::write(
+            dir.path().join("third_party/dep/src/lib.rs"),
+            "fn dep_lib() {}",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("third_party/shim.rs"),
+            "fn third_party_shim() {}",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("third_party.rs"),
+            "fn third_party_here() {}",
+        )
+        .unwrap();
+        fs::write(dir.path().join("src/main.rs"), "fn delete_path_main() {}").unwrap();
+
+        Indexer::new(IndexingOptions::default())
+            .index_codebase(dir.path())
+            .unwrap();
+
+        let conn = init_db().unwrap();
+        let deleted = delete_path(&conn, dir.path(), "./third_party/").unwrap();
+        assert_eq!(deleted, 2);
+
+        let root = dir.path().canonicalize().unwrap();
+        let codebase_id = get_codebase_hash(&root);
+        let mut remaining: Vec<String> = crate::database::chunk_counts_by_file(&conn, &codebase_id)
             .unwrap()
-            .progress_chars("=>-"),
-    );
-    process_pb.set_message("Processing files...");
+            .into_iter()
+            .map(|(path, _)| path)
+            .collect();
+        remaining.sort();
+        assert_eq!(remaining, vec!["src/main.rs", "third_party.rs"]);
+
+        let manifest_path = get_manifest_path()
+            .unwrap()
+            .join(format!("{}.json", codebase_id));
+        let manifest = load_manifest_internal(&manifest_path).unwrap();
+        assert!(manifest
+            .keys()
+            .all(|path| !path.starts_with("third_party/")));
+        assert!(manifest.contains_key("src/main.rs"));
+
+        assert!(matches!(
+            delete_path(&conn, dir.path(), ""),
+            Err(CodeSearchError::InvalidInput(_))
+        ));
+    }
 
-    let mut all_chunks: Vec<Chunk> = Vec::new();
-    stats.files_indexed = files_to_index.len();
+    #[test]
+    fn test_delete_path_matches_case() {
+        let _data_dir = crate::test_support::use_temp_data_dir();
 
-    for (relative_path, file_hash) in &files_to_index {
-        let file_path = codebase_path.join(relative_path);
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("Proto")).unwrap();
+        fs::create_dir_all(dir.path().join("proto")).unwrap();
+        fs::write(dir.path().join("Proto/upper.rs"), "fn proto_upper() {}").unwrap();
+        fs::write(dir.path().join("proto/lower.rs"), "fn proto_lower() {}").unwrap();
 
-        delete_chunks_for_file(&conn, &codebase_id, relative_path)?;
+        Indexer::new(IndexingOptions::default())
+            .index_codebase(dir.path())
+            .unwrap();
 
-        match process_file_for_indexing(&file_path, relative_path, &codebase_id, file_hash) {
-            Ok(chunks) => {
-                all_chunks.extend(chunks);
-            }
-            Err(e) => {
-                eprintln!("Warning: Failed to process {}: {}", relative_path, e);
-            }
+        let conn = init_db().unwrap();
+        assert_eq!(delete_path(&conn, dir.path(), "proto/").unwrap(), 1);
+
+        // The index and the manifest agree on what is left
+        let codebase_id = get_codebase_hash(&dir.path().canonicalize().unwrap());
+        let remaining: Vec<String> = crate::database::chunk_counts_by_file(&conn, &codebase_id)
+            .unwrap()
+            .into_iter()
+            .map(|(path, _)| path)
+            .collect();
+        assert_eq!(remaining, vec!["Proto/upper.rs"]);
+        let manifest_path = get_manifest_path()
+            .unwrap()
+            .join(format!("{}.json", codebase_id));
+        let manifest = load_manifest_internal(&manifest_path).unwrap();
+        assert_eq!(manifest.keys().collect::<Vec<_>>(), vec!["Proto/upper.rs"]);
+    }
+
+    #[test]
+    fn test_removed_directory_deleted_with_one_prefix() {
+        let _data_dir = crate::test_support::use_temp_data_dir();
+
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("gen/nested")).unwrap();
+        fs::create_dir_all(dir.path().join("src")).unwrap();
+        for i in 0..20 {
+            fs::write(
+                dir.path().join(format!("gen/file_{}.rs", i)),
+                format!("fn gen_{}() {{}}", i),
+            )
+            .unwrap();
+        }
+        for i in 0..5 {
+            fs::write(
+                dir.path().join(format!("gen/nested/deep_{}.rs", i)),
+                format!("fn deep_{}() {{}}", i),
+            )
+            .unwrap();
         }
+        fs::write(dir.path().join("gen_keep.rs"), "fn keep() {}").unwrap();
+        fs::write(dir.path().join("src/lib.rs"), "fn lib() {}").unwrap();
+        fs::write(dir.path().join("src/old.rs"), "fn old() {}").unwrap();
 
-        process_pb.inc(1);
+        let mut indexer = Indexer::new(IndexingOptions::default());
+        indexer.index_codebase(dir.path()).unwrap();
+
+        let root = dir.path().canonicalize().unwrap();
+        let manifest: Manifest =
+            get_changes_with_gitignore(&root, &HashMap::new(), None, false, None, false)
+                .unwrap()
+                .added
+                .into_iter()
+                .collect();
+
+        fs::remove_dir_all(dir.path().join("gen")).unwrap();
+        fs::remove_file(dir.path().join("src/old.rs")).unwrap();
+
+        let changes =
+            get_changes_with_gitignore(&root, &manifest, None, false, None, false).unwrap();
+        assert_eq!(changes.removed.len(), 26);
+        // The whole gen/ tree is one prefix delete; src/ still has files
+        assert_eq!(changes.removed_dirs, vec!["gen/"]);
+        let singles: Vec<&String> = changes.removed_outside_dirs().collect();
+        assert_eq!(singles, vec!["src/old.rs"]);
+
+        let stats = indexer.index_codebase(dir.path()).unwrap();
+        assert_eq!(stats.files_removed, 26);
+        assert_eq!(stats.chunks_removed, 26);
+
+        let codebase_id = get_codebase_hash(&root);
+        let conn = init_db().unwrap();
+        let mut remaining: Vec<String> = crate::database::chunk_counts_by_file(&conn, &codebase_id)
+            .unwrap()
+            .into_iter()
+            .map(|(path, _)| path)
+            .collect();
+        remaining.sort();
+        assert_eq!(remaining, vec!["gen_keep.rs", "src/lib.rs"]);
     }
-    process_pb.finish_with_message("Files processed");
 
-    if all_chunks.is_empty() {
-        save_manifest_internal(&manifest_path, &manifest)?;
-        stats.duration_ms = start.elapsed().as_millis() as u64;
-        return Ok(stats);
+    #[test]
+    fn test_chunking_change_rechunks_unchanged_files() {
+        let _data_dir = crate::test_support::use_temp_data_dir();
+
+        let dir = tempfile::tempdir().unwrap();
+        let lines = |prefix: &str| {
+            (1..=20)
+                .map(|i| format!("fn {}_{}() {{}}", prefix, i))
+                .collect::<Vec<_>>()
+                .join("\n")
+        };
+        fs::write(dir.path().join("a.rs"), lines("a")).unwrap();
+
+        let options = |chunk_size| IndexingOptions {
+            chunk_size: Some(chunk_size),
+            chunk_overlap: Some(0),
+            ..Default::default()
+        };
+        let stats = Indexer::new(options(4)).index_codebase(dir.path()).unwrap();
+        assert_eq!(stats.chunks_created, 5);
+
+        // The configured chunk size changes, then a file is added: the
+        // unchanged a.rs is re-chunked along with the new b.rs
+        fs::write(dir.path().join("b.rs"), lines("b")).unwrap();
+        let stats = Indexer::new(options(10))
+            .index_codebase(dir.path())
+            .unwrap();
+        assert_eq!(stats.files_indexed, 2);
+        assert_eq!(stats.chunks_removed, 5);
+        assert_eq!(stats.chunks_created, 4);
+
+        let codebase_id = get_codebase_hash(&dir.path().canonicalize().unwrap());
+        let conn = init_db().unwrap();
+        let counts: HashMap<String, i64> =
+            crate::database::chunk_counts_by_file(&conn, &codebase_id)
+                .unwrap()
+                .into_iter()
+                .collect();
+        assert_eq!(counts["a.rs"], 2);
+        assert_eq!(
+            crate::database::get_chunking_params(&conn, &codebase_id).unwrap(),
+            Some((10, 0))
+        );
+        let manifest_path = get_manifest_path()
+            .unwrap()
+            .join(format!("{}.json", codebase_id));
+        let recorded = load_index_params(&manifest_path).unwrap().unwrap();
+        assert_eq!((recorded.chunk_size, recorded.chunk_overlap), (10, 0));
+
+        // With the settings unchanged, nothing is re-chunked
+        let stats = Indexer::new(options(10))
+            .index_codebase(dir.path())
+            .unwrap();
+        assert_eq!(stats.files_indexed, 0);
+        assert_eq!(stats.chunks_created, 0);
     }
 
-    println!("Generating embeddings for {} chunks...", all_chunks.len());
+    #[test]
+    fn test_unchanged_chunks_keep_their_embeddings() {
+        let _data_dir = crate::test_support::use_temp_data_dir();
+
+        let dir = tempfile::tempdir().unwrap();
+        let body = |last: &str| {
+            let mut lines: Vec<String> =
+                (1..=12).map(|i| format!("fn reuse_{}() {{}}", i)).collect();
+            lines[11] = last.to_string();
+            lines.join("\n")
+        };
+        fs::write(dir.path().join("lib.rs"), body("fn reuse_12() {}")).unwrap();
+
+        let options = || IndexingOptions {
+            chunk_size: Some(4),
+            chunk_overlap: Some(0),
+            ..Default::default()
+        };
+        let stats = Indexer::new(options()).index_codebase(dir.path()).unwrap();
+        assert_eq!(stats.chunks_created, 3);
+        assert_eq!(stats.chunks_reused, 0);
+
+        // Editing the last line only changes the last chunk
+        fs::write(dir.path().join("lib.rs"), body("fn edited() {}")).unwrap();
+        let stats = Indexer::new(options()).index_codebase(dir.path()).unwrap();
+        assert_eq!(stats.files_indexed, 1);
+        assert_eq!(stats.chunks_created, 3);
+        assert_eq!(stats.chunks_reused, 2);
+
+        // Inside a run transaction the stored embeddings are still readable
+        fs::write(dir.path().join("lib.rs"), body("fn edited_again() {}")).unwrap();
+        let stats = Indexer::new(IndexingOptions {
+            on_error: ErrorPolicy::Abort,
+            ..options()
+        })
+        .index_codebase(dir.path())
+        .unwrap();
+        assert_eq!(stats.chunks_reused, 2);
+
+        let codebase_id = get_codebase_hash(&dir.path().canonicalize().unwrap());
+        let conn = init_db().unwrap();
+        let stored = embeddings_by_content_hash(&conn, &codebase_id, "lib.rs").unwrap();
+        assert_eq!(stored.len(), 3);
+        let last = crate::database::chunks_at(&conn, &codebase_id, "lib.rs", 12).unwrap();
+        assert!(stored.contains_key(&chunk_content_hash(&last[0].content)));
+    }
 
-    let embed_pb = ProgressBar::new(all_chunks.len() as u64);
-    embed_pb.set_style(
-        ProgressStyle::default_bar()
-            .template("{bar:40.magenta/blue} {pos}/{len} {msg}")
+    #[test]
+    fn test_shrunk_file_loses_its_stale_chunks() {
+        let _data_dir = crate::test_support::use_temp_data_dir();
+
+        let dir = tempfile::tempdir().unwrap();
+        let body = |lines: usize| {
+            (1..=lines)
+                .map(|i| format!("fn shrink_{}() {{}}", i))
+                .collect::<Vec<_>>()
+                .join("\n")
+        };
+        let options = || IndexingOptions {
+            chunk_size: Some(4),
+            chunk_overlap: Some(0),
+            ..Default::default()
+        };
+        fs::write(dir.path().join("lib.rs"), body(12)).unwrap();
+        Indexer::new(options()).index_codebase(dir.path()).unwrap();
+
+        fs::write(dir.path().join("lib.rs"), body(5)).unwrap();
+        Indexer::new(options()).index_codebase(dir.path()).unwrap();
+
+        let codebase_id = get_codebase_hash(&dir.path().canonicalize().unwrap());
+        let conn = init_db().unwrap();
+        let mut stmt = conn
+            .prepare(
+                "SELECT start_line, end_line FROM chunks \
+                 WHERE codebase_id = ?1 ORDER BY start_line",
+            )
+            .unwrap();
+        let ranges: Vec<(i64, i64)> = stmt
+            .query_map([&codebase_id], |row| Ok((row.get(0)?, row.get(1)?)))
             .unwrap()
-            .progress_chars("=>-"),
-    );
-    embed_pb.set_message("Generating embeddings...");
+            .map(|r| r.unwrap())
+            .collect();
+        assert_eq!(ranges, vec![(1, 4), (5, 5)]);
+    }
 
-    let contents: Vec<String> = all_chunks.iter().map(|c| c.content.clone()).collect();
-    let embeddings = get_embeddings_batch_with_model(&contents, get_batch_size(), false, model);
+    #[test]
+    fn test_indexers_with_own_data_dirs_are_isolated() {
+        let _data_dir = crate::test_support::use_temp_data_dir();
+
+        let stores = [tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap()];
+        let codebases = [tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap()];
+        let mut ids = Vec::new();
+        for (store, codebase) in stores.iter().zip(&codebases) {
+            fs::write(codebase.path().join("lib.rs"), "fn stored_apart() {}\n").unwrap();
+            Indexer::new(IndexingOptions {
+                data_dir: Some(store.path().to_path_buf()),
+                ..Default::default()
+            })
+            .index_codebase(codebase.path())
+            .unwrap();
+            ids.push(get_codebase_hash(&codebase.path().canonicalize().unwrap()));
+        }
 
-    for (i, chunk) in all_chunks.iter_mut().enumerate() {
-        if i < embeddings.len() {
-            chunk.embedding = embeddings[i].clone();
-        } else {
-            chunk.embedding = zero_embedding_with_model(model);
+        let open =
+            |dir: Option<&Path>| init_db_at(&resolve_db_path(dir, &get_config()).unwrap()).unwrap();
+        for (i, store) in stores.iter().enumerate() {
+            let conn = open(Some(store.path()));
+            let manifests = resolve_manifest_dir(Some(store.path())).unwrap();
+            for (j, id) in ids.iter().enumerate() {
+                let indexed = get_codebase_stats(&conn, id).unwrap().is_some();
+                assert_eq!(indexed, i == j);
+                assert_eq!(manifests.join(format!("{}.json", id)).exists(), i == j);
+            }
+        }
+
+        // Nothing reached the process-wide data dir
+        let shared = init_db().unwrap();
+        for id in &ids {
+            assert!(get_codebase_stats(&shared, id).unwrap().is_none());
         }
-        embed_pb.inc(1);
     }
-    embed_pb.finish_with_message("Embeddings generated");
 
-    stats.chunks_created = all_chunks.len();
+    #[test]
+    fn test_progress_callback_reports_phases_in_order() {
+        let _data_dir = crate::test_support::use_temp_data_dir();
+
+        let dir = tempfile::tempdir().unwrap();
+        for (name, body) in [
+            ("alpha.rs", "fn alpha_progress() {}\n"),
+            ("beta.rs", "fn beta_progress() {}\n"),
+            ("gamma.rs", "fn gamma_progress() {}\n"),
+        ] {
+            fs::write(dir.path().join(name), body).unwrap();
+        }
 
-    let insert_pb = ProgressBar::new(1);
-    insert_pb.set_style(
-        ProgressStyle::default_bar()
-            .template("{bar:40.yellow/blue} {msg}")
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&events);
+        let stats = Indexer::new(IndexingOptions {
+            progress: Some(ProgressCallback::new(move |event| {
+                sink.lock().unwrap().push(event)
+            })),
+            ..Default::default()
+        })
+        .index_codebase(dir.path())
+        .unwrap();
+
+        let events = events.lock().unwrap();
+        assert_eq!(events.first(), Some(&IndexingProgress::Scanning));
+        assert_eq!(
+            &events[events.len() - 2..],
+            &[IndexingProgress::Inserting, IndexingProgress::Done]
+        );
+        let embedding: Vec<(usize, usize)> = events[1..events.len() - 2]
+            .iter()
+            .map(|event| match event {
+                IndexingProgress::Embedding { done, total } => (*done, *total),
+                other => panic!("unexpected {:?} between scanning and inserting", other),
+            })
+            .collect();
+        assert_eq!(embedding.first(), Some(&(0, stats.files_indexed)));
+        assert_eq!(
+            embedding.last(),
+            Some(&(stats.files_indexed, stats.files_indexed))
+        );
+        assert!(embedding.windows(2).all(|pair| pair[0].0 <= pair[1].0));
+    }
+
+    #[test]
+    fn test_ignore_file_pattern_skips_file_when_indexing() {
+        let _data_dir = crate::test_support::use_temp_data_dir();
+
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join(".ignore"), "generated.rs\n").unwrap();
+        fs::write(dir.path().join("generated.rs"), "fn generated_stub() {}\n").unwrap();
+        fs::write(dir.path().join("handwritten.rs"), "fn handwritten() {}\n").unwrap();
+
+        Indexer::new(IndexingOptions::default())
+            .index_codebase(dir.path())
+            .unwrap();
+
+        let codebase_id = get_codebase_hash(&dir.path().canonicalize().unwrap());
+        let conn = init_db().unwrap();
+        let files: Vec<String> = conn
+            .prepare("SELECT DISTINCT file_path FROM chunks WHERE codebase_id = ?1")
             .unwrap()
-            .progress_chars("=>-"),
-    );
-    insert_pb.set_message("Inserting chunks into database...");
+            .query_map([&codebase_id], |row| row.get(0))
+            .unwrap()
+            .map(|r| r.unwrap())
+            .collect();
+        assert!(files.contains(&"handwritten.rs".to_string()));
+        assert!(!files.contains(&"generated.rs".to_string()));
+    }
 
-    insert_chunks(&conn, &all_chunks)?;
-    insert_pb.finish_with_message("Chunks inserted");
+    #[test]
+    fn test_search_ignore_applies_with_and_without_gitignore() {
+        let _data_dir = crate::test_support::use_temp_data_dir();
+
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join(".git")).unwrap();
+        fs::create_dir_all(dir.path().join("fixtures")).unwrap();
+        fs::write(dir.path().join(".gitignore"), "scratch.rs\n").unwrap();
+        fs::write(
+            dir.path().join(".code-search-ignore"),
+            "fixtures/\n!scratch.rs\n",
+        )
+        .unwrap();
+        // Tracked by git, but left out of search
+        fs::write(
+            dir.path().join("fixtures/snapshot.rs"),
+            "fn search_ignored_snapshot() {}\n",
+        )
+        .unwrap();
+        fs::write(dir.path().join("scratch.rs"), "fn search_scratch() {}\n").unwrap();
+        fs::write(dir.path().join("lib.rs"), "fn search_ignore_lib() {}\n").unwrap();
+
+        let codebase_id = get_codebase_hash(&dir.path().canonicalize().unwrap());
+        let conn = init_db().unwrap();
+        let indexed_files = || -> Vec<String> {
+            let mut files: Vec<String> = crate::database::chunk_counts_by_file(&conn, &codebase_id)
+                .unwrap()
+                .into_iter()
+                .map(|(path, _)| path)
+                .filter(|path| path.ends_with(".rs"))
+                .collect();
+            files.sort();
+            files
+        };
 
-    for (relative_path, file_hash) in &files_to_index {
-        manifest.insert(relative_path.clone(), file_hash.clone());
+        Indexer::new(IndexingOptions::default())
+            .index_codebase(dir.path())
+            .unwrap();
+        assert_eq!(indexed_files(), vec!["lib.rs", "scratch.rs"]);
+        assert_eq!(
+            explain_file(&conn, dir.path(), "fixtures/snapshot.rs")
+                .unwrap()
+                .skipped,
+            Some(SkipReason::SearchIgnored)
+        );
+
+        Indexer::new(IndexingOptions {
+            force: true,
+            use_gitignore: false,
+            ..Default::default()
+        })
+        .index_codebase(dir.path())
+        .unwrap();
+        assert_eq!(indexed_files(), vec!["lib.rs", "scratch.rs"]);
     }
 
-    save_manifest_internal(&manifest_path, &manifest)?;
+    #[test]
+    fn test_files_over_max_file_bytes_are_skipped() {
+        let _data_dir = crate::test_support::use_temp_data_dir();
 
-    stats.duration_ms = start.elapsed().as_millis() as u64;
-    println!(
-        "Indexing complete: {} files indexed, {} chunks created ({}ms)",
-        stats.files_indexed, stats.chunks_created, stats.duration_ms
-    );
+        let dir = tempfile::tempdir().unwrap();
+        let body = |len: usize| format!("// {}\n", "s".repeat(len - 4));
+        fs::write(dir.path().join("over.rs"), body(101)).unwrap();
+        fs::write(dir.path().join("under.rs"), body(99)).unwrap();
 
-    Ok(stats)
-}
+        let options = IndexingOptions {
+            max_file_bytes: Some(100),
+            ..Default::default()
+        };
+        let stats = Indexer::new(options).index_codebase(dir.path()).unwrap();
+        assert_eq!(stats.files_indexed, 1);
+        assert_eq!(stats.files_skipped, 1);
 
-fn process_file_for_indexing(
-    file_path: &Path,
-    relative_path: &str,
-    codebase_id: &str,
-    file_hash: &str,
-) -> Result<Vec<Chunk>> {
-    let content = fs::read_to_string(file_path).map_err(|_| CodeSearchError::FileRead {
-        path: file_path.to_string_lossy().to_string(),
-    })?;
+        let codebase_id = get_codebase_hash(&dir.path().canonicalize().unwrap());
+        let conn = init_db().unwrap();
+        let files: Vec<String> = crate::database::chunk_counts_by_file(&conn, &codebase_id)
+            .unwrap()
+            .into_iter()
+            .map(|(path, _)| path)
+            .collect();
+        assert_eq!(files, vec!["under.rs"]);
+
+        // The override holds for explain-file and status, not the config's
+        // 1 MiB limit
+        let explanation = explain_file(&conn, dir.path(), "over.rs").unwrap();
+        assert_eq!(
+            explanation.skipped,
+            Some(SkipReason::TooLarge {
+                size: 101,
+                limit: 100
+            })
+        );
+        assert_eq!(explanation.chunks, 0);
+        assert!(stale_files(dir.path()).unwrap().added.is_empty());
+    }
 
-    if content.is_empty() {
-        return Ok(Vec::new());
+    #[test]
+    fn test_long_line_chunks_are_counted_as_truncated() {
+        let _data_dir = crate::test_support::use_temp_data_dir();
+
+        let dir = tempfile::tempdir().unwrap();
+        let long_line = format!("let table = [{}];", "0x7f, ".repeat(40));
+        let wide: String = (0..120).map(|_| format!("{}\n", long_line)).collect();
+        fs::write(dir.path().join("tables.rs"), wide).unwrap();
+        fs::write(dir.path().join("small.rs"), "fn tiny() {}\n").unwrap();
+
+        let stats = Indexer::new(IndexingOptions::default())
+            .index_codebase(dir.path())
+            .unwrap();
+
+        let codebase_id = get_codebase_hash(&dir.path().canonicalize().unwrap());
+        let conn = init_db().unwrap();
+        let wide_chunks: usize = conn
+            .query_row(
+                "SELECT COUNT(*) FROM chunks WHERE codebase_id = ?1 AND file_path = 'tables.rs'",
+                [&codebase_id],
+                |row| row.get::<_, i64>(0),
+            )
+            .unwrap() as usize;
+        assert!(wide_chunks >= 3);
+        assert_eq!(stats.chunks_truncated, wide_chunks);
+
+        assert_eq!(stats.truncated_files.len(), 1);
+        let truncated = &stats.truncated_files[0];
+        assert_eq!(truncated.file_path, "tables.rs");
+        assert_eq!(truncated.chunks_truncated, wide_chunks);
+        assert!(truncated.suggestion.contains("chunking.chunk_size"));
+        assert!(!truncated.suggestion.contains("split_strategy"));
+        assert!(truncated.suggestion.contains("(rust files)"));
+        assert!(stats.to_string().contains(&truncated.suggestion));
     }
 
-    let code_chunks = split_file(relative_path, &content, None, None);
+    #[test]
+    fn test_reindex_file_replaces_only_that_file() {
+        let _data_dir = crate::test_support::use_temp_data_dir();
+
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("saved.rs"), "fn before_save() {}\n").unwrap();
+        fs::write(dir.path().join("other.rs"), "fn untouched() {}\n").unwrap();
+        Indexer::new(IndexingOptions::default())
+            .index_codebase(dir.path())
+            .unwrap();
+
+        let codebase_id = get_codebase_hash(&dir.path().canonicalize().unwrap());
+        let conn = init_db().unwrap();
+        let rows = || -> Vec<(i64, String, String)> {
+            let mut stmt = conn
+                .prepare(
+                    "SELECT id, file_path, content FROM chunks \
+                     WHERE codebase_id = ?1 ORDER BY file_path, id",
+                )
+                .unwrap();
+            stmt.query_map([&codebase_id], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+            })
+            .unwrap()
+            .map(|r| r.unwrap())
+            .collect()
+        };
+        let before = rows();
+
+        fs::write(
+            dir.path().join("saved.rs"),
+            "fn after_save() {}\nfn added_on_save() {}\n",
+        )
+        .unwrap();
+        let created = Indexer::new(IndexingOptions::default())
+            .reindex_file(dir.path(), "saved.rs")
+            .unwrap();
+        assert_eq!(created, 1);
+
+        let after = rows();
+        let of = |rows: &[(i64, String, String)], file: &str| -> Vec<(i64, String, String)> {
+            rows.iter().filter(|r| r.1 == file).cloned().collect()
+        };
+        assert_eq!(of(&after, "other.rs"), of(&before, "other.rs"));
+        let saved = of(&after, "saved.rs");
+        assert_eq!(saved.len(), 1);
+        assert!(saved[0].2.contains("added_on_save"));
+        assert!(stale_files(dir.path()).unwrap().is_empty());
+
+        // A file deleted on disk loses its chunks and manifest entry
+        fs::remove_file(dir.path().join("saved.rs")).unwrap();
+        let created = Indexer::new(IndexingOptions::default())
+            .reindex_file(dir.path(), "saved.rs")
+            .unwrap();
+        assert_eq!(created, 0);
+        assert!(of(&rows(), "saved.rs").is_empty());
+        assert_eq!(of(&rows(), "other.rs"), of(&before, "other.rs"));
+        assert!(stale_files(dir.path()).unwrap().is_empty());
+    }
 
-    let chunks: Vec<Chunk> = code_chunks
-        .into_iter()
-        .map(|chunk| Chunk {
-            id: None,
-            codebase_id: codebase_id.to_string(),
-            file_path: chunk.file_path,
-            start_line: chunk.start_line as i64,
-            end_line: chunk.end_line as i64,
-            content: chunk.content,
-            language: Some(chunk.language),
-            embedding: vec![],
-            hash: file_hash.to_string(),
+    #[test]
+    fn test_non_utf8_file_is_indexed_lossily() {
+        let _data_dir = crate::test_support::use_temp_data_dir();
+
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join("legacy.c"),
+            b"/* r\xE9sum\xE9 parser */\nint parse_latin1_resume(void) { return 0; }\n",
+        )
+        .unwrap();
+        let stats = Indexer::new(IndexingOptions::default())
+            .index_codebase(dir.path())
+            .unwrap();
+        assert_eq!(stats.files_indexed, 1);
+        assert_eq!(stats.chunks_created, 1);
+
+        let codebase_id = get_codebase_hash(&dir.path().canonicalize().unwrap());
+        let conn = init_db().unwrap();
+        let content: String = conn
+            .query_row(
+                "SELECT content FROM chunks WHERE codebase_id = ?1 AND file_path = 'legacy.c'",
+                [&codebase_id],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert!(
+            content.contains("r\u{FFFD}sum\u{FFFD} parser"),
+            "{}",
+            content
+        );
+        assert!(content.contains("parse_latin1_resume"));
+    }
+
+    #[test]
+    fn test_index_files_indexes_only_listed_paths() {
+        let _data_dir = crate::test_support::use_temp_data_dir();
+
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["alpha", "beta", "gamma", "delta", "epsilon"] {
+            fs::write(
+                dir.path().join(format!("{}.rs", name)),
+                format!("fn listed_file_{}() {{}}\n", name),
+            )
+            .unwrap();
+        }
+        let absolute = dir.path().canonicalize().unwrap().join("delta.rs");
+        let files = vec![
+            "./beta.rs".to_string(),
+            absolute.to_string_lossy().to_string(),
+        ];
+        let stats = Indexer::new(IndexingOptions::default())
+            .index_files(dir.path(), &files)
+            .unwrap();
+        assert_eq!(stats.files_indexed, 2);
+        assert_eq!(stats.chunks_created, 2);
+
+        let codebase_id = get_codebase_hash(&dir.path().canonicalize().unwrap());
+        let conn = init_db().unwrap();
+        let mut stmt = conn
+            .prepare("SELECT DISTINCT file_path FROM chunks WHERE codebase_id = ?1 ORDER BY 1")
+            .unwrap();
+        let indexed: Vec<String> = stmt
+            .query_map([&codebase_id], |row| row.get(0))
+            .unwrap()
+            .map(|r| r.unwrap())
+            .collect();
+        assert_eq!(indexed, vec!["beta.rs", "delta.rs"]);
+        assert!(crate::database::get_codebase_metadata(&conn, &codebase_id)
+            .unwrap()
+            .is_some());
+
+        // A path outside the root fails the whole list
+        for outside in ["../elsewhere.rs", "/etc/hostname"] {
+            let files = vec!["gamma.rs".to_string(), outside.to_string()];
+            let err = Indexer::new(IndexingOptions::default())
+                .index_files(dir.path(), &files)
+                .unwrap_err();
+            assert!(matches!(err, CodeSearchError::InvalidInput(_)), "{}", err);
+        }
+        let gamma: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM chunks WHERE codebase_id = ?1 AND file_path = 'gamma.rs'",
+                [&codebase_id],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(gamma, 0);
+    }
+
+    #[test]
+    fn test_index_files_follows_the_error_policy() {
+        let _data_dir = crate::test_support::use_temp_data_dir();
+
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("good.rs"), "fn good() {}\n").unwrap();
+        fs::write(dir.path().join("bad.rs"), "fn was_readable() {}\n").unwrap();
+        Indexer::new(IndexingOptions::default())
+            .index_codebase(dir.path())
+            .unwrap();
+        let codebase_id = get_codebase_hash(&dir.path().canonicalize().unwrap());
+        let conn = init_db().unwrap();
+        let indexed = || -> Vec<String> {
+            let mut stmt = conn
+                .prepare("SELECT content FROM chunks WHERE codebase_id = ?1 ORDER BY file_path")
+                .unwrap();
+            stmt.query_map([&codebase_id], |row| row.get(0))
+                .unwrap()
+                .map(|r| r.unwrap())
+                .collect()
+        };
+        let before = indexed();
+
+        fs::write(dir.path().join("good.rs"), "fn good() {}\nfn better() {}\n").unwrap();
+        fs::write(dir.path().join("bad.rs"), [0x66, 0x6e, 0xff, 0xfe, 0x0a]).unwrap();
+        let files = vec!["good.rs".to_string(), "bad.rs".to_string()];
+
+        // Abort leaves the earlier good.rs write uncommitted
+        let err = Indexer::new(IndexingOptions {
+            on_error: ErrorPolicy::Abort,
+            index_non_utf8: Some(false),
+            ..Default::default()
         })
-        .collect();
+        .index_files(dir.path(), &files)
+        .unwrap_err();
+        assert!(matches!(err, CodeSearchError::FileRead { .. }), "{}", err);
+        assert_eq!(indexed(), before);
+
+        let stats = Indexer::new(IndexingOptions {
+            index_non_utf8: Some(false),
+            ..Default::default()
+        })
+        .index_files(dir.path(), &files)
+        .unwrap();
+        assert_eq!(stats.files_indexed, 1);
+        assert_eq!(stats.files_skipped, 1);
+        let files: Vec<String> = crate::database::chunk_counts_by_file(&conn, &codebase_id)
+            .unwrap()
+            .into_iter()
+            .map(|(path, _)| path)
+            .collect();
+        assert_eq!(files, vec!["good.rs"]);
+        assert!(indexed()[0].contains("fn better()"));
+    }
 
-    Ok(chunks)
-}
+    #[test]
+    fn test_explain_file_reports_each_outcome() {
+        let _data_dir = crate::test_support::use_temp_data_dir();
+
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("src")).unwrap();
+        fs::write(dir.path().join(".gitignore"), "generated.rs\n").unwrap();
+        fs::write(dir.path().join("generated.rs"), "fn generated() {}\n").unwrap();
+        fs::write(dir.path().join("notes.xyz"), "plain notes\n").unwrap();
+        fs::write(dir.path().join("src/lib.rs"), "fn a() {}\nfn b() {}\n").unwrap();
+        fs::write(dir.path().join("src/empty.rs"), "").unwrap();
+
+        Indexer::new(IndexingOptions::default())
+            .index_codebase(dir.path())
+            .unwrap();
+        fs::write(dir.path().join("src/new.rs"), "fn new() {}\n").unwrap();
+
+        let conn = init_db().unwrap();
+        let explain = |file: &str| explain_file(&conn, dir.path(), file).unwrap();
+
+        let indexed = explain("src/lib.rs");
+        assert_eq!(indexed.skipped, None);
+        assert_eq!(indexed.chunks, 1);
+
+        let ignored = explain("generated.rs");
+        assert_eq!(ignored.skipped, Some(SkipReason::Gitignored));
+        assert_eq!(ignored.chunks, 0);
+
+        let wrong_extension = explain("notes.xyz");
+        assert_eq!(
+            wrong_extension.skipped,
+            Some(SkipReason::Extension(".xyz".to_string()))
+        );
+        assert_eq!(
+            wrong_extension.skipped.unwrap().to_string(),
+            "extension .xyz is not in indexing.extensions"
+        );
+
+        assert_eq!(explain("src/empty.rs").skipped, Some(SkipReason::Empty));
+        assert_eq!(explain("src/missing.rs").skipped, Some(SkipReason::Missing));
+        // Not skipped, just not indexed since it appeared
+        let fresh = explain("src/new.rs");
+        assert_eq!((fresh.skipped, fresh.chunks), (None, 0));
+    }
 
-fn detect_changes(file_hashes: &[FileHash], manifest: &HashMap<String, String>) -> Changes {
-    let mut changes = Changes::default();
-    let mut current_files: HashMap<String, String> = HashMap::new();
+    #[test]
+    fn test_status_and_explain_follow_indexed_global_gitignore() {
+        let _data_dir = crate::test_support::use_temp_data_dir();
+
+        // A .gitignore above the repository only applies with the global rules
+        let parent = tempfile::tempdir().unwrap();
+        let repo = parent.path().join("repo");
+        fs::create_dir_all(repo.join(".git")).unwrap();
+        fs::write(parent.path().join(".gitignore"), "scratch.rs\n").unwrap();
+        fs::write(repo.join("scratch.rs"), "fn scratch() {}\n").unwrap();
+        fs::write(repo.join("lib.rs"), "fn lib() {}\n").unwrap();
+
+        let stats = Indexer::new(IndexingOptions {
+            use_global_gitignore: true,
+            ..Default::default()
+        })
+        .index_codebase(&repo)
+        .unwrap();
+        assert_eq!(stats.files_indexed, 1);
+
+        let changes = stale_files(&repo).unwrap();
+        assert!(changes.added.is_empty(), "{:?}", changes.added);
+        let conn = init_db().unwrap();
+        assert_eq!(
+            explain_file(&conn, &repo, "scratch.rs").unwrap().skipped,
+            Some(SkipReason::Gitignored)
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlinked_files_are_indexed_once() {
+        use std::os::unix::fs::symlink;
+
+        let _data_dir = crate::test_support::use_temp_data_dir();
+
+        let dir = tempfile::tempdir().unwrap();
+        let outside = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("src")).unwrap();
+        fs::write(dir.path().join("src/real.rs"), "fn real() {}\n").unwrap();
+        fs::write(outside.path().join("shared.rs"), "fn shared() {}\n").unwrap();
+        // A file link and a directory link to the real file, plus a link to
+        // a file only reachable through it
+        symlink(dir.path().join("src/real.rs"), dir.path().join("alias.rs")).unwrap();
+        symlink(dir.path().join("src"), dir.path().join("mirror")).unwrap();
+        symlink(
+            outside.path().join("shared.rs"),
+            dir.path().join("shared.rs"),
+        )
+        .unwrap();
+
+        let codebase_id = get_codebase_hash(&dir.path().canonicalize().unwrap());
+        let conn = init_db().unwrap();
+        let indexed_files = || -> Vec<String> {
+            conn.prepare(
+                "SELECT DISTINCT file_path FROM chunks WHERE codebase_id = ?1 ORDER BY file_path",
+            )
+            .unwrap()
+            .query_map([&codebase_id], |row| row.get(0))
+            .unwrap()
+            .collect::<std::result::Result<_, _>>()
+            .unwrap()
+        };
+        let index = |follow_symlinks| {
+            Indexer::new(IndexingOptions {
+                force: true,
+                follow_symlinks: Some(follow_symlinks),
+                ..Default::default()
+            })
+            .index_codebase(dir.path())
+            .unwrap()
+        };
 
-    for file_hash in file_hashes {
-        current_files.insert(file_hash.relative_path.clone(), file_hash.hash.clone());
+        index(false);
+        assert_eq!(indexed_files(), vec!["src/real.rs"]);
 
-        if let Some(old_hash) = manifest.get(&file_hash.relative_path) {
-            if old_hash != &file_hash.hash {
-                changes
-                    .modified
-                    .push((file_hash.relative_path.clone(), file_hash.hash.clone()));
-            }
-        } else {
-            changes
-                .added
-                .push((file_hash.relative_path.clone(), file_hash.hash.clone()));
+        let stats = index(true);
+        assert_eq!(stats.files_indexed, 2);
+        assert_eq!(indexed_files(), vec!["shared.rs", "src/real.rs"]);
+    }
+
+    #[test]
+    fn test_file_name_chunks_match_by_name() {
+        use crate::database::{fts_search, SearchFilters};
+
+        let _data_dir = crate::test_support::use_temp_data_dir();
+
+        let dir = tempfile::tempdir().unwrap();
+        let auth = dir.path().join("src/auth");
+        fs::create_dir_all(&auth).unwrap();
+        fs::write(
+            auth.join("loginHandler.rs"),
+            "fn verify(user: &User) -> bool {\n    user.ok\n}\n",
+        )
+        .unwrap();
+        fs::write(dir.path().join("src/session.rs"), "fn expire() {}\n").unwrap();
+        let codebase_id = get_codebase_hash(&dir.path().canonicalize().unwrap());
+        let conn = init_db().unwrap();
+        let search = |expr: &str| {
+            fts_search(
+                &conn,
+                "login",
+                Some(&codebase_id),
+                10,
+                &SearchFilters::parse(expr).unwrap(),
+            )
+            .unwrap()
+        };
+
+        Indexer::new(IndexingOptions::default())
+            .index_codebase(dir.path())
+            .unwrap();
+        assert!(search("").is_empty(), "nothing mentions login");
+
+        let stats = Indexer::new(IndexingOptions {
+            force: true,
+            index_file_names: Some(true),
+            ..Default::default()
+        })
+        .index_codebase(dir.path())
+        .unwrap();
+        assert_eq!(stats.chunks_created, 4);
+
+        let hits = search("");
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].file_path, "src/auth/loginHandler.rs");
+        assert_eq!(hits[0].start_line, 0);
+        assert_eq!(
+            hits[0].content,
+            "src/auth/loginHandler.rs\nsrc auth login Handler rs"
+        );
+        assert_eq!(search("kind:path").len(), 1);
+        assert!(search("-kind:path").is_empty());
+    }
+
+    #[test]
+    fn test_deterministic_order_assigns_identical_rowids() {
+        let _data_dir = crate::test_support::use_temp_data_dir();
+
+        let dir = tempfile::tempdir().unwrap();
+        for name in [
+            "m.rs", "b.rs", "z.rs", "a/x.rs", "a/c.rs", "q.rs", "d.rs", "k.rs",
+        ] {
+            let path = dir.path().join(name);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            let body = (1..=12)
+                .map(|i| format!("fn f_{}() {{}}", i))
+                .collect::<Vec<_>>()
+                .join("\n");
+            fs::write(path, body).unwrap();
         }
+        let codebase_id = get_codebase_hash(&dir.path().canonicalize().unwrap());
+
+        // Rowid ranks rather than raw rowids, since other tests share the DB
+        // and may insert between two files of this run
+        let assignment = || {
+            Indexer::new(IndexingOptions {
+                force: true,
+                chunk_size: Some(5),
+                chunk_overlap: Some(0),
+                deterministic_order: Some(true),
+                ..Default::default()
+            })
+            .index_codebase(dir.path())
+            .unwrap();
+            let conn = init_db().unwrap();
+            let mut stmt = conn
+                .prepare(
+                    "SELECT file_path, start_line FROM chunks \
+                     WHERE codebase_id = ?1 ORDER BY id",
+                )
+                .unwrap();
+            stmt.query_map([&codebase_id], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?))
+            })
+            .unwrap()
+            .enumerate()
+            .map(|(rank, r)| {
+                let (file, line) = r.unwrap();
+                (rank, file, line)
+            })
+            .collect::<Vec<_>>()
+        };
+
+        let first = assignment();
+        assert_eq!(first.len(), 24);
+        assert_eq!(first, assignment());
+
+        let mut sorted: Vec<(String, i64)> =
+            first.iter().map(|(_, f, l)| (f.clone(), *l)).collect();
+        sorted.sort();
+        let order: Vec<(String, i64)> = first.into_iter().map(|(_, f, l)| (f, l)).collect();
+        assert_eq!(order, sorted);
     }
 
-    for path in manifest.keys() {
-        if !current_files.contains_key(path) {
-            changes.removed.push(path.clone());
+    #[test]
+    fn test_error_policy_skip_and_abort() {
+        let _data_dir = crate::test_support::use_temp_data_dir();
+
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("good.rs"), "fn good() {}\n").unwrap();
+        // Not valid UTF-8, so without lossy decoding it hashes fine but
+        // cannot be read as source
+        fs::write(dir.path().join("bad.rs"), [0x66, 0x6e, 0xff, 0xfe, 0x0a]).unwrap();
+
+        let err = Indexer::new(IndexingOptions {
+            on_error: ErrorPolicy::Abort,
+            index_non_utf8: Some(false),
+            ..Default::default()
+        })
+        .index_codebase(dir.path())
+        .unwrap_err();
+        match err {
+            CodeSearchError::FileRead { path } => assert!(path.ends_with("bad.rs")),
+            other => panic!("expected FileRead, got {:?}", other),
         }
+        let codebase_id = get_codebase_hash(&dir.path().canonicalize().unwrap());
+        let conn = init_db().unwrap();
+        assert!(crate::database::chunk_counts_by_file(&conn, &codebase_id)
+            .unwrap()
+            .is_empty());
+
+        let stats = Indexer::new(IndexingOptions {
+            index_non_utf8: Some(false),
+            ..Default::default()
+        })
+        .index_codebase(dir.path())
+        .unwrap();
+        assert_eq!(stats.files_indexed, 2);
+        let files: Vec<String> = crate::database::chunk_counts_by_file(&conn, &codebase_id)
+            .unwrap()
+            .into_iter()
+            .map(|(path, _)| path)
+            .collect();
+        assert_eq!(files, vec!["good.rs"]);
     }
 
-    changes
-}
+    #[test]
+    fn test_abort_rolls_back_files_inserted_before_the_error() {
+        let _data_dir = crate::test_support::use_temp_data_dir();
+
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a_kept.rs"), "fn before_abort() {}\n").unwrap();
+        Indexer::new(IndexingOptions::default())
+            .index_codebase(dir.path())
+            .unwrap();
+        let codebase_id = get_codebase_hash(&dir.path().canonicalize().unwrap());
+        let conn = init_db().unwrap();
+        let before = crate::database::chunk_counts_by_file(&conn, &codebase_id).unwrap();
+
+        // More files than one producer window, so earlier batches are
+        // inserted before the unreadable file is reached
+        fs::write(dir.path().join("a_kept.rs"), "fn after_abort() {}\n").unwrap();
+        for i in 0..FILES_PER_WINDOW + 8 {
+            fs::write(
+                dir.path().join(format!("b_{:03}.rs", i)),
+                format!("fn filler_{}() {{}}\n", i),
+            )
+            .unwrap();
+        }
+        fs::write(dir.path().join("z_bad.rs"), [0x66, 0x6e, 0xff, 0xfe, 0x0a]).unwrap();
 
-pub fn list_indexed_codebases() -> Result<Vec<CodebaseInfo>> {
-    let conn = init_db()?;
-    let mut stmt = conn
-        .prepare(
-            "SELECT DISTINCT codebase_id, COUNT(*) as chunk_count, COUNT(DISTINCT file_path) as file_count 
-             FROM chunks 
-             GROUP BY codebase_id",
+        let err = Indexer::new(IndexingOptions {
+            on_error: ErrorPolicy::Abort,
+            index_non_utf8: Some(false),
+            deterministic_order: Some(true),
+            ..Default::default()
+        })
+        .index_codebase(dir.path())
+        .unwrap_err();
+        assert!(matches!(err, CodeSearchError::FileRead { .. }));
+        assert_eq!(
+            crate::database::chunk_counts_by_file(&conn, &codebase_id).unwrap(),
+            before
+        );
+        let results = crate::database::fts_search(
+            &conn,
+            "before_abort",
+            Some(&codebase_id),
+            5,
+            &crate::database::SearchFilters::default(),
         )
-        .map_err(CodeSearchError::Database)?;
+        .unwrap();
+        assert_eq!(results.len(), 1);
+    }
 
-    let codebases = stmt
-        .query_map([], |row| {
-            Ok(CodebaseInfo {
-                codebase_id: row.get(0)?,
-                chunk_count: row.get(1)?,
-                file_count: row.get(2)?,
-            })
+    #[test]
+    fn test_identifier_counts_cover_files_larger_than_a_batch() {
+        let _data_dir = crate::test_support::use_temp_data_dir();
+
+        let dir = tempfile::tempdir().unwrap();
+        let lines = get_batch_size() * 8;
+        let content = (0..lines)
+            .map(|_| "spanning_marker();")
+            .collect::<Vec<_>>()
+            .join("\n");
+        fs::write(dir.path().join("long.rs"), content).unwrap();
+        fs::write(dir.path().join("short.rs"), "fn spanning_marker() {}\n").unwrap();
+
+        let stats = Indexer::new(IndexingOptions {
+            chunk_size: Some(4),
+            chunk_overlap: Some(0),
+            whole_file_max_lines: Some(0),
+            ..Default::default()
         })
-        .map_err(CodeSearchError::Database)?
-        .collect::<std::result::Result<Vec<_>, _>>()
-        .map_err(CodeSearchError::Database)?;
+        .index_codebase(dir.path())
+        .unwrap();
+        assert!(stats.chunks_created > get_batch_size());
+
+        let codebase_id = get_codebase_hash(&dir.path().canonicalize().unwrap());
+        let conn = init_db().unwrap();
+        let suggestions =
+            crate::database::suggest_terms(&conn, &codebase_id, "spanning", 5).unwrap();
+        assert_eq!(suggestions.len(), 1);
+        assert_eq!(suggestions[0].frequency, lines as i64 + 1);
+    }
 
-    Ok(codebases)
-}
+    #[test]
+    fn test_moved_codebase_searchable_without_reindex() {
+        let _data_dir = crate::test_support::use_temp_data_dir();
+
+        let parent = tempfile::tempdir().unwrap();
+        let old_dir = parent.path().join("before");
+        fs::create_dir(&old_dir).unwrap();
+        fs::write(
+            old_dir.join("lib.rs"),
+            "fn relocated_marker() -> u32 {\n    42\n}\n",
+        )
+        .unwrap();
+        let stats = Indexer::new(IndexingOptions::default())
+            .index_codebase(&old_dir)
+            .unwrap();
+        assert!(stats.chunks_created > 0);
+        let old_id = get_codebase_hash(&old_dir.canonicalize().unwrap());
+        let old_path = old_dir
+            .canonicalize()
+            .unwrap()
+            .to_string_lossy()
+            .to_string();
 
-#[derive(Debug, Clone, serde::Serialize)]
-pub struct CodebaseInfo {
-    pub codebase_id: String,
-    pub chunk_count: i64,
-    pub file_count: i64,
-}
+        let new_dir = parent.path().join("after");
+        fs::rename(&old_dir, &new_dir).unwrap();
+        let conn = init_db().unwrap();
+        let moved = relocate_codebase(&conn, &old_path, &new_dir).unwrap();
+        assert_eq!(moved, stats.chunks_created as i64);
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+        let new_id = get_codebase_hash(&new_dir.canonicalize().unwrap());
+        assert!(get_codebase_stats(&conn, &old_id).unwrap().is_none());
+        let results = crate::database::fts_search(
+            &conn,
+            "relocated_marker",
+            Some(&new_id),
+            5,
+            &crate::database::SearchFilters::default(),
+        )
+        .unwrap();
+        assert_eq!(results.len(), 1);
+        let metadata = crate::database::get_codebase_metadata(&conn, &new_id)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            metadata.path,
+            new_dir.canonicalize().unwrap().to_string_lossy()
+        );
+
+        // The manifest moved too, so an incremental run finds nothing to do
+        let stats = Indexer::new(IndexingOptions::default())
+            .index_codebase(&new_dir)
+            .unwrap();
+        assert_eq!(stats.files_indexed, 0);
+        assert_eq!(stats.chunks_created, 0);
+    }
+
+    fn pipeline_chunk(line: usize) -> Chunk {
+        Chunk {
+            id: None,
+            codebase_id: "pipeline".to_string(),
+            file_path: "synthetic.rs".to_string(),
+            start_line: line as i64,
+            end_line: line as i64,
+            content: "x".repeat(64),
+            language: None,
+            embedding: Vec::new(),
+            hash: String::new(),
+            chunk_kind: None,
+            author: None,
+        }
+    }
 
     #[test]
-    fn test_should_skip_file() {
-        assert!(should_skip_file(".git/config"));
-        assert!(should_skip_file("node_modules/package/index.js"));
-        assert!(should_skip_file("target/debug/main"));
-        assert!(should_skip_file("image.png"));
-        assert!(should_skip_file("archive.zip"));
+    fn test_embedding_inputs_normalize_whitespace_only_when_enabled() {
+        let mut chunk = pipeline_chunk(1);
+        chunk.content = "fn main() {\n\tlet  x =   1;   \n}\n".to_string();
+        let mut embedded = pipeline_chunk(2);
+        embedded.embedding = vec![1.0; 8];
+        let chunks = vec![chunk, embedded];
+
+        let normalized = embedding_inputs(&chunks, true);
+        assert_eq!(normalized, vec!["fn main() {\n let x = 1;\n}".to_string()]);
+        assert_ne!(normalized[0], chunks[0].content);
+        assert!(chunks[0].content.contains("\tlet  x =   1;   "));
+
+        assert_eq!(
+            embedding_inputs(&chunks, false),
+            vec![chunks[0].content.clone()]
+        );
+    }
 
-        assert!(!should_skip_file("src/main.rs"));
-        assert!(!should_skip_file("lib.py"));
-        assert!(!should_skip_file("index.js"));
+    #[test]
+    fn test_embedding_pipeline_bounds_chunks_in_flight() {
+        use std::sync::atomic::AtomicUsize;
+
+        const TOTAL_BATCHES: usize = 2_000;
+        const BATCH: usize = 16;
+        const WORKERS: usize = 3;
+        const BUFFER: usize = 2;
+
+        let live = AtomicUsize::new(0);
+        let peak = AtomicUsize::new(0);
+        let mut produced = 0;
+        let produce = std::iter::from_fn(|| {
+            if produced == TOTAL_BATCHES {
+                return None;
+            }
+            produced += 1;
+            let now = live.fetch_add(BATCH, Ordering::SeqCst) + BATCH;
+            peak.fetch_max(now, Ordering::SeqCst);
+            Some((0..BATCH).map(pipeline_chunk).collect())
+        });
+
+        let mut consumed = 0;
+        run_embedding_pipeline(
+            produce,
+            |batch: &mut Vec<Chunk>| {
+                for chunk in batch {
+                    chunk.embedding = vec![1.0; 8];
+                }
+                Ok(())
+            },
+            |batch: Vec<Chunk>| {
+                assert!(batch.iter().all(|c| c.embedding.len() == 8));
+                consumed += batch.len();
+                live.fetch_sub(batch.len(), Ordering::SeqCst);
+                Ok(())
+            },
+            WORKERS,
+            BUFFER,
+        )
+        .unwrap();
+
+        assert_eq!(consumed, TOTAL_BATCHES * BATCH);
+        let bound = (2 * BUFFER + WORKERS + 2) * BATCH;
+        let peak = peak.load(Ordering::SeqCst);
+        assert!(
+            peak <= bound,
+            "peak {} chunks in flight exceeds {}",
+            peak,
+            bound
+        );
     }
 
     #[test]
-    fn test_default_config() {
-        let config = IndexingOptions::default();
-        assert!(!config.force);
-        assert!(!config.verbose);
-        assert!(config.use_gitignore);
+    fn test_embedding_pipeline_stops_on_consumer_error() {
+        let batches = (0..1_000).map(|i| vec![pipeline_chunk(i)]);
+        let mut seen = 0;
+        let err = run_embedding_pipeline(
+            batches,
+            |_: &mut Vec<Chunk>| Ok(()),
+            |_: Vec<Chunk>| {
+                seen += 1;
+                if seen == 3 {
+                    Err(CodeSearchError::Other("insert failed".to_string()))
+                } else {
+                    Ok(())
+                }
+            },
+            2,
+            1,
+        )
+        .unwrap_err();
+        assert!(matches!(err, CodeSearchError::Other(_)));
+        assert_eq!(seen, 3);
+    }
+
+    #[test]
+    fn test_short_embedding_batch_is_surfaced() {
+        let short = || vec![vec![1.0; 384], vec![1.0; 384]];
+
+        let mut chunks: Vec<Chunk> = (1..=3).map(pipeline_chunk).collect();
+        let zeroed = assign_embeddings(&mut chunks, short(), "minilm", ErrorPolicy::Skip).unwrap();
+        assert_eq!(zeroed, 1);
+        assert!(chunks[2].embedding.iter().all(|&v| v == 0.0));
+
+        let mut chunks: Vec<Chunk> = (1..=3).map(pipeline_chunk).collect();
+        let err =
+            assign_embeddings(&mut chunks, short(), "minilm", ErrorPolicy::Abort).unwrap_err();
+        assert!(matches!(err, CodeSearchError::EmbeddingInference(_)));
+
+        // An embedding failure stops the pipeline before its batch is stored
+        let mut stored = 0;
+        let err = run_embedding_pipeline(
+            (1..=3).map(|i| vec![pipeline_chunk(i)]),
+            |batch: &mut Vec<Chunk>| {
+                let embeddings = if batch[0].start_line == 2 {
+                    Vec::new()
+                } else {
+                    short()
+                };
+                assign_embeddings(batch, embeddings, "minilm", ErrorPolicy::Abort).map(|_| ())
+            },
+            |batch: Vec<Chunk>| {
+                stored += batch.len();
+                Ok(())
+            },
+            1,
+            1,
+        )
+        .unwrap_err();
+        assert!(matches!(err, CodeSearchError::EmbeddingInference(_)));
+        assert_eq!(stored, 1);
+    }
+
+    #[test]
+    fn test_small_file_indexed_as_one_chunk() {
+        let _data_dir = crate::test_support::use_temp_data_dir();
+
+        let dir = tempfile::tempdir().unwrap();
+        let small = (1..=10)
+            .map(|i| format!("fn small_{}() {{}}", i))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let large = (1..=40)
+            .map(|i| format!("fn large_{}() {{}}", i))
+            .collect::<Vec<_>>()
+            .join("\n");
+        fs::write(dir.path().join("small.rs"), small).unwrap();
+        fs::write(dir.path().join("large.rs"), large).unwrap();
+
+        let mut indexer = Indexer::new(IndexingOptions {
+            chunk_size: Some(4),
+            chunk_overlap: Some(0),
+            whole_file_max_lines: Some(20),
+            ..Default::default()
+        });
+        let stats = indexer.index_codebase(dir.path()).unwrap();
+
+        // One whole-file chunk for small.rs, ten 4-line chunks for large.rs
+        assert_eq!(stats.chunks_created, 11);
+    }
+
+    #[test]
+    fn test_reembed_restores_zeroed_embeddings() {
+        let _data_dir = crate::test_support::use_temp_data_dir();
+
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join("parser.rs"),
+            "fn parse_tokens(input: &str) -> Vec<Token> {\n    lex(input)\n}\n",
+        )
+        .unwrap();
+        fs::write(dir.path().join("net.rs"), "fn open_socket(port: u16) {}\n").unwrap();
+
+        let mut indexer = Indexer::new(IndexingOptions::default());
+        indexer.index_codebase(dir.path()).unwrap();
+
+        let codebase_id = get_codebase_hash(&dir.path().canonicalize().unwrap());
+        let conn = init_db().unwrap();
+        let model = get_config().model_name().to_string();
+        let dimension = crate::embedding::get_model_dimension(&model);
+
+        let target = "fn open_socket(port: u16) {}";
+        let query = crate::embedding::get_embedding_with_model(target, &model);
+        let top_score = |conn: &Connection| {
+            let results =
+                crate::database::vector_search(conn, Some(&codebase_id), &query, 1).unwrap();
+            (results[0].content.clone(), results[0].score)
+        };
+        assert!(top_score(&conn).1 > 0.99);
+
+        conn.execute(
+            "UPDATE chunks SET embedding = zeroblob(?2) WHERE codebase_id = ?1",
+            rusqlite::params![codebase_id, (dimension * 4) as i64],
+        )
+        .unwrap();
+        assert_eq!(top_score(&conn).1, 0.0);
+
+        assert_eq!(reembed_codebase(&conn, &codebase_id, &model).unwrap(), 2);
+        let (content, score) = top_score(&conn);
+        assert_eq!(content, target);
+        assert!(score > 0.99);
+
+        // Embeddings were rewritten in place; FTS still sees the content
+        let fts = crate::database::fts_search(
+            &conn,
+            "open_socket",
+            Some(&codebase_id),
+            5,
+            &crate::database::SearchFilters::default(),
+        )
+        .unwrap();
+        assert_eq!(fts.len(), 1);
+    }
+
+    #[test]
+    fn test_reembed_records_the_new_model() {
+        let _data_dir = crate::test_support::use_temp_data_dir();
+
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("lib.rs"), "fn reembedded_lib() {}\n").unwrap();
+        Indexer::new(IndexingOptions::default())
+            .index_codebase(dir.path())
+            .unwrap();
+
+        let codebase_id = get_codebase_hash(&dir.path().canonicalize().unwrap());
+        let conn = init_db().unwrap();
+        let model = match get_config().model_name() {
+            "nomic" => "minilm",
+            _ => "nomic",
+        };
+        reembed_codebase(&conn, &codebase_id, model).unwrap();
+
+        let manifest_path = get_manifest_path()
+            .unwrap()
+            .join(format!("{}.json", codebase_id));
+        let params = load_index_params(&manifest_path).unwrap().unwrap();
+        assert_eq!(params.model, model);
+        assert_eq!(
+            params.embedding_dim,
+            crate::embedding::get_model_dimension(model)
+        );
+
+        // Indexing with the new model finds nothing to re-chunk
+        let stats = Indexer::new(IndexingOptions {
+            model_name: Some(model.to_string()),
+            ..Default::default()
+        })
+        .index_codebase(dir.path())
+        .unwrap();
+        assert_eq!(stats.files_indexed, 0);
     }
 }
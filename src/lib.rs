@@ -69,7 +69,9 @@
 //! - [`embedding`]: Embedding model loading and inference
 //! - [`indexing`]: Codebase indexing with incremental updates
 //! - [`database`]: SQLite database operations and search functions
+//! - [`ann`]: Persisted HNSW index for approximate vector search
 //! - [`search`]: High-level search API with result formatting
+//! - `serve`: Warm-model daemon answering JSON requests on a Unix socket
 //!
 //! ### Utility Modules
 //!
@@ -79,6 +81,8 @@
 //! - [`error`]: Error types and Result alias
 //! - [`cli`]: Command-line interface
 
+pub mod ann;
+pub mod bundle;
 pub mod cli;
 pub mod config;
 pub mod context_enriched;
@@ -89,78 +93,126 @@ pub mod gitignore;
 pub mod indexing;
 pub mod manifest;
 pub mod mcp;
+pub mod metrics;
 pub mod performance;
 pub mod query_expansion;
 pub mod search;
+#[cfg(unix)]
+pub mod serve;
 pub mod session;
 pub mod splitter;
+#[cfg(feature = "tree-sitter")]
 pub mod syntax_aware;
+#[cfg(test)]
+pub(crate) mod test_support;
 
 #[cfg(feature = "advanced")]
 pub mod advanced;
 
-pub use cli::{run, Cli};
+#[cfg(feature = "git")]
+pub mod git_history;
+
+pub use ann::{ann_index_stats, ann_search, has_ann_index, rebuild_ann_index, AnnIndexStats};
+pub use bundle::{
+    create_bundle, export_index, import_index, open_bundle, BundleInfo, BUNDLE_VERSION,
+};
+pub use cli::{run, Cli, StatusReport, STATUS_SCHEMA_VERSION};
 pub use config::{
-    get_config, set_config, reset_config, Config, ChunkingConfig, DatabaseConfig,
-    DistributedConfig, ModelConfig, PerformanceConfig, SearchConfig,
+    data_dir_override, get_config, reset_config, set_config, set_data_dir_override, ChunkOverlap,
+    ChunkParams, ChunkingConfig, Config, DatabaseConfig, DistributedConfig, ExecutionProvider,
+    FtsOperator, FusionStrategy, ModelConfig, PerformanceConfig, SearchConfig, SplitStrategy,
+};
+pub use context_enriched::{
+    enrich_chunk, estimate_tokens, extract_context, extract_doc_comments,
+    extract_function_signatures, extract_imports, extract_types, ChunkMetadata, EnrichedChunk,
+    LineRange,
 };
+#[allow(deprecated)]
 pub use database::{
-    delete_chunks_for_codebase, delete_chunks_for_file, get_codebase_stats, get_db_path,
-    get_global_stats, hybrid_search, init_db, insert_chunks, reset_db, vector_search, Chunk,
-    SearchResult, SearchFilters, Stats, CodebaseMetadata, register_codebase, 
-    get_codebase_metadata, list_codebases_with_metadata, DATA_DIR, DB_NAME,
+    chunk_content_hash, chunk_contents_after, chunks_at, commits_touching_file,
+    delete_chunks_for_codebase, delete_chunks_for_file, delete_chunks_for_prefix, embedding_format,
+    embeddings_by_content_hash, files_touched_by_commit, fts_search, fts_search_with_operator,
+    get_adjacent_chunks, get_chunk, get_chunking_params, get_codebase_breakdown,
+    get_codebase_metadata, get_codebase_stats, get_db_path, get_global_stats, get_search_history,
+    hybrid_search, hybrid_search_with_ltr, init_db, init_db_at, insert_chunks, iter_chunks,
+    iter_chunks_by_file, list_codebases_with_metadata, move_codebase, record_search_history,
+    refresh_codebase_summary, register_codebase, reset_db, resolve_codebase, resolve_data_dir,
+    resolve_db_path, set_chunking_params, set_codebase_model, set_embedding_format, suggest_terms,
+    update_embeddings, vector_only_search, vector_search, vector_search_filtered, verify_stats,
+    Breakdown, Chunk, CodebaseMetadata, EmbeddingFormat, FileBreakdown, FtsColumns,
+    LanguageBreakdown, SearchFilters, SearchHistoryEntry, SearchResult, Stats, StatsVerification,
+    TermSuggestion, BREAKDOWN_TOP_FILES, DATA_DIR, DB_NAME, TRUNCATION_MARKER,
 };
 pub use embedding::{
-    check_available, check_available_with_model, ensure_model_available,
-    ensure_model_available_with_model, get_embedding, get_embedding_with_model,
+    check_available, check_available_with_model, clear_query_cache, ensure_model_available,
+    ensure_model_available_with_model, fetch_with_retry, get_embedding, get_embedding_with_model,
     get_embeddings_batch, get_embeddings_batch_with_model, get_model_dimension,
-    get_query_embedding, get_query_embedding_with_model, is_model_loaded, zero_embedding,
-    zero_embedding_with_model, EmbeddingModel, ModelType, DEFAULT_MODEL,
+    get_query_embedding, get_query_embedding_with_model, is_model_loaded, truncate_embedding,
+    zero_embedding, zero_embedding_with_model, BackendInfo, EmbeddingModel, ModelFileFetcher,
+    ModelType, RemoteModelConfig, RetryPolicy, DEFAULT_MODEL, REMOTE_API_KEY_ENV,
 };
 pub use error::{CodeSearchError, Result};
-pub use gitignore::GitignoreMatcher;
-pub use indexing::{list_indexed_codebases, CodebaseInfo, Indexer, IndexingOptions, IndexingStats};
-pub use manifest::{
-    get_changes, get_codebase_hash, get_manifest_path, hash_file_content, load_manifest,
-    save_manifest, Changes,
-};
-pub use search::{format_results, search, FormattedResult, SearchResult as SearchAPIResult};
-pub use splitter::{
-    detect_language, generate_chunk_id, language_map, split_file, CodeChunk, DEFAULT_CHUNK_SIZE,
-    DEFAULT_OVERLAP,
+pub use gitignore::{GitignoreMatcher, GitignoreOptions};
+pub use indexing::{
+    delete_path, explain_file, list_indexed_codebases, reembed_codebase, relocate_codebase,
+    stale_files, CodebaseInfo, ErrorPolicy, FileExplanation, Indexer, IndexingOptions,
+    IndexingProgress, IndexingStats, ProgressCallback, RemovedFile, SkipReason, TruncatedFile,
 };
-pub use syntax_aware::{
-    get_language_config, is_language_supported, parse_source, split_file_syntax_aware, LanguageConfig,
-    Point, SyntaxNode,
-};
-pub use context_enriched::{
-    enrich_chunk, extract_context, extract_doc_comments, extract_function_signatures,
-    extract_imports, extract_types, estimate_tokens, ChunkMetadata, EnrichedChunk, LineRange,
+pub use manifest::{
+    get_changes, get_codebase_hash, get_manifest_path, hash_file_content, load_index_params,
+    load_manifest, resolve_manifest_dir, save_index_params, save_manifest, ChangeKind, Changes,
+    IndexParams, Manifest, ManifestEntry,
 };
+pub use metrics::{set_metrics_sink, IndexMetrics, MetricsSink, SearchMetrics};
 pub use query_expansion::{
-    correct_typos, expand_query, expand_query_fts, process_query, ProcessedQuery,
-    TokenBudget,
+    correct_typos, expand_query, expand_query_fts, process_query, ProcessedQuery, TokenBudget,
+};
+pub use search::{
+    bench_search, extract_snippet, format_results, search, search_iter, search_with_model,
+    search_with_options, BenchReport, FormattedResult, SearchOptions,
+    SearchResult as SearchAPIResult, Snippet,
 };
+#[cfg(unix)]
+pub use serve::{default_socket_path, send_request, ServeRequest, ServeResponse, Server};
 pub use session::{
     MultiStepSearchRequest, MultiStepSearchResult, QueryStep, SearchSession, SessionManager,
     SessionSearchResult, SessionSummary,
 };
+#[allow(deprecated)]
+pub use splitter::{
+    classify_chunk, detect_language, file_name_chunk, generate_chunk_id, identifier_parts,
+    is_test_path, language_map, normalize_whitespace, preview_chunks, read_source,
+    resolve_chunk_params, split_file, split_file_with_strategy, split_file_with_threshold,
+    split_identifiers, CodeChunk, CHUNK_KINDS, DEFAULT_CHUNK_SIZE, DEFAULT_OVERLAP,
+};
+#[cfg(feature = "tree-sitter")]
+pub use syntax_aware::{
+    get_language_config, is_language_supported, parse_source, split_file_syntax_aware,
+    LanguageConfig, Point, SyntaxNode,
+};
 
 // Performance module exports
 pub use performance::{
-    batch::{BatchConfig, BatchProgress, BatchResult, detect_gpu_acceleration, get_optimal_batch_size, process_embeddings_batch, process_embeddings_with_callback, GpuInfo, GpuProvider},
+    batch::{
+        detect_gpu_acceleration, get_optimal_batch_size, process_embeddings_batch,
+        process_embeddings_with_callback, BatchConfig, BatchProgress, BatchResult, GpuInfo,
+        GpuProvider,
+    },
     cache::{get_query_cache, init_query_cache, invalidate_query_cache, CacheStats, QueryCache},
-    distributed::{DistributedConfig as DistConfig, DistributedQueryRouter, DistributedSearchPlan, ShardConfig, ShardManager, ShardRouter, ConsistencyLevel, get_distributed_router, init_distributed},
+    distributed::{
+        get_distributed_router, init_distributed, ConsistencyLevel,
+        DistributedConfig as DistConfig, DistributedQueryRouter, DistributedSearchPlan,
+        ShardConfig, ShardManager, ShardRouter,
+    },
     hnsw::{distance_to_similarity, HnswConfig, HnswIndex},
 };
 
 #[cfg(feature = "advanced")]
 pub use advanced::{
-    analyze_api_change, predict_changes, find_related_tests, get_cached_graph,
-    get_graph_resources, search_multi_codebase, rerank_results_llm, summarize_chunk_llm,
-    CodeGraph, ChangePrediction, ChangeType, EdgeType, GraphEdge, GraphNode, GraphResource,
-    LlmConfig, MultiCodebaseResult, NodeType, PredictedChange, SemanticAction, ActionType,
-    ActionTarget,
+    analyze_api_change, find_related_tests, get_cached_graph, get_graph_resources, predict_changes,
+    rerank_results_llm, search_multi_codebase, summarize_chunk_llm, ActionTarget, ActionType,
+    ChangePrediction, ChangeType, CodeGraph, EdgeType, GraphEdge, GraphNode, GraphResource,
+    LlmConfig, MultiCodebaseResult, NodeType, PredictedChange, SemanticAction,
 };
 
 #[cfg(test)]
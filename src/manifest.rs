@@ -1,15 +1,281 @@
+use crate::config::SplitStrategy;
 use crate::error::{CodeSearchError, Result};
 use directories::ProjectDirs;
+use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Indexed files by path relative to the codebase root
+pub type Manifest = HashMap<String, ManifestEntry>;
+
+/// Modification times this close to when an entry was recorded can't be
+/// trusted: the file may change again within the same timestamp tick
+const MTIME_RESOLUTION: Duration = Duration::from_secs(2);
+
+/// What the manifest records about an indexed file. A file whose size and
+/// mtime still match is taken as unchanged without being read; otherwise its
+/// content hash decides.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "StoredEntry")]
+pub struct ManifestEntry {
+    pub hash: String,
+    pub size: u64,
+    /// Nanoseconds since the Unix epoch; `None` when unknown or too recent
+    /// to rely on, so the file is always hashed
+    pub mtime: Option<u64>,
+}
+
+/// Manifests written before size and mtime were recorded hold bare hashes
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredEntry {
+    Hash(String),
+    Entry {
+        hash: String,
+        size: u64,
+        mtime: Option<u64>,
+    },
+}
+
+impl From<StoredEntry> for ManifestEntry {
+    fn from(stored: StoredEntry) -> Self {
+        match stored {
+            StoredEntry::Hash(hash) => Self {
+                hash,
+                size: 0,
+                mtime: None,
+            },
+            StoredEntry::Entry { hash, size, mtime } => Self { hash, size, mtime },
+        }
+    }
+}
+
+impl ManifestEntry {
+    /// An entry for content hashing to `hash`, with `metadata` read before
+    /// the content was
+    pub fn new(hash: String, metadata: &fs::Metadata) -> Self {
+        let now = SystemTime::now();
+        let mtime = metadata
+            .modified()
+            .ok()
+            .filter(|&modified| modified + MTIME_RESOLUTION < now)
+            .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+            .map(|since_epoch| since_epoch.as_nanos() as u64);
+        Self {
+            hash,
+            size: metadata.len(),
+            mtime,
+        }
+    }
+
+    /// Whether `metadata` shows the file as it was when this entry was
+    /// recorded, so hashing it again can be skipped
+    pub fn matches(&self, metadata: &fs::Metadata) -> bool {
+        let Some(mtime) = self.mtime else {
+            return false;
+        };
+        let current = metadata
+            .modified()
+            .ok()
+            .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+            .map(|since_epoch| since_epoch.as_nanos() as u64);
+        metadata.len() == self.size && current == Some(mtime)
+    }
+}
+
+/// The settings a codebase's chunks were produced with, stored next to its
+/// manifest. Unchanged content hashes say nothing about chunks made with
+/// other settings, so a change here means every file is chunked again.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IndexParams {
+    pub chunk_size: usize,
+    pub chunk_overlap: usize,
+    pub model: String,
+    /// Length of the stored embeddings, which a model may let the config
+    /// shorten
+    pub embedding_dim: usize,
+    /// `chunking.per_language` sizes and overlaps, when no explicit chunk
+    /// size overrode them
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub per_language: BTreeMap<String, (usize, usize)>,
+    /// How files were cut into chunks
+    #[serde(default = "configured_split_strategy")]
+    pub split_strategy: SplitStrategy,
+    /// Files with at most this many lines became a single chunk; 0 for
+    /// none
+    #[serde(default = "configured_whole_file_max_lines")]
+    pub whole_file_max_lines: usize,
+    /// Whether files were picked with the global gitignore rules too. This
+    /// decides which files are indexed, not their chunks, so it is left out
+    /// of [`IndexParams::produces_same_chunks`].
+    #[serde(default)]
+    pub global_gitignore: bool,
+    /// Size limit files were picked with, `None` for no limit. Like
+    /// `global_gitignore`, it is not compared by `produces_same_chunks`.
+    #[serde(default = "configured_size_limit")]
+    pub max_file_bytes: Option<u64>,
+}
+
+// Params recorded before a setting was tracked assume the configured
+// value, rather than forcing every such codebase to be re-chunked
+
+fn configured_split_strategy() -> SplitStrategy {
+    crate::config::get_config().chunking.split_strategy
+}
+
+fn configured_whole_file_max_lines() -> usize {
+    crate::config::get_config().chunking.whole_file_max_lines
+}
+
+fn configured_size_limit() -> Option<u64> {
+    size_limit(crate::config::get_config().indexing.max_file_bytes)
+}
+
+/// An `indexing.max_file_bytes` value as a limit; 0 means none
+pub fn size_limit(max_file_bytes: u64) -> Option<u64> {
+    (max_file_bytes > 0).then_some(max_file_bytes)
+}
+
+impl IndexParams {
+    /// The parameters for chunking with `chunk_size` / `chunk_overlap` and
+    /// embedding with `model` as currently configured
+    pub fn new(chunk_size: usize, chunk_overlap: usize, model: &str) -> Self {
+        Self {
+            chunk_size,
+            chunk_overlap,
+            model: model.to_string(),
+            embedding_dim: crate::embedding::ModelType::parse(model).dimension(),
+            per_language: BTreeMap::new(),
+            split_strategy: configured_split_strategy(),
+            whole_file_max_lines: configured_whole_file_max_lines(),
+            global_gitignore: false,
+            max_file_bytes: configured_size_limit(),
+        }
+    }
+
+    /// Whether chunks made with `self` are what `other` would make. Model
+    /// names are compared by the model they resolve to.
+    pub fn produces_same_chunks(&self, other: &IndexParams) -> bool {
+        self.chunk_size == other.chunk_size
+            && self.chunk_overlap == other.chunk_overlap
+            && self.embedding_dim == other.embedding_dim
+            && self.per_language == other.per_language
+            && self.split_strategy == other.split_strategy
+            && self.whole_file_max_lines == other.whole_file_max_lines
+            && crate::embedding::ModelType::parse(&self.model)
+                == crate::embedding::ModelType::parse(&other.model)
+    }
+}
 
 #[derive(Debug, Clone, Default)]
 pub struct Changes {
-    pub added: Vec<(String, String)>,
-    pub modified: Vec<(String, String)>,
+    pub added: Vec<(String, ManifestEntry)>,
+    pub modified: Vec<(String, ManifestEntry)>,
     pub removed: Vec<String>,
+    /// Files whose content is unchanged but whose size or mtime moved, with
+    /// fresh entries so the next scan can skip hashing them
+    pub touched: Vec<(String, ManifestEntry)>,
+    /// Directories (with a trailing `/`) that lost every file, so their
+    /// chunks can go in one prefix delete. Files under them stay listed in
+    /// `removed` as well.
+    pub removed_dirs: Vec<String>,
+    /// Files left out for being larger than the size limit
+    pub too_large: Vec<String>,
+}
+
+impl Changes {
+    /// Group removed files under the highest directory that no longer holds
+    /// any of `current_files`. Only directories covering at least two
+    /// removed files are worth a prefix delete.
+    pub fn coalesce_removals<'a>(&mut self, current_files: impl IntoIterator<Item = &'a String>) {
+        let mut live_dirs: HashSet<&str> = HashSet::new();
+        for path in current_files {
+            for (i, _) in path.match_indices('/') {
+                live_dirs.insert(&path[..=i]);
+            }
+        }
+
+        let mut by_dir: HashMap<&str, usize> = HashMap::new();
+        for path in &self.removed {
+            let dead_dir = path
+                .match_indices('/')
+                .map(|(i, _)| &path[..=i])
+                .find(|dir| !live_dirs.contains(dir));
+            if let Some(dir) = dead_dir {
+                *by_dir.entry(dir).or_default() += 1;
+            }
+        }
+
+        let mut dirs: Vec<String> = by_dir
+            .into_iter()
+            .filter(|&(_, files)| files >= 2)
+            .map(|(dir, _)| dir.to_string())
+            .collect();
+        dirs.sort();
+        self.removed_dirs = dirs;
+    }
+
+    /// Sort the file at `rel_path` into added, modified or touched against
+    /// its manifest entry `old`, reading it with `read` only when its size
+    /// or mtime no longer match. Returns which list it went to, `None` when
+    /// it is unchanged, or the error from reading it.
+    pub fn record(
+        &mut self,
+        rel_path: String,
+        metadata: &fs::Metadata,
+        old: Option<&ManifestEntry>,
+        read: impl FnOnce() -> std::io::Result<Vec<u8>>,
+    ) -> std::io::Result<Option<ChangeKind>> {
+        if old.is_some_and(|old| old.matches(metadata)) {
+            return Ok(None);
+        }
+        let entry = ManifestEntry::new(hash_file_content(&read()?), metadata);
+        Ok(match old {
+            None => {
+                self.added.push((rel_path, entry));
+                Some(ChangeKind::Added)
+            }
+            Some(old) if old.hash != entry.hash => {
+                self.modified.push((rel_path, entry));
+                Some(ChangeKind::Modified)
+            }
+            Some(old) if *old != entry => {
+                self.touched.push((rel_path, entry));
+                Some(ChangeKind::Touched)
+            }
+            Some(_) => None,
+        })
+    }
+
+    /// Number of added, modified and removed files
+    pub fn len(&self) -> usize {
+        self.added.len() + self.modified.len() + self.removed.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Removed files that no entry of `removed_dirs` covers
+    pub fn removed_outside_dirs(&self) -> impl Iterator<Item = &String> {
+        self.removed.iter().filter(move |path| {
+            !self
+                .removed_dirs
+                .iter()
+                .any(|dir| path.starts_with(dir.as_str()))
+        })
+    }
+}
+
+/// Where [`Changes::record`] put a file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    Added,
+    Modified,
+    Touched,
 }
 
 pub fn get_codebase_hash(codebase_path: &Path) -> String {
@@ -19,37 +285,73 @@ pub fn get_codebase_hash(codebase_path: &Path) -> String {
     hex[..16].to_string()
 }
 
-pub fn get_manifest_path() -> Result<PathBuf> {
-    let project_dirs = ProjectDirs::from("com", "code-search", "code-search").ok_or_else(|| {
-        CodeSearchError::Manifest("Failed to get project directories".to_string())
-    })?;
-    let manifests_dir = project_dirs.data_dir().join("manifests");
+/// The manifests directory: `manifests` under `data_dir` when given,
+/// otherwise under the platform data directory. Created if missing.
+pub fn resolve_manifest_dir(data_dir: Option<&Path>) -> Result<PathBuf> {
+    let manifests_dir = match data_dir {
+        Some(dir) => dir.join("manifests"),
+        None => {
+            let project_dirs =
+                ProjectDirs::from("com", "code-search", "code-search").ok_or_else(|| {
+                    CodeSearchError::Manifest("Failed to get project directories".to_string())
+                })?;
+            project_dirs.data_dir().join("manifests")
+        }
+    };
     fs::create_dir_all(&manifests_dir).map_err(CodeSearchError::Io)?;
     Ok(manifests_dir)
 }
 
-pub fn load_manifest(manifest_path: &Path) -> Result<HashMap<String, String>> {
+/// The manifests directory of this process: under `--data-dir` when set
+pub fn get_manifest_path() -> Result<PathBuf> {
+    resolve_manifest_dir(crate::config::data_dir_override().as_deref())
+}
+
+pub fn load_manifest(manifest_path: &Path) -> Result<Manifest> {
     let content = fs::read_to_string(manifest_path).map_err(CodeSearchError::Io)?;
-    let manifest: HashMap<String, String> =
+    let manifest: Manifest =
         serde_json::from_str(&content).map_err(CodeSearchError::Serialization)?;
     Ok(manifest)
 }
 
-pub fn save_manifest(manifest_path: &Path, manifest: &HashMap<String, String>) -> Result<()> {
+pub fn save_manifest(manifest_path: &Path, manifest: &Manifest) -> Result<()> {
     let content = serde_json::to_string_pretty(manifest).map_err(CodeSearchError::Serialization)?;
     fs::write(manifest_path, content).map_err(CodeSearchError::Io)?;
     Ok(())
 }
 
+/// Where the [`IndexParams`] of the manifest at `manifest_path` are kept:
+/// `<codebase_id>.params.json` beside it
+pub fn index_params_path(manifest_path: &Path) -> PathBuf {
+    manifest_path.with_extension("params.json")
+}
+
+/// The parameters recorded beside the manifest at `manifest_path`, or
+/// `None` when it was written before they were recorded
+pub fn load_index_params(manifest_path: &Path) -> Result<Option<IndexParams>> {
+    let path = index_params_path(manifest_path);
+    if !path.exists() {
+        return Ok(None);
+    }
+    let content = fs::read_to_string(&path).map_err(CodeSearchError::Io)?;
+    Ok(Some(serde_json::from_str(&content)?))
+}
+
+pub fn save_index_params(manifest_path: &Path, params: &IndexParams) -> Result<()> {
+    let content = serde_json::to_string_pretty(params)?;
+    fs::write(index_params_path(manifest_path), content).map_err(CodeSearchError::Io)?;
+    Ok(())
+}
+
 pub fn hash_file_content(content: &[u8]) -> String {
     let hash = Sha256::digest(content);
     let hex = hex::encode(hash);
     hex[..16].to_string()
 }
 
-pub fn get_changes(codebase_path: &Path, manifest: &HashMap<String, String>) -> Result<Changes> {
+pub fn get_changes(codebase_path: &Path, manifest: &Manifest) -> Result<Changes> {
     let mut changes = Changes::default();
-    let mut current_files: HashMap<String, String> = HashMap::new();
+    let mut current_files: HashSet<String> = HashSet::new();
 
     for entry in walkdir::WalkDir::new(codebase_path)
         .into_iter()
@@ -62,45 +364,187 @@ pub fn get_changes(codebase_path: &Path, manifest: &HashMap<String, String>) ->
             Err(_) => continue,
         };
 
-        if let Ok(content) = fs::read(file_path) {
-            let hash = hash_file_content(&content);
-            current_files.insert(rel_path.clone(), hash.clone());
-
-            if let Some(old_hash) = manifest.get(&rel_path) {
-                if old_hash != &hash {
-                    changes.modified.push((rel_path.clone(), hash));
-                }
-            } else {
-                changes.added.push((rel_path, hash));
-            }
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+        let old = manifest.get(&rel_path);
+        if changes
+            .record(rel_path.clone(), &metadata, old, || fs::read(file_path))
+            .is_ok()
+        {
+            current_files.insert(rel_path);
         }
     }
 
     for path in manifest.keys() {
-        if !current_files.contains_key(path) {
+        if !current_files.contains(path) {
             changes.removed.push(path.clone());
         }
     }
+    changes.coalesce_removals(&current_files);
 
     Ok(changes)
 }
 
-pub fn save_manifest_internal(
-    manifest_path: &Path,
-    manifest: &HashMap<String, String>,
-) -> Result<()> {
+pub fn save_manifest_internal(manifest_path: &Path, manifest: &Manifest) -> Result<()> {
     save_manifest(manifest_path, manifest)
 }
 
-pub fn load_manifest_internal(manifest_path: &Path) -> Result<HashMap<String, String>> {
+pub fn load_manifest_internal(manifest_path: &Path) -> Result<Manifest> {
     load_manifest(manifest_path)
 }
 
+/// Store a codebase's manifest under a new id, as when its directory moves
+pub fn rename_manifest(old_codebase_id: &str, new_codebase_id: &str) -> Result<()> {
+    let manifest_dir = get_manifest_path()?;
+    let old_path = manifest_dir.join(format!("{}.json", old_codebase_id));
+    let new_path = manifest_dir.join(format!("{}.json", new_codebase_id));
+    for (old, new) in [
+        (old_path.clone(), new_path.clone()),
+        (index_params_path(&old_path), index_params_path(&new_path)),
+    ] {
+        if old.exists() {
+            fs::rename(&old, &new).map_err(CodeSearchError::Io)?;
+        }
+    }
+    Ok(())
+}
+
+/// Drop the entries of files whose path starts with `path_prefix` from a
+/// codebase's manifest. Returns how many were removed.
+pub fn prune_manifest_prefix(codebase_id: &str, path_prefix: &str) -> Result<usize> {
+    let manifest_path = get_manifest_path()?.join(format!("{}.json", codebase_id));
+    if !manifest_path.exists() {
+        return Ok(0);
+    }
+    let mut manifest = load_manifest(&manifest_path)?;
+    let before = manifest.len();
+    manifest.retain(|path, _| !path.starts_with(path_prefix));
+    let pruned = before - manifest.len();
+    if pruned > 0 {
+        save_manifest(&manifest_path, &manifest)?;
+    }
+    Ok(pruned)
+}
+
 pub fn delete_manifest(codebase_id: &str) -> Result<()> {
     let manifest_dir = get_manifest_path()?;
     let manifest_path = manifest_dir.join(format!("{}.json", codebase_id));
-    if manifest_path.exists() {
-        fs::remove_file(&manifest_path).map_err(CodeSearchError::Io)?;
+    for path in [index_params_path(&manifest_path), manifest_path] {
+        if path.exists() {
+            fs::remove_file(&path).map_err(CodeSearchError::Io)?;
+        }
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set_mtime(path: &Path, ago: Duration) {
+        let file = fs::File::options().write(true).open(path).unwrap();
+        file.set_modified(SystemTime::now() - ago).unwrap();
+    }
+
+    #[test]
+    fn test_load_accepts_bare_hash_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("manifest.json");
+        fs::write(
+            &path,
+            r#"{"old.rs": "abc123", "new.rs": {"hash": "def456", "size": 7, "mtime": 42}}"#,
+        )
+        .unwrap();
+
+        let manifest = load_manifest(&path).unwrap();
+        assert_eq!(manifest["old.rs"].hash, "abc123");
+        assert_eq!(manifest["old.rs"].mtime, None);
+        assert_eq!(manifest["new.rs"].size, 7);
+        assert_eq!(manifest["new.rs"].mtime, Some(42));
+
+        save_manifest(&path, &manifest).unwrap();
+        assert_eq!(load_manifest(&path).unwrap(), manifest);
+    }
+
+    #[test]
+    fn test_index_params_round_trip_beside_manifest() {
+        let dir = tempfile::tempdir().unwrap();
+        let manifest_path = dir.path().join("abc.json");
+        assert_eq!(load_index_params(&manifest_path).unwrap(), None);
+
+        let params = IndexParams::new(40, 5, "nomic");
+        save_index_params(&manifest_path, &params).unwrap();
+        assert!(dir.path().join("abc.params.json").exists());
+        let loaded = load_index_params(&manifest_path).unwrap().unwrap();
+        assert_eq!(loaded, params);
+
+        // Aliases of one model produce the same chunks; other sizes don't
+        assert!(loaded.produces_same_chunks(&IndexParams::new(40, 5, "nomic-embed-text-v1.5")));
+        assert!(!loaded.produces_same_chunks(&IndexParams::new(20, 5, "nomic")));
+        assert!(!loaded.produces_same_chunks(&IndexParams::new(40, 5, "minilm")));
+
+        // So does any other way of cutting files
+        let mut line_windows = params.clone();
+        line_windows.split_strategy = match params.split_strategy {
+            SplitStrategy::LineWindow => SplitStrategy::Syntactic,
+            SplitStrategy::Syntactic => SplitStrategy::LineWindow,
+        };
+        assert!(!loaded.produces_same_chunks(&line_windows));
+        let mut whole_files = params.clone();
+        whole_files.whole_file_max_lines += 1;
+        assert!(!loaded.produces_same_chunks(&whole_files));
+    }
+
+    #[test]
+    fn test_touched_file_is_hashed_but_not_modified() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("lib.rs");
+        fs::write(&file, "fn lib() {}").unwrap();
+        set_mtime(&file, Duration::from_secs(3600));
+
+        let manifest = get_changes(dir.path(), &Manifest::new())
+            .unwrap()
+            .added
+            .into_iter()
+            .collect::<Manifest>();
+        assert!(manifest["lib.rs"].mtime.is_some());
+
+        set_mtime(&file, Duration::from_secs(60));
+        let changes = get_changes(dir.path(), &manifest).unwrap();
+        assert!(changes.is_empty());
+        assert_eq!(changes.touched.len(), 1);
+        assert_eq!(changes.touched[0].1.hash, manifest["lib.rs"].hash);
+        assert_ne!(changes.touched[0].1.mtime, manifest["lib.rs"].mtime);
+    }
+
+    #[test]
+    fn test_matching_size_and_mtime_skip_reading() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("lib.rs");
+        fs::write(&file, "fn lib() {}").unwrap();
+        set_mtime(&file, Duration::from_secs(3600));
+        let metadata = fs::metadata(&file).unwrap();
+        let entry = ManifestEntry::new(hash_file_content(b"fn lib() {}"), &metadata);
+
+        let mut changes = Changes::default();
+        let kind = changes
+            .record("lib.rs".to_string(), &metadata, Some(&entry), || {
+                panic!("an unchanged file should not be read")
+            })
+            .unwrap();
+        assert_eq!(kind, None);
+
+        // A recent mtime is ambiguous, so the content decides
+        set_mtime(&file, Duration::ZERO);
+        let metadata = fs::metadata(&file).unwrap();
+        let recent = ManifestEntry::new(entry.hash.clone(), &metadata);
+        assert_eq!(recent.mtime, None);
+        let kind = changes
+            .record("lib.rs".to_string(), &metadata, Some(&recent), || {
+                Ok(b"fn lib() {}".to_vec())
+            })
+            .unwrap();
+        assert_eq!(kind, None);
+    }
+}
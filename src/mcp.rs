@@ -6,7 +6,7 @@
 use crate::config::get_config;
 use crate::database::{
     delete_chunks_for_codebase, get_codebase_stats, get_global_stats, hybrid_search, init_db,
-    SearchFilters,
+    suggest_terms, SearchFilters,
 };
 use crate::embedding::{ensure_model_available_with_model, get_query_embedding_with_model};
 use crate::error::{CodeSearchError, Result};
@@ -286,6 +286,10 @@ impl McpServer {
                             "type": "number",
                             "description": "Maximum number of results",
                             "default": 10
+                        },
+                        "filter": {
+                            "type": "string",
+                            "description": "Filter expression, e.g. 'lang:rust path:src/** -path:src/gen/**'"
                         }
                     },
                     "required": ["query"]
@@ -305,6 +309,29 @@ impl McpServer {
                     }
                 }),
             },
+            Tool {
+                name: "codebase_suggest".to_string(),
+                description: "Suggest identifiers from an indexed codebase that start with a prefix, most frequent first. Useful for completing search queries as they are typed.".to_string(),
+                input_schema: serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "codebase": {
+                            "type": "string",
+                            "description": "Path, name or ID of the indexed codebase"
+                        },
+                        "prefix": {
+                            "type": "string",
+                            "description": "Beginning of the identifier to complete"
+                        },
+                        "limit": {
+                            "type": "number",
+                            "description": "Maximum number of suggestions",
+                            "default": 10
+                        }
+                    },
+                    "required": ["codebase", "prefix"]
+                }),
+            },
             Tool {
                 name: "codebase_delete".to_string(),
                 description: "Remove a codebase from the index. Deletes all chunks and metadata associated with the codebase.".to_string(),
@@ -356,6 +383,7 @@ impl McpServer {
             "codebase_index" => self.tool_codebase_index(args),
             "codebase_search" => self.tool_codebase_search(args),
             "codebase_status" => self.tool_codebase_status(args),
+            "codebase_suggest" => self.tool_codebase_suggest(args),
             "codebase_delete" => self.tool_codebase_delete(args),
             _ => {
                 return JsonRpcResponse::Error(JsonRpcErrorResponse {
@@ -408,7 +436,7 @@ impl McpServer {
             .get("model")
             .and_then(|v| v.as_str())
             .unwrap_or("minilm");
-        let tags = args.get("tags").and_then(|v| v.as_str());
+        let _tags = args.get("tags").and_then(|v| v.as_str());
 
         let path = Path::new(path);
         if !path.exists() {
@@ -467,7 +495,11 @@ impl McpServer {
             .ok_or_else(|| CodeSearchError::Other("Missing query argument".to_string()))?;
 
         let codebase_param = args.get("codebase").and_then(|v| v.as_str());
-        let limit = args.get("limit").and_then(|v| v.as_i64()).unwrap_or(10) as i64;
+        let limit = args.get("limit").and_then(|v| v.as_i64()).unwrap_or(10);
+        let filters = match args.get("filter").and_then(|v| v.as_str()) {
+            Some(expr) => SearchFilters::parse(expr)?,
+            None => SearchFilters::default(),
+        };
 
         if query.trim().is_empty() {
             return Ok(serde_json::json!({ "results": [] }));
@@ -507,6 +539,7 @@ impl McpServer {
                     codebase_param.unwrap_or("unknown").to_string(),
                 ));
             }
+            crate::database::check_codebase_model(&conn, id, model)?;
         }
 
         // Ensure model is available
@@ -519,7 +552,6 @@ impl McpServer {
 
         let query_embedding = get_query_embedding_with_model(query, model);
 
-        let filters = SearchFilters::default();
         let db_results = hybrid_search(
             &conn,
             query,
@@ -540,19 +572,21 @@ impl McpServer {
         let results: Vec<serde_json::Value> = db_results
             .into_iter()
             .map(|r| {
-                let codebase_name = codebase_map
-                    .get(&r.codebase_id)
-                    .cloned()
-                    .unwrap_or_else(|| r.codebase_id.clone());
+                let name_of =
+                    |id: &String| codebase_map.get(id).cloned().unwrap_or_else(|| id.clone());
+                let codebase_name = name_of(&r.codebase_id);
+                let also_in: Vec<String> = r.also_in.iter().map(name_of).collect();
                 serde_json::json!({
                     "file": r.file_path,
                     "lines": format!("{}-{}", r.start_line, r.end_line),
                     "content": r.content,
                     "score": r.score,
                     "language": r.language,
+                    "author": r.author,
                     "rank": r.rank,
                     "codebase_id": r.codebase_id,
-                    "codebase_name": codebase_name
+                    "codebase_name": codebase_name,
+                    "also_in": also_in
                 })
             })
             .collect();
@@ -597,6 +631,44 @@ impl McpServer {
         }))
     }
 
+    fn tool_codebase_suggest(
+        &self,
+        args: Option<&serde_json::Map<String, serde_json::Value>>,
+    ) -> Result<serde_json::Value> {
+        let args = args.ok_or_else(|| CodeSearchError::Other("Missing arguments".to_string()))?;
+
+        let codebase = args
+            .get("codebase")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| CodeSearchError::Other("Missing codebase argument".to_string()))?;
+        let prefix = args
+            .get("prefix")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| CodeSearchError::Other("Missing prefix argument".to_string()))?;
+        let limit = args.get("limit").and_then(|v| v.as_i64()).unwrap_or(10);
+
+        let conn = init_db()?;
+
+        let path = Path::new(codebase);
+        let codebase_id = if path.exists() {
+            let canonical_path = path.canonicalize().map_err(CodeSearchError::Io)?;
+            get_codebase_hash(&canonical_path)
+        } else {
+            crate::database::list_codebases_with_metadata(&conn)?
+                .into_iter()
+                .find(|cb| cb.name == codebase || cb.codebase_id == codebase || cb.path == codebase)
+                .map(|cb| cb.codebase_id)
+                .ok_or_else(|| CodeSearchError::CodebaseNotIndexed(codebase.to_string()))?
+        };
+
+        let suggestions = suggest_terms(&conn, &codebase_id, prefix, limit)?;
+
+        Ok(serde_json::json!({
+            "prefix": prefix,
+            "suggestions": suggestions
+        }))
+    }
+
     fn tool_codebase_delete(
         &mut self,
         args: Option<&serde_json::Map<String, serde_json::Value>>,
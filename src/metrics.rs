@@ -0,0 +1,210 @@
+//! Optional metrics hook for indexing and search.
+//!
+//! Integrators install a [`MetricsSink`] with [`set_metrics_sink`] and export
+//! the reported data however they like (Prometheus, StatsD, logs). When no
+//! sink is installed the hooks reduce to a single atomic load.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+/// Metrics reported at the end of an indexing run
+#[derive(Debug, Clone)]
+pub struct IndexMetrics {
+    pub codebase_id: String,
+    pub files_indexed: usize,
+    pub files_removed: usize,
+    pub chunks_created: usize,
+    pub chunks_removed: usize,
+    pub duration: Duration,
+    /// Chunks created per second of wall-clock indexing time
+    pub chunks_per_sec: f64,
+}
+
+/// Metrics reported after each search
+#[derive(Debug, Clone)]
+pub struct SearchMetrics {
+    pub query: String,
+    /// `None` when searching across all codebases
+    pub codebase_id: Option<String>,
+    pub limit: i64,
+    pub result_count: usize,
+    pub duration: Duration,
+}
+
+/// Receives metrics from indexing and search. Both methods default to no-ops
+/// so implementors only override what they need.
+pub trait MetricsSink: Send + Sync {
+    fn on_index_metrics(&self, _metrics: &IndexMetrics) {}
+    fn on_search_metrics(&self, _metrics: &SearchMetrics) {}
+}
+
+static SINK_INSTALLED: AtomicBool = AtomicBool::new(false);
+static SINK: RwLock<Option<Arc<dyn MetricsSink>>> = RwLock::new(None);
+
+/// Install (or with `None`, remove) the process-wide metrics sink
+pub fn set_metrics_sink(sink: Option<Arc<dyn MetricsSink>>) {
+    if let Ok(mut guard) = SINK.write() {
+        SINK_INSTALLED.store(sink.is_some(), Ordering::Release);
+        *guard = sink;
+    }
+}
+
+/// Start timing an operation, only if a sink is installed
+pub(crate) fn start_timer() -> Option<Instant> {
+    if SINK_INSTALLED.load(Ordering::Acquire) {
+        Some(Instant::now())
+    } else {
+        None
+    }
+}
+
+fn with_sink(f: impl FnOnce(&dyn MetricsSink)) {
+    if !SINK_INSTALLED.load(Ordering::Acquire) {
+        return;
+    }
+    if let Ok(guard) = SINK.read() {
+        if let Some(ref sink) = *guard {
+            f(sink.as_ref());
+        }
+    }
+}
+
+pub(crate) fn report_index(codebase_id: &str, stats: &crate::indexing::IndexingStats) {
+    with_sink(|sink| {
+        let duration = Duration::from_millis(stats.duration_ms);
+        let secs = duration.as_secs_f64();
+        sink.on_index_metrics(&IndexMetrics {
+            codebase_id: codebase_id.to_string(),
+            files_indexed: stats.files_indexed,
+            files_removed: stats.files_removed,
+            chunks_created: stats.chunks_created,
+            chunks_removed: stats.chunks_removed,
+            duration,
+            chunks_per_sec: if secs > 0.0 {
+                stats.chunks_created as f64 / secs
+            } else {
+                0.0
+            },
+        });
+    });
+}
+
+pub(crate) fn report_search(
+    started: Option<Instant>,
+    query: &str,
+    codebase_id: Option<&str>,
+    limit: i64,
+    result_count: usize,
+) {
+    let Some(started) = started else {
+        return;
+    };
+    with_sink(|sink| {
+        sink.on_search_metrics(&SearchMetrics {
+            query: query.to_string(),
+            codebase_id: codebase_id.map(str::to_string),
+            limit,
+            result_count,
+            duration: started.elapsed(),
+        });
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct Collector {
+        index: Mutex<Vec<IndexMetrics>>,
+        search: Mutex<Vec<SearchMetrics>>,
+    }
+
+    impl MetricsSink for Collector {
+        fn on_index_metrics(&self, metrics: &IndexMetrics) {
+            self.index.lock().unwrap().push(metrics.clone());
+        }
+
+        fn on_search_metrics(&self, metrics: &SearchMetrics) {
+            self.search.lock().unwrap().push(metrics.clone());
+        }
+    }
+
+    #[test]
+    fn test_metrics_from_index_and_search() {
+        use crate::database::{hybrid_search, init_db, vector_only_search, SearchFilters};
+        use crate::embedding::get_query_embedding;
+        use crate::indexing::{Indexer, IndexingOptions};
+
+        let _data_dir = crate::test_support::use_temp_data_dir();
+
+        let collector = Arc::new(Collector::default());
+        set_metrics_sink(Some(collector.clone()));
+
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("metrics.rs"),
+            "fn record_latency_histogram() {}\n",
+        )
+        .unwrap();
+        let codebase_id = crate::manifest::get_codebase_hash(&dir.path().canonicalize().unwrap());
+
+        let mut indexer = Indexer::new(IndexingOptions::default());
+        indexer.index_codebase(dir.path()).unwrap();
+
+        let conn = init_db().unwrap();
+        let query = "record_latency_histogram";
+        let results = hybrid_search(
+            &conn,
+            query,
+            Some(&codebase_id),
+            &get_query_embedding(query),
+            5,
+            &SearchFilters::default(),
+            false,
+        )
+        .unwrap();
+        let vector_query = "latency histogram recorder";
+        let vector_results = vector_only_search(
+            &conn,
+            vector_query,
+            Some(&codebase_id),
+            &get_query_embedding(vector_query),
+            3,
+            &SearchFilters::default(),
+        )
+        .unwrap();
+
+        set_metrics_sink(None);
+
+        let index = collector.index.lock().unwrap();
+        let index = index
+            .iter()
+            .find(|m| m.codebase_id == codebase_id)
+            .expect("index metrics reported");
+        assert_eq!(index.files_indexed, 1);
+        assert!(index.chunks_created >= 1);
+        assert!(index.chunks_per_sec >= 0.0);
+
+        let searches = collector.search.lock().unwrap();
+        let search_metrics_for = |query: &str| {
+            searches
+                .iter()
+                .find(|m| {
+                    m.codebase_id.as_deref() == Some(codebase_id.as_str()) && m.query == query
+                })
+                .expect("search metrics reported")
+        };
+        let search = search_metrics_for(query);
+        assert_eq!(search.limit, 5);
+        assert_eq!(search.result_count, results.len());
+        assert!(search.result_count >= 1);
+        assert!(search.duration > Duration::ZERO);
+
+        let vector = search_metrics_for(vector_query);
+        assert_eq!(vector.limit, 3);
+        assert_eq!(vector.result_count, vector_results.len());
+    }
+}
@@ -76,11 +76,7 @@ impl BatchProgress {
         let new_count = self.completed.fetch_add(amount, Ordering::Relaxed) + amount;
         if let Some(ref pb) = self.pb {
             pb.set_position(new_count as u64);
-            let pct = if self.total > 0 {
-                (new_count * 100) / self.total
-            } else {
-                0
-            };
+            let pct = (new_count * 100).checked_div(self.total).unwrap_or(0);
             pb.set_message(format!("{}%", pct));
         }
     }
@@ -125,7 +121,7 @@ pub fn process_embeddings_batch(
     let progress = Arc::new(BatchProgress::new(total_items, config.show_progress));
 
     let batch_size = config.batch_size;
-    let num_batches = (total_items + batch_size - 1) / batch_size;
+    let num_batches = total_items.div_ceil(batch_size);
 
     let mut all_embeddings = Vec::with_capacity(total_items);
 
@@ -205,10 +201,10 @@ where
     let mut all_embeddings = Vec::with_capacity(total_items);
 
     for (batch_idx, batch) in batches.iter().enumerate() {
-        let embeddings = get_embeddings_batch_with_model(*batch, batch.len(), false, model);
+        let embeddings = get_embeddings_batch_with_model(batch, batch.len(), false, model);
         all_embeddings.extend(embeddings);
 
-        let completed = ((batch_idx + 1) * batch.len()).min(total_items);
+        let _completed = ((batch_idx + 1) * batch.len()).min(total_items);
         progress.increment(batch.len());
         callback(batch_idx + 1, num_batches);
     }
@@ -330,14 +326,17 @@ mod tests {
 
         assert_eq!(result.embeddings.len(), 20);
         assert_eq!(result.total_items, 20);
-        assert!(result.duration_ms > 0);
+        // The fallback backend finishes in well under a millisecond, so
+        // duration_ms may legitimately be 0; check the embeddings instead.
+        let dim = crate::embedding::get_model_dimension("minilm");
+        assert!(result.embeddings.iter().all(|e| e.len() == dim));
     }
 
     #[test]
     fn test_gpu_detection() {
         let info = detect_gpu_acceleration();
         // Should not panic, just return info
-        assert!(info.name.len() > 0);
+        assert!(!info.name.is_empty());
     }
 
     #[test]
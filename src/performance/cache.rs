@@ -11,7 +11,9 @@ use std::sync::{Arc, OnceLock, RwLock};
 struct CacheEntry {
     embedding: Vec<f32>,
     hash: u64,
+    #[allow(dead_code)]
     access_count: u64,
+    #[allow(dead_code)]
     timestamp: std::time::Instant,
 }
 
@@ -235,9 +237,9 @@ mod tests {
         let cache = QueryCache::new(10, "minilm");
 
         // Same query with different codebase IDs should be cached separately
-        let emb1 = cache.get("test", Some("codebase1"));
-        let emb2 = cache.get("test", Some("codebase2"));
-        let emb3 = cache.get("test", None);
+        let _emb1 = cache.get("test", Some("codebase1"));
+        let _emb2 = cache.get("test", Some("codebase2"));
+        let _emb3 = cache.get("test", None);
 
         // They should all be cached (3 misses)
         assert_eq!(cache.stats().misses, 3);
@@ -75,6 +75,7 @@ fn uuid_simple() -> String {
 pub struct ShardRouter {
     config: RwLock<DistributedConfig>,
     // Cache of shard to use based on hash
+    #[allow(dead_code)]
     routing_cache: RwLock<HashMap<String, usize>>,
 }
 
@@ -159,6 +160,7 @@ fn simple_hash(s: &str) -> usize {
 /// Query router that combines results from multiple shards
 pub struct DistributedQueryRouter {
     router: Arc<ShardRouter>,
+    #[allow(dead_code)]
     model: String,
 }
 
@@ -173,9 +175,9 @@ impl DistributedQueryRouter {
     /// Route a search query to appropriate shards
     pub fn route_search(
         &self,
-        query: &str,
+        _query: &str,
         codebase_id: Option<&str>,
-        limit: usize,
+        _limit: usize,
     ) -> DistributedSearchPlan {
         let config = self.router.get_config();
 
@@ -416,8 +418,11 @@ mod tests {
                 end_line: 10,
                 content: "content".to_string(),
                 language: Some("rust".to_string()),
+                author: None,
                 score: 0.9,
                 rank: 1,
+                is_truncated: false,
+                also_in: Vec::new(),
             },
             SearchResult {
                 chunk_id: 2,
@@ -427,8 +432,11 @@ mod tests {
                 end_line: 15,
                 content: "content".to_string(),
                 language: Some("rust".to_string()),
+                author: None,
                 score: 0.8,
                 rank: 2,
+                is_truncated: false,
+                also_in: Vec::new(),
             },
         ];
 
@@ -441,8 +449,11 @@ mod tests {
                 end_line: 10,
                 content: "content".to_string(),
                 language: Some("rust".to_string()),
+                author: None,
                 score: 0.85,
                 rank: 1,
+                is_truncated: false,
+                also_in: Vec::new(),
             },
             SearchResult {
                 chunk_id: 3,
@@ -452,8 +463,11 @@ mod tests {
                 end_line: 20,
                 content: "content".to_string(),
                 language: Some("rust".to_string()),
+                author: None,
                 score: 0.7,
                 rank: 2,
+                is_truncated: false,
+                also_in: Vec::new(),
             },
         ];
 
@@ -463,6 +477,6 @@ mod tests {
         );
 
         // Chunk 1 should have higher score due to appearing in both shards
-        assert!(merged.len() > 0);
+        assert!(!merged.is_empty());
     }
 }
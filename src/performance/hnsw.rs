@@ -2,8 +2,6 @@
 // Provides 10-100x faster vector search through Hierarchical Navigable Small World graphs
 
 use serde::{Deserialize, Serialize};
-use std::collections::BinaryHeap;
-use std::cmp::Reverse;
 use std::sync::RwLock;
 
 /// HNSW Configuration
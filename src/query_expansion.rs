@@ -125,7 +125,7 @@ pub fn expand_query(query: &str) -> Vec<String> {
         // Check if this word has synonyms
         if let Some(syn_list) = synonyms.get(word_lower.as_str()) {
             for syn in syn_list {
-                let expanded_term = query.replace(word, *syn);
+                let expanded_term = query.replace(word, syn);
                 if expanded_term != query {
                     expanded.push(expanded_term);
                 }
@@ -137,7 +137,7 @@ pub fn expand_query(query: &str) -> Vec<String> {
             for syn in syn_list {
                 if syn.contains(&word_lower) || word_lower.contains(syn) {
                     // Add the key term
-                    let expanded_term = query.replace(word, *key);
+                    let expanded_term = query.replace(word, key);
                     if expanded_term != query {
                         expanded.push(expanded_term);
                     }
@@ -313,10 +313,12 @@ pub struct ProcessedQuery {
 
 /// Token budget management for chunks
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Default)]
 pub enum TokenBudget {
     /// Small chunks (~256 tokens) - for focused, specific searches
     Small,
     /// Medium chunks (~512 tokens) - balanced
+    #[default]
     Medium,
     /// Large chunks (~1024 tokens) - for broader context
     Large,
@@ -346,11 +348,6 @@ impl TokenBudget {
     }
 }
 
-impl Default for TokenBudget {
-    fn default() -> Self {
-        TokenBudget::Medium
-    }
-}
 
 impl From<usize> for TokenBudget {
     fn from(n: usize) -> Self {
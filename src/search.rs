@@ -1,13 +1,109 @@
-use crate::database::{init_db, vector_search};
-use crate::error::Result;
+use crate::database::{
+    hybrid_search, hybrid_search_inner, init_db, record_finished_search, search_result_for_chunk,
+    vector_only_search, vector_scores, SearchFilters,
+};
+use crate::embedding::{
+    ensure_model_available_with_model, get_query_embedding_with_model, EmbeddingModel,
+    DEFAULT_MODEL,
+};
+use crate::error::{CodeSearchError, Result};
+use crate::manifest::get_codebase_hash;
+use rusqlite::Connection;
+use serde::Serialize;
+use std::path::Path;
+use std::time::{Duration, Instant};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct SearchResult {
     pub file: String,
     pub lines: String,
     pub content: String,
     pub score: f64,
     pub language: Option<String>,
+    /// Main author per `git blame`, when recorded at indexing
+    pub author: Option<String>,
+    /// The lines of `content` around the best query match, when requested
+    /// with [`SearchResult::with_snippet`]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub snippet: Option<Snippet>,
+}
+
+/// A window of a result's content, with its byte range in that content
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Snippet {
+    pub text: String,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl SearchResult {
+    /// Attach the snippet of `content` around the best hit for `query`.
+    /// See [`extract_snippet`].
+    pub fn with_snippet(mut self, query: &str, context_lines: usize) -> Self {
+        let (text, start, end) = extract_snippet(&self.content, query, context_lines);
+        self.snippet = Some(Snippet { text, start, end });
+        self
+    }
+}
+
+impl From<crate::database::SearchResult> for SearchResult {
+    fn from(r: crate::database::SearchResult) -> Self {
+        Self {
+            file: r.file_path,
+            lines: format!("{}-{}", r.start_line, r.end_line),
+            content: r.content,
+            score: r.score,
+            language: r.language,
+            author: r.author,
+            snippet: None,
+        }
+    }
+}
+
+/// The lines of `content` around its best literal match for `query`, and the
+/// byte range of that window in `content`.
+///
+/// The best line holds the most distinct query terms (case-insensitive),
+/// the first such line on a tie; the window is that line and up to
+/// `context_lines` lines either side. When no term occurs literally, as for
+/// a purely semantic match, the window is the first `context_lines * 2`
+/// lines (at least one).
+pub fn extract_snippet(content: &str, query: &str, context_lines: usize) -> (String, usize, usize) {
+    let terms: Vec<String> = query
+        .split(|c: char| !c.is_alphanumeric() && c != '_')
+        .filter(|term| !term.is_empty())
+        .map(str::to_lowercase)
+        .collect();
+    let lines: Vec<&str> = content.split_inclusive('\n').collect();
+    if lines.is_empty() {
+        return (String::new(), 0, 0);
+    }
+
+    let mut best: Option<(usize, usize)> = None;
+    for (i, line) in lines.iter().enumerate() {
+        let line = line.to_lowercase();
+        let hits = terms
+            .iter()
+            .enumerate()
+            .filter(|(j, term)| !terms[..*j].contains(term) && line.contains(term.as_str()))
+            .count();
+        if hits > 0 && best.is_none_or(|(_, most)| hits > most) {
+            best = Some((i, hits));
+        }
+    }
+
+    let (first, last) = match best {
+        Some((hit, _)) => (
+            hit.saturating_sub(context_lines),
+            (hit + context_lines).min(lines.len() - 1),
+        ),
+        None => (0, (context_lines * 2).clamp(1, lines.len()) - 1),
+    };
+
+    let start: usize = lines[..first].iter().map(|line| line.len()).sum();
+    let window_len: usize = lines[first..=last].iter().map(|line| line.len()).sum();
+    let text = content[start..start + window_len].trim_end_matches(['\r', '\n']);
+    (text.to_string(), start, start + text.len())
 }
 
 #[derive(Debug, Clone)]
@@ -17,42 +113,315 @@ pub struct FormattedResult {
     pub content: String,
     pub score: String,
     pub language: Option<String>,
+    pub author: Option<String>,
+}
+
+/// What to search and how, for [`search_with_options`]
+#[derive(Debug, Clone)]
+pub struct SearchOptions {
+    /// Path of the indexed codebase to search; `None` searches every
+    /// indexed codebase
+    pub codebase_path: Option<String>,
+    /// Most results to return
+    pub limit: i64,
+    /// Model to embed the query with, which must be the one the codebase
+    /// was indexed with; `None` uses `model.model_type` from the config
+    pub model: Option<String>,
+    /// Rank by vector similarity alone, skipping the full-text search
+    pub vector_only: bool,
+    /// Languages a result must be one of; empty allows any
+    pub languages: Vec<String>,
+    /// Drop results scoring below this. Scores are cosine similarity with
+    /// `vector_only`, and otherwise the weighted fusion of full-text and
+    /// vector scores, roughly 0 to 1 with the default weights.
+    pub min_score: Option<f64>,
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        Self {
+            codebase_path: None,
+            limit: 10,
+            model: None,
+            vector_only: false,
+            languages: Vec::new(),
+            min_score: None,
+        }
+    }
 }
 
+/// Search the codebase indexed at `codebase_path` (all codebases when empty)
+/// with the default model. See [`search_with_options`].
 pub fn search(
     query: &str,
     codebase_path: &str,
     limit: i64,
-    _vector_only: bool,
+    vector_only: bool,
 ) -> Result<Vec<SearchResult>> {
+    search_with_options(
+        query,
+        &SearchOptions {
+            codebase_path: Some(codebase_path.to_string()).filter(|path| !path.is_empty()),
+            limit,
+            model: Some(DEFAULT_MODEL.to_string()),
+            vector_only,
+            ..Default::default()
+        },
+    )
+}
+
+/// Search with query embeddings from `model`, which should be the model the
+/// codebase was indexed with. See [`search_with_options`].
+pub fn search_with_model(
+    query: &str,
+    codebase_path: &str,
+    limit: i64,
+    vector_only: bool,
+    model: &str,
+) -> Result<Vec<SearchResult>> {
+    search_with_options(
+        query,
+        &SearchOptions {
+            codebase_path: Some(codebase_path.to_string()).filter(|path| !path.is_empty()),
+            limit,
+            model: Some(model.to_string()),
+            vector_only,
+            ..Default::default()
+        },
+    )
+}
+
+/// Hybrid full-text and vector search, or vector search alone with
+/// `vector_only`, as `options` describe. Results scoring below `min_score`
+/// are dropped, so an off-topic query can return nothing.
+pub fn search_with_options(query: &str, options: &SearchOptions) -> Result<Vec<SearchResult>> {
     if query.trim().is_empty() {
         return Ok(Vec::new());
     }
 
+    let config = crate::config::get_config();
+    let model = options
+        .model
+        .as_deref()
+        .unwrap_or(config.model.model_type.as_str());
     let conn = init_db()?;
+    let codebase_id = codebase_id_for(options.codebase_path.as_deref().unwrap_or(""))?;
+    if let Some(ref id) = codebase_id {
+        crate::database::check_codebase_model(&conn, id, model)?;
+    }
 
-    let codebase_id = if codebase_path.is_empty() {
-        None
+    ensure_model_available_with_model(model)?;
+    let embedding = get_query_embedding_with_model(query, model);
+
+    let filters = SearchFilters {
+        include_languages: options
+            .languages
+            .iter()
+            .map(|lang| lang.trim().to_lowercase())
+            .filter(|lang| !lang.is_empty())
+            .collect(),
+        min_score: options.min_score,
+        ..Default::default()
+    };
+    let db_results = if options.vector_only {
+        vector_only_search(
+            &conn,
+            query,
+            codebase_id.as_deref(),
+            &embedding,
+            options.limit,
+            &filters,
+        )?
     } else {
-        Some(codebase_path.to_string())
+        hybrid_search(
+            &conn,
+            query,
+            codebase_id.as_deref(),
+            &embedding,
+            options.limit,
+            &filters,
+            config.search.enable_fuzzy,
+        )?
     };
 
-    let embedding = vec![0.0_f32; 384]; // Placeholder
+    Ok(db_results.into_iter().map(SearchResult::from).collect())
+}
 
-    let db_results = vector_search(&conn, codebase_id.as_deref(), &embedding, limit)?;
+/// Vector search yielding results lazily, best first.
+///
+/// Results come in descending cosine similarity, ties in index order, which
+/// is the order of `search_with_model` with `vector_only` set, without a
+/// limit or `min_score`, on a codebase with no ANN index. Every chunk's
+/// embedding is scored when the iterator is created, and the search is
+/// recorded then with every scored chunk as its results; a result's content
+/// is only read when it is pulled, so `take(1)` costs one scan of embeddings
+/// and one row read. A failure to set up the search is yielded as the only
+/// item.
+pub fn search_iter(
+    query: &str,
+    codebase_path: &str,
+    model: &str,
+) -> impl Iterator<Item = Result<SearchResult>> {
+    let (iter, error) = match SearchIter::open(query, codebase_path, model) {
+        Ok(iter) => (Some(iter), None),
+        Err(e) => (None, Some(Err(e))),
+    };
+    error.into_iter().chain(iter.into_iter().flatten())
+}
 
-    let results: Vec<SearchResult> = db_results
-        .into_iter()
-        .map(|r| SearchResult {
-            file: r.file_path,
-            lines: format!("{}-{}", r.start_line, r.end_line),
-            content: r.content,
-            score: r.score,
-            language: r.language,
+/// Ranked chunk ids and the connection to read each result from
+struct SearchIter {
+    conn: Connection,
+    ranked: std::vec::IntoIter<(i64, f64)>,
+    rank: i64,
+}
+
+impl SearchIter {
+    fn open(query: &str, codebase_path: &str, model: &str) -> Result<Self> {
+        let conn = init_db()?;
+        let ranked = if query.trim().is_empty() {
+            Vec::new()
+        } else {
+            let codebase_id = codebase_id_for(codebase_path)?;
+            if let Some(ref id) = codebase_id {
+                crate::database::check_codebase_model(&conn, id, model)?;
+            }
+            ensure_model_available_with_model(model)?;
+            let started = crate::metrics::start_timer();
+            let embedding = get_query_embedding_with_model(query, model);
+            let ranked = vector_scores(&conn, codebase_id.as_deref(), &embedding)?;
+            record_finished_search(
+                &conn,
+                started,
+                query,
+                codebase_id.as_deref(),
+                ranked.len() as i64,
+                ranked.len(),
+                crate::config::get_config().search.record_history,
+            );
+            ranked
+        };
+        Ok(Self {
+            conn,
+            ranked: ranked.into_iter(),
+            rank: 0,
         })
-        .collect();
+    }
+}
 
-    Ok(results)
+impl Iterator for SearchIter {
+    type Item = Result<SearchResult>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        for (chunk_id, score) in self.ranked.by_ref() {
+            // Chunks deleted since scoring are skipped
+            match search_result_for_chunk(&self.conn, chunk_id, score, self.rank + 1) {
+                Ok(Some(result)) => {
+                    self.rank += 1;
+                    return Some(Ok(result.into()));
+                }
+                Ok(None) => continue,
+                Err(e) => return Some(Err(e)),
+            }
+        }
+        None
+    }
+}
+
+/// The codebase id of an indexed path, or `None` (all codebases) when empty
+fn codebase_id_for(codebase_path: &str) -> Result<Option<String>> {
+    if codebase_path.is_empty() {
+        return Ok(None);
+    }
+    let canonical = Path::new(codebase_path)
+        .canonicalize()
+        .map_err(|_| CodeSearchError::CodebaseNotIndexed(codebase_path.to_string()))?;
+    Ok(Some(get_codebase_hash(&canonical)))
+}
+
+/// Search latency over repeated runs of a set of queries
+#[derive(Debug, Clone, Serialize)]
+pub struct BenchReport {
+    pub queries: usize,
+    pub runs: usize,
+    pub p50_ms: f64,
+    pub p90_ms: f64,
+    pub p99_ms: f64,
+    pub mean_results: f64,
+}
+
+/// Time `runs` hybrid searches of each query against the codebase indexed at
+/// `codebase_path`. One connection and one loaded model serve every run, so
+/// the timings cover query embedding and ranking, not startup. Every run
+/// embeds its query afresh, bypassing the query cache, and nothing is
+/// written: no query stats, which would shift the ranking weights between
+/// runs, and no history or metrics.
+pub fn bench_search(
+    codebase_path: &str,
+    queries: &[String],
+    runs: usize,
+    limit: i64,
+    model: &str,
+) -> Result<BenchReport> {
+    if queries.is_empty() {
+        return Err(CodeSearchError::InvalidInput(
+            "no queries to benchmark".to_string(),
+        ));
+    }
+    if runs == 0 {
+        return Err(CodeSearchError::InvalidInput(
+            "runs must be at least 1".to_string(),
+        ));
+    }
+
+    let conn = init_db()?;
+    let codebase_id = codebase_id_for(codebase_path)?;
+    if let Some(ref id) = codebase_id {
+        crate::database::check_codebase_model(&conn, id, model)?;
+    }
+    ensure_model_available_with_model(model)?;
+    let embedder = EmbeddingModel::new(Some(model))?;
+    let config = crate::config::get_config();
+
+    let mut latencies = Vec::with_capacity(queries.len() * runs);
+    let mut total_results = 0;
+    for query in queries {
+        for _ in 0..runs {
+            let start = Instant::now();
+            let embedding = embedder
+                .embed_query_batch(&[query.as_str()])?
+                .pop()
+                .unwrap_or_default();
+            let results = hybrid_search_inner(
+                &conn,
+                query,
+                codebase_id.as_deref(),
+                &embedding,
+                limit,
+                &SearchFilters::default(),
+                config.search.enable_fuzzy,
+                config.search.enable_ltr,
+            )?;
+            latencies.push(start.elapsed());
+            total_results += results.len();
+        }
+    }
+
+    latencies.sort();
+    Ok(BenchReport {
+        queries: queries.len(),
+        runs,
+        p50_ms: percentile_ms(&latencies, 50),
+        p90_ms: percentile_ms(&latencies, 90),
+        p99_ms: percentile_ms(&latencies, 99),
+        mean_results: total_results as f64 / latencies.len() as f64,
+    })
+}
+
+/// Nearest-rank percentile of sorted, non-empty `latencies`, in milliseconds
+fn percentile_ms(latencies: &[Duration], percentile: usize) -> f64 {
+    let rank = (latencies.len() * percentile).div_ceil(100).max(1);
+    latencies[rank - 1].as_nanos() as f64 / 1_000_000.0
 }
 
 pub fn format_results(results: &[SearchResult]) -> Vec<FormattedResult> {
@@ -64,6 +433,7 @@ pub fn format_results(results: &[SearchResult]) -> Vec<FormattedResult> {
             content: r.content.clone(),
             score: format!("{:.4}", r.score),
             language: r.language.clone(),
+            author: r.author.clone(),
         })
         .collect()
 }
@@ -79,9 +449,264 @@ mod tests {
         assert!(formatted.is_empty());
     }
 
+    #[test]
+    fn test_bench_search_reports_latency_and_result_counts() {
+        let _data_dir = crate::test_support::use_temp_data_dir();
+
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("cache.rs"),
+            "fn evict_lru_entry(cache: &mut Cache) {\n    cache.pop_oldest();\n}\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("parse.rs"),
+            "fn parse_header(line: &str) -> Header {\n    Header::from(line)\n}\n",
+        )
+        .unwrap();
+        crate::indexing::Indexer::new(Default::default())
+            .index_codebase(dir.path())
+            .unwrap();
+
+        let queries = vec!["evict lru entry".to_string(), "parse header".to_string()];
+        let path = dir.path().to_str().unwrap();
+        let report = bench_search(path, &queries, 3, 5, DEFAULT_MODEL).unwrap();
+        assert_eq!(report.queries, 2);
+        assert_eq!(report.runs, 3);
+        assert!(report.p50_ms > 0.0);
+        assert!(report.p50_ms <= report.p90_ms && report.p90_ms <= report.p99_ms);
+        assert!(report.mean_results >= 1.0);
+
+        // Nothing is recorded, so the query weights never shift between runs
+        let conn = init_db().unwrap();
+        for query in &queries {
+            assert_eq!(
+                crate::database::get_query_popularity(&conn, query).unwrap(),
+                None
+            );
+        }
+
+        assert!(matches!(
+            bench_search(path, &[], 3, 5, DEFAULT_MODEL),
+            Err(CodeSearchError::InvalidInput(_))
+        ));
+    }
+
+    #[test]
+    fn test_extract_snippet_centres_on_best_match() {
+        let content = "use std::io;\n\nfn open(path: &Path) -> File {\n    File::open(path)\n}\n\nfn refresh_token(client: &Client) -> Token {\n    let token = client.refresh();\n    token.validate();\n    token\n}\n";
+        let (snippet, start, end) = extract_snippet(content, "refresh Token", 1);
+        assert_eq!(
+            snippet,
+            "\nfn refresh_token(client: &Client) -> Token {\n    let token = client.refresh();"
+        );
+        assert_eq!(&content[start..end], snippet);
+
+        let (snippet, start, _) = extract_snippet(content, "validate", 0);
+        assert_eq!(snippet, "    token.validate();");
+        assert_eq!(start, content.find("    token.validate").unwrap());
+    }
+
+    #[test]
+    fn test_extract_snippet_without_literal_match_takes_leading_lines() {
+        let content = "line one\nline two\nline three\nline four\nline five\n";
+        let (snippet, start, end) = extract_snippet(content, "authentication flow", 2);
+        assert_eq!(snippet, "line one\nline two\nline three\nline four");
+        assert_eq!((start, end), (0, snippet.len()));
+
+        let (snippet, _, _) = extract_snippet(content, "", 0);
+        assert_eq!(snippet, "line one");
+    }
+
+    #[test]
+    fn test_percentile_ms_nearest_rank() {
+        let latencies: Vec<Duration> = (1..=10).map(Duration::from_millis).collect();
+        assert_eq!(percentile_ms(&latencies, 50), 5.0);
+        assert_eq!(percentile_ms(&latencies, 90), 9.0);
+        assert_eq!(percentile_ms(&latencies, 99), 10.0);
+    }
+
     #[test]
     fn test_search_empty_query() {
         let results = search("", "", 10, false).unwrap();
         assert!(results.is_empty());
     }
+
+    #[test]
+    fn test_search_ranks_relevant_chunk_first() {
+        let _data_dir = crate::test_support::use_temp_data_dir();
+
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("tokens.rs"),
+            "fn refresh_oauth_token(client: &Client) -> Token {\n    client.refresh()\n}\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("math.rs"),
+            "fn add(a: i32, b: i32) -> i32 { a + b }\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("log.rs"),
+            "fn log_line(msg: &str) { println!(\"{}\", msg) }\n",
+        )
+        .unwrap();
+        crate::indexing::Indexer::new(Default::default())
+            .index_codebase(dir.path())
+            .unwrap();
+
+        let path = dir.path().to_str().unwrap();
+        let results = search("refresh oauth token", path, 3, false).unwrap();
+        assert_eq!(results[0].file, "tokens.rs");
+        assert!(results[0].score > 0.0);
+
+        // Vector-only results stay within the codebase
+        let results = search("refresh oauth token", path, 10, true).unwrap();
+        assert_eq!(results.len(), 3);
+
+        assert!(matches!(
+            search("token", "/no/such/codebase", 3, false),
+            Err(CodeSearchError::CodebaseNotIndexed(_))
+        ));
+    }
+
+    #[test]
+    fn test_search_with_options_applies_each_field() {
+        let _data_dir = crate::test_support::use_temp_data_dir();
+
+        let dir = tempfile::tempdir().unwrap();
+        for (name, body) in [
+            ("retry.rs", "fn retry_with_backoff_options() {}\n"),
+            ("retry.py", "def retry_with_backoff_options():\n    pass\n"),
+            (
+                "backoff.py",
+                "def backoff_delay_options(attempt):\n    pass\n",
+            ),
+        ] {
+            std::fs::write(dir.path().join(name), body).unwrap();
+        }
+        crate::indexing::Indexer::new(Default::default())
+            .index_codebase(dir.path())
+            .unwrap();
+
+        let options = SearchOptions {
+            codebase_path: Some(dir.path().to_str().unwrap().to_string()),
+            limit: 5,
+            model: Some(DEFAULT_MODEL.to_string()),
+            vector_only: true,
+            languages: vec!["Python".to_string()],
+            ..Default::default()
+        };
+        let results = search_with_options("retry with backoff", &options).unwrap();
+        assert_eq!(results.len(), 2);
+        assert!(results
+            .iter()
+            .all(|r| r.language.as_deref() == Some("python")));
+
+        let limited = SearchOptions {
+            limit: 1,
+            vector_only: false,
+            ..options.clone()
+        };
+        let results = search_with_options("retry with backoff", &limited).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].file, "retry.py");
+
+        // Cosine similarity never exceeds 1
+        let strict = SearchOptions {
+            min_score: Some(1.5),
+            ..options
+        };
+        assert!(search_with_options("retry with backoff", &strict)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_search_iter_yields_eager_order_lazily() {
+        let _data_dir = crate::test_support::use_temp_data_dir();
+
+        let dir = tempfile::tempdir().unwrap();
+        for (name, body) in [
+            (
+                "cache.rs",
+                "fn evict_least_recently_used(cache: &mut Cache) {}\n",
+            ),
+            ("queue.rs", "fn push_job(queue: &Queue, job: Job) {}\n"),
+            (
+                "http.rs",
+                "fn parse_request_headers(raw: &str) -> Headers {}\n",
+            ),
+            ("disk.rs", "fn flush_pages_to_disk(pages: &[Page]) {}\n"),
+        ] {
+            std::fs::write(dir.path().join(name), body).unwrap();
+        }
+        crate::indexing::Indexer::new(Default::default())
+            .index_codebase(dir.path())
+            .unwrap();
+
+        let path = dir.path().to_str().unwrap();
+        let query = "evict cache entries";
+        let eager = search_with_model(query, path, 10, true, DEFAULT_MODEL).unwrap();
+        assert_eq!(eager.len(), 4);
+
+        let first: Vec<SearchResult> = search_iter(query, path, DEFAULT_MODEL)
+            .take(1)
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(first.len(), 1);
+        assert_eq!(first[0].file, eager[0].file);
+        assert_eq!(first[0].content, eager[0].content);
+        assert_eq!(first[0].score, eager[0].score);
+
+        let all: Vec<(String, f64)> = search_iter(query, path, DEFAULT_MODEL)
+            .map(|r| r.map(|r| (r.file, r.score)))
+            .collect::<Result<_>>()
+            .unwrap();
+        let expected: Vec<(String, f64)> = eager.into_iter().map(|r| (r.file, r.score)).collect();
+        assert_eq!(all, expected);
+
+        let mut missing = search_iter(query, "/no/such/codebase", DEFAULT_MODEL);
+        assert!(matches!(
+            missing.next(),
+            Some(Err(CodeSearchError::CodebaseNotIndexed(_)))
+        ));
+        assert!(missing.next().is_none());
+        assert_eq!(search_iter(" ", path, DEFAULT_MODEL).count(), 0);
+    }
+
+    #[test]
+    fn test_search_with_other_model_is_rejected() {
+        let _data_dir = crate::test_support::use_temp_data_dir();
+
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("render.rs"),
+            "fn render_frame(scene: &Scene) {}\n",
+        )
+        .unwrap();
+        crate::indexing::Indexer::new(crate::indexing::IndexingOptions {
+            model_name: Some("nomic".to_string()),
+            ..Default::default()
+        })
+        .index_codebase(dir.path())
+        .unwrap();
+
+        let path = dir.path().to_str().unwrap();
+        match search("render frame", path, 5, true) {
+            Err(CodeSearchError::InvalidConfiguration(msg)) => assert!(msg.contains("nomic")),
+            other => panic!(
+                "expected a model mismatch, got {:?}",
+                other.map(|r| r.len())
+            ),
+        }
+        assert!(matches!(
+            search_iter("render frame", path, DEFAULT_MODEL).next(),
+            Some(Err(CodeSearchError::InvalidConfiguration(_)))
+        ));
+
+        let results = search_with_model("render frame", path, 5, true, "nomic").unwrap();
+        assert_eq!(results[0].file, "render.rs");
+    }
 }
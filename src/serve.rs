@@ -0,0 +1,422 @@
+//! Warm-model daemon for `code-search serve`.
+//!
+//! Loading the embedding model dominates the latency of a one-shot CLI run.
+//! The daemon loads it once and answers requests on a local Unix socket, so
+//! editors can index, search and embed without paying that cost each time.
+//!
+//! The protocol is newline-delimited JSON: each line a client writes is a
+//! [`ServeRequest`], answered by one line holding a [`ServeResponse`]. A
+//! connection may carry any number of requests. Paths must be absolute,
+//! since the daemon's working directory is not the client's.
+//!
+//! ```text
+//! > {"method":"search","query":"open the database","codebase":"/repo","limit":5}
+//! < {"status":"ok","result":[{"file":"src/db.rs","lines":"10-42",...}]}
+//! > {"method":"embed","texts":["fn main() {}"]}
+//! < {"status":"ok","result":[[0.012,-0.034,...]]}
+//! > {"method":"frobnicate"}
+//! < {"status":"error","message":"unknown variant `frobnicate`, ..."}
+//! ```
+
+use crate::embedding::EmbeddingModel;
+use crate::error::{CodeSearchError, Result};
+use crate::indexing::{Indexer, IndexingOptions, IndexingStats, ProgressCallback};
+use crate::manifest::get_codebase_hash;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+
+/// File name of the socket in the data directory when `--socket` is not given
+pub const DEFAULT_SOCKET_NAME: &str = "serve.sock";
+
+fn default_limit() -> i64 {
+    10
+}
+
+/// A request to the daemon, tagged by `method`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "method", rename_all = "snake_case")]
+pub enum ServeRequest {
+    /// Index (or incrementally update) the codebase at the absolute `path`;
+    /// answered with the run's `IndexingStats`. Requests for the same
+    /// codebase run one after another.
+    Index {
+        path: String,
+        #[serde(default)]
+        force: bool,
+    },
+    /// Search the codebase indexed at the absolute path `codebase`, or every
+    /// codebase when it is omitted; answered with a list of search results
+    Search {
+        query: String,
+        #[serde(default)]
+        codebase: Option<String>,
+        #[serde(default = "default_limit")]
+        limit: i64,
+        #[serde(default)]
+        vector_only: bool,
+        #[serde(default)]
+        min_score: Option<f64>,
+    },
+    /// Embed `texts` as documents, or as search queries with `query`;
+    /// answered with one vector per text
+    Embed {
+        texts: Vec<String>,
+        #[serde(default)]
+        query: bool,
+    },
+    /// Stop accepting connections once this one is answered
+    Shutdown,
+}
+
+/// The daemon's answer to one request
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum ServeResponse {
+    Ok { result: serde_json::Value },
+    Error { message: String },
+}
+
+impl ServeResponse {
+    fn from_result<T: Serialize>(result: Result<T>) -> Self {
+        match result.and_then(|value| serde_json::to_value(value).map_err(Into::into)) {
+            Ok(result) => ServeResponse::Ok { result },
+            Err(e) => ServeResponse::Error {
+                message: e.to_string(),
+            },
+        }
+    }
+}
+
+/// The data directory's `serve.sock`
+pub fn default_socket_path() -> Result<PathBuf> {
+    let config = crate::config::get_config();
+    let data_dir =
+        crate::database::resolve_data_dir(crate::config::data_dir_override().as_deref(), &config)?;
+    Ok(data_dir.join(DEFAULT_SOCKET_NAME))
+}
+
+/// A daemon bound to its socket, with its embedding model loaded
+pub struct Server {
+    listener: UnixListener,
+    socket: PathBuf,
+    model: Arc<EmbeddingModel>,
+    stopping: Arc<AtomicBool>,
+    index_locks: IndexLocks,
+}
+
+/// One lock per codebase id, held while a request indexes that codebase
+type IndexLocks = Arc<Mutex<HashMap<String, Arc<Mutex<()>>>>>;
+
+impl Server {
+    /// Load `model_name` and listen on `socket`. A socket file left behind
+    /// by a daemon that is gone is replaced; one that still answers is an
+    /// error.
+    pub fn bind<P: AsRef<Path>>(socket: P, model_name: &str) -> Result<Self> {
+        let socket = socket.as_ref().to_path_buf();
+        if socket.exists() {
+            if UnixStream::connect(&socket).is_ok() {
+                return Err(CodeSearchError::InvalidInput(format!(
+                    "a daemon is already listening on {}",
+                    socket.display()
+                )));
+            }
+            std::fs::remove_file(&socket)?;
+        }
+
+        let model = EmbeddingModel::new(Some(model_name))?;
+        let listener = UnixListener::bind(&socket)?;
+        Ok(Self {
+            listener,
+            socket,
+            model: Arc::new(model),
+            stopping: Arc::new(AtomicBool::new(false)),
+            index_locks: IndexLocks::default(),
+        })
+    }
+
+    pub fn socket_path(&self) -> &Path {
+        &self.socket
+    }
+
+    /// Accept connections until a `shutdown` request, serving each on its
+    /// own thread, then remove the socket file
+    pub fn run(self) -> Result<()> {
+        for stream in self.listener.incoming() {
+            if self.stopping.load(Ordering::SeqCst) {
+                break;
+            }
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(e) => {
+                    eprintln!("Warning: failed to accept a connection: {}", e);
+                    continue;
+                }
+            };
+            let handler = self.handler();
+            std::thread::spawn(move || {
+                if let Err(e) = handler.serve_connection(stream) {
+                    eprintln!("Warning: connection failed: {}", e);
+                }
+            });
+        }
+        let _ = std::fs::remove_file(&self.socket);
+        Ok(())
+    }
+
+    /// Answer one request without going through the socket
+    pub fn handle(&self, request: ServeRequest) -> ServeResponse {
+        self.handler().handle(request)
+    }
+
+    fn handler(&self) -> Handler {
+        Handler {
+            model: Arc::clone(&self.model),
+            stopping: Arc::clone(&self.stopping),
+            socket: self.socket.clone(),
+            index_locks: Arc::clone(&self.index_locks),
+        }
+    }
+}
+
+/// What a connection thread needs of its server
+struct Handler {
+    model: Arc<EmbeddingModel>,
+    stopping: Arc<AtomicBool>,
+    socket: PathBuf,
+    index_locks: IndexLocks,
+}
+
+impl Handler {
+    fn serve_connection(&self, stream: UnixStream) -> Result<()> {
+        let mut writer = stream.try_clone()?;
+        for line in BufReader::new(stream).lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let response = match serde_json::from_str::<ServeRequest>(&line) {
+                Ok(request) => self.handle(request),
+                Err(e) => ServeResponse::Error {
+                    message: format!("invalid request: {}", e),
+                },
+            };
+            writeln!(writer, "{}", serde_json::to_string(&response)?)?;
+            writer.flush()?;
+        }
+        Ok(())
+    }
+
+    fn handle(&self, request: ServeRequest) -> ServeResponse {
+        match request {
+            ServeRequest::Index { path, force } => {
+                ServeResponse::from_result(self.index(&path, force))
+            }
+            ServeRequest::Search {
+                query,
+                codebase,
+                limit,
+                vector_only,
+                min_score,
+            } => ServeResponse::from_result(self.search(
+                &query,
+                crate::search::SearchOptions {
+                    codebase_path: codebase,
+                    limit,
+                    vector_only,
+                    min_score,
+                    ..Default::default()
+                },
+            )),
+            ServeRequest::Embed { texts, query } => {
+                let embeddings = if query {
+                    let texts: Vec<&str> = texts.iter().map(String::as_str).collect();
+                    self.model.embed_query_batch(&texts)
+                } else {
+                    let batch_size = crate::config::get_config().indexing.batch_size;
+                    self.model.embed_documents(&texts, batch_size)
+                };
+                ServeResponse::from_result(embeddings)
+            }
+            ServeRequest::Shutdown => {
+                self.stopping.store(true, Ordering::SeqCst);
+                // Wake the accept loop so it sees the flag
+                let _ = UnixStream::connect(&self.socket);
+                ServeResponse::Ok {
+                    result: serde_json::Value::Null,
+                }
+            }
+        }
+    }
+
+    /// Index `path` once no other request is indexing the same codebase.
+    /// Progress goes nowhere: the daemon's stdout is not the client's.
+    fn index(&self, path: &str, force: bool) -> Result<IndexingStats> {
+        let path = absolute(path)?.canonicalize()?;
+        let lock = Arc::clone(
+            self.index_locks
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .entry(get_codebase_hash(&path))
+                .or_default(),
+        );
+        let _indexing = lock.lock().unwrap_or_else(PoisonError::into_inner);
+
+        let mut indexer = Indexer::new(IndexingOptions {
+            force,
+            model_name: Some(self.model.name().to_string()),
+            progress: Some(ProgressCallback::new(|_| {})),
+            ..Default::default()
+        });
+        indexer.index_codebase(&path)
+    }
+
+    /// Search as `options` describe, with the daemon's model
+    fn search(
+        &self,
+        query: &str,
+        options: crate::search::SearchOptions,
+    ) -> Result<Vec<crate::search::SearchResult>> {
+        if let Some(codebase) = &options.codebase_path {
+            absolute(codebase)?;
+        }
+        crate::search::search_with_options(
+            query,
+            &crate::search::SearchOptions {
+                model: Some(self.model.name().to_string()),
+                ..options
+            },
+        )
+    }
+}
+
+/// `path` from a request, which must be absolute
+fn absolute(path: &str) -> Result<&Path> {
+    let path = Path::new(path);
+    if path.is_absolute() {
+        Ok(path)
+    } else {
+        Err(CodeSearchError::InvalidInput(format!(
+            "{} is relative; the daemon needs absolute paths",
+            path.display()
+        )))
+    }
+}
+
+/// Send one request to the daemon on `socket` and wait for its answer
+pub fn send_request<P: AsRef<Path>>(socket: P, request: &ServeRequest) -> Result<ServeResponse> {
+    let mut stream = UnixStream::connect(socket)?;
+    writeln!(stream, "{}", serde_json::to_string(request)?)?;
+    stream.flush()?;
+    let mut line = String::new();
+    BufReader::new(stream).read_line(&mut line)?;
+    Ok(serde_json::from_str(&line)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_schema_defaults() {
+        let request: ServeRequest =
+            serde_json::from_str(r#"{"method":"search","query":"open file"}"#).unwrap();
+        assert_eq!(
+            request,
+            ServeRequest::Search {
+                query: "open file".to_string(),
+                codebase: None,
+                limit: 10,
+                vector_only: false,
+                min_score: None,
+            }
+        );
+
+        let request: ServeRequest =
+            serde_json::from_str(r#"{"method":"embed","texts":["a","b"]}"#).unwrap();
+        assert_eq!(
+            request,
+            ServeRequest::Embed {
+                texts: vec!["a".to_string(), "b".to_string()],
+                query: false,
+            }
+        );
+
+        assert!(serde_json::from_str::<ServeRequest>(r#"{"method":"frobnicate"}"#).is_err());
+
+        let response = ServeResponse::Error {
+            message: "boom".to_string(),
+        };
+        assert_eq!(
+            serde_json::to_string(&response).unwrap(),
+            r#"{"status":"error","message":"boom"}"#
+        );
+    }
+
+    #[test]
+    fn test_serve_rejects_relative_paths() {
+        let _data_dir = crate::test_support::use_temp_data_dir();
+
+        let socket_dir = tempfile::tempdir().unwrap();
+        let server = Server::bind(socket_dir.path().join("test.sock"), "minilm").unwrap();
+        let relative = |response: ServeResponse| match response {
+            ServeResponse::Error { message } => {
+                assert!(message.contains("relative"), "{}", message)
+            }
+            other => panic!("expected an error, got {:?}", other),
+        };
+        relative(server.handle(ServeRequest::Index {
+            path: "src".to_string(),
+            force: false,
+        }));
+        relative(server.handle(ServeRequest::Search {
+            query: "open session".to_string(),
+            codebase: Some("./src".to_string()),
+            limit: 5,
+            vector_only: false,
+            min_score: None,
+        }));
+    }
+
+    #[test]
+    fn test_serve_indexes_one_codebase_at_a_time() {
+        let _data_dir = crate::test_support::use_temp_data_dir();
+
+        let codebase = tempfile::tempdir().unwrap();
+        for i in 0..20 {
+            std::fs::write(
+                codebase.path().join(format!("file_{}.rs", i)),
+                format!("fn concurrent_{}() {{}}\n", i),
+            )
+            .unwrap();
+        }
+        let socket_dir = tempfile::tempdir().unwrap();
+        let server = Arc::new(Server::bind(socket_dir.path().join("test.sock"), "minilm").unwrap());
+
+        // Both see the whole codebase as new, unless the second waits for
+        // the first to record it
+        let path = codebase.path().to_str().unwrap().to_string();
+        let runs: Vec<_> = (0..2)
+            .map(|_| {
+                let server = Arc::clone(&server);
+                let path = path.clone();
+                std::thread::spawn(move || {
+                    server.handle(ServeRequest::Index { path, force: false })
+                })
+            })
+            .collect();
+        let mut indexed: Vec<i64> = runs
+            .into_iter()
+            .map(|run| match run.join().unwrap() {
+                ServeResponse::Ok { result } => result["files_indexed"].as_i64().unwrap(),
+                other => panic!("index failed: {:?}", other),
+            })
+            .collect();
+        indexed.sort();
+        assert_eq!(indexed, vec![0, 20]);
+    }
+}
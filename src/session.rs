@@ -219,11 +219,7 @@ impl SessionManager {
         results: Vec<DbSearchResult>,
     ) -> Option<QueryStep> {
         let mut sessions = self.sessions.write().await;
-        if let Some(session) = sessions.get_mut(session_id) {
-            Some(session.add_step(query, results))
-        } else {
-            None
-        }
+        sessions.get_mut(session_id).map(|session| session.add_step(query, results))
     }
 
     /// Select a result in a session
@@ -256,17 +252,13 @@ impl SessionManager {
     /// Get session summary
     pub async fn get_session_summary(&self, session_id: &str) -> Option<SessionSummary> {
         let sessions = self.sessions.read().await;
-        if let Some(session) = sessions.get(session_id) {
-            Some(SessionSummary {
+        sessions.get(session_id).map(|session| SessionSummary {
                 session_id: session.session_id.clone(),
                 step_count: session.steps.len(),
                 relevant_file_count: session.relevant_files.len(),
                 created_at: session.created_at,
                 query_history: session.query_history.clone(),
             })
-        } else {
-            None
-        }
     }
 }
 
@@ -344,8 +336,11 @@ mod tests {
             end_line: 10,
             content: "fn main() {}".to_string(),
             language: Some("rust".to_string()),
+            author: None,
             score: 0.9,
             rank: 1,
+            is_truncated: false,
+            also_in: Vec::new(),
         }];
 
         let step = session.add_step("test query".to_string(), db_results);
@@ -364,8 +359,11 @@ mod tests {
             end_line: 10,
             content: "fn main() {}".to_string(),
             language: Some("rust".to_string()),
+            author: None,
             score: 0.9,
             rank: 1,
+            is_truncated: false,
+            also_in: Vec::new(),
         }];
 
         session.add_step("test".to_string(), db_results);
@@ -385,8 +383,11 @@ mod tests {
                 end_line: 10,
                 content: "fn main() {}".to_string(),
                 language: Some("rust".to_string()),
+                author: None,
                 score: 0.9,
                 rank: 1,
+                is_truncated: false,
+                also_in: Vec::new(),
             },
             DbSearchResult {
                 chunk_id: 2,
@@ -396,8 +397,11 @@ mod tests {
                 end_line: 10,
                 content: "fn foo() {}".to_string(),
                 language: Some("rust".to_string()),
+                author: None,
                 score: 0.8,
                 rank: 2,
+                is_truncated: false,
+                also_in: Vec::new(),
             },
         ];
 
@@ -1,4 +1,4 @@
-use crate::config::get_config;
+use crate::config::{get_config, ChunkParams, SplitStrategy};
 use sha2::{Digest, Sha256};
 use std::collections::HashMap;
 
@@ -13,10 +13,53 @@ fn get_default_chunk_size() -> usize {
     get_config().chunk_size()
 }
 
-fn get_default_overlap() -> usize {
-    get_config().chunk_overlap()
+fn get_default_overlap(chunk_size: usize) -> usize {
+    get_config().chunking.chunk_overlap.resolve(chunk_size)
 }
 
+/// Resolve the effective chunk size and overlap (in lines), falling back to
+/// the configuration for anything not given explicitly. A percentage overlap
+/// is resolved against the effective chunk size.
+pub fn resolve_chunk_params(
+    chunk_size: Option<usize>,
+    overlap: Option<usize>,
+) -> crate::error::Result<(usize, usize)> {
+    let chunk_size = chunk_size.unwrap_or_else(get_default_chunk_size);
+    let overlap = overlap.unwrap_or_else(|| get_default_overlap(chunk_size));
+    if overlap >= chunk_size {
+        return Err(crate::error::CodeSearchError::InvalidConfiguration(
+            format!(
+                "chunk overlap ({} lines) must be smaller than the chunk size ({} lines)",
+                overlap, chunk_size
+            ),
+        ));
+    }
+    Ok((chunk_size, overlap))
+}
+
+/// `content` with tabs turned to spaces, runs of spaces collapsed to one and
+/// trailing whitespace trimmed from each line. Line breaks are kept, so
+/// line numbers still match.
+pub fn normalize_whitespace(content: &str) -> String {
+    content
+        .lines()
+        .map(|line| {
+            let mut normalized = String::with_capacity(line.len());
+            for c in line.trim_end().chars() {
+                let c = if c == '\t' { ' ' } else { c };
+                if !(c == ' ' && normalized.ends_with(' ')) {
+                    normalized.push(c);
+                }
+            }
+            normalized
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// File names and extensions (with the dot) to language names. Every key is
+/// inserted once; an extension shared by two languages goes to the more
+/// common one.
 pub fn language_map() -> HashMap<&'static str, &'static str> {
     let mut map = HashMap::new();
 
@@ -74,6 +117,7 @@ pub fn language_map() -> HashMap<&'static str, &'static str> {
     map.insert(".fsx", "fsharp");
     map.insert(".ml", "ocaml");
     map.insert(".mli", "ocaml");
+    // Coq also uses `.v`; Verilog is far more common, so Coq only gets `.coq`
     map.insert(".v", "verilog");
     map.insert(".vh", "verilog");
     map.insert(".vhd", "vhdl");
@@ -183,8 +227,6 @@ pub fn language_map() -> HashMap<&'static str, &'static str> {
     map.insert("project.clj", "clojure");
     map.insert("build.sbt", "scala");
     map.insert("pom.xml", "maven");
-    map.insert("build.gradle", "gradle");
-    map.insert("build.gradle.kts", "gradle");
     map.insert("settings.gradle", "gradle");
     map.insert("settings.gradle.kts", "gradle");
     map.insert("gradle.properties", "gradle");
@@ -245,14 +287,12 @@ pub fn language_map() -> HashMap<&'static str, &'static str> {
     map.insert(".dfy", "dafny");
     map.insert(".spl", "sparkle");
     map.insert(".coq", "coq");
-    map.insert(".v", "coq");
     map.insert(".lean", "lean");
     map.insert(".agda", "agda");
     map.insert(".idr", "idris");
     map.insert(".lidr", "idris");
     map.insert(".purescript", "purescript");
     map.insert(".purs", "purescript");
-    map.insert(".elm", "elm");
     map.insert(".glsl", "glsl");
     map.insert(".vert", "glsl");
     map.insert(".frag", "glsl");
@@ -263,11 +303,8 @@ pub fn language_map() -> HashMap<&'static str, &'static str> {
     map.insert(".spv", "spirv");
     map.insert(".d", "d");
     map.insert(".di", "d");
-    map.insert(".nim", "nim");
     map.insert(".nims", "nim");
-    map.insert(".cr", "crystal");
     map.insert(".ecr", "crystal");
-    map.insert(".slang", "slang");
     map.insert(".zig", "zig");
     map.insert(".zon", "zig");
     map.insert(".odin", "odin");
@@ -279,12 +316,8 @@ pub fn language_map() -> HashMap<&'static str, &'static str> {
     map.insert(".fyr", "fyr");
     map.insert(".ferret", "ferret");
     map.insert(".gleam", "gleam");
-    map.insert(".gleam", "gleam");
     map.insert(".inko", "inko");
     map.insert(".mojo", "mojo");
-    map.insert(".wat", "wat");
-    map.insert(".wit", "wit");
-    map.insert(".wast", "wast");
     map.insert(".witx", "witx");
     map.insert(".wai", "wai");
     map.insert(".wai-bindgen", "wai-bindgen");
@@ -301,6 +334,7 @@ pub fn language_map() -> HashMap<&'static str, &'static str> {
     map.insert(".capnp", "capnproto");
     map.insert(".asn", "asn1");
     map.insert(".asn1", "asn1");
+    // DER is an ASN.1 encoding, like `.cer`
     map.insert(".der", "asn1");
     map.insert(".cer", "asn1");
     map.insert(".pem", "pem");
@@ -310,7 +344,6 @@ pub fn language_map() -> HashMap<&'static str, &'static str> {
     map.insert(".pfx", "p12");
     map.insert(".csr", "csr");
     map.insert(".tsr", "csr");
-    map.insert(".der", "der");
 
     map
 }
@@ -348,6 +381,302 @@ pub struct CodeChunk {
     pub start_line: usize,
     pub end_line: usize,
     pub content: String,
+    /// One of [`CHUNK_KINDS`], from [`classify_chunk`]
+    pub kind: String,
+}
+
+/// Chunk kinds accepted by `search --kind`: the ones [`classify_chunk`]
+/// produces, plus `commit` for commit messages ingested with the `git` feature
+/// and `path` for file names indexed with `indexing.index_file_names`
+pub const CHUNK_KINDS: [&str; 6] = ["code", "comment", "doc", "test", "commit", "path"];
+
+// Share of non-blank lines that must be comments for a chunk to count as
+// comment or doc rather than code
+const COMMENT_HEAVY_RATIO: f64 = 0.6;
+
+/// Classify a chunk as `test` (test file or test module), `doc`
+/// (documentation file or mostly doc comments), `comment` (mostly comment
+/// lines) or `code`.
+pub fn classify_chunk(file_path: &str, content: &str, language: &str) -> &'static str {
+    if is_test_path(file_path) || content.contains("#[test]") || content.contains("#[cfg(test)]") {
+        return "test";
+    }
+    if matches!(language, "markdown" | "rst" | "text") {
+        return "doc";
+    }
+
+    // `#` and `--` open comments only where the language says so; elsewhere
+    // they start CSS selectors and custom properties, C# directives and the like
+    let hash_comments = matches!(
+        language,
+        "python"
+            | "ruby"
+            | "shell"
+            | "perl"
+            | "r"
+            | "julia"
+            | "elixir"
+            | "crystal"
+            | "nim"
+            | "powershell"
+            | "cmake"
+            | "makefile"
+            | "dockerfile"
+            | "yaml"
+            | "toml"
+            | "ini"
+            | "config"
+            | "gitignore"
+            | "dockerignore"
+            | "gitattributes"
+            | "editorconfig"
+    );
+    let dash_comments = matches!(language, "sql" | "lua" | "haskell" | "ada" | "elm" | "vhdl");
+    let mut code = 0usize;
+    let mut comment = 0usize;
+    let mut doc = 0usize;
+    // Open block comment or docstring, and whether it is documentation
+    let mut open_block: Option<bool> = None;
+
+    for line in content.lines().map(str::trim).filter(|l| !l.is_empty()) {
+        if let Some(is_doc) = open_block {
+            if is_doc {
+                doc += 1;
+            } else {
+                comment += 1;
+            }
+            if line.contains("*/") || line.contains("\"\"\"") || line.contains("'''") {
+                open_block = None;
+            }
+            continue;
+        }
+
+        if line.starts_with("///") || line.starts_with("//!") {
+            doc += 1;
+        } else if line.starts_with("//") || (dash_comments && line.starts_with("--")) {
+            comment += 1;
+        } else if line.starts_with("/**") || line.starts_with("/*!") {
+            doc += 1;
+            if !line.contains("*/") {
+                open_block = Some(true);
+            }
+        } else if line.starts_with("/*") {
+            comment += 1;
+            if !line.contains("*/") {
+                open_block = Some(false);
+            }
+        } else if line.starts_with("\"\"\"") || line.starts_with("'''") {
+            doc += 1;
+            if line.len() < 6 || !(line.ends_with("\"\"\"") || line.ends_with("'''")) {
+                open_block = Some(true);
+            }
+        } else if hash_comments && line.starts_with('#') && !line.starts_with("#!") {
+            comment += 1;
+        } else {
+            code += 1;
+        }
+    }
+
+    let total = code + comment + doc;
+    if total == 0 || ((comment + doc) as f64) < total as f64 * COMMENT_HEAVY_RATIO {
+        "code"
+    } else if doc >= comment {
+        "doc"
+    } else {
+        "comment"
+    }
+}
+
+/// Whether a path follows a common test file or directory convention
+/// (`tests/`, `__tests__/`, `test_*.py`, `*_test.go`, `*.spec.ts`, `FooTest.java`).
+pub fn is_test_path(file_path: &str) -> bool {
+    let path = std::path::Path::new(file_path);
+    let in_test_dir = path
+        .parent()
+        .into_iter()
+        .flat_map(|p| p.components())
+        .filter_map(|c| c.as_os_str().to_str())
+        .any(|dir| matches!(dir, "test" | "tests" | "__tests__" | "spec" | "specs"));
+    if in_test_dir {
+        return true;
+    }
+
+    let name = match path.file_name().and_then(|n| n.to_str()) {
+        Some(name) => name,
+        None => return false,
+    };
+    let stem = name.split('.').next().unwrap_or(name);
+    stem.starts_with("test_")
+        || stem.ends_with("_test")
+        || stem.ends_with("_tests")
+        || stem.ends_with("_spec")
+        || stem.ends_with("Test")
+        || stem.ends_with("Tests")
+        || stem == "conftest"
+        || name.contains(".test.")
+        || name.contains(".spec.")
+}
+
+/// The text of a source file. A UTF-8 byte order mark is dropped and
+/// UTF-16 with a byte order mark is decoded. Other invalid UTF-8 has U+FFFD
+/// in place of the bad bytes when `lossy`, and is an `InvalidData` error
+/// otherwise.
+pub fn read_source<P: AsRef<std::path::Path>>(path: P, lossy: bool) -> std::io::Result<String> {
+    let bytes = std::fs::read(path)?;
+    decode_source(&bytes, lossy)
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidData, "not valid UTF-8 text"))
+}
+
+fn decode_source(bytes: &[u8], lossy: bool) -> Option<String> {
+    let utf16 = |rest: &[u8], unit: fn([u8; 2]) -> u16| {
+        let units = rest.chunks_exact(2).map(|pair| unit([pair[0], pair[1]]));
+        let mut text = String::with_capacity(rest.len() / 2);
+        for c in char::decode_utf16(units) {
+            match c {
+                Ok(c) => text.push(c),
+                Err(_) if lossy => text.push(char::REPLACEMENT_CHARACTER),
+                Err(_) => return None,
+            }
+        }
+        // A dangling odd byte
+        if rest.len() % 2 == 1 {
+            if !lossy {
+                return None;
+            }
+            text.push(char::REPLACEMENT_CHARACTER);
+        }
+        Some(text)
+    };
+
+    if let Some(rest) = bytes.strip_prefix(b"\xFF\xFE") {
+        return utf16(rest, u16::from_le_bytes);
+    }
+    if let Some(rest) = bytes.strip_prefix(b"\xFE\xFF") {
+        return utf16(rest, u16::from_be_bytes);
+    }
+    let bytes = bytes.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(bytes);
+    match std::str::from_utf8(bytes) {
+        Ok(text) => Some(text.to_string()),
+        Err(_) if lossy => Some(String::from_utf8_lossy(bytes).into_owned()),
+        Err(_) => None,
+    }
+}
+
+/// Chunk a file the way indexing would under the current configuration,
+/// without embedding it or writing to the index.
+pub fn preview_chunks<P: AsRef<std::path::Path>>(path: P) -> crate::error::Result<Vec<CodeChunk>> {
+    let path = path.as_ref();
+    let lossy = get_config().indexing.index_non_utf8;
+    let content =
+        read_source(path, lossy).map_err(|_| crate::error::CodeSearchError::FileRead {
+            path: path.display().to_string(),
+        })?;
+    // Refuse the settings indexing refuses; the sizes themselves are left
+    // to the split, which applies any `chunking.per_language` entry
+    resolve_chunk_params(None, None)?;
+
+    Ok(split_file_with_threshold(
+        &path.to_string_lossy(),
+        &content,
+        None,
+        None,
+        None,
+    ))
+}
+
+fn is_identifier_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Parts of one identifier, split at underscores, lower-to-upper case
+/// changes (`parseHttp`) and the end of an acronym (`HTTPResponse`)
+pub fn identifier_parts(identifier: &str) -> Vec<&str> {
+    let chars: Vec<(usize, char)> = identifier.char_indices().collect();
+    let mut parts = Vec::new();
+    let mut start: Option<usize> = None;
+
+    for (i, &(offset, c)) in chars.iter().enumerate() {
+        if c == '_' {
+            if let Some(s) = start.take() {
+                parts.push(&identifier[s..offset]);
+            }
+            continue;
+        }
+        if let Some(s) = start {
+            let prev = chars[i - 1].1;
+            let next_is_lower = chars.get(i + 1).is_some_and(|&(_, n)| n.is_lowercase());
+            let boundary = c.is_uppercase()
+                && (prev.is_lowercase()
+                    || prev.is_numeric()
+                    || (prev.is_uppercase() && next_is_lower));
+            if boundary {
+                parts.push(&identifier[s..offset]);
+                start = Some(offset);
+            }
+        } else {
+            start = Some(offset);
+        }
+    }
+    if let Some(s) = start {
+        parts.push(&identifier[s..]);
+    }
+    parts
+}
+
+/// Rewrite every identifier in `text` as its space-separated parts (see
+/// [`identifier_parts`]), so `parseHTTPResponse(raw_body)` becomes
+/// `parse HTTP Response(raw body)`. Other characters are kept as they are.
+pub fn split_identifiers(text: &str) -> String {
+    let mut out = String::with_capacity(text.len() + text.len() / 8);
+    let mut rest = text;
+    while let Some(start) = rest.find(is_identifier_char) {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let end = rest.find(|c| !is_identifier_char(c)).unwrap_or(rest.len());
+        out.push_str(&identifier_parts(&rest[..end]).join(" "));
+        rest = &rest[end..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// The split parts of each distinct mixed-case identifier in `text`, one
+/// identifier per line, or `None` if there are none. Indexed next to a
+/// chunk's content so its identifiers match by their parts; snake_case
+/// needs no help, as the FTS tokenizer already splits at underscores.
+pub(crate) fn compound_identifier_terms(text: &str) -> Option<String> {
+    let mut seen = std::collections::HashSet::new();
+    let terms: Vec<String> = text
+        .split(|c| !is_identifier_char(c))
+        .filter(|word| seen.insert(*word))
+        .filter_map(|word| {
+            let parts = identifier_parts(word);
+            let snake_parts = word.split('_').filter(|p| !p.is_empty()).count();
+            (parts.len() > snake_parts).then(|| parts.join(" "))
+        })
+        .collect();
+    (!terms.is_empty()).then(|| terms.join("\n"))
+}
+
+/// A `path` chunk standing for a file by its name, so a well-named file can
+/// match a query its content never mentions. The content is the path and
+/// its words, `src/auth/loginHandler.rs` giving
+/// `src/auth/loginHandler.rs\nsrc auth login Handler rs`. It spans line 0,
+/// which no real chunk does.
+pub fn file_name_chunk(file_path: &str) -> CodeChunk {
+    let words: Vec<&str> = file_path
+        .split(|c| !is_identifier_char(c))
+        .flat_map(identifier_parts)
+        .collect();
+    CodeChunk {
+        chunk_id: generate_chunk_id(file_path, 0, 0),
+        file_path: file_path.to_string(),
+        language: detect_language(file_path),
+        start_line: 0,
+        end_line: 0,
+        content: format!("{}\n{}", file_path, words.join(" ")),
+        kind: "path".to_string(),
+    }
 }
 
 pub fn generate_chunk_id(file_path: &str, start_line: usize, end_line: usize) -> String {
@@ -364,48 +693,219 @@ pub fn split_file(
     chunk_size: Option<usize>,
     overlap: Option<usize>,
 ) -> Vec<CodeChunk> {
-    let chunk_size = chunk_size.unwrap_or_else(get_default_chunk_size);
-    let overlap = overlap.unwrap_or_else(get_default_overlap);
+    split_file_with_threshold(file_path, content, chunk_size, overlap, None)
+}
 
-    let lines: Vec<&str> = content.lines().collect();
+/// Like [`split_file`], but a file with at most `whole_file_max_lines` lines
+/// becomes one whole-file chunk so its embedding sees the full context.
+/// `None` uses `chunking.whole_file_max_lines` from the config; 0 disables.
+pub fn split_file_with_threshold(
+    file_path: &str,
+    content: &str,
+    chunk_size: Option<usize>,
+    overlap: Option<usize>,
+    whole_file_max_lines: Option<usize>,
+) -> Vec<CodeChunk> {
+    split_file_with_strategy(
+        file_path,
+        content,
+        chunk_size,
+        overlap,
+        whole_file_max_lines,
+        None,
+    )
+}
+
+/// Like [`split_file_with_threshold`], choosing how the file is cut. `None`
+/// uses `chunking.split_strategy` from the config.
+///
+/// Without an explicit `chunk_size`, a `chunking.per_language` entry for the
+/// file's language replaces the configured chunk size and overlap.
+pub fn split_file_with_strategy(
+    file_path: &str,
+    content: &str,
+    chunk_size: Option<usize>,
+    overlap: Option<usize>,
+    whole_file_max_lines: Option<usize>,
+    strategy: Option<SplitStrategy>,
+) -> Vec<CodeChunk> {
     let language = detect_language(file_path);
-    let mut chunks = Vec::new();
+    let language_params = match chunk_size {
+        Some(_) => None,
+        None => language_chunk_params(&language),
+    };
+    // Each window must take at least one new line, so an overlap that isn't
+    // below the chunk size is clamped (`resolve_chunk_params` rejects it)
+    let chunk_size = chunk_size
+        .or(language_params.map(|params| params.chunk_size))
+        .unwrap_or_else(get_default_chunk_size)
+        .max(1);
+    let overlap = overlap
+        .or(language_params.map(|params| params.chunk_overlap.resolve(chunk_size)))
+        .unwrap_or_else(|| get_default_overlap(chunk_size))
+        .min(chunk_size - 1);
+    let whole_file_max_lines =
+        whole_file_max_lines.unwrap_or_else(|| get_config().chunking.whole_file_max_lines);
+    let strategy = strategy.unwrap_or_else(|| get_config().chunking.split_strategy);
+
+    let lines: Vec<&str> = content.lines().collect();
 
     if lines.is_empty() {
-        return chunks;
+        return Vec::new();
     }
 
-    let mut start = 0;
+    let ranges = if lines.len() <= whole_file_max_lines {
+        vec![(0, lines.len())]
+    } else {
+        let syntactic = match strategy {
+            SplitStrategy::Syntactic => {
+                syntactic_ranges(content, &language, lines.len(), chunk_size, overlap)
+            }
+            SplitStrategy::LineWindow => None,
+        };
+        syntactic.unwrap_or_else(|| line_window_ranges(0, lines.len(), chunk_size, overlap))
+    };
+
+    ranges
+        .into_iter()
+        .map(|(start, end)| {
+            let chunk_content: String = lines[start..end].join("\n");
+            let kind = classify_chunk(file_path, &chunk_content, &language).to_string();
+            CodeChunk {
+                chunk_id: generate_chunk_id(file_path, start + 1, end),
+                file_path: file_path.to_string(),
+                language: language.clone(),
+                start_line: start + 1,
+                end_line: end,
+                content: chunk_content,
+                kind,
+            }
+        })
+        .collect()
+}
 
-    while start < lines.len() {
-        let end = std::cmp::min(start + chunk_size, lines.len());
-        let chunk_content: String = lines[start..end].join("\n");
+/// The `chunking.per_language` entry for `language`, if any
+fn language_chunk_params(language: &str) -> Option<ChunkParams> {
+    get_config().chunking.per_language.get(language).copied()
+}
 
-        let chunk_id = generate_chunk_id(file_path, start + 1, end);
+/// Half-open line ranges of `chunk_size` lines covering `from..to`, each
+/// sharing `overlap` lines with the one before it.
+fn line_window_ranges(
+    from: usize,
+    to: usize,
+    chunk_size: usize,
+    overlap: usize,
+) -> Vec<(usize, usize)> {
+    let mut ranges = Vec::new();
+    let mut start = from;
 
-        chunks.push(CodeChunk {
-            chunk_id,
-            file_path: file_path.to_string(),
-            language: language.clone(),
-            start_line: start + 1,
-            end_line: end,
-            content: chunk_content,
-        });
+    while start < to {
+        let end = std::cmp::min(start + chunk_size, to);
+        ranges.push((start, end));
 
-        if end >= lines.len() {
+        if end >= to {
             break;
         }
 
-        start = end - overlap;
+        start = end.saturating_sub(overlap).max(start + 1);
     }
 
-    chunks
+    ranges
+}
+
+/// Half-open line ranges that cut only between top-level items, packing
+/// consecutive items into chunks of up to `chunk_size` lines. An item longer
+/// than that is cut into line windows of its own. `None` when the language
+/// has no grammar, so the caller falls back to line windows.
+#[cfg(feature = "tree-sitter")]
+fn syntactic_ranges(
+    content: &str,
+    language: &str,
+    line_count: usize,
+    chunk_size: usize,
+    overlap: usize,
+) -> Option<Vec<(usize, usize)>> {
+    let mut starts = crate::syntax_aware::top_level_item_starts(content, language)?;
+    starts.retain(|&start| start < line_count);
+    // Anything above the first item (a license header, imports) goes with it
+    match starts.first_mut() {
+        Some(first) => *first = 0,
+        None => return None,
+    }
+
+    let mut ranges = Vec::new();
+    let mut current: Option<(usize, usize)> = None;
+    for (i, &start) in starts.iter().enumerate() {
+        let end = starts.get(i + 1).copied().unwrap_or(line_count);
+        if end - start > chunk_size {
+            ranges.extend(current.take());
+            ranges.extend(line_window_ranges(start, end, chunk_size, overlap));
+            continue;
+        }
+        current = match current {
+            Some((from, _)) if end - from <= chunk_size => Some((from, end)),
+            Some(full) => {
+                ranges.push(full);
+                Some((start, end))
+            }
+            None => Some((start, end)),
+        };
+    }
+    ranges.extend(current);
+
+    Some(ranges)
+}
+
+#[cfg(not(feature = "tree-sitter"))]
+fn syntactic_ranges(
+    _content: &str,
+    _language: &str,
+    _line_count: usize,
+    _chunk_size: usize,
+    _overlap: usize,
+) -> Option<Vec<(usize, usize)>> {
+    None
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_split_identifiers() {
+        assert_eq!(
+            identifier_parts("parseHTTPResponse"),
+            ["parse", "HTTP", "Response"]
+        );
+        assert_eq!(
+            identifier_parts("XMLHttpRequest"),
+            ["XML", "Http", "Request"]
+        );
+        assert_eq!(
+            identifier_parts("snake_case_name"),
+            ["snake", "case", "name"]
+        );
+        assert_eq!(identifier_parts("__init__"), ["init"]);
+        assert_eq!(identifier_parts("utf8Decoder"), ["utf8", "Decoder"]);
+        assert_eq!(identifier_parts("HTTP"), ["HTTP"]);
+        assert_eq!(identifier_parts("Size"), ["Size"]);
+        assert!(identifier_parts("_").is_empty());
+
+        assert_eq!(
+            split_identifiers("let r = parseHTTPResponse(raw_body);"),
+            "let r = parse HTTP Response(raw body);"
+        );
+        assert_eq!(split_identifiers("plain words"), "plain words");
+
+        assert_eq!(
+            compound_identifier_terms("fn parseHTTPResponse(raw_body) { parseHTTPResponse }")
+                .as_deref(),
+            Some("parse HTTP Response")
+        );
+        assert_eq!(compound_identifier_terms("fn snake_only(x) {}"), None);
+    }
+
     #[test]
     fn test_detect_language() {
         assert_eq!(detect_language("test.rs"), "rust");
@@ -418,6 +918,135 @@ mod tests {
         assert_eq!(detect_language("unknown.xyz"), "unknown");
     }
 
+    #[test]
+    fn test_detect_language_shared_extensions() {
+        assert_eq!(detect_language("rtl/module.v"), "verilog");
+        assert_eq!(detect_language("proofs/Lemmas.coq"), "coq");
+        assert_eq!(detect_language("certs/root.der"), "asn1");
+        assert_eq!(detect_language("src/app.cr"), "crystal");
+        assert_eq!(detect_language("shaders/lit.slang"), "slang");
+        assert_eq!(detect_language("wasm/add.wat"), "wat");
+    }
+
+    #[test]
+    fn test_normalize_whitespace() {
+        assert_eq!(
+            normalize_whitespace("if x {\n\t\treturn   y;  \n}\t"),
+            "if x {\n return y;\n}"
+        );
+        assert_eq!(normalize_whitespace("a\n\n  b"), "a\n\n b");
+        assert_eq!(normalize_whitespace("plain"), "plain");
+    }
+
+    #[test]
+    fn test_decode_source() {
+        // Latin-1 comment: 0xE9 is not UTF-8 on its own
+        let latin1 = b"// caf\xE9\nfn main() {}\n";
+        assert_eq!(
+            decode_source(latin1, true).unwrap(),
+            "// caf\u{FFFD}\nfn main() {}\n"
+        );
+        assert_eq!(decode_source(latin1, false), None);
+
+        assert_eq!(
+            decode_source(b"\xEF\xBB\xBFfn a() {}", false).unwrap(),
+            "fn a() {}"
+        );
+        assert_eq!(decode_source(b"\xFF\xFEf\0n\0", false).unwrap(), "fn");
+        assert_eq!(decode_source(b"\xFE\xFF\0f\0n", false).unwrap(), "fn");
+        assert_eq!(decode_source(b"\xFF\xFEf\0n", true).unwrap(), "f\u{FFFD}");
+        assert_eq!(decode_source(b"\xFF\xFEf\0n", false), None);
+    }
+
+    #[test]
+    fn test_classify_comment_heavy_chunk() {
+        let content = "// Connection pooling\n// Reuses sockets across requests\n// See RFC 7230\nlet pool = Pool::new();";
+        assert_eq!(classify_chunk("src/net.rs", content, "rust"), "comment");
+
+        let docs =
+            "/// Opens a pooled connection.\n///\n/// Blocks until one is free.\npub fn open() {}";
+        assert_eq!(classify_chunk("src/net.rs", docs, "rust"), "doc");
+
+        let docstring = "def open():\n    \"\"\"\n    Open a pooled connection.\n    Blocks until free.\n    \"\"\"";
+        assert_eq!(classify_chunk("net.py", docstring, "python"), "doc");
+    }
+
+    #[test]
+    fn test_classify_test_file_chunk() {
+        let content = "fn parses_header() {\n    assert!(parse(\"x\").is_ok());\n}";
+        assert_eq!(classify_chunk("tests/parser.rs", content, "rust"), "test");
+        assert_eq!(classify_chunk("pkg/parser_test.go", content, "go"), "test");
+        assert_eq!(
+            classify_chunk("src/parser.spec.ts", content, "typescript"),
+            "test"
+        );
+        assert_eq!(classify_chunk("test_parser.py", content, "python"), "test");
+        assert_eq!(
+            classify_chunk("src/parser.rs", "#[test]\nfn parses() {}", "rust"),
+            "test"
+        );
+    }
+
+    #[test]
+    fn test_classify_code_chunk() {
+        let content = "// Parse one header line\nfn parse(line: &str) -> Header {\n    let (k, v) = line.split_once(':').unwrap();\n    Header::new(k, v)\n}";
+        assert_eq!(classify_chunk("src/parser.rs", content, "rust"), "code");
+        // Rust attributes are not hash comments
+        assert_eq!(
+            classify_chunk(
+                "src/model.rs",
+                "#[derive(Debug)]\n#[serde(default)]\nstruct A;",
+                "rust"
+            ),
+            "code"
+        );
+        assert_eq!(
+            classify_chunk("src/contest.rs", "fn main() {}", "rust"),
+            "code"
+        );
+        assert_eq!(classify_chunk("README.md", "# Title", "markdown"), "doc");
+    }
+
+    #[test]
+    fn test_classify_comment_prefixes_follow_the_language() {
+        let css = "--accent: #c33;\n--muted: #888;\n#header {\n  color: var(--accent);\n}";
+        assert_eq!(classify_chunk("theme.css", css, "css"), "code");
+        let csharp = "#region Parsing\n#if DEBUG\nvoid Trace() {}\n#endif\n#endregion";
+        assert_eq!(classify_chunk("Parser.cs", csharp, "csharp"), "code");
+
+        let sql = "-- Active users\n-- joined this year\nSELECT id FROM users;";
+        assert_eq!(classify_chunk("users.sql", sql, "sql"), "comment");
+        let shell = "# Rebuild the index\n# before each release\nmake index";
+        assert_eq!(classify_chunk("build.sh", shell, "shell"), "comment");
+    }
+
+    #[test]
+    fn test_preview_chunks_matches_configured_split() {
+        let _config = crate::test_support::read_config();
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("preview.rs");
+        let content = (1..=120)
+            .map(|i| format!("fn line_{}() {{}}", i))
+            .collect::<Vec<_>>()
+            .join("\n");
+        std::fs::write(&file, &content).unwrap();
+
+        let (size, overlap) = resolve_chunk_params(None, None).unwrap();
+        let chunks = preview_chunks(&file).unwrap();
+
+        assert_eq!(chunks[0].start_line, 1);
+        assert_eq!(chunks[0].end_line, size);
+        assert_eq!(chunks[1].start_line, size - overlap + 1);
+        assert_eq!(chunks.last().unwrap().end_line, 120);
+        assert_eq!(chunks[0].language, "rust");
+        assert_eq!(chunks[0].content.lines().next(), Some("fn line_1() {}"));
+        assert!(chunks
+            .windows(2)
+            .all(|w| w[1].start_line == w[0].end_line - overlap + 1));
+
+        assert!(preview_chunks(dir.path().join("missing.rs")).is_err());
+    }
+
     #[test]
     fn test_generate_chunk_id() {
         let id1 = generate_chunk_id("test.rs", 1, 50);
@@ -456,12 +1085,82 @@ mod tests {
         assert_eq!(chunks[0].end_line, 3);
     }
 
+    #[test]
+    fn test_split_small_file_as_whole_file_chunk() {
+        let content = (1..=10)
+            .map(|i| format!("line {}", i))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        let chunks = split_file_with_threshold("test.rs", &content, Some(4), Some(1), Some(20));
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].start_line, 1);
+        assert_eq!(chunks[0].end_line, 10);
+        assert_eq!(chunks[0].content, content);
+
+        // Above the threshold (or with it disabled) the file chunks normally
+        assert_eq!(
+            split_file_with_threshold("test.rs", &content, Some(4), Some(1), Some(9)).len(),
+            3
+        );
+        assert_eq!(
+            split_file_with_threshold("test.rs", &content, Some(4), Some(1), Some(0)).len(),
+            3
+        );
+    }
+
     #[test]
     fn test_split_file_empty() {
         let chunks = split_file("test.rs", "", Some(50), Some(10));
         assert_eq!(chunks.len(), 0);
     }
 
+    #[cfg(feature = "tree-sitter")]
+    #[test]
+    fn test_syntactic_split_cuts_between_items() {
+        let body: Vec<String> = (1..=8)
+            .map(|i| format!("    let v{} = {};", i, i))
+            .collect();
+        let content = format!(
+            "//! Module header\nuse std::fmt;\n\n/// Adds.\nfn add(a: i32, b: i32) -> i32 {{\n    a + b\n}}\n\n\
+             #[derive(Debug)]\nstruct Point {{\n    x: i32,\n    y: i32,\n}}\n\nfn long() {{\n{}\n}}\n",
+            body.join("\n")
+        );
+        let split = |path: &str, strategy| {
+            split_file_with_strategy(path, &content, Some(8), Some(2), Some(0), Some(strategy))
+        };
+
+        // Header and `add` share a chunk, `Point` keeps its attribute, and
+        // only the 10-line `long` is cut into windows
+        let chunks = split("lib.rs", SplitStrategy::Syntactic);
+        assert_eq!(
+            boundaries(&chunks),
+            vec![(1, 8), (9, 14), (15, 22), (21, 24)]
+        );
+        assert!(chunks[0].content.contains("/// Adds.\nfn add"));
+        assert!(chunks[1]
+            .content
+            .starts_with("#[derive(Debug)]\nstruct Point"));
+        let lines: Vec<&str> = content.lines().collect();
+        for chunk in &chunks {
+            assert_eq!(
+                chunk.content,
+                lines[chunk.start_line - 1..chunk.end_line].join("\n")
+            );
+        }
+
+        let windows = vec![(1, 8), (7, 14), (13, 20), (19, 24)];
+        assert_eq!(
+            boundaries(&split("lib.rs", SplitStrategy::LineWindow)),
+            windows
+        );
+        // No grammar for the language: line windows
+        assert_eq!(
+            boundaries(&split("lib.xyz", SplitStrategy::Syntactic)),
+            windows
+        );
+    }
+
     #[test]
     fn test_language_map() {
         let map = language_map();
@@ -471,4 +1170,113 @@ mod tests {
         assert!(map.contains_key("Cargo.toml"));
         assert!(map.contains_key("Makefile"));
     }
+
+    fn numbered_lines(n: usize) -> String {
+        (1..=n)
+            .map(|i| format!("line {}", i))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn boundaries(chunks: &[CodeChunk]) -> Vec<(usize, usize)> {
+        chunks.iter().map(|c| (c.start_line, c.end_line)).collect()
+    }
+
+    #[test]
+    fn test_absolute_overlap_boundaries() {
+        let overlap = crate::config::ChunkOverlap::Lines(5).resolve(20);
+        let chunks = split_file("test.rs", &numbered_lines(50), Some(20), Some(overlap));
+        assert_eq!(boundaries(&chunks), vec![(1, 20), (16, 35), (31, 50)]);
+    }
+
+    #[test]
+    fn test_percentage_overlap_boundaries() {
+        let overlap: crate::config::ChunkOverlap = "25%".parse().unwrap();
+        let chunks = split_file(
+            "test.rs",
+            &numbered_lines(50),
+            Some(20),
+            Some(overlap.resolve(20)),
+        );
+        assert_eq!(boundaries(&chunks), vec![(1, 20), (16, 35), (31, 50)]);
+
+        // The same percentage scales with a different chunk size
+        let chunks = split_file(
+            "test.rs",
+            &numbered_lines(50),
+            Some(40),
+            Some(overlap.resolve(40)),
+        );
+        assert_eq!(boundaries(&chunks), vec![(1, 40), (31, 50)]);
+    }
+
+    #[test]
+    fn test_resolve_chunk_params_rejects_overlap_not_below_size() {
+        assert_eq!(resolve_chunk_params(Some(20), Some(5)).unwrap(), (20, 5));
+        assert!(resolve_chunk_params(Some(20), Some(20)).is_err());
+        assert!(resolve_chunk_params(Some(20), Some(30)).is_err());
+    }
+
+    #[test]
+    fn test_overlap_not_below_size_still_advances() {
+        let content = numbered_lines(25);
+        let chunks = split_file("x.rs", &content, Some(10), Some(10));
+        // Clamped to an overlap of 9: every window moves on by one line
+        assert_eq!(chunks.len(), 16);
+        assert!(chunks.iter().all(|c| c.end_line - c.start_line + 1 == 10));
+        assert!(chunks
+            .windows(2)
+            .all(|w| w[1].start_line == w[0].start_line + 1));
+        assert_eq!(chunks.last().unwrap().end_line, 25);
+
+        let chunks = split_file("x.rs", &content, Some(10), Some(40));
+        assert_eq!(chunks.len(), 16);
+
+        let chunks = split_file("x.rs", &content, Some(0), Some(0));
+        assert_eq!(chunks.len(), 25);
+        assert!(chunks.iter().all(|c| c.start_line == c.end_line));
+    }
+
+    #[test]
+    fn test_per_language_chunk_sizes() {
+        use crate::config::{ChunkOverlap, ChunkParams, Config};
+
+        let mut config = Config::default();
+        config.chunking.chunk_size = 40;
+        config.chunking.chunk_overlap = ChunkOverlap::Lines(0);
+        for (language, chunk_size) in [("python", 30), ("rust", 100)] {
+            config.chunking.per_language.insert(
+                language.to_string(),
+                ChunkParams {
+                    chunk_size,
+                    chunk_overlap: ChunkOverlap::Lines(0),
+                },
+            );
+        }
+        let _config = crate::test_support::override_config(config);
+
+        let content = (1..=120)
+            .map(|i| format!("line_{} = {}", i, i))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let count = |path: &str| split_file(path, &content, None, None).len();
+        assert_eq!(count("app.py"), 4);
+        assert_eq!(count("lib.rs"), 2);
+        // Languages without an entry keep the configured size
+        assert_eq!(count("main.go"), 3);
+
+        // Previews cut files as indexing does
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["app.py", "lib.rs"] {
+            std::fs::write(dir.path().join(name), &content).unwrap();
+            assert_eq!(
+                preview_chunks(dir.path().join(name)).unwrap().len(),
+                count(name)
+            );
+        }
+
+        // An explicit size holds for every language
+        assert_eq!(split_file("app.py", &content, Some(60), Some(0)).len(), 2);
+        assert_eq!(split_file("lib.rs", &content, Some(60), Some(0)).len(), 2);
+    }
 }
@@ -259,11 +259,8 @@ pub fn get_language_config(language: &str) -> LanguageConfig {
     }
 }
 
-/// Parse source code and extract syntax nodes
-pub fn parse_source(
-    source: &str,
-    language: &str,
-) -> Result<Vec<SyntaxNode>, String> {
+/// The tree-sitter grammar for a detected language, if there is one
+fn grammar(language: &str) -> Option<tree_sitter::Language> {
     let lang = match language {
         "rust" => tree_sitter_rust::LANGUAGE,
         "python" => tree_sitter_python::LANGUAGE,
@@ -277,13 +274,20 @@ pub fn parse_source(
         "bash" | "shell" | "sh" | "zsh" => tree_sitter_bash::LANGUAGE,
         "json" => tree_sitter_json::LANGUAGE,
         "yaml" | "yml" => tree_sitter_yaml::LANGUAGE,
-        _ => {
-            return Err(format!("Unsupported language for syntax parsing: {}", language));
-        }
+        _ => return None,
     };
+    Some(lang.into())
+}
+
+/// Parse source code and extract syntax nodes
+pub fn parse_source(
+    source: &str,
+    language: &str,
+) -> Result<Vec<SyntaxNode>, String> {
+    let lang_obj = grammar(language)
+        .ok_or_else(|| format!("Unsupported language for syntax parsing: {}", language))?;
 
     let mut parser = tree_sitter::Parser::new();
-    let lang_obj: tree_sitter::Language = lang.into();
     parser.set_language(&lang_obj).map_err(|e| e.to_string())?;
 
     let tree = parser.parse(source, None).ok_or("Failed to parse source")?;
@@ -297,7 +301,45 @@ pub fn parse_source(
     Ok(nodes)
 }
 
+/// The 0-based line on which each top-level item of `source` starts, in
+/// order. Comments and attributes directly above an item count as part of
+/// it, and an item starting on the line another one ends on is merged into
+/// that one, so every start is a clean line boundary. `None` when the
+/// language has no grammar or the source cannot be parsed.
+pub fn top_level_item_starts(source: &str, language: &str) -> Option<Vec<usize>> {
+    let mut parser = tree_sitter::Parser::new();
+    parser.set_language(&grammar(language)?).ok()?;
+    let tree = parser.parse(source, None)?;
+
+    let mut starts: Vec<usize> = Vec::new();
+    let mut leading: Option<usize> = None;
+    let mut prev_end: Option<usize> = None;
+    let root = tree.root_node();
+    let mut cursor = root.walk();
+    for node in root.named_children(&mut cursor) {
+        let row = node.start_position().row;
+        let clean = prev_end.is_none_or(|end| row > end);
+        let kind = node.kind();
+        if kind.contains("comment") || kind.ends_with("attribute_item") {
+            if clean {
+                leading.get_or_insert(row);
+            }
+            continue;
+        }
+
+        let start = leading.take().unwrap_or(row);
+        if clean {
+            starts.push(start);
+        }
+        let end = node.end_position().row;
+        prev_end = Some(prev_end.map_or(end, |prev| prev.max(end)));
+    }
+
+    Some(starts)
+}
+
 /// Recursively extract relevant nodes from the AST
+#[allow(clippy::only_used_in_recursion)]
 fn extract_nodes(
     node: tree_sitter::Node,
     config: &LanguageConfig,
@@ -409,6 +451,8 @@ pub fn split_file_syntax_aware(
                 start_line,
                 end_line,
                 content: current_chunk_content.clone(),
+                kind: crate::splitter::classify_chunk(file_path, &current_chunk_content, &language)
+                    .to_string(),
             });
 
             // Start new chunk with overlap (include last node for context)
@@ -439,6 +483,9 @@ pub fn split_file_syntax_aware(
             current_chunk_start + current_chunk_content.len(),
         );
 
+        let kind =
+            crate::splitter::classify_chunk(file_path, &current_chunk_content, &language)
+                .to_string();
         chunks.push(CodeChunk {
             chunk_id: crate::splitter::generate_chunk_id(file_path, start_line, end_line),
             file_path: file_path.to_string(),
@@ -446,6 +493,7 @@ pub fn split_file_syntax_aware(
             start_line,
             end_line,
             content: current_chunk_content,
+            kind,
         });
     }
 
@@ -484,6 +532,8 @@ fn split_file_line_based(
         let chunk_content: String = lines[start..end].join("\n");
         let chunk_id = crate::splitter::generate_chunk_id(file_path, start + 1, end);
 
+        let kind =
+            crate::splitter::classify_chunk(file_path, &chunk_content, &language).to_string();
         chunks.push(CodeChunk {
             chunk_id,
             file_path: file_path.to_string(),
@@ -491,6 +541,7 @@ fn split_file_line_based(
             start_line: start + 1,
             end_line: end,
             content: chunk_content,
+            kind,
         });
 
         if end >= lines.len() {
@@ -559,6 +610,14 @@ struct MyStruct {
         assert!(!chunks.is_empty());
     }
 
+    #[test]
+    fn test_top_level_item_starts() {
+        let source = "// header\nfn a() {} fn b() {}\n\n#[test]\nfn c() {\n}\n";
+        // `b` shares a line with `a`; the comment and attribute lead their items
+        assert_eq!(top_level_item_starts(source, "rust"), Some(vec![0, 3]));
+        assert_eq!(top_level_item_starts(source, "unknown"), None);
+    }
+
     #[test]
     fn test_split_file_syntax_aware_unsupported() {
         let source = "some unknown content";
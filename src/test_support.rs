@@ -0,0 +1,101 @@
+//! Helpers shared by unit tests.
+//!
+//! The config and the data directory are process-wide. Tests that rely on
+//! them hold a guard shared with other readers, through
+//! [`use_temp_data_dir`] or [`read_config`]; tests that change the config
+//! hold one exclusively through [`override_config`]. A test takes only one
+//! of these, since a second read guard can wait behind a queued writer.
+
+use std::sync::{Mutex, OnceLock, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+static DATA_DIR: OnceLock<tempfile::TempDir> = OnceLock::new();
+
+/// How many [`TempDataDir`] guards are alive across the process
+static DATA_DIR_USERS: Mutex<usize> = Mutex::new(0);
+
+/// Held shared while a test reads the global config, exclusively while one
+/// replaces it
+static GLOBAL_STATE: RwLock<()> = RwLock::new(());
+
+fn shared_data_dir() -> std::path::PathBuf {
+    DATA_DIR
+        .get_or_init(|| tempfile::tempdir().expect("failed to create temp data dir"))
+        .path()
+        .to_path_buf()
+}
+
+/// Keeps the data directory pointed at the shared temp dir; the last one
+/// dropped restores the configured location.
+#[must_use = "the data directory override is cleared when the guard drops"]
+pub struct TempDataDir {
+    _state: RwLockReadGuard<'static, ()>,
+}
+
+impl Drop for TempDataDir {
+    fn drop(&mut self) {
+        let mut users = DATA_DIR_USERS
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        *users -= 1;
+        if *users == 0 {
+            crate::config::set_data_dir_override(None);
+        }
+    }
+}
+
+/// Point the database and manifests at a temporary directory shared by every
+/// unit test in this process, so tests never touch the user's real index.
+/// Tests stay independent by indexing distinct temp codebases.
+pub fn use_temp_data_dir() -> TempDataDir {
+    let state = GLOBAL_STATE.read().unwrap_or_else(PoisonError::into_inner);
+    let mut users = DATA_DIR_USERS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+    *users += 1;
+    crate::config::set_data_dir_override(Some(shared_data_dir()));
+    TempDataDir { _state: state }
+}
+
+/// Keeps the global config from being replaced while a test relies on it
+#[must_use = "the config may change once the guard drops"]
+pub struct ConfigReader {
+    _state: RwLockReadGuard<'static, ()>,
+}
+
+/// Hold off [`override_config`] in other tests, for a test that reads the
+/// config without a data directory
+pub fn read_config() -> ConfigReader {
+    ConfigReader {
+        _state: GLOBAL_STATE.read().unwrap_or_else(PoisonError::into_inner),
+    }
+}
+
+/// A replaced global config, with the data directory at the shared temp
+/// dir; dropping it reloads the config and restores the location
+#[must_use = "the config is reset when the guard drops"]
+pub struct ConfigOverride {
+    _state: RwLockWriteGuard<'static, ()>,
+}
+
+impl ConfigOverride {
+    /// Replace the config again, as further steps of the same test
+    pub fn set(&self, config: crate::config::Config) {
+        crate::config::set_config(config);
+    }
+}
+
+impl Drop for ConfigOverride {
+    fn drop(&mut self) {
+        crate::config::reset_config();
+        crate::config::set_data_dir_override(None);
+    }
+}
+
+/// Replace the global config with `config` until the guard drops, once no
+/// other test holds a guard
+pub fn override_config(config: crate::config::Config) -> ConfigOverride {
+    let state = GLOBAL_STATE.write().unwrap_or_else(PoisonError::into_inner);
+    crate::config::set_data_dir_override(Some(shared_data_dir()));
+    crate::config::set_config(config);
+    ConfigOverride { _state: state }
+}
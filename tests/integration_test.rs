@@ -120,4 +120,143 @@ mod integration_tests {
             "51 lines should produce 2 chunks with overlap"
         );
     }
+
+    #[test]
+    fn test_index_and_search_in_temp_data_dir() {
+        use clap::Parser;
+        use code_search::{
+            database::{fts_search, get_codebase_stats, init_db_at, SearchFilters},
+            run, Cli,
+        };
+
+        let data_dir = tempfile::tempdir().unwrap();
+        let codebase = tempfile::tempdir().unwrap();
+        std::fs::write(
+            codebase.path().join("auth.rs"),
+            "pub fn authenticate_user(token: &str) -> bool {\n    !token.is_empty()\n}\n",
+        )
+        .unwrap();
+
+        let data_dir_arg = data_dir.path().to_str().unwrap();
+        let codebase_arg = codebase.path().to_str().unwrap();
+
+        let cli = Cli::try_parse_from([
+            "code-search",
+            "--data-dir",
+            data_dir_arg,
+            "index",
+            codebase_arg,
+        ])
+        .unwrap();
+        run(cli).unwrap();
+
+        let cli = Cli::try_parse_from([
+            "code-search",
+            "search",
+            "authenticate_user",
+            "--codebase",
+            codebase_arg,
+            "--data-dir",
+            data_dir_arg,
+        ])
+        .unwrap();
+        run(cli).unwrap();
+        // --data-dir lasts only for the run
+        assert_eq!(code_search::data_dir_override(), None);
+
+        // Everything the run wrote lives under the temp data dir
+        let codebase_id = manifest::get_codebase_hash(&codebase.path().canonicalize().unwrap());
+        assert!(data_dir
+            .path()
+            .join("manifests")
+            .join(format!("{}.json", codebase_id))
+            .exists());
+
+        let conn = init_db_at(&data_dir.path().join("index.db")).unwrap();
+        let stats = get_codebase_stats(&conn, &codebase_id).unwrap().unwrap();
+        assert_eq!(stats.total_files, 1);
+
+        let results = fts_search(
+            &conn,
+            "authenticate_user",
+            Some(&codebase_id),
+            10,
+            &SearchFilters::default(),
+        )
+        .unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].file_path, "auth.rs");
+    }
+
+    #[test]
+    fn test_search_vector_only_skips_full_text() {
+        use code_search::{database::init_db_at, get_query_embedding, vector_search};
+        use std::process::Command;
+
+        let data_dir = tempfile::tempdir().unwrap();
+        let codebase = tempfile::tempdir().unwrap();
+        for (name, body) in [
+            (
+                "parse.rs",
+                "fn parse_header(buf: &[u8]) -> Header { todo!() }\n",
+            ),
+            (
+                "render.rs",
+                "fn render_page(page: &Page) -> String { todo!() }\n",
+            ),
+            (
+                "store.rs",
+                "fn store_blob(key: &str, blob: Vec<u8>) { todo!() }\n",
+            ),
+        ] {
+            std::fs::write(codebase.path().join(name), body).unwrap();
+        }
+
+        let code_search = |args: &[&str]| {
+            let output = Command::new(env!("CARGO_BIN_EXE_code-search"))
+                .arg("--data-dir")
+                .arg(data_dir.path())
+                .args(args)
+                .output()
+                .unwrap();
+            assert!(output.status.success(), "{:?} failed", args);
+            String::from_utf8(output.stdout).unwrap()
+        };
+        let codebase_arg = codebase.path().to_str().unwrap();
+        code_search(&["index", codebase_arg]);
+
+        let search = |extra: &[&str]| {
+            let mut args = vec![
+                "search",
+                "parse_header",
+                "--codebase",
+                codebase_arg,
+                "-n",
+                "3",
+            ];
+            args.extend_from_slice(extra);
+            code_search(&args)
+        };
+        let hybrid = search(&[]);
+        let vector_only = search(&["--vector-only"]);
+        assert_ne!(hybrid, vector_only);
+
+        // Vector-only scores are the plain cosine similarities
+        let codebase_id = manifest::get_codebase_hash(&codebase.path().canonicalize().unwrap());
+        let conn = init_db_at(&data_dir.path().join("index.db")).unwrap();
+        let expected = vector_search(
+            &conn,
+            Some(&codebase_id),
+            &get_query_embedding("parse_header"),
+            3,
+        )
+        .unwrap();
+        let scores: Vec<&str> = vector_only
+            .lines()
+            .filter_map(|line| line.trim().strip_prefix("Score: "))
+            .collect();
+        let expected_scores: Vec<String> =
+            expected.iter().map(|r| format!("{:.4}", r.score)).collect();
+        assert_eq!(scores, expected_scores);
+    }
 }
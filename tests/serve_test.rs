@@ -0,0 +1,91 @@
+// Runs in its own test binary: it points the global data directory at a
+// temp dir, which other tests in the same process would see.
+#![cfg(unix)]
+
+use code_search::{send_request, set_data_dir_override, ServeRequest, ServeResponse, Server};
+
+#[test]
+fn test_serve_answers_search_over_socket() {
+    let data_dir = tempfile::tempdir().unwrap();
+    set_data_dir_override(Some(data_dir.path().to_path_buf()));
+
+    let codebase = tempfile::tempdir().unwrap();
+    std::fs::write(
+        codebase.path().join("session.rs"),
+        "fn open_session(user: &User) -> Session {\n    Session::new(user)\n}\n",
+    )
+    .unwrap();
+    std::fs::write(
+        codebase.path().join("math.rs"),
+        "fn add(a: i32, b: i32) -> i32 { a + b }\n",
+    )
+    .unwrap();
+
+    let socket = data_dir.path().join("test.sock");
+    let server = Server::bind(&socket, "minilm").unwrap();
+    let running = std::thread::spawn(move || server.run());
+
+    let path = codebase.path().to_str().unwrap().to_string();
+    match send_request(
+        &socket,
+        &ServeRequest::Index {
+            path: path.clone(),
+            force: false,
+        },
+    )
+    .unwrap()
+    {
+        ServeResponse::Ok { result } => assert_eq!(result["files_indexed"], 2),
+        other => panic!("index failed: {:?}", other),
+    }
+
+    let response = send_request(
+        &socket,
+        &ServeRequest::Search {
+            query: "open session".to_string(),
+            codebase: Some(path),
+            limit: 5,
+            vector_only: false,
+            min_score: None,
+        },
+    )
+    .unwrap();
+    match response {
+        ServeResponse::Ok { result } => {
+            let results = result.as_array().unwrap();
+            assert!(!results.is_empty());
+            assert_eq!(results[0]["file"], "session.rs");
+        }
+        other => panic!("search failed: {:?}", other),
+    }
+
+    match send_request(
+        &socket,
+        &ServeRequest::Embed {
+            texts: vec!["fn main() {}".to_string()],
+            query: false,
+        },
+    )
+    .unwrap()
+    {
+        ServeResponse::Ok { result } => assert_eq!(result.as_array().unwrap().len(), 1),
+        other => panic!("embed failed: {:?}", other),
+    }
+
+    let response = send_request(
+        &socket,
+        &ServeRequest::Search {
+            query: "anything".to_string(),
+            codebase: Some("/no/such/codebase".to_string()),
+            limit: 5,
+            vector_only: false,
+            min_score: None,
+        },
+    )
+    .unwrap();
+    assert!(matches!(response, ServeResponse::Error { .. }));
+
+    send_request(&socket, &ServeRequest::Shutdown).unwrap();
+    running.join().unwrap().unwrap();
+    assert!(!socket.exists());
+}